@@ -1386,6 +1386,7 @@ impl MnsServer {
             sdp_record: Some(sdp_xml),
             sdp_version: Some(0x0100),
             sdp_features: Some(0x001f),
+            role: None,
         };
         log::info!("The profile is {:#?}", psettings);
         if let Some(adapter) = adapter.supports_async() {
@@ -1590,7 +1591,7 @@ async fn main() -> Result<(), String> {
     // producing a spurious 0xC6 (Not Acceptable) and making every subsequent
     // OBEX operation fail.
     if let Some(a) = adapter.supports_async() {
-        if let Some(devs) = a.get_paired_devices() {
+        if let Ok(devs) = a.get_paired_devices() {
             for mut dev in devs {
                 match dev.get_uuids() {
                     Ok(uuids) => {
@@ -0,0 +1,108 @@
+// GattCallbackBridge.java
+package com.github.uglyoldbob.bluetooth_rust;
+
+import android.bluetooth.BluetoothGatt;
+import android.bluetooth.BluetoothGattCallback;
+import android.bluetooth.BluetoothGattCharacteristic;
+import android.bluetooth.BluetoothGattDescriptor;
+
+// Forwards the callbacks of a gatt client to the bluetooth-rust library. The handle identifies the rust
+// state of the client, once it is released no more callbacks are forwarded.
+public class GattCallbackBridge extends BluetoothGattCallback {
+    private long handle;
+
+    public GattCallbackBridge(long handle) {
+        this.handle = handle;
+    }
+
+    public synchronized void release() {
+        handle = 0;
+    }
+
+    @Override
+    public synchronized void onConnectionStateChange(BluetoothGatt gatt, int status, int newState) {
+        if (handle != 0) {
+            nativeConnectionStateChange(handle, status, newState);
+        }
+    }
+
+    @Override
+    public synchronized void onServicesDiscovered(BluetoothGatt gatt, int status) {
+        if (handle != 0) {
+            nativeServicesDiscovered(handle, status);
+        }
+    }
+
+    @Override
+    public synchronized void onCharacteristicRead(BluetoothGatt gatt,
+            BluetoothGattCharacteristic characteristic, byte[] value, int status) {
+        if (handle != 0) {
+            nativeCharacteristicRead(handle, characteristic.getInstanceId(), value, status);
+        }
+    }
+
+    // Used before android 13
+    @Override
+    @SuppressWarnings("deprecation")
+    public synchronized void onCharacteristicRead(BluetoothGatt gatt,
+            BluetoothGattCharacteristic characteristic, int status) {
+        if (handle != 0) {
+            nativeCharacteristicRead(handle, characteristic.getInstanceId(), characteristic.getValue(), status);
+        }
+    }
+
+    @Override
+    public synchronized void onCharacteristicWrite(BluetoothGatt gatt,
+            BluetoothGattCharacteristic characteristic, int status) {
+        if (handle != 0) {
+            nativeCharacteristicWrite(handle, characteristic.getInstanceId(), status);
+        }
+    }
+
+    @Override
+    public synchronized void onCharacteristicChanged(BluetoothGatt gatt,
+            BluetoothGattCharacteristic characteristic, byte[] value) {
+        if (handle != 0) {
+            nativeCharacteristicChanged(handle, characteristic.getInstanceId(), value);
+        }
+    }
+
+    // Used before android 13
+    @Override
+    @SuppressWarnings("deprecation")
+    public synchronized void onCharacteristicChanged(BluetoothGatt gatt,
+            BluetoothGattCharacteristic characteristic) {
+        if (handle != 0) {
+            nativeCharacteristicChanged(handle, characteristic.getInstanceId(), characteristic.getValue());
+        }
+    }
+
+    @Override
+    public synchronized void onDescriptorWrite(BluetoothGatt gatt, BluetoothGattDescriptor descriptor,
+            int status) {
+        if (handle != 0) {
+            nativeDescriptorWrite(handle, descriptor.getCharacteristic().getInstanceId(), status);
+        }
+    }
+
+    @Override
+    public synchronized void onReadRemoteRssi(BluetoothGatt gatt, int rssi, int status) {
+        if (handle != 0) {
+            nativeReadRemoteRssi(handle, rssi, status);
+        }
+    }
+
+    private static native void nativeConnectionStateChange(long handle, int status, int newState);
+
+    private static native void nativeServicesDiscovered(long handle, int status);
+
+    private static native void nativeCharacteristicRead(long handle, int id, byte[] value, int status);
+
+    private static native void nativeCharacteristicWrite(long handle, int id, int status);
+
+    private static native void nativeCharacteristicChanged(long handle, int id, byte[] value);
+
+    private static native void nativeDescriptorWrite(long handle, int id, int status);
+
+    private static native void nativeReadRemoteRssi(long handle, int rssi, int status);
+}
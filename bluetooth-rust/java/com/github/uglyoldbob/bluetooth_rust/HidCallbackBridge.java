@@ -0,0 +1,75 @@
+// HidCallbackBridge.java
+package com.github.uglyoldbob.bluetooth_rust;
+
+import android.bluetooth.BluetoothDevice;
+import android.bluetooth.BluetoothHidDevice;
+
+// Forwards the callbacks of a registered hid device to the bluetooth-rust library. The handle identifies the rust
+// state of the hid device, once it is released no more callbacks are forwarded.
+public class HidCallbackBridge extends BluetoothHidDevice.Callback {
+    private long handle;
+
+    public HidCallbackBridge(long handle) {
+        this.handle = handle;
+    }
+
+    public synchronized void release() {
+        handle = 0;
+    }
+
+    @Override
+    public synchronized void onAppStatusChanged(BluetoothDevice pluggedDevice, boolean registered) {
+        if (handle != 0) {
+            nativeAppStatusChanged(handle, pluggedDevice, registered);
+        }
+    }
+
+    @Override
+    public synchronized void onConnectionStateChanged(BluetoothDevice device, int state) {
+        if (handle != 0) {
+            nativeConnectionStateChanged(handle, device, state);
+        }
+    }
+
+    @Override
+    public synchronized void onGetReport(BluetoothDevice device, byte type, byte id, int bufferSize) {
+        if (handle != 0) {
+            nativeGetReport(handle, device, type, id, bufferSize);
+        }
+    }
+
+    @Override
+    public synchronized void onSetReport(BluetoothDevice device, byte type, byte id, byte[] data) {
+        if (handle != 0) {
+            nativeSetReport(handle, device, type, id, data);
+        }
+    }
+
+    @Override
+    public synchronized void onInterruptData(BluetoothDevice device, byte reportId, byte[] data) {
+        if (handle != 0) {
+            nativeInterruptData(handle, device, reportId, data);
+        }
+    }
+
+    @Override
+    public synchronized void onVirtualCableUnplug(BluetoothDevice device) {
+        if (handle != 0) {
+            nativeVirtualCableUnplug(handle, device);
+        }
+    }
+
+    private static native void nativeAppStatusChanged(long handle, BluetoothDevice device, boolean registered);
+
+    private static native void nativeConnectionStateChanged(long handle, BluetoothDevice device, int state);
+
+    private static native void nativeGetReport(long handle, BluetoothDevice device, byte type, byte id,
+            int bufferSize);
+
+    private static native void nativeSetReport(long handle, BluetoothDevice device, byte type, byte id,
+            byte[] data);
+
+    private static native void nativeInterruptData(long handle, BluetoothDevice device, byte id, byte[] data);
+
+    private static native void nativeVirtualCableUnplug(long handle, BluetoothDevice device);
+}
@@ -0,0 +1,18 @@
+// PermissionResultBridge.java
+package com.github.uglyoldbob.bluetooth_rust;
+
+// Forwards the results of permission requests to the bluetooth-rust library. The activity of the app calls
+// onRequestPermissionsResult from its own onRequestPermissionsResult. Results of requests the library did not
+// make are ignored.
+public class PermissionResultBridge {
+    public static void onRequestPermissionsResult(int requestCode, String[] permissions, int[] grantResults) {
+        try {
+            nativeRequestPermissionsResult(requestCode, permissions, grantResults);
+        } catch (UnsatisfiedLinkError e) {
+            // The library has not requested any permissions yet
+        }
+    }
+
+    private static native void nativeRequestPermissionsResult(int requestCode, String[] permissions,
+            int[] grantResults);
+}
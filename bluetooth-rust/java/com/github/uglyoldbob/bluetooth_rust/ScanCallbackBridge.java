@@ -0,0 +1,55 @@
+// ScanCallbackBridge.java
+package com.github.uglyoldbob.bluetooth_rust;
+
+import android.bluetooth.BluetoothDevice;
+import android.bluetooth.le.ScanCallback;
+import android.bluetooth.le.ScanRecord;
+import android.bluetooth.le.ScanResult;
+import java.util.List;
+
+// Forwards the results of a low energy scan to the bluetooth-rust library. The handle identifies the rust
+// state of the scan, once it is released no more results are forwarded.
+public class ScanCallbackBridge extends ScanCallback {
+    private long handle;
+
+    public ScanCallbackBridge(long handle) {
+        this.handle = handle;
+    }
+
+    public synchronized void release() {
+        handle = 0;
+    }
+
+    @Override
+    public synchronized void onScanResult(int callbackType, ScanResult result) {
+        if (handle != 0) {
+            forward(result);
+        }
+    }
+
+    @Override
+    public synchronized void onBatchScanResults(List<ScanResult> results) {
+        if (handle != 0) {
+            for (ScanResult result : results) {
+                forward(result);
+            }
+        }
+    }
+
+    @Override
+    public synchronized void onScanFailed(int errorCode) {
+        if (handle != 0) {
+            nativeScanFailed(handle, errorCode);
+        }
+    }
+
+    private void forward(ScanResult result) {
+        ScanRecord record = result.getScanRecord();
+        byte[] bytes = record == null ? null : record.getBytes();
+        nativeScanResult(handle, result.getDevice(), result.getRssi(), bytes);
+    }
+
+    private static native void nativeScanResult(long handle, BluetoothDevice device, int rssi, byte[] record);
+
+    private static native void nativeScanFailed(long handle, int errorCode);
+}
@@ -1,7 +1,6 @@
 //! Android specific bluetooth code
 
 use jni::objects::GlobalRef;
-use jni::sys::_jobject;
 use jni_min_helper::*;
 use winit::platform::android::activity::AndroidApp;
 
@@ -22,7 +21,7 @@ pub(crate) fn jerr(env: &mut jni::JNIEnv, err: jni::errors::Error) -> std::io::E
                 } else if cls.contains("IllegalArgumentException") {
                     std::io::Error::new(std::io::ErrorKind::InvalidInput, msg)
                 } else {
-                    std::io::Error::other(format!("{cls}: {msg}"))
+                    crate::BluetoothError::Platform(format!("{cls}: {msg}")).into()
                 }
             })
             .unwrap_or(std::io::Error::other(err))
@@ -31,6 +30,72 @@ pub(crate) fn jerr(env: &mut jni::JNIEnv, err: jni::errors::Error) -> std::io::E
     }
 }
 
+/// The permission an adapter method throws a SecurityException without, at the api level
+fn method_permission(method: &str, sdk: i32) -> Option<crate::BluetoothPermission> {
+    use crate::BluetoothPermission;
+    match method {
+        "startDiscovery" | "startScan" => Some(BluetoothPermission::Scan),
+        "getBondedDevices"
+        | "getName"
+        | "setName"
+        | "listenUsingSocketSettings"
+        | "listenUsingRfcommWithServiceRecord"
+        | "listenUsingInsecureRfcommWithServiceRecord"
+        | "registerApp" => Some(if sdk >= 31 {
+            BluetoothPermission::Connect
+        } else {
+            BluetoothPermission::LegacyBluetooth
+        }),
+        _ => None,
+    }
+}
+
+/// Like `jerr`, but a SecurityException from calling the adapter method becomes the permission the method needs.
+pub(crate) fn permission_err(
+    env: &mut jni::JNIEnv,
+    method: &str,
+    err: jni::errors::Error,
+) -> crate::BluetoothError {
+    let err = jerr(env, err);
+    if err.kind() == std::io::ErrorKind::PermissionDenied {
+        if let Some(p) = sdk_int(env)
+            .ok()
+            .and_then(|sdk| method_permission(method, sdk))
+        {
+            return crate::BluetoothError::MissingPermission(p);
+        }
+    }
+    err.into()
+}
+
+/// Load a class shipped with the application. Classes of the app are not found by `FindClass` from threads
+/// created in native code, so the class loader of the context is used instead.
+fn load_app_class<'a>(
+    env: &mut jni::JNIEnv<'a>,
+    context: &jni::objects::JObject,
+    name: &str,
+) -> Result<jni::objects::JClass<'a>, jni::errors::Error> {
+    let class_loader = env
+        .call_method(context, "getClassLoader", "()Ljava/lang/ClassLoader;", &[])
+        .get_object(env)?;
+    let class_name = name.new_jobject(env)?;
+    Ok(env
+        .call_method(
+            class_loader,
+            "loadClass",
+            "(Ljava/lang/String;)Ljava/lang/Class;",
+            &[(&class_name).into()],
+        )
+        .get_object(env)?
+        .into())
+}
+
+/// Get the api level of the running android version, from Build.VERSION.SDK_INT
+pub(crate) fn sdk_int(env: &mut jni::JNIEnv) -> Result<i32, jni::errors::Error> {
+    env.get_static_field("android/os/Build$VERSION", "SDK_INT", "I")
+        .get_int()
+}
+
 #[ouroboros::self_referencing]
 pub struct Java {
     app: AndroidApp,
@@ -87,14 +152,221 @@ use crate::bluetooth_uuid::ParcelUuid;
 mod device;
 pub use device::BluetoothDevice;
 
+mod gatt;
+pub use gatt::GattClient;
+
+mod hid;
+pub use hid::HidDevice;
+
+mod scan;
+pub use scan::LeScanSession;
+
+/// BluetoothDevice.ACTION_FOUND
+const ACTION_FOUND: &str = "android.bluetooth.device.action.FOUND";
+/// BluetoothAdapter.ACTION_DISCOVERY_FINISHED
+const ACTION_DISCOVERY_FINISHED: &str = "android.bluetooth.adapter.action.DISCOVERY_FINISHED";
+
+/// A device found by discovery, with the details reported in the broadcast that found it
+pub struct DiscoveredDevice {
+    /// The device that was found
+    pub device: BluetoothDevice,
+    /// The signal strength of the device in dBm, when reported
+    pub rssi: Option<i16>,
+    /// The name of the device, when reported
+    pub name: Option<String>,
+    /// The radio transports supported by the device
+    pub transport: crate::DeviceTransport,
+}
+
+/// The senders for the devices found during discovery, dropped when discovery finishes
+struct DiscoverySenders {
+    /// The sender for the cross platform events
+    events: tokio::sync::mpsc::Sender<crate::DiscoveryEvent>,
+    /// The sender for the android specific devices
+    devices: tokio::sync::mpsc::Sender<DiscoveredDevice>,
+}
+
 pub struct BluetoothDiscovery {
     adapter: jni::objects::GlobalRef,
     java: Arc<Mutex<super::Java>>,
+    /// The devices found, until taken by the user
+    events: Option<tokio::sync::mpsc::Receiver<crate::DiscoveryEvent>>,
+    /// The devices found with their details, until taken by the user
+    devices: Option<tokio::sync::mpsc::Receiver<DiscoveredDevice>>,
+    /// Becomes true when the discovery finished broadcast is received
+    finished: tokio::sync::watch::Receiver<bool>,
+    /// The receiver for found devices, unregistered when discovery stops
+    _found: Option<RegisteredReceiver>,
 }
 
 impl<'a> BluetoothDiscovery {
-    fn new(adapter: jni::objects::GlobalRef, java: Arc<Mutex<super::Java>>) -> Self {
-        Self { adapter, java }
+    fn new(
+        adapter: jni::objects::GlobalRef,
+        java: Arc<Mutex<super::Java>>,
+        battery_levels: Arc<Mutex<std::collections::HashMap<String, u8>>>,
+        rssi_readings: Arc<Mutex<std::collections::HashMap<String, crate::RssiReading>>>,
+        profile_proxies: ProfileProxyMap,
+        response_timeout: std::time::Duration,
+    ) -> Result<Self, crate::BluetoothError> {
+        let (tx, rx) = tokio::sync::mpsc::channel(32);
+        let (devices_tx, devices_rx) = tokio::sync::mpsc::channel(32);
+        let senders = Mutex::new(Some(DiscoverySenders {
+            events: tx,
+            devices: devices_tx,
+        }));
+        let (finished_tx, finished) = tokio::sync::watch::channel(false);
+        let java2 = java.clone();
+        let found = RegisteredReceiver::new(
+            &java,
+            &[ACTION_FOUND, ACTION_DISCOVERY_FINISHED],
+            move |env, _context, intent| {
+                let action = env
+                    .call_method(intent, "getAction", "()Ljava/lang/String;", &[])
+                    .get_object(env)?
+                    .get_string(env)?;
+                if action == ACTION_DISCOVERY_FINISHED {
+                    // Closes the channels, so the user knows no more devices will be found
+                    senders.lock().unwrap().take();
+                    finished_tx.send_replace(true);
+                    return Ok(());
+                }
+                let senders = senders.lock().unwrap();
+                let Some(senders) = senders.as_ref() else {
+                    return Ok(());
+                };
+                let extra = "android.bluetooth.device.extra.DEVICE".new_jobject(env)?;
+                let device = env
+                    .call_method(
+                        intent,
+                        "getParcelableExtra",
+                        "(Ljava/lang/String;)Landroid/os/Parcelable;",
+                        &[(&extra).into()],
+                    )
+                    .get_object(env)?;
+                if device.is_null() {
+                    return Ok(());
+                }
+                let extra = "android.bluetooth.device.extra.RSSI".new_jobject(env)?;
+                let rssi = env
+                    .call_method(
+                        intent,
+                        "getShortExtra",
+                        "(Ljava/lang/String;S)S",
+                        &[(&extra).into(), i16::MIN.into()],
+                    )
+                    .get_short()?;
+                let rssi = (rssi != i16::MIN).then_some(rssi);
+                if let Some(rssi) = rssi {
+                    let address = env
+                        .call_method(&device, "getAddress", "()Ljava/lang/String;", &[])
+                        .get_object(env)?
+                        .get_string(env)?;
+                    rssi_readings
+                        .lock()
+                        .unwrap()
+                        .insert(address, crate::RssiReading::new(rssi));
+                }
+                let extra = "android.bluetooth.device.extra.NAME".new_jobject(env)?;
+                let name = env
+                    .call_method(
+                        intent,
+                        "getStringExtra",
+                        "(Ljava/lang/String;)Ljava/lang/String;",
+                        &[(&extra).into()],
+                    )
+                    .get_object(env)?;
+                let name = if name.is_null() {
+                    None
+                } else {
+                    Some(name.get_string(env)?)
+                };
+                let transport = env.call_method(&device, "getType", "()I", &[]).get_int()?;
+                let transport = device::device_transport(transport);
+                let mut build = || -> Result<BluetoothDevice, jni::errors::Error> {
+                    Ok(
+                        BluetoothDevice::new(env.new_global_ref(&device)?, java2.clone())
+                            .with_battery_levels(battery_levels.clone())
+                            .with_rssi_readings(rssi_readings.clone())
+                            .with_profile_proxies(profile_proxies.clone())
+                            .with_response_timeout(response_timeout),
+                    )
+                };
+                let _ = senders.events.try_send(crate::DiscoveryEvent::DeviceAdded {
+                    device: crate::BluetoothDevice::Android(build()?),
+                    transport,
+                });
+                let _ = senders.devices.try_send(DiscoveredDevice {
+                    device: build()?,
+                    rssi,
+                    name,
+                    transport,
+                });
+                Ok(())
+            },
+        );
+        let found = match found {
+            Ok(r) => Some(r),
+            Err(e) => {
+                log::error!("Failed to watch for discovered devices: {}", e);
+                None
+            }
+        };
+        {
+            let mut java = java.lock().unwrap();
+            java.use_env(|env, _context| {
+                env.call_method(&adapter, "startDiscovery", "()Z", &[])
+                    .get_boolean()
+                    .map_err(|e| permission_err(env, "startDiscovery", e))
+            })?;
+        }
+        Ok(Self {
+            adapter,
+            java,
+            events: Some(rx),
+            devices: Some(devices_rx),
+            finished,
+            _found: found,
+        })
+    }
+
+    /// Take the receiver for the devices found, along with the name and signal strength reported when they were
+    /// found. This can only be taken once, later calls return `None`. The receiver is closed when discovery
+    /// finishes.
+    pub fn devices(&mut self) -> Option<tokio::sync::mpsc::Receiver<DiscoveredDevice>> {
+        self.devices.take()
+    }
+}
+
+impl super::BluetoothDiscoveryTrait for BluetoothDiscovery {
+    fn take_events(&mut self) -> Option<tokio::sync::mpsc::Receiver<crate::DiscoveryEvent>> {
+        self.events.take()
+    }
+
+    fn try_recv(&mut self) -> Option<crate::DiscoveryEvent> {
+        self.events.as_mut()?.try_recv().ok()
+    }
+
+    /// Written out by hand because the java instance of the discovery is not Send, the future only borrows the
+    /// receiver
+    fn next<'life0, 'async_trait>(
+        &'life0 mut self,
+    ) -> std::pin::Pin<
+        Box<dyn std::future::Future<Output = Option<crate::DiscoveryEvent>> + Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let events = self.events.as_mut();
+        Box::pin(async move { events?.recv().await })
+    }
+
+    fn is_running(&self) -> bool {
+        !*self.finished.borrow()
+    }
+
+    fn finished(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.finished.clone()
     }
 }
 
@@ -109,7 +381,362 @@ impl Drop for BluetoothDiscovery {
     }
 }
 
-/// And object used for communication with a remote bluetooth device
+/// How long the user has to answer a permission request
+const PERMISSION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+/// The java class the activity forwards onRequestPermissionsResult to
+const PERMISSION_BRIDGE_CLASS: &str = "com.github.uglyoldbob.bluetooth_rust.PermissionResultBridge";
+/// The first request code of permission requests, so their results are told apart from requests of the app
+const PERMISSION_REQUEST_BASE: i32 = 0x4254;
+/// How many request codes are used for permission requests
+const PERMISSION_REQUEST_CODES: u32 = 256;
+/// PackageManager.PERMISSION_GRANTED
+const PERMISSION_GRANTED: i32 = 0;
+
+/// Where the granted android permissions of a permission request are sent
+type PermissionReply = tokio::sync::oneshot::Sender<Vec<String>>;
+
+/// The permission requests waiting for their result, by request code
+static PERMISSION_REQUESTS: Mutex<std::collections::BTreeMap<i32, PermissionReply>> =
+    Mutex::new(std::collections::BTreeMap::new());
+/// Used to pick the request code of the next permission request
+static NEXT_PERMISSION_REQUEST: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+/// The android permissions granted by a permission request. A request the user cancelled has no results.
+fn granted_permissions(
+    env: &mut jni::JNIEnv,
+    permissions: &jni::objects::JObjectArray,
+    grant_results: &jni::objects::JIntArray,
+) -> Result<Vec<String>, jni::errors::Error> {
+    let mut results = vec![0; env.get_array_length(grant_results)? as usize];
+    env.get_int_array_region(grant_results, 0, &mut results)?;
+    let mut granted = Vec::new();
+    for (i, result) in results.into_iter().enumerate() {
+        if result == PERMISSION_GRANTED {
+            let permission = env.get_object_array_element(permissions, i as i32)?;
+            granted.push(permission.get_string(env)?);
+        }
+    }
+    Ok(granted)
+}
+
+/// PermissionResultBridge.nativeRequestPermissionsResult
+extern "system" fn native_request_permissions_result(
+    mut env: jni::JNIEnv,
+    _class: jni::objects::JClass,
+    request_code: jni::sys::jint,
+    permissions: jni::objects::JObjectArray,
+    grant_results: jni::objects::JIntArray,
+) {
+    let Some(reply) = PERMISSION_REQUESTS.lock().unwrap().remove(&request_code) else {
+        return;
+    };
+    match granted_permissions(&mut env, &permissions, &grant_results) {
+        Ok(granted) => {
+            let _ = reply.send(granted);
+        }
+        Err(e) => {
+            // Dropping the reply reports every permission of the request as denied
+            let e = jerr(&mut env, e);
+            log::error!("Failed to read the permission results: {}", e);
+        }
+    }
+}
+
+/// Load the PermissionResultBridge class from the application and connect its native method
+fn register_permission_bridge(
+    env: &mut jni::JNIEnv,
+    context: &jni::objects::JObject,
+) -> Result<(), jni::errors::Error> {
+    let class = load_app_class(env, context, PERMISSION_BRIDGE_CLASS)?;
+    let native = jni::NativeMethod {
+        name: "nativeRequestPermissionsResult".into(),
+        sig: "(I[Ljava/lang/String;[I)V".into(),
+        fn_ptr: native_request_permissions_result as *mut _,
+    };
+    env.register_native_methods(&class, &[native])
+}
+
+/// The android permission for a permission at the api level, which is `None` when no permission is needed
+fn permission_name(permission: crate::BluetoothPermission, sdk: i32) -> Option<&'static str> {
+    use crate::BluetoothPermission;
+    match (permission, sdk >= 31) {
+        (BluetoothPermission::Connect, true) => Some("android.permission.BLUETOOTH_CONNECT"),
+        (BluetoothPermission::Connect, false) => Some("android.permission.BLUETOOTH"),
+        (BluetoothPermission::Scan, true) => Some("android.permission.BLUETOOTH_SCAN"),
+        (BluetoothPermission::Advertise, true) => Some("android.permission.BLUETOOTH_ADVERTISE"),
+        (BluetoothPermission::Scan | BluetoothPermission::Advertise, false) => {
+            Some("android.permission.BLUETOOTH_ADMIN")
+        }
+        (BluetoothPermission::LegacyBluetooth, true) => None,
+        (BluetoothPermission::LegacyBluetooth, false) => Some("android.permission.BLUETOOTH"),
+        (BluetoothPermission::FineLocation, _) => Some("android.permission.ACCESS_FINE_LOCATION"),
+    }
+}
+
+/// Ask the user for the permissions with Activity.requestPermissions. The result is given to
+/// onRequestPermissionsResult of the activity with the request code.
+fn request_permissions(
+    env: &mut jni::JNIEnv,
+    activity: &jni::objects::JObject,
+    permissions: &[&str],
+    request_code: i32,
+) -> Result<(), jni::errors::Error> {
+    let array = env.new_object_array(
+        permissions.len() as i32,
+        "java/lang/String",
+        jni::objects::JObject::null(),
+    )?;
+    for (i, permission) in permissions.iter().enumerate() {
+        let permission = permission.new_jobject(env)?;
+        env.set_object_array_element(&array, i as i32, permission)?;
+    }
+    env.call_method(
+        activity,
+        "requestPermissions",
+        "([Ljava/lang/String;I)V",
+        &[(&array).into(), request_code.into()],
+    )?;
+    Ok(())
+}
+
+/// BluetoothProfile.HEADSET
+const PROFILE_HEADSET: i32 = 1;
+/// BluetoothProfile.A2DP
+const PROFILE_A2DP: i32 = 2;
+/// BluetoothProfile.HID_DEVICE
+const PROFILE_HID_DEVICE: i32 = 19;
+/// How long to wait for android to connect the hid device profile proxy
+const HID_PROXY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// The connected profile proxies, by android profile number
+pub(crate) type ProfileProxyMap =
+    Arc<Mutex<std::collections::HashMap<i32, jni::objects::GlobalRef>>>;
+
+/// The android profile number of a well known profile
+fn profile_number(profile: crate::WellKnownProfile) -> i32 {
+    match profile {
+        crate::WellKnownProfile::A2dp => PROFILE_A2DP,
+        crate::WellKnownProfile::Hfp => PROFILE_HEADSET,
+    }
+}
+
+/// Requests the profile proxies of the well known profiles and of the hid device profile, and closes them when
+/// dropped
+struct ProfileProxies {
+    /// The adapter the proxies were requested from
+    adapter: jni::objects::GlobalRef,
+    /// The java instance
+    java: Arc<Mutex<super::Java>>,
+    /// The BluetoothProfile.ServiceListener given to getProfileProxy, kept for as long as the proxies are used
+    _listener: jni_min_helper::JniProxy,
+    /// The proxies connected so far
+    proxies: ProfileProxyMap,
+}
+
+impl ProfileProxies {
+    /// Request the proxies, which android connects in the background
+    fn new(
+        adapter: &jni::objects::GlobalRef,
+        java: &Arc<Mutex<super::Java>>,
+        proxies: ProfileProxyMap,
+    ) -> Result<Self, std::io::Error> {
+        let proxies2 = proxies.clone();
+        let listener = jni_min_helper::JniProxy::build(
+            None,
+            ["android/bluetooth/BluetoothProfile$ServiceListener"],
+            move |env, method, args| {
+                let name = env
+                    .call_method(method, "getName", "()Ljava/lang/String;", &[])
+                    .get_object(env)?
+                    .get_string(env)?;
+                match (name.as_str(), args) {
+                    ("onServiceConnected", [profile, proxy]) => {
+                        let profile = env.call_method(profile, "intValue", "()I", &[]).get_int()?;
+                        let proxy = env.new_global_ref(proxy)?;
+                        proxies2.lock().unwrap().insert(profile, proxy);
+                    }
+                    ("onServiceDisconnected", [profile]) => {
+                        let profile = env.call_method(profile, "intValue", "()I", &[]).get_int()?;
+                        proxies2.lock().unwrap().remove(&profile);
+                    }
+                    _ => {}
+                }
+                Ok(jni::objects::JObject::null())
+            },
+        )
+        .map_err(std::io::Error::other)?;
+        {
+            let mut java2 = java.lock().unwrap();
+            java2.use_env(|env, context| {
+                for profile in [PROFILE_A2DP, PROFILE_HEADSET, PROFILE_HID_DEVICE] {
+                    let requested = env
+                        .call_method(
+                            adapter,
+                            "getProfileProxy",
+                            "(Landroid/content/Context;Landroid/bluetooth/BluetoothProfile$ServiceListener;I)Z",
+                            &[(&context).into(), (&*listener).into(), profile.into()],
+                        )
+                        .get_boolean()
+                        .map_err(|e| jerr(env, e))?;
+                    if !requested {
+                        log::warn!("The proxy for profile {} was not requested", profile);
+                    }
+                }
+                Ok::<_, std::io::Error>(())
+            })?;
+        }
+        Ok(Self {
+            adapter: adapter.clone(),
+            java: java.clone(),
+            _listener: listener,
+            proxies,
+        })
+    }
+}
+
+impl Drop for ProfileProxies {
+    fn drop(&mut self) {
+        let proxies: Vec<_> = self.proxies.lock().unwrap().drain().collect();
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            for (profile, proxy) in proxies {
+                let _ = env
+                    .call_method(
+                        &self.adapter,
+                        "closeProfileProxy",
+                        "(ILandroid/bluetooth/BluetoothProfile;)V",
+                        &[profile.into(), (&proxy).into()],
+                    )
+                    .clear_ex();
+            }
+        });
+    }
+}
+
+/// Decode the name, services, and manufacturer data from the raw advertising data of a scan record
+fn parse_advertisement(
+    record: &[u8],
+) -> (
+    Option<String>,
+    Vec<crate::BluetoothUuid>,
+    std::collections::HashMap<u16, Vec<u8>>,
+) {
+    let mut name = None;
+    let mut uuids = Vec::new();
+    let mut manufacturer_data = std::collections::HashMap::new();
+    let mut rest = record;
+    while let [len, tail @ ..] = rest {
+        let len = *len as usize;
+        // A zero length ends the significant part of the data
+        if len == 0 || len > tail.len() {
+            break;
+        }
+        let (field, next) = tail.split_at(len);
+        rest = next;
+        let (kind, data) = (field[0], &field[1..]);
+        match kind {
+            // Incomplete and complete lists of 16 bit service uuids
+            0x02 | 0x03 => {
+                for id in data.chunks_exact(2) {
+                    let id = u16::from_le_bytes([id[0], id[1]]);
+                    uuids.push(crate::BluetoothUuid::from_u16(id));
+                }
+            }
+            // Incomplete and complete lists of 32 bit service uuids
+            0x04 | 0x05 => {
+                for id in data.chunks_exact(4) {
+                    let id = u32::from_le_bytes([id[0], id[1], id[2], id[3]]);
+                    uuids.push(crate::BluetoothUuid::from_u32(id));
+                }
+            }
+            // Incomplete and complete lists of 128 bit service uuids
+            0x06 | 0x07 => {
+                for id in data.chunks_exact(16) {
+                    let id = u128::from_le_bytes(id.try_into().unwrap());
+                    uuids.push(crate::BluetoothUuid::from_u128(id));
+                }
+            }
+            // Shortened and complete local name, the complete name is preferred
+            0x08 if name.is_none() => name = Some(String::from_utf8_lossy(data).into_owned()),
+            0x09 => name = Some(String::from_utf8_lossy(data).into_owned()),
+            0xff if data.len() >= 2 => {
+                let company = u16::from_le_bytes([data[0], data[1]]);
+                manufacturer_data.insert(company, data[2..].to_vec());
+            }
+            _ => {}
+        }
+    }
+    (name, uuids, manufacturer_data)
+}
+
+/// A write to perform on the writer thread, and where to send the result. An empty write is a flush.
+type WriteRequest = (
+    Vec<u8>,
+    tokio::sync::oneshot::Sender<std::io::Result<usize>>,
+);
+
+/// The signature of InputStream.read(byte[], int, int)
+const INPUT_STREAM_READ: &str = "([BII)I";
+/// The signature of OutputStream.write(byte[], int, int)
+const OUTPUT_STREAM_WRITE: &str = "([BII)V";
+/// The signature of OutputStream.flush()
+const OUTPUT_STREAM_FLUSH: &str = "()V";
+
+/// The number of bytes InputStream.read copied into the java array, when it was asked for at most `len` bytes.
+/// The end of the stream (-1) is reported as 0 bytes, like `std::io::Read`.
+fn read_count(ret: jni::sys::jint, len: usize) -> std::io::Result<usize> {
+    match usize::try_from(ret) {
+        Err(_) => Ok(0),
+        Ok(n) if n > len => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("InputStream.read returned {n} bytes for a read of {len} bytes"),
+        )),
+        Ok(n) => Ok(n),
+    }
+}
+
+/// Copy as much of the pending data as fits in the buffer, removing it from the pending data
+fn take_pending(pending: &mut Vec<u8>, buf: &mut [u8]) -> usize {
+    let n = pending.len().min(buf.len());
+    buf[..n].copy_from_slice(&pending[..n]);
+    pending.drain(..n);
+    n
+}
+
+/// The java methods used for stream io, looked up once when the stream is built
+#[derive(Clone, Copy)]
+struct StreamMethods {
+    /// InputStream.read([BII)I
+    read: jni::objects::JMethodID,
+    /// OutputStream.write([BII)V
+    write: jni::objects::JMethodID,
+    /// OutputStream.flush()V
+    flush: jni::objects::JMethodID,
+}
+
+impl StreamMethods {
+    /// The return type of InputStream.read, given to `call_method_unchecked`
+    const READ_RETURN: jni::signature::ReturnType =
+        jni::signature::ReturnType::Primitive(jni::signature::Primitive::Int);
+    /// The return type of OutputStream.write and OutputStream.flush, given to `call_method_unchecked`
+    const WRITE_RETURN: jni::signature::ReturnType =
+        jni::signature::ReturnType::Primitive(jni::signature::Primitive::Void);
+
+    /// Look up the methods
+    fn new(env: &mut jni::JNIEnv) -> Result<Self, jni::errors::Error> {
+        Ok(Self {
+            read: env.get_method_id("java/io/InputStream", "read", INPUT_STREAM_READ)?,
+            write: env.get_method_id("java/io/OutputStream", "write", OUTPUT_STREAM_WRITE)?,
+            flush: env.get_method_id("java/io/OutputStream", "flush", OUTPUT_STREAM_FLUSH)?,
+        })
+    }
+}
+
+/// And object used for communication with a remote bluetooth device. The socket is closed when this is dropped.
+///
+/// The blocking java streams are used directly by the sync functions. The async functions use a reader
+/// thread and a writer thread, which are started on first use. Once the stream has been read asynchronously,
+/// the sync read also takes its data from the reader thread.
 pub struct RfcommStream {
     /// The BluetoothSocket object
     socket: OnceLock<jni::objects::GlobalRef>,
@@ -119,33 +746,89 @@ pub struct RfcommStream {
     output: OnceLock<jni::objects::GlobalRef>,
     /// The java instance
     java: Arc<Mutex<super::Java>>,
+    /// The methods of the input and output streams
+    methods: StreamMethods,
+    /// The reusable java array for sync reads, replaced by a larger one when needed
+    array_read: GlobalRef,
+    /// The size of the reads done by the reader thread
+    chunk_size: usize,
+    /// The reusable java array for sync writes, replaced by a larger one when needed
+    array_write: GlobalRef,
+    /// The data read by the reader thread
+    reader: Option<tokio::sync::mpsc::Receiver<std::io::Result<Vec<u8>>>>,
+    /// Data from the reader thread that did not fit in the buffer of the last read
+    pending: Vec<u8>,
+    /// The queue of writes for the writer thread
+    writer: Option<tokio::sync::mpsc::UnboundedSender<WriteRequest>>,
+    /// The result of the write in progress on the writer thread
+    write_reply: Option<tokio::sync::oneshot::Receiver<std::io::Result<usize>>>,
+    /// The address of the remote device, read when the stream is created
+    peer: crate::BluetoothAdapterAddress,
+    /// The name of the remote device, read when the stream is created
+    peer_name: Option<String>,
+    /// The socket has been closed with `close`
+    closed: bool,
+    /// How long a sync read waits for data, forever when `None`
+    read_timeout: Option<std::time::Duration>,
+    /// How long a sync write or flush waits, forever when `None`
+    write_timeout: Option<std::time::Duration>,
+    /// Was the socket created to require an authenticated and encrypted link
+    secure: bool,
 }
 
 impl RfcommStream {
-    /// Build a new Self, getting the input and output streams needed for communication
+    /// The initial size of the reusable java arrays
+    const ARRAY_SIZE: usize = 4096;
+
+    /// Build a new Self, getting the input and output streams needed for communication. Secure is how the
+    /// socket was created, because BluetoothSocket does not report it.
     pub fn new(
         socket: OnceLock<jni::objects::GlobalRef>,
         java: Arc<Mutex<super::Java>>,
-    ) -> Result<Self, String> {
-        let (input, output) = {
+        secure: bool,
+    ) -> Result<Self, crate::BluetoothError> {
+        let (input, output, methods, array_read, array_write, peer, peer_name) = {
             let mut java2 = java.lock().unwrap();
             java2.use_env(|env, _context| {
                 let socket = socket.get().unwrap().as_obj();
                 let e = env
                     .call_method(socket, "getInputStream", "()Ljava/io/InputStream;", &[])
                     .get_object(env)
-                    .map_err(|e| jerr(env, e).to_string())?;
-                let input = env
-                    .new_global_ref(&e)
-                    .map_err(|e| jerr(env, e).to_string())?;
+                    .map_err(|e| jerr(env, e))?;
+                let input = env.new_global_ref(&e).map_err(|e| jerr(env, e))?;
                 let e = env
                     .call_method(socket, "getOutputStream", "()Ljava/io/OutputStream;", &[])
                     .get_object(env)
-                    .map_err(|e| jerr(env, e).to_string())?;
-                let output = env
-                    .new_global_ref(&e)
-                    .map_err(|e| jerr(env, e).to_string())?;
-                Ok::<(GlobalRef, GlobalRef), String>((input, output))
+                    .map_err(|e| jerr(env, e))?;
+                let output = env.new_global_ref(&e).map_err(|e| jerr(env, e))?;
+                let device = env
+                    .call_method(
+                        socket,
+                        "getRemoteDevice",
+                        "()Landroid/bluetooth/BluetoothDevice;",
+                        &[],
+                    )
+                    .get_object(env)
+                    .map_err(|e| jerr(env, e))?;
+                let (peer, peer_name) = device_identity(env, &device)?;
+                let methods = StreamMethods::new(env).map_err(|e| jerr(env, e))?;
+                let array_read = env
+                    .new_byte_array(Self::ARRAY_SIZE as i32)
+                    .global_ref(env)
+                    .map_err(|e| jerr(env, e))?;
+                let array_write = env
+                    .new_byte_array(Self::ARRAY_SIZE as i32)
+                    .global_ref(env)
+                    .map_err(|e| jerr(env, e))?;
+                Ok::<_, crate::BluetoothError>((
+                    input,
+                    output,
+                    methods,
+                    array_read,
+                    array_write,
+                    peer,
+                    peer_name,
+                ))
             })
         }?;
         Ok(Self {
@@ -153,181 +836,1074 @@ impl RfcommStream {
             input: input.into(),
             output: output.into(),
             java,
+            methods,
+            array_read,
+            array_write,
+            chunk_size: Self::ARRAY_SIZE,
+            reader: None,
+            pending: Vec::new(),
+            writer: None,
+            write_reply: None,
+            peer,
+            peer_name,
+            closed: false,
+            read_timeout: None,
+            write_timeout: None,
+            secure,
         })
     }
-}
 
-impl std::io::Read for RfcommStream {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let mut java2 = self.java.lock().unwrap();
-        java2.use_env(|env, _context| {
-            let ba = env
-                .new_byte_array(buf.len() as i32)
-                .map_err(|e| std::io::Error::other(e))?;
-            let socket = self.socket.get().unwrap().as_obj();
-            let e = env
-                .call_method(socket, "readNBytes", "([BII)I", &[(&ba).into()])
-                .get_object(env)
-                .map_err(|e| std::io::Error::other(jerr(env, e).to_string()))?;
-            let l = e.get_int(env).map_err(|e| std::io::Error::other(e))?;
-            let a = env
-                .convert_byte_array(ba)
-                .map_err(|e| std::io::Error::other(e))?;
-            buf[0..l as usize].copy_from_slice(&a);
-            Ok(l as usize)
-        })
+    /// The address of the remote device, from BluetoothSocket.getRemoteDevice
+    pub fn peer_address(&self) -> crate::BluetoothAdapterAddress {
+        self.peer.clone()
     }
-}
 
-impl std::io::Write for RfcommStream {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let mut java2 = self.java.lock().unwrap();
-        java2.use_env(|env, _context| {
-            let ba = env
-                .byte_array_from_slice(buf)
-                .map_err(|e| std::io::Error::other(e))?;
-            let socket = self.socket.get().unwrap().as_obj();
-            let _ = env
-                .call_method(socket, "write", "([B)", &[(&ba).into()])
-                .get_object(env)
-                .map_err(|e| jerr(env, e))?;
-            Ok(buf.len())
-        })
+    /// The name of the remote device, if it was known when the stream was created
+    pub fn peer_name(&self) -> Option<String> {
+        self.peer_name.clone()
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        let mut java2 = self.java.lock().unwrap();
-        java2.use_env(|env, _context| {
-            let socket = self.socket.get().unwrap().as_obj();
-            let _ = env
-                .call_method(socket, "flush", "()", &[])
-                .get_object(env)
-                .map_err(|e| jerr(env, e))?;
-            Ok(())
-        })
+    /// Flush the stream and close the socket, from BluetoothSocket.close. The reader and writer threads stop
+    /// once the socket is closed. Closing a closed stream does nothing.
+    pub fn close(&mut self) -> Result<(), crate::BluetoothError> {
+        use std::io::Write;
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        let _ = self.flush();
+        // Dropping the queue stops the writer thread, and the reader thread stops when its read fails
+        self.writer = None;
+        self.write_reply = None;
+        self.reader = None;
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            env.call_method(self.socket.get().unwrap(), "close", "()V", &[])
+                .clear_ex()
+                .map_err(|e| jerr(env, e))
+        })?;
+        Ok(())
     }
-}
 
-/// Very similar to the BluetoothRfcommProfile
-pub struct BluetoothRfcommConnectable {
-    /// A socket that can be used to accept bluetooth connections
-    socket: OnceLock<jni::objects::GlobalRef>,
-    /// The java instance
-    java: Arc<Mutex<super::Java>>,
-}
+    /// Set how long a sync read waits for data, `None` waits forever. Reads with a timeout are done by the reader
+    /// thread, so that the wait can be given up.
+    pub fn set_read_timeout(&mut self, timeout: Option<std::time::Duration>) {
+        self.read_timeout = timeout;
+    }
 
-impl super::BluetoothL2capConnectableSyncTrait for BluetoothRfcommConnectable {
-    fn accept(self, timeout: std::time::Duration) -> Result<crate::BluetoothStream, String> {
-        let mut java2 = self.java.lock().unwrap();
-        let millis = (timeout.as_millis() as i32).into();
-        java2.use_env(|env, _context| {
-            let socket = self.socket.get().unwrap().as_obj();
-            let e = env
-                .call_method(
-                    socket,
-                    "accept",
-                    "(I)Landroid/bluetooth/BluetoothSocket;",
-                    &[millis],
-                )
-                .get_object(env)
-                .map_err(|e| jerr(env, e).to_string())?;
-            let socket = env
-                .new_global_ref(&e)
-                .map_err(|e| jerr(env, e).to_string())?;
-            let s = RfcommStream::new(socket.into(), self.java.clone())?;
-            let comm = crate::BluetoothStream::Android(s);
-            Ok(comm)
-        })
+    /// Set how long a sync write or flush waits, `None` waits forever. Writes with a timeout are done by the
+    /// writer thread, a write that times out may still be sent.
+    pub fn set_write_timeout(&mut self, timeout: Option<std::time::Duration>) {
+        self.write_timeout = timeout;
     }
-}
 
-impl super::BluetoothRfcommConnectableSyncTrait for BluetoothRfcommConnectable {
-    fn accept(self, timeout: std::time::Duration) -> Result<crate::BluetoothStream, String> {
-        let mut java2 = self.java.lock().unwrap();
-        let millis = (timeout.as_millis() as i32).into();
-        java2.use_env(|env, _context| {
-            let socket = self.socket.get().unwrap().as_obj();
-            let e = env
-                .call_method(
-                    socket,
-                    "accept",
-                    "(I)Landroid/bluetooth/BluetoothSocket;",
-                    &[millis],
-                )
-                .get_object(env)
-                .map_err(|e| jerr(env, e).to_string())?;
-            let socket = env
-                .new_global_ref(&e)
-                .map_err(|e| jerr(env, e).to_string())?;
-            let s = RfcommStream::new(socket.into(), self.java.clone())?;
-            let comm = crate::BluetoothStream::Android(s);
-            Ok(comm)
-        })
+    /// The read timeout, `None` means sync reads wait forever
+    pub fn read_timeout(&self) -> Option<std::time::Duration> {
+        self.read_timeout
     }
-}
 
-/// A bluetooth rfcomm profile
-pub struct BluetoothRfcommProfile {
-    /// A socket that can be used to accept bluetooth connections
-    socket: OnceLock<jni::objects::GlobalRef>,
-    /// The java instance
-    java: Arc<Mutex<super::Java>>,
-}
+    /// The write timeout, `None` means sync writes wait forever
+    pub fn write_timeout(&self) -> Option<std::time::Duration> {
+        self.write_timeout
+    }
 
-impl crate::BluetoothRfcommProfileSyncTrait for BluetoothRfcommProfile {
-    fn connectable(&mut self) -> Result<crate::BluetoothRfcommConnectableSync, String> {
-        Ok(crate::BluetoothRfcommConnectableSync::Android(
-            BluetoothRfcommConnectable {
-                socket: self.socket.clone(),
-                java: self.java.clone(),
-            },
-        ))
+    /// Was the socket created to require an authenticated and encrypted link
+    pub fn is_secure(&self) -> bool {
+        self.secure
     }
-}
 
-/// The bluetooth adapter struct for android code
-pub struct Bluetooth {
-    adapter: jni::objects::GlobalRef,
-    java: Arc<Mutex<super::Java>>,
-    /// An instance of Intent, created with registerReceiver
-    receiver: Option<jni::objects::GlobalRef>,
-    /// The broadcast_receiver for the bluetooth uuid
-    blue_uuid_receiver: Option<jni_min_helper::BroadcastReceiver>,
-}
+    /// Is the socket still connected, from BluetoothSocket.isConnected
+    pub fn is_open(&self) -> bool {
+        if self.closed {
+            return false;
+        }
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            env.call_method(self.socket.get().unwrap(), "isConnected", "()Z", &[])
+                .get_boolean()
+                .map_err(|e| jerr(env, e))
+                .unwrap_or(false)
+        })
+    }
 
-impl super::BluetoothAdapterTrait for Bluetooth {
-    fn supports_async(&mut self) -> Option<&mut dyn super::AsyncBluetoothAdapterTrait> {
-        None
+    /// Set the size of the reads done by the reader thread, when the stream is read asynchronously. This must
+    /// be done before the first read to have an effect. Sync reads are sized by the buffer of the caller.
+    pub fn set_chunk_size(&mut self, size: usize) {
+        self.chunk_size = size.clamp(1, i32::MAX as usize);
     }
 
-    fn supports_sync(&mut self) -> Option<&mut dyn super::SyncBluetoothAdapterTrait> {
-        Some(self)
+    /// The largest packet that can be received at once, from BluetoothSocket.getMaxReceivePacketSize
+    pub fn max_receive_packet_size(&self) -> Result<usize, std::io::Error> {
+        self.packet_size("getMaxReceivePacketSize")
     }
-}
 
-impl crate::SyncBluetoothAdapterTrait for Bluetooth {
-    fn register_l2cap_profile(
-        &self,
-        _settings: super::BluetoothL2capProfileSettings,
-    ) -> Result<crate::BluetoothL2capProfileAsync, String> {
-        todo!()
+    /// The largest packet that can be sent at once, from BluetoothSocket.getMaxTransmitPacketSize. Writes larger
+    /// than this fail on l2cap channels.
+    pub fn max_transmit_packet_size(&self) -> Result<usize, std::io::Error> {
+        self.packet_size("getMaxTransmitPacketSize")
     }
 
-    fn register_rfcomm_profile(
-        &self,
-        settings: crate::BluetoothRfcommProfileSettings,
-    ) -> Result<crate::BluetoothRfcommProfileSync, String> {
-        let mut java2 = self.java.lock().unwrap();
-        {
-            java2.use_env(|env, context| {
-                let jsettings = {
+    /// Call one of the packet size methods of the socket
+    fn packet_size(&self, method: &str) -> Result<usize, std::io::Error> {
+        let Some(socket) = self.socket.get() else {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        };
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            env.call_method(socket, method, "()I", &[])
+                .get_int()
+                .map(|size| size.max(0) as usize)
+                .map_err(|e| jerr(env, e))
+        })
+    }
+
+    /// Blocks until data is available from the input stream. The java array is replaced by a larger one when the
+    /// buffer does not fit. The end of the stream is reported as a read of 0 bytes.
+    fn read_input(
+        env: &mut jni::JNIEnv,
+        input: &jni::objects::JObject,
+        methods: &StreamMethods,
+        array: &mut GlobalRef,
+        buf: &mut [u8],
+    ) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let len = buf.len().min(i32::MAX as usize);
+        let java_array: &jni::objects::JByteArray<'_> = array.as_obj().into();
+        if (env.get_array_length(java_array).map_err(|e| jerr(env, e))? as usize) < len {
+            *array = env
+                .new_byte_array(len as i32)
+                .global_ref(env)
+                .map_err(|e| jerr(env, e))?;
+        }
+        let array: &jni::objects::JByteArray<'_> = array.as_obj().into();
+        // Safety: the method id is InputStream.read([BII)I, and the arguments match the signature
+        let l = unsafe {
+            env.call_method_unchecked(
+                input,
+                methods.read,
+                StreamMethods::READ_RETURN,
+                &[
+                    jni::sys::jvalue { l: array.as_raw() },
+                    jni::sys::jvalue { i: 0 },
+                    jni::sys::jvalue {
+                        i: len as jni::sys::jint,
+                    },
+                ],
+            )
+        }
+        .get_int()
+        .map_err(|e| jerr(env, e))?;
+        let l = read_count(l, len)?;
+        // Safety: casts `&mut [u8]` to `&mut [i8]` for `get_byte_array_region`,
+        // `read_count` checked that `l` <= `len` <= `buf.len()`.
+        let tmp = unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut i8, l) };
+        env.get_byte_array_region(array, 0, tmp)
+            .map_err(|e| jerr(env, e))?;
+        Ok(l)
+    }
+
+    /// Write all of the data to the output stream, or flush it when there is no data. The java array is replaced
+    /// by a larger one when the data does not fit.
+    fn write_output(
+        env: &mut jni::JNIEnv,
+        socket: &jni::objects::JObject,
+        output: &jni::objects::JObject,
+        methods: &StreamMethods,
+        array: &mut GlobalRef,
+        buf: &[u8],
+    ) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            // Safety: the method id is OutputStream.flush()V, which takes no arguments
+            return unsafe {
+                env.call_method_unchecked(output, methods.flush, StreamMethods::WRITE_RETURN, &[])
+            }
+            .clear_ex()
+            .map(|_| 0)
+            .map_err(|e| Self::write_error(env, socket, e));
+        }
+        let java_array: &jni::objects::JByteArray<'_> = array.as_obj().into();
+        let len = env.get_array_length(java_array).map_err(|e| jerr(env, e))? as usize;
+        if len < buf.len() {
+            *array = env
+                .byte_array_from_slice(buf)
+                .global_ref(env)
+                .map_err(|e| jerr(env, e))?;
+        } else {
+            // Safety: casts `&[u8]` to `&[i8]` for `set_byte_array_region`.
+            let tmp = unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const i8, buf.len()) };
+            env.set_byte_array_region(java_array, 0, tmp)
+                .map_err(|e| jerr(env, e))?;
+        }
+        // Safety: the method id is OutputStream.write([BII)V, and the arguments match the signature
+        unsafe {
+            env.call_method_unchecked(
+                output,
+                methods.write,
+                StreamMethods::WRITE_RETURN,
+                &[
+                    jni::sys::jvalue { l: array.as_raw() },
+                    jni::sys::jvalue { i: 0 },
+                    jni::sys::jvalue {
+                        i: buf.len() as jni::sys::jint,
+                    },
+                ],
+            )
+        }
+        .clear_ex()
+        .map_err(|e| Self::write_error(env, socket, e))?;
+        Ok(buf.len())
+    }
+
+    /// Convert the error from writing to the output stream, which is reported as not connected when the socket
+    /// has disconnected
+    fn write_error(
+        env: &mut jni::JNIEnv,
+        socket: &jni::objects::JObject,
+        e: jni::errors::Error,
+    ) -> std::io::Error {
+        let err = jerr(env, e);
+        let connected = env
+            .call_method(socket, "isConnected", "()Z", &[])
+            .get_boolean()
+            .unwrap_or(false);
+        if connected {
+            err
+        } else {
+            std::io::Error::from(std::io::ErrorKind::NotConnected)
+        }
+    }
+
+    /// Start the thread that reads the input stream. The thread stops at the end of the stream, or when the
+    /// stream is dropped.
+    fn start_reader(&mut self) -> tokio::sync::mpsc::Receiver<std::io::Result<Vec<u8>>> {
+        let app = self.java.lock().unwrap().get_app();
+        let input = self.input.get().unwrap().clone();
+        let methods = self.methods;
+        let chunk_size = self.chunk_size;
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        std::thread::spawn(move || {
+            let mut java = super::Java::make(app);
+            let array = java.use_env(|env, _context| {
+                env.new_byte_array(chunk_size as i32)
+                    .global_ref(env)
+                    .map_err(|e| jerr(env, e))
+            });
+            let mut array = match array {
+                Ok(a) => a,
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(e));
+                    return;
+                }
+            };
+            let mut buf = vec![0u8; chunk_size];
+            loop {
+                let r = java.use_env(|env, _context| {
+                    Self::read_input(env, input.as_obj(), &methods, &mut array, &mut buf)
+                });
+                let done = !matches!(r, Ok(n) if n > 0);
+                if tx.blocking_send(r.map(|n| buf[..n].to_vec())).is_err() || done {
+                    break;
+                }
+            }
+        });
+        rx
+    }
+
+    /// Start the thread that writes to the output stream. The thread stops when the stream is dropped.
+    fn start_writer(&mut self) -> tokio::sync::mpsc::UnboundedSender<WriteRequest> {
+        let app = self.java.lock().unwrap().get_app();
+        let socket = self.socket.get().unwrap().clone();
+        let output = self.output.get().unwrap().clone();
+        let methods = self.methods;
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<WriteRequest>();
+        std::thread::spawn(move || {
+            let mut java = super::Java::make(app);
+            let mut array: Option<GlobalRef> = None;
+            while let Some((data, reply)) = rx.blocking_recv() {
+                let r = java.use_env(|env, _context| {
+                    if array.is_none() {
+                        array = Some(
+                            env.new_byte_array(Self::ARRAY_SIZE as i32)
+                                .global_ref(env)
+                                .map_err(|e| jerr(env, e))?,
+                        );
+                    }
+                    let array = array.as_mut().unwrap();
+                    Self::write_output(
+                        env,
+                        socket.as_obj(),
+                        output.as_obj(),
+                        &methods,
+                        array,
+                        &data,
+                    )
+                });
+                let _ = reply.send(r);
+            }
+        });
+        tx
+    }
+
+    /// Hand the data to the writer thread, and wait for the result
+    fn poll_writer(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+        data: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        use std::future::Future;
+        if self.write_reply.is_none() {
+            if self.writer.is_none() {
+                self.writer = Some(self.start_writer());
+            }
+            let (reply, rx) = tokio::sync::oneshot::channel();
+            if self
+                .writer
+                .as_ref()
+                .unwrap()
+                .send((data.to_vec(), reply))
+                .is_err()
+            {
+                return std::task::Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()));
+            }
+            self.write_reply = Some(rx);
+        }
+        let r = std::task::ready!(std::pin::Pin::new(self.write_reply.as_mut().unwrap()).poll(cx));
+        self.write_reply = None;
+        std::task::Poll::Ready(r.unwrap_or_else(|_| Err(std::io::ErrorKind::BrokenPipe.into())))
+    }
+
+    /// Wait up to the timeout for data from the reader thread, starting it if needed
+    fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: std::time::Duration,
+    ) -> std::io::Result<usize> {
+        if self.pending.is_empty() {
+            if self.reader.is_none() {
+                self.reader = Some(self.start_reader());
+            }
+            let reader = self.reader.as_mut().unwrap();
+            let deadline = std::time::Instant::now() + timeout;
+            loop {
+                match reader.try_recv() {
+                    Ok(Ok(data)) => {
+                        self.pending = data;
+                        break;
+                    }
+                    Ok(Err(e)) => return Err(e),
+                    Err(tokio::sync::mpsc::error::TryRecvError::Disconnected) => return Ok(0),
+                    Err(tokio::sync::mpsc::error::TryRecvError::Empty) => {}
+                }
+                if std::time::Instant::now() >= deadline {
+                    return Err(crate::BluetoothError::Timeout.into());
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+        Ok(self.take_pending(buf))
+    }
+
+    /// Hand the data to the writer thread, and wait up to the timeout for the result. An empty write is a flush.
+    fn write_with_timeout(
+        &mut self,
+        data: &[u8],
+        timeout: std::time::Duration,
+    ) -> std::io::Result<usize> {
+        if self.writer.is_none() {
+            self.writer = Some(self.start_writer());
+        }
+        let (reply, mut rx) = tokio::sync::oneshot::channel();
+        if self
+            .writer
+            .as_ref()
+            .unwrap()
+            .send((data.to_vec(), reply))
+            .is_err()
+        {
+            return Err(std::io::ErrorKind::BrokenPipe.into());
+        }
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            match rx.try_recv() {
+                Ok(r) => return r,
+                Err(tokio::sync::oneshot::error::TryRecvError::Closed) => {
+                    return Err(std::io::ErrorKind::BrokenPipe.into());
+                }
+                Err(tokio::sync::oneshot::error::TryRecvError::Empty) => {}
+            }
+            if std::time::Instant::now() >= deadline {
+                return Err(crate::BluetoothError::Timeout.into());
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    /// Copy the slices of a vectored write into one buffer, so they are written with a single call to the
+    /// output stream
+    fn coalesce(bufs: &[std::io::IoSlice<'_>]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(bufs.iter().map(|b| b.len()).sum());
+        for b in bufs {
+            data.extend_from_slice(b);
+        }
+        data
+    }
+
+    /// Copy as much of the data from the reader thread as fits
+    fn take_pending(&mut self, buf: &mut [u8]) -> usize {
+        take_pending(&mut self.pending, buf)
+    }
+}
+
+impl std::io::Read for RfcommStream {
+    /// Blocks until data is available, or the read timeout passes. The end of the stream is reported as a read of
+    /// 0 bytes.
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(timeout) = self.read_timeout {
+            return self.read_with_timeout(buf, timeout);
+        }
+        if let Some(reader) = &mut self.reader {
+            if self.pending.is_empty() {
+                match reader.blocking_recv() {
+                    Some(Ok(data)) => self.pending = data,
+                    Some(Err(e)) => return Err(e),
+                    None => return Ok(0),
+                }
+            }
+            return Ok(self.take_pending(buf));
+        }
+        let mut java2 = self.java.lock().unwrap();
+        java2.use_env(|env, _context| {
+            Self::read_input(
+                env,
+                self.input.get().unwrap().as_obj(),
+                &self.methods,
+                &mut self.array_read,
+                buf,
+            )
+        })
+    }
+}
+
+impl std::io::Write for RfcommStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(timeout) = self.write_timeout {
+            return self.write_with_timeout(buf, timeout);
+        }
+        let mut java2 = self.java.lock().unwrap();
+        java2.use_env(|env, _context| {
+            Self::write_output(
+                env,
+                self.socket.get().unwrap().as_obj(),
+                self.output.get().unwrap().as_obj(),
+                &self.methods,
+                &mut self.array_write,
+                buf,
+            )
+        })
+    }
+
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+        self.write(&Self::coalesce(bufs))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if let Some(timeout) = self.write_timeout {
+            return self.write_with_timeout(&[], timeout).map(|_| ());
+        }
+        let mut java2 = self.java.lock().unwrap();
+        java2.use_env(|env, _context| {
+            Self::write_output(
+                env,
+                self.socket.get().unwrap().as_obj(),
+                self.output.get().unwrap().as_obj(),
+                &self.methods,
+                &mut self.array_write,
+                &[],
+            )
+            .map(|_| ())
+        })
+    }
+}
+
+impl tokio::io::AsyncRead for RfcommStream {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        if this.pending.is_empty() {
+            if this.reader.is_none() {
+                this.reader = Some(this.start_reader());
+            }
+            match std::task::ready!(this.reader.as_mut().unwrap().poll_recv(cx)) {
+                Some(Ok(data)) => this.pending = data,
+                Some(Err(e)) => return std::task::Poll::Ready(Err(e)),
+                None => return std::task::Poll::Ready(Ok(())),
+            }
+        }
+        let n = this.take_pending(buf.initialize_unfilled());
+        buf.advance(n);
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+impl tokio::io::AsyncWrite for RfcommStream {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        if buf.is_empty() {
+            return std::task::Poll::Ready(Ok(0));
+        }
+        self.get_mut().poll_writer(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        if bufs.iter().all(|b| b.is_empty()) {
+            return std::task::Poll::Ready(Ok(0));
+        }
+        let this = self.get_mut();
+        // The data is only handed to the writer thread when no write is in progress
+        let data = if this.write_reply.is_none() {
+            Self::coalesce(bufs)
+        } else {
+            Vec::new()
+        };
+        this.poll_writer(cx, &data)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        self.get_mut().poll_writer(cx, &[]).map_ok(|_| ())
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        tokio::io::AsyncWrite::poll_flush(self, cx)
+    }
+}
+
+impl Drop for RfcommStream {
+    fn drop(&mut self) {
+        // Closing the socket ends the blocking read of the reader thread
+        let _ = self.close();
+    }
+}
+
+/// Send a request to the bluetooth host and wait up to the timeout for the answer. This is `None` when the host
+/// is gone or does not answer in time. The request is sent and waited for on a thread of its own, so this can be
+/// called from any thread, including the threads of a tokio runtime where `blocking_send` panics.
+fn ask_host<T: Send + 'static>(
+    host: &tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>,
+    timeout: std::time::Duration,
+    request: impl FnOnce(tokio::sync::mpsc::Sender<T>) -> super::MessageToBluetoothHost,
+) -> Option<T> {
+    let (responder, mut answer) = tokio::sync::mpsc::channel(1);
+    let m = request(responder);
+    let host = host.clone();
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .ok()?;
+        rt.block_on(async move {
+            host.send(m).await.ok()?;
+            tokio::time::timeout(timeout, answer.recv()).await.ok()?
+        })
+    })
+    .join()
+    .ok()?
+}
+
+/// Tell the bluetooth host to stop displaying a pairing request, without waiting for the message to be sent
+fn cancel_display(host: &tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>) {
+    let host = host.clone();
+    std::thread::spawn(move || {
+        let _ = host.blocking_send(super::MessageToBluetoothHost::CancelDisplayPasskey);
+    });
+}
+
+/// Asks the bluetooth host whether an incoming connection to a service is allowed
+#[derive(Clone)]
+struct ServiceAuthorizer {
+    /// The sender to send messages to the bluetooth host
+    host: Option<tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>>,
+    /// How long to wait for an answer and what to do without one
+    config: super::AgentConfig,
+    /// The service being connected to
+    service: crate::BluetoothUuid,
+}
+
+impl ServiceAuthorizer {
+    /// Build the authorizer for the service of a profile
+    fn new(
+        host: Option<tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>>,
+        config: super::AgentConfig,
+        uuid: &str,
+    ) -> Result<Self, crate::BluetoothError> {
+        use std::str::FromStr;
+        let service = crate::BluetoothUuid::from_str(uuid)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        Ok(Self {
+            host,
+            config,
+            service,
+        })
+    }
+
+    /// Ask the host if the device is allowed to connect. The configured default is used when there is no host,
+    /// or when the host does not answer in time.
+    fn authorize(&self, device: super::BluetoothAdapterAddress, name: Option<String>) -> bool {
+        let Some(host) = &self.host else {
+            return self.config.authorize_service_default;
+        };
+        let service = self.service.clone();
+        ask_host(host, self.config.response_timeout, |responder| {
+            super::MessageToBluetoothHost::AuthorizeService {
+                device,
+                name,
+                service,
+                responder,
+            }
+        })
+        .unwrap_or(self.config.authorize_service_default)
+    }
+}
+
+/// A connection accepted from a bluetooth peer that has not been handed to the user yet.
+/// The connection is closed if this is dropped without accepting it.
+pub struct BluetoothRfcommConnectable {
+    /// The accepted BluetoothSocket, taken when it is handed to the user
+    socket: Option<jni::objects::GlobalRef>,
+    /// The BluetoothDevice of the peer
+    device: jni::objects::GlobalRef,
+    /// The address of the peer
+    peer: crate::BluetoothAdapterAddress,
+    /// The name of the peer, if it is known
+    peer_name: Option<String>,
+    /// The java instance
+    java: Arc<Mutex<super::Java>>,
+    /// Decides if the connection is handed out
+    authorizer: ServiceAuthorizer,
+    /// Was the server socket created to require an authenticated and encrypted link
+    secure: bool,
+}
+
+impl BluetoothRfcommConnectable {
+    /// Ask the host if the connection is allowed before building a stream for it
+    fn accept_authorized(mut self) -> Result<RfcommStream, crate::BluetoothError> {
+        if !self
+            .authorizer
+            .authorize(self.peer.clone(), self.peer_name.clone())
+        {
+            log::info!(
+                "Connection from {} to {} was not authorized",
+                self.peer,
+                self.authorizer.service
+            );
+            self.close();
+            return Err(crate::BluetoothError::PermissionDenied);
+        }
+        let Some(socket) = self.socket.take() else {
+            return Err(crate::BluetoothError::ConnectionFailed(
+                "The connection was already closed".to_string(),
+            ));
+        };
+        RfcommStream::new(socket.into(), self.java.clone(), self.secure)
+    }
+
+    /// Close the accepted socket, if it has not been handed out
+    fn close(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            let mut java2 = self.java.lock().unwrap();
+            java2.use_env(|env, _context| {
+                let _ = env.call_method(&socket, "close", "()V", &[]).clear_ex();
+            });
+        }
+    }
+}
+
+impl Drop for BluetoothRfcommConnectable {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl super::BluetoothL2capConnectableSyncTrait for BluetoothRfcommConnectable {
+    fn accept(self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        self.accept_authorized()
+            .map(crate::BluetoothStream::AndroidL2cap)
+    }
+}
+
+impl super::BluetoothRfcommConnectableSyncTrait for BluetoothRfcommConnectable {
+    fn accept(self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        self.accept_authorized()
+            .map(crate::BluetoothStream::Android)
+    }
+
+    fn reject(mut self) {
+        self.close();
+    }
+
+    fn peer_address(&self) -> crate::BluetoothAdapterAddress {
+        self.peer.clone()
+    }
+
+    fn peer_name(&self) -> Option<String> {
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            let name = env
+                .call_method(&self.device, "getName", "()Ljava/lang/String;", &[])
+                .get_object(env)
+                .ok()?;
+            if name.is_null() {
+                return None;
+            }
+            name.get_string(env).ok()
+        })
+    }
+}
+
+/// A bluetooth rfcomm profile. The server socket stays open for as long as the profile exists, so
+/// connectable can be called repeatedly to serve several peers.
+pub struct BluetoothRfcommProfile {
+    /// A socket that can be used to accept bluetooth connections
+    socket: OnceLock<jni::objects::GlobalRef>,
+    /// The java instance
+    java: Arc<Mutex<super::Java>>,
+    /// Decides if accepted connections are handed out
+    authorizer: ServiceAuthorizer,
+    /// Does the server socket require an authenticated and encrypted link
+    secure: bool,
+}
+
+/// Get the address and name of a BluetoothDevice
+fn device_identity(
+    env: &mut jni::JNIEnv,
+    device: &jni::objects::JObject,
+) -> Result<(crate::BluetoothAdapterAddress, Option<String>), crate::BluetoothError> {
+    let address = env
+        .call_method(device, "getAddress", "()Ljava/lang/String;", &[])
+        .get_object(env)
+        .and_then(|a| a.get_string(env))
+        .map_err(|e| jerr(env, e))?;
+    // The name only describes the peer, so nothing fails without it
+    let name = env
+        .call_method(device, "getName", "()Ljava/lang/String;", &[])
+        .get_object(env)
+        .and_then(|n| {
+            if n.is_null() {
+                Ok(None)
+            } else {
+                n.get_string(env).map(Some)
+            }
+        })
+        .map_err(|e| jerr(env, e))
+        .ok()
+        .flatten();
+    Ok((
+        crate::BluetoothAdapterAddress::String(address).normalized(),
+        name,
+    ))
+}
+
+/// Wait up to the timeout for a connection on the BluetoothServerSocket. The connection is accepted from the
+/// server socket here, so that the peer is known before the user decides to accept it.
+fn accept_connection(
+    server: &jni::objects::GlobalRef,
+    java: &Arc<Mutex<super::Java>>,
+    authorizer: &ServiceAuthorizer,
+    secure: bool,
+    timeout: std::time::Duration,
+) -> Result<BluetoothRfcommConnectable, crate::BluetoothError> {
+    let millis = (timeout.as_millis() as i32).into();
+    let mut java2 = java.lock().unwrap();
+    let (socket, device, peer, peer_name) = java2.use_env(|env, _context| {
+        let e = env
+            .call_method(
+                server.as_obj(),
+                "accept",
+                "(I)Landroid/bluetooth/BluetoothSocket;",
+                &[millis],
+            )
+            .get_object(env)
+            .map_err(|e| jerr(env, e))?;
+        let device = env
+            .call_method(
+                &e,
+                "getRemoteDevice",
+                "()Landroid/bluetooth/BluetoothDevice;",
+                &[],
+            )
+            .get_object(env)
+            .map_err(|e| jerr(env, e))?;
+        let (peer, peer_name) = device_identity(env, &device)?;
+        let socket = env.new_global_ref(&e).map_err(|e| jerr(env, e))?;
+        let device = env.new_global_ref(&device).map_err(|e| jerr(env, e))?;
+        Ok::<_, crate::BluetoothError>((socket, device, peer, peer_name))
+    })?;
+    Ok(BluetoothRfcommConnectable {
+        socket: Some(socket),
+        device,
+        peer,
+        peer_name,
+        java: java.clone(),
+        authorizer: authorizer.clone(),
+        secure,
+    })
+}
+
+impl BluetoothRfcommProfile {
+    /// Close the server socket, so that the uuid can be registered again. Dropping the profile also closes
+    /// the socket, this reports the error instead. Client profiles and closed profiles have nothing to close.
+    pub fn close(&mut self) -> Result<(), std::io::Error> {
+        let Some(socket) = self.socket.take() else {
+            return Ok(());
+        };
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            env.call_method(&socket, "close", "()V", &[])
+                .map(|_| ())
+                .map_err(|e| jerr(env, e))
+        })
+    }
+}
+
+impl Drop for BluetoothRfcommProfile {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            log::error!("Failed to close the rfcomm server socket: {e}");
+        }
+    }
+}
+
+impl crate::BluetoothRfcommProfileSyncTrait for BluetoothRfcommProfile {
+    fn connectable(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<crate::BluetoothRfcommConnectableSync, crate::BluetoothError> {
+        let Some(server) = self.socket.get() else {
+            return Err(crate::BluetoothError::Unsupported(
+                "Accepting connections on a client or closed profile",
+            ));
+        };
+        accept_connection(server, &self.java, &self.authorizer, self.secure, timeout)
+            .map(crate::BluetoothRfcommConnectableSync::Android)
+    }
+}
+
+/// A bluetooth l2cap profile, listening on a psm assigned by android
+pub struct BluetoothL2capProfile {
+    /// The BluetoothServerSocket that accepts the connections
+    socket: jni::objects::GlobalRef,
+    /// The psm assigned to the server socket
+    psm: u16,
+    /// The java instance
+    java: Arc<Mutex<super::Java>>,
+    /// Decides if accepted connections are handed out
+    authorizer: ServiceAuthorizer,
+    /// Does the server socket require an authenticated and encrypted link
+    secure: bool,
+}
+
+impl crate::BluetoothL2capProfileSyncTrait for BluetoothL2capProfile {
+    fn connectable(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<crate::BluetoothL2capConnectableSync, crate::BluetoothError> {
+        accept_connection(
+            &self.socket,
+            &self.java,
+            &self.authorizer,
+            self.secure,
+            timeout,
+        )
+        .map(crate::BluetoothL2capConnectableSync::Android)
+    }
+
+    fn psm(&self) -> Result<u16, crate::BluetoothError> {
+        Ok(self.psm)
+    }
+}
+
+impl Drop for BluetoothL2capProfile {
+    fn drop(&mut self) {
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            let _ = env
+                .call_method(&self.socket, "close", "()V", &[])
+                .clear_ex();
+        });
+    }
+}
+
+/// The bluetooth adapter struct for android code
+pub struct Bluetooth {
+    adapter: jni::objects::GlobalRef,
+    java: Arc<Mutex<super::Java>>,
+    /// An instance of Intent, created with registerReceiver
+    receiver: Option<jni::objects::GlobalRef>,
+    /// The broadcast_receiver for the bluetooth uuid
+    blue_uuid_receiver: Option<jni_min_helper::BroadcastReceiver>,
+    /// Receivers that forward broadcasts to event streams, unregistered when the adapter is dropped
+    receivers: Mutex<Vec<RegisteredReceiver>>,
+    /// The sender to send messages to the bluetooth host
+    host: Option<tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>>,
+    /// How requests to the bluetooth host are handled
+    agent_config: super::AgentConfig,
+    /// Forwards pin code requests to the bluetooth host, while there is one
+    pin_receiver: Option<RegisteredReceiver>,
+    /// The battery levels of devices, from the battery level changed broadcast
+    battery_levels: Arc<Mutex<std::collections::HashMap<String, u8>>>,
+    /// The signal strengths of devices, from the broadcasts of discovery
+    rssi_readings: Arc<Mutex<std::collections::HashMap<String, crate::RssiReading>>>,
+    /// The profile proxies of the well known profiles, shared with the devices
+    profile_proxies: ProfileProxyMap,
+    /// The discovery started with a command, stopped when it is dropped
+    discovery: Mutex<Option<crate::BluetoothDiscovery>>,
+    /// The streams connected with a command, waiting to be taken
+    connections:
+        Mutex<std::collections::HashMap<super::BluetoothAdapterAddress, super::BluetoothStream>>,
+    /// Requests the profile proxies, and closes them when the adapter is dropped
+    _profile_listener: Option<ProfileProxies>,
+}
+
+impl super::BluetoothAdapterTrait for Bluetooth {
+    fn supports_async(&mut self) -> Option<&mut dyn super::AsyncBluetoothAdapterTrait> {
+        None
+    }
+
+    fn supports_sync(&mut self) -> Option<&mut dyn super::SyncBluetoothAdapterTrait> {
+        Some(self)
+    }
+}
+
+impl crate::SyncBluetoothAdapterTrait for Bluetooth {
+    /// Android assigns the psm of the channel, which is available from the psm of the profile. L2cap channels
+    /// are only available from api level 29.
+    fn register_l2cap_profile(
+        &self,
+        settings: super::BluetoothL2capProfileSettings,
+    ) -> Result<crate::BluetoothL2capProfileSync, crate::BluetoothError> {
+        if settings.role == Some(crate::ProfileRole::Client) {
+            // Android l2cap client connections are made with the l2cap socket of a device
+            return Err(crate::BluetoothError::Unsupported(
+                "A client role l2cap profile",
+            ));
+        }
+        if let Some(psm) = settings.psm {
+            log::warn!(
+                "Android assigns the l2cap psm, the requested psm {} is not used",
+                psm
+            );
+        }
+        let secure = settings.authenticate.unwrap_or(true);
+        let authorizer =
+            ServiceAuthorizer::new(self.host.clone(), self.agent_config.clone(), &settings.uuid)?;
+        let mut java = self.java.lock().unwrap();
+        let (socket, psm) = java.use_env(|env, _context| {
+            if sdk_int(env).map_err(|e| jerr(env, e))? < 29 {
+                return Err(crate::BluetoothError::Unsupported(
+                    "L2cap channels before api level 29",
+                ));
+            }
+            let method = if secure {
+                "listenUsingL2capChannel"
+            } else {
+                "listenUsingInsecureL2capChannel"
+            };
+            let server = env
+                .call_method(
+                    &self.adapter,
+                    method,
+                    "()Landroid/bluetooth/BluetoothServerSocket;",
+                    &[],
+                )
+                .get_object(env)
+                .map_err(|e| jerr(env, e))?;
+            let psm = env
+                .call_method(&server, "getPsm", "()I", &[])
+                .get_int()
+                .map_err(|e| jerr(env, e))?;
+            let server = env.new_global_ref(&server).map_err(|e| jerr(env, e))?;
+            Ok((server, psm as u16))
+        })?;
+        Ok(crate::BluetoothL2capProfileSync::Android(
+            BluetoothL2capProfile {
+                socket,
+                psm,
+                java: self.java.clone(),
+                authorizer,
+                secure,
+            },
+        ))
+    }
+
+    /// Before api level 36, the profile listens with listenUsingRfcommWithServiceRecord. That does not allow
+    /// choosing the psm or providing an sdp record, so settings with either of them are rejected.
+    fn register_rfcomm_profile(
+        &self,
+        settings: crate::BluetoothRfcommProfileSettings,
+    ) -> Result<crate::BluetoothRfcommProfileSync, crate::BluetoothError> {
+        settings.validate()?;
+        let authorizer =
+            ServiceAuthorizer::new(self.host.clone(), self.agent_config.clone(), &settings.uuid)?;
+        if settings.role == Some(crate::ProfileRole::Client) {
+            // Client profiles never listen, connections are made with outgoing sockets instead
+            return Ok(crate::BluetoothRfcommProfileSync::Android(
+                BluetoothRfcommProfile {
+                    socket: OnceLock::new(),
+                    java: self.java.clone(),
+                    authorizer,
+                    secure: settings.authenticate != Some(false),
+                },
+            ));
+        }
+        let mut java2 = self.java.lock().unwrap();
+        {
+            java2.use_env(|env, context| {
+                if sdk_int(env).map_err(|e| jerr(env, e))? < 36 {
+                    let socket = self.listen_rfcomm_legacy(env, &settings)?;
+                    return Ok(crate::BluetoothRfcommProfileSync::Android(
+                        BluetoothRfcommProfile {
+                            socket: socket.into(),
+                            java: self.java.clone(),
+                            authorizer,
+                            secure: settings.authenticate != Some(false),
+                        },
+                    ));
+                }
+                let jsettings = {
                     log::error!("Register rfcomm 1");
                     log::error!("Finding builder class");
-                    let ss = env.find_class("android/bluetooth/BluetoothSocketSettings$Builder").map_err(|e| jerr(env, e).to_string())?;
+                    let ss = env.find_class("android/bluetooth/BluetoothSocketSettings$Builder").map_err(|e| jerr(env, e))?;
                     log::error!("Found builder class");
-                    let builder_constructor = env.get_method_id(&ss, "<init>", "()V").map_err(|e| jerr(env, e).to_string())?;
+                    let builder_constructor = env.get_method_id(&ss, "<init>", "()V").map_err(|e| jerr(env, e))?;
                     log::error!("Got constructor");
-                    let obj = env.new_object(&ss, "()V", &[]).map_err(|e| jerr(env, e).to_string())?;
+                    let obj = env.new_object(&ss, "()V", &[]).map_err(|e| jerr(env, e))?;
                     log::error!("Success in making socket settings builder?");
                     let mut jsettings = obj;
                     log::error!("Register rfcomm 2");
@@ -335,16 +1911,16 @@ impl crate::SyncBluetoothAdapterTrait for Bluetooth {
                         let e = env
                             .call_method(jsettings, "setAuthenticationRequired", "(Z)Landroid/bluetooth/BluetoothSocketSettings$Builder;", &[auth.into()])
                             .get_object(env)
-                            .map_err(|e| jerr(env, e).to_string())?;
-                        jsettings = env.new_local_ref(&e).map_err(|e| jerr(env, e).to_string())?;
+                            .map_err(|e| jerr(env, e))?;
+                        jsettings = env.new_local_ref(&e).map_err(|e| jerr(env, e))?;
                     }
                     log::error!("Register rfcomm 3");
                     if let Some(val) = settings.psm {
                         let e = env
                             .call_method(jsettings, "setL2capPsm", "(I)Landroid/bluetooth/BluetoothSocketSettings$Builder;", &[val.into()])
                             .get_object(env)
-                            .map_err(|e| jerr(env, e).to_string())?;
-                        jsettings = env.new_local_ref(&e).map_err(|e| jerr(env, e).to_string())?;
+                            .map_err(|e| jerr(env, e))?;
+                        jsettings = env.new_local_ref(&e).map_err(|e| jerr(env, e))?;
                     }
                     log::error!("Register rfcomm 4");
                     if let Some(name) = &settings.name {
@@ -355,8 +1931,8 @@ impl crate::SyncBluetoothAdapterTrait for Bluetooth {
                         let e = env
                             .call_method(jsettings, "setRfcommServiceName", "(Ljava/lang/String;)Landroid/bluetooth/BluetoothSocketSettings$Builder;", &[(&arg).into()])
                             .get_object(env)
-                            .map_err(|e| jerr(env, e).to_string())?;
-                        jsettings = env.new_local_ref(&e).map_err(|e| jerr(env, e).to_string())?;
+                            .map_err(|e| jerr(env, e))?;
+                        jsettings = env.new_local_ref(&e).map_err(|e| jerr(env, e))?;
                     }
                     log::error!("Register rfcomm 5");
                     {
@@ -364,25 +1940,26 @@ impl crate::SyncBluetoothAdapterTrait for Bluetooth {
                             .new_jobject(env)
                             .map_err(|e| jerr(env, e))
                             .unwrap();
-                        let uuid_class = env.find_class("java/util/UUID").map_err(|e| jerr(env, e).to_string())?;
-                        let uuid = env.call_static_method(uuid_class, "fromString", "(Ljava/lang/String;)Ljava/util/UUID;", &[(&arg).into()]).map_err(|e| jerr(env, e).to_string())?;
+                        let uuid_class = env.find_class("java/util/UUID").map_err(|e| jerr(env, e))?;
+                        let uuid = env.call_static_method(uuid_class, "fromString", "(Ljava/lang/String;)Ljava/util/UUID;", &[(&arg).into()]).map_err(|e| jerr(env, e))?;
                         let e = env
                             .call_method(jsettings, "setRfcommUuid", "(Ljava/util/UUID;)Landroid/bluetooth/BluetoothSocketSettings$Builder;", &[uuid.borrow()])
                             .get_object(env)
-                            .map_err(|e| jerr(env, e).to_string())?;
-                        jsettings = env.new_local_ref(&e).map_err(|e| jerr(env, e).to_string())?;
+                            .map_err(|e| jerr(env, e))?;
+                        jsettings = env.new_local_ref(&e).map_err(|e| jerr(env, e))?;
                     }
                     log::error!("Register rfcomm 6");
                     let e = env
                             .call_method(jsettings, "build", "()Landroid/bluetooth/BluetoothSocketSettings;", &[])
                             .get_object(env)
-                            .map_err(|e| jerr(env, e).to_string())?;
-                    jsettings = env.new_local_ref(&e).map_err(|e| jerr(env, e).to_string())?;
+                            .map_err(|e| jerr(env, e))?;
+                    jsettings = env.new_local_ref(&e).map_err(|e| jerr(env, e))?;
                     log::error!("Register rfcomm 7");
-                    Ok::<jni::objects::JObject<'_>, String>(jsettings)
+                    Ok::<jni::objects::JObject<'_>, crate::BluetoothError>(jsettings)
                 }?;
                 log::error!("Register rfcomm 8");
-                let jsettings = jni::objects::JValueGen::try_from(jsettings).map_err(|e| e.to_string())?;
+                let jsettings = jni::objects::JValueGen::try_from(jsettings)
+                    .map_err(|e| crate::BluetoothError::Platform(e.to_string()))?;
                 log::error!("Register rfcomm 9");
                 let mut sig = String::new();
                 log::error!("Register rfcomm 9.1");
@@ -391,72 +1968,691 @@ impl crate::SyncBluetoothAdapterTrait for Bluetooth {
                 let e = env
                     .call_method(
                         &self.adapter,
-                        "listenUsingSocketSettings",
-                        &sig,
-                        &[jsettings.borrow()],
+                        "listenUsingSocketSettings",
+                        &sig,
+                        &[jsettings.borrow()],
+                    )
+                    .get_object(env)
+                    .map_err(|e| permission_err(env, "listenUsingSocketSettings", e))?;
+                log::error!("Register rfcomm 10");
+                let socket = env.new_global_ref(&e).map_err(|e| jerr(env, e))?;
+                log::error!("Register rfcomm 11");
+                // BluetoothSocketSettings only requires authentication when it is asked for
+                Ok(crate::BluetoothRfcommProfileSync::Android(
+                    BluetoothRfcommProfile {
+                        socket: socket.into(),
+                        java: self.java.clone(),
+                        authorizer,
+                        secure: settings.authenticate == Some(true),
+                    },
+                ))
+            })
+        }
+    }
+
+    fn has_permission(&self, permission: crate::BluetoothPermission) -> bool {
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, context| {
+            let sdk = sdk_int(env).map_err(|e| jerr(env, e))?;
+            match permission_name(permission, sdk) {
+                Some(name) => self.check_permission2(env, &context, name),
+                None => Ok(true),
+            }
+        })
+        .unwrap_or(false)
+    }
+
+    /// The activity of the application has to forward onRequestPermissionsResult to the
+    /// PermissionResultBridge class shipped with this crate, otherwise the permissions are reported as denied
+    /// after one minute.
+    fn request_permissions(
+        &self,
+        permissions: &[crate::BluetoothPermission],
+    ) -> crate::PermissionRequest {
+        use crate::PermissionOutcome;
+        let missing: Vec<crate::BluetoothPermission> = permissions
+            .iter()
+            .copied()
+            .filter(|p| !self.has_permission(*p))
+            .collect();
+        if missing.is_empty() {
+            return Box::pin(std::future::ready(PermissionOutcome::Granted));
+        }
+        let code = PERMISSION_REQUEST_BASE
+            + (NEXT_PERMISSION_REQUEST.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+                % PERMISSION_REQUEST_CODES) as i32;
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        PERMISSION_REQUESTS.lock().unwrap().insert(code, tx);
+        let requested = {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, context| {
+                let sdk = sdk_int(env).map_err(|e| jerr(env, e))?;
+                register_permission_bridge(env, &context).map_err(|e| jerr(env, e))?;
+                let names: Vec<(crate::BluetoothPermission, &'static str)> = missing
+                    .iter()
+                    .filter_map(|p| permission_name(*p, sdk).map(|name| (*p, name)))
+                    .collect();
+                let mut list: Vec<&str> = Vec::new();
+                for (_, name) in &names {
+                    if !list.contains(name) {
+                        list.push(name);
+                    }
+                }
+                request_permissions(env, &context, &list, code).map_err(|e| jerr(env, e))?;
+                Ok::<_, std::io::Error>(names)
+            })
+        };
+        let names = match requested {
+            Ok(names) => names,
+            Err(e) => {
+                PERMISSION_REQUESTS.lock().unwrap().remove(&code);
+                log::error!("Failed to request permissions: {}", e);
+                return Box::pin(std::future::ready(PermissionOutcome::Denied(missing)));
+            }
+        };
+        Box::pin(async move {
+            let granted = match tokio::time::timeout(PERMISSION_TIMEOUT, rx).await {
+                Ok(Ok(granted)) => granted,
+                Ok(Err(_)) => Vec::new(),
+                Err(_) => {
+                    PERMISSION_REQUESTS.lock().unwrap().remove(&code);
+                    log::warn!(
+                        "No answer to the permission request, is the result forwarded by the activity?"
+                    );
+                    Vec::new()
+                }
+            };
+            let denied: Vec<crate::BluetoothPermission> = names
+                .into_iter()
+                .filter(|(_, name)| !granted.iter().any(|g| g == name))
+                .map(|(p, _)| p)
+                .collect();
+            if denied.is_empty() {
+                PermissionOutcome::Granted
+            } else {
+                PermissionOutcome::Denied(denied)
+            }
+        })
+    }
+
+    /// Asks the user to make the device discoverable. The timeout is passed as the requested discoverable duration.
+    /// This waits for the scan mode to change for up to the response timeout of the agent config, and must not be
+    /// called from the main thread, since the scan mode broadcast is delivered there. Android does not allow
+    /// turning discoverability off, the device stops being discoverable when the duration ends.
+    fn set_discoverable(
+        &self,
+        d: bool,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<bool, crate::BluetoothError> {
+        if !d {
+            return Err(crate::BluetoothError::Unsupported(
+                "Turning discoverability off on android",
+            ));
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+        let _receiver = RegisteredReceiver::new(
+            &self.java,
+            &["android.bluetooth.adapter.action.SCAN_MODE_CHANGED"],
+            move |env, _context, intent| {
+                let name = "android.bluetooth.adapter.extra.SCAN_MODE".new_jobject(env)?;
+                let mode = env
+                    .call_method(
+                        intent,
+                        "getIntExtra",
+                        "(Ljava/lang/String;I)I",
+                        &[(&name).into(), (-1).into()],
+                    )
+                    .get_int()?;
+                if mode == SCAN_MODE_CONNECTABLE_DISCOVERABLE {
+                    let _ = tx.send(());
+                }
+                Ok(())
+            },
+        )?;
+        {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, context| {
+                let extra = timeout.map(|t| {
+                    (
+                        "android.bluetooth.adapter.extra.DISCOVERABLE_DURATION",
+                        t.as_secs().clamp(1, i32::MAX as u64) as i32,
+                    )
+                });
+                start_activity(
+                    env,
+                    &context,
+                    "android.bluetooth.adapter.action.REQUEST_DISCOVERABLE",
+                    extra,
+                )
+            })?;
+        }
+        // The broadcast is not sent when the user refuses, or when the device was already discoverable
+        if rx.recv_timeout(self.agent_config.response_timeout).is_ok() {
+            return Ok(true);
+        }
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            env.call_method(&self.adapter, "getScanMode", "()I", &[])
+                .get_int()
+                .map(|m| m == SCAN_MODE_CONNECTABLE_DISCOVERABLE)
+                .map_err(|e| jerr(env, e).into())
+        })
+    }
+
+    fn get_paired_devices(&self) -> Result<Vec<crate::BluetoothDevice>, crate::BluetoothError> {
+        Ok(self
+            .get_bonded_devices()?
+            .into_iter()
+            .map(crate::BluetoothDevice::Android)
+            .collect())
+    }
+
+    fn start_le_scan(
+        &self,
+        filter: crate::LeScanFilter,
+    ) -> Result<crate::LeScanSession, crate::BluetoothError> {
+        Ok(LeScanSession::new(&self.adapter, self.java.clone(), filter)?.into())
+    }
+
+    /// Requires api level 28. Android connects the hid device profile proxy in the background after the adapter
+    /// is created, so this waits briefly for it and must not be called from the main thread. Only one app at a time
+    /// can be registered as a hid device.
+    fn register_hid_device(
+        &self,
+        settings: crate::HidSettings,
+    ) -> Result<crate::HidDeviceHandle, crate::BluetoothError> {
+        let deadline = std::time::Instant::now() + HID_PROXY_TIMEOUT;
+        let proxy = loop {
+            if let Some(proxy) = self
+                .profile_proxies
+                .lock()
+                .unwrap()
+                .get(&PROFILE_HID_DEVICE)
+            {
+                break proxy.clone();
+            }
+            if std::time::Instant::now() >= deadline {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "The hid device profile is not available",
+                )
+                .into());
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        };
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, context| {
+            HidDevice::register(
+                env,
+                &context,
+                &self.adapter,
+                proxy,
+                &settings,
+                self.java.clone(),
+            )
+        })
+        .map(crate::HidDeviceHandle::Android)
+    }
+
+    fn start_discovery(&self) -> Result<crate::BluetoothDiscovery, crate::BluetoothError> {
+        BluetoothDiscovery::new(
+            self.adapter.clone(),
+            self.java.clone(),
+            self.battery_levels.clone(),
+            self.rssi_readings.clone(),
+            self.profile_proxies.clone(),
+            self.agent_config.response_timeout,
+        )
+        .map(Into::into)
+    }
+
+    fn set_powered(&self, powered: bool) -> Result<crate::PowerOutcome, crate::BluetoothError> {
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, context| {
+            let enabled = env
+                .call_method(&self.adapter, "isEnabled", "()Z", &[])
+                .get_boolean()
+                .map_err(|e| jerr(env, e))?;
+            if enabled == powered {
+                return Ok(crate::PowerOutcome::Completed);
+            }
+            // enable() and disable() always return false for regular apps starting with api level 33
+            let method = if powered { "enable" } else { "disable" };
+            let changed = env
+                .call_method(&self.adapter, method, "()Z", &[])
+                .get_boolean()
+                .map_err(|e| jerr(env, e))?;
+            if changed {
+                Ok(crate::PowerOutcome::Completed)
+            } else if powered {
+                start_activity(
+                    env,
+                    &context,
+                    "android.bluetooth.adapter.action.REQUEST_ENABLE",
+                    None,
+                )?;
+                Ok(crate::PowerOutcome::PromptShown)
+            } else {
+                Err(crate::BluetoothError::Unsupported(
+                    "Turning off bluetooth on this api level",
+                ))
+            }
+        })
+    }
+
+    fn is_powered(&self) -> Result<bool, crate::BluetoothError> {
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            env.call_method(&self.adapter, "isEnabled", "()Z", &[])
+                .get_boolean()
+                .map_err(|e| jerr(env, e).into())
+        })
+    }
+
+    fn alias(&self) -> Result<String, crate::BluetoothError> {
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            let name = env
+                .call_method(&self.adapter, "getName", "()Ljava/lang/String;", &[])
+                .get_object(env)
+                .map_err(|e| permission_err(env, "getName", e))?;
+            if name.is_null() {
+                return Err(crate::BluetoothError::PermissionDenied);
+            }
+            name.get_string(env).map_err(|e| jerr(env, e).into())
+        })
+    }
+
+    /// Requires the BLUETOOTH_CONNECT permission. The name is changed in the background, so this waits for the
+    /// local name changed broadcast to confirm the change. This must not be called from the main thread, since the
+    /// broadcast is delivered there.
+    fn set_alias(&self, alias: &str) -> Result<(), crate::BluetoothError> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let _receiver = RegisteredReceiver::new(
+            &self.java,
+            &["android.bluetooth.adapter.action.LOCAL_NAME_CHANGED"],
+            move |env, _context, intent| {
+                let extra = "android.bluetooth.adapter.extra.LOCAL_NAME".new_jobject(env)?;
+                let name = env
+                    .call_method(
+                        intent,
+                        "getStringExtra",
+                        "(Ljava/lang/String;)Ljava/lang/String;",
+                        &[(&extra).into()],
+                    )
+                    .get_object(env)?;
+                if !name.is_null() {
+                    let _ = tx.send(name.get_string(env)?);
+                }
+                Ok(())
+            },
+        )?;
+        {
+            let mut java = self.java.lock().unwrap();
+            let set = java.use_env(|env, _context| {
+                let name = alias.new_jobject(env).map_err(|e| jerr(env, e))?;
+                env.call_method(
+                    &self.adapter,
+                    "setName",
+                    "(Ljava/lang/String;)Z",
+                    &[(&name).into()],
+                )
+                .get_boolean()
+                .map_err(|e| permission_err(env, "setName", e))
+            })?;
+            if !set {
+                return Err(crate::BluetoothError::Platform(
+                    "The adapter name was not changed".to_string(),
+                ));
+            }
+        }
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while let Some(left) = deadline.checked_duration_since(std::time::Instant::now()) {
+            match rx.recv_timeout(left) {
+                Ok(name) if name == alias => return Ok(()),
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        // The broadcast is not sent when the name did not actually change
+        if self.alias()? == alias {
+            Ok(())
+        } else {
+            Err(crate::BluetoothError::Timeout)
+        }
+    }
+
+    /// Android does not have a pairable mode, the device is pairable whenever bluetooth is on
+    fn set_pairable(&self, _pairable: bool) -> Result<(), crate::BluetoothError> {
+        Err(crate::BluetoothError::Unsupported(
+            "Controlling the pairable mode",
+        ))
+    }
+
+    /// Android does not have a pairable mode, the device is pairable whenever bluetooth is on
+    fn set_pairable_timeout(
+        &self,
+        _timeout: std::time::Duration,
+    ) -> Result<(), crate::BluetoothError> {
+        Err(crate::BluetoothError::Unsupported(
+            "Controlling the pairable timeout",
+        ))
+    }
+
+    /// Android has a single adapter, so the adapter turning on and off is reported as it being added and removed
+    fn adapter_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::AdapterHotplugEvent>, crate::BluetoothError>
+    {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let adapter = self.adapter.clone();
+        let receiver = RegisteredReceiver::new(
+            &self.java,
+            &["android.bluetooth.adapter.action.STATE_CHANGED"],
+            move |env, _context, intent| {
+                let name = "android.bluetooth.adapter.extra.STATE".new_jobject(env)?;
+                let state = env
+                    .call_method(
+                        intent,
+                        "getIntExtra",
+                        "(Ljava/lang/String;I)I",
+                        &[(&name).into(), (-1).into()],
+                    )
+                    .get_int()?;
+                let address = env
+                    .call_method(&adapter, "getAddress", "()Ljava/lang/String;", &[])
+                    .get_object(env)?
+                    .get_string(env)?;
+                let address = super::BluetoothAdapterAddress::String(address).normalized();
+                let event = match state {
+                    STATE_ON => super::AdapterHotplugEvent::AdapterAdded(address),
+                    STATE_OFF => super::AdapterHotplugEvent::AdapterRemoved(address),
+                    _ => return Ok(()),
+                };
+                let _ = tx.try_send(event);
+                Ok(())
+            },
+        )?;
+        self.receivers.lock().unwrap().push(receiver);
+        Ok(rx)
+    }
+
+    fn adapter_state_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::AdapterState>, crate::BluetoothError> {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let receiver = RegisteredReceiver::new(
+            &self.java,
+            &["android.bluetooth.adapter.action.STATE_CHANGED"],
+            move |env, _context, intent| {
+                let name = "android.bluetooth.adapter.extra.STATE".new_jobject(env)?;
+                let state = env
+                    .call_method(
+                        intent,
+                        "getIntExtra",
+                        "(Ljava/lang/String;I)I",
+                        &[(&name).into(), (-1).into()],
+                    )
+                    .get_int()?;
+                let state = match state {
+                    STATE_OFF => super::AdapterState::Off,
+                    STATE_TURNING_ON => super::AdapterState::TurningOn,
+                    STATE_ON => super::AdapterState::On,
+                    STATE_TURNING_OFF => super::AdapterState::TurningOff,
+                    _ => return Ok(()),
+                };
+                let _ = tx.try_send(state);
+                Ok(())
+            },
+        )?;
+        self.receivers.lock().unwrap().push(receiver);
+        Ok(rx)
+    }
+
+    fn scan_mode(&self) -> Result<super::ScanMode, crate::BluetoothError> {
+        let mut java = self.java.lock().unwrap();
+        let mode = java.use_env(|env, _context| {
+            env.call_method(&self.adapter, "getScanMode", "()I", &[])
+                .get_int()
+                .map_err(|e| jerr(env, e))
+        })?;
+        Ok(scan_mode(mode))
+    }
+
+    fn scan_mode_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::ScanMode>, crate::BluetoothError> {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let receiver = RegisteredReceiver::new(
+            &self.java,
+            &["android.bluetooth.adapter.action.SCAN_MODE_CHANGED"],
+            move |env, _context, intent| {
+                let name = "android.bluetooth.adapter.extra.SCAN_MODE".new_jobject(env)?;
+                let mode = env
+                    .call_method(
+                        intent,
+                        "getIntExtra",
+                        "(Ljava/lang/String;I)I",
+                        &[(&name).into(), (-1).into()],
+                    )
+                    .get_int()?;
+                let _ = tx.try_send(scan_mode(mode));
+                Ok(())
+            },
+        )?;
+        self.receivers.lock().unwrap().push(receiver);
+        Ok(rx)
+    }
+
+    fn pairing_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::PairingEvent>, crate::BluetoothError> {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let stream = tx.clone();
+        let receiver = RegisteredReceiver::new(
+            &self.java,
+            &["android.bluetooth.device.action.BOND_STATE_CHANGED"],
+            move |env, _context, intent| {
+                if tx.is_closed() {
+                    return Ok(());
+                }
+                let extra = "android.bluetooth.device.extra.DEVICE".new_jobject(env)?;
+                let device = env
+                    .call_method(
+                        intent,
+                        "getParcelableExtra",
+                        "(Ljava/lang/String;)Landroid/os/Parcelable;",
+                        &[(&extra).into()],
+                    )
+                    .get_object(env)?;
+                if device.is_null() {
+                    return Ok(());
+                }
+                let address = env
+                    .call_method(&device, "getAddress", "()Ljava/lang/String;", &[])
+                    .get_object(env)?
+                    .get_string(env)?;
+                let mut state = |name: &str| -> Result<i32, jni::errors::Error> {
+                    let extra = name.new_jobject(env)?;
+                    env.call_method(
+                        intent,
+                        "getIntExtra",
+                        "(Ljava/lang/String;I)I",
+                        &[(&extra).into(), (-1).into()],
+                    )
+                    .get_int()
+                };
+                let old = state("android.bluetooth.device.extra.PREVIOUS_BOND_STATE")?;
+                let new = state("android.bluetooth.device.extra.BOND_STATE")?;
+                let _ = tx.try_send(super::PairingEvent {
+                    address: super::BluetoothAdapterAddress::String(address).normalized(),
+                    old: pairing_status(old),
+                    new: pairing_status(new),
+                });
+                Ok(())
+            },
+        )?
+        .feeding(stream);
+        self.keep_receiver(receiver);
+        Ok(rx)
+    }
+
+    fn connection_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::ConnectionEvent>, crate::BluetoothError> {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let receiver = RegisteredReceiver::new(
+            &self.java,
+            &[
+                "android.bluetooth.device.action.ACL_CONNECTED",
+                "android.bluetooth.device.action.ACL_DISCONNECTED",
+            ],
+            move |env, _context, intent| {
+                let extra = "android.bluetooth.device.extra.DEVICE".new_jobject(env)?;
+                let device = env
+                    .call_method(
+                        intent,
+                        "getParcelableExtra",
+                        "(Ljava/lang/String;)Landroid/os/Parcelable;",
+                        &[(&extra).into()],
+                    )
+                    .get_object(env)?;
+                if device.is_null() {
+                    return Ok(());
+                }
+                let address = env
+                    .call_method(&device, "getAddress", "()Ljava/lang/String;", &[])
+                    .get_object(env)?
+                    .get_string(env)?;
+                let action = env
+                    .call_method(intent, "getAction", "()Ljava/lang/String;", &[])
+                    .get_object(env)?
+                    .get_string(env)?;
+                let connected = action == "android.bluetooth.device.action.ACL_CONNECTED";
+                let address = super::BluetoothAdapterAddress::String(address).normalized();
+                let _ = tx.try_send(super::ConnectionEvent { address, connected });
+                Ok(())
+            },
+        )?;
+        self.receivers.lock().unwrap().push(receiver);
+        Ok(rx)
+    }
+
+    fn device_by_address(
+        &self,
+        address: &super::BluetoothAdapterAddress,
+    ) -> Result<super::BluetoothDevice, crate::BluetoothError> {
+        // getRemoteDevice throws for addresses that are not upper case and separated by colons
+        let address = address
+            .as_bytes()
+            .map(|b| super::BluetoothAdapterAddress::Byte(b).to_string())
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Invalid bluetooth address {}", address),
+                )
+            })?;
+        let mut java = self.java.lock().unwrap();
+        let device = java.use_env(|env, _context| {
+            let address = address.new_jobject(env).map_err(|e| jerr(env, e))?;
+            env.call_method(
+                &self.adapter,
+                "getRemoteDevice",
+                "(Ljava/lang/String;)Landroid/bluetooth/BluetoothDevice;",
+                &[(&address).into()],
+            )
+            .get_object(env)
+            .globalize(env)
+            .map_err(|e| jerr(env, e))
+        })?;
+        drop(java);
+        Ok(super::BluetoothDevice::Android(
+            BluetoothDevice::new(device, self.java.clone())
+                .with_battery_levels(self.battery_levels.clone())
+                .with_rssi_readings(self.rssi_readings.clone())
+                .with_profile_proxies(self.profile_proxies.clone())
+                .with_response_timeout(self.agent_config.response_timeout),
+        ))
+    }
+
+    /// Connecting to a channel number uses the hidden createRfcommSocket method, since android only exposes
+    /// connecting by service uuid. The connection blocks until it completes or fails.
+    fn connect_rfcomm(
+        &self,
+        address: &super::BluetoothAdapterAddress,
+        target: super::RfcommTarget,
+    ) -> Result<super::BluetoothStream, crate::BluetoothError> {
+        // Android requires upper case addresses, which is how addresses are displayed
+        let address = address.to_string();
+        let socket = {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, _context| {
+                let address = address.new_jobject(env).map_err(|e| jerr(env, e))?;
+                let device = env
+                    .call_method(
+                        &self.adapter,
+                        "getRemoteDevice",
+                        "(Ljava/lang/String;)Landroid/bluetooth/BluetoothDevice;",
+                        &[(&address).into()],
                     )
                     .get_object(env)
-                    .map_err(|e| jerr(env, e).to_string())?;
-                log::error!("Register rfcomm 10");
-                let socket = env
-                    .new_global_ref(&e)
-                    .map_err(|e| jerr(env, e).to_string())?;
-                log::error!("Register rfcomm 11");
-                Ok(crate::BluetoothRfcommProfileSync::Android(
-                    BluetoothRfcommProfile {
-                        socket: socket.into(),
-                        java: self.java.clone(),
-                    },
-                ))
-            })
-        }
-    }
-
-    fn set_discoverable(&self, d: bool) -> Result<(), ()> {
-        let mut java = self.java.lock().unwrap();
-        java.use_env(|env, context| {
-            let arg = "android.bluetooth.adapter.action.REQUEST_DISCOVERABLE"
-                .new_jobject(env)
-                .map_err(|e| jerr(env, e))
-                .unwrap();
-            let intent = env
-                .new_object(
-                    "android/content/Intent",
-                    "(Ljava/lang/String;)V",
-                    &[(&arg).into()],
-                )
-                .unwrap();
-            let mut args = Vec::new();
-            args.push(&intent);
-            let mut args2: Vec<jni::objects::JValueGen<&jni::objects::JObject>> =
-                args.iter().map(|a| a.try_into().unwrap()).collect();
-            args2.push(1.into());
-            let a = env.call_method(
-                context,
-                "startActivityForResult",
-                "(Landroid/content/Intent;I)V",
-                args2.as_slice(),
-            );
-            log::error!("Results of bluetooth enable discoverable is {:?}", a);
-        });
-        Ok(())
+                    .map_err(|e| jerr(env, e))?;
+                let socket = match &target {
+                    super::RfcommTarget::Channel(c) => env
+                        .call_method(
+                            &device,
+                            "createRfcommSocket",
+                            "(I)Landroid/bluetooth/BluetoothSocket;",
+                            &[(*c as i32).into()],
+                        )
+                        .get_object(env),
+                    super::RfcommTarget::Uuid(uuid) => {
+                        let uuid = uuid.as_str().new_jobject(env).map_err(|e| jerr(env, e))?;
+                        let uuid = env
+                            .call_static_method(
+                                "java/util/UUID",
+                                "fromString",
+                                "(Ljava/lang/String;)Ljava/util/UUID;",
+                                &[(&uuid).into()],
+                            )
+                            .get_object(env)
+                            .map_err(|e| jerr(env, e))?;
+                        env.call_method(
+                            &device,
+                            "createRfcommSocketToServiceRecord",
+                            "(Ljava/util/UUID;)Landroid/bluetooth/BluetoothSocket;",
+                            &[(&uuid).into()],
+                        )
+                        .get_object(env)
+                    }
+                }
+                .map_err(|e| jerr(env, e))?;
+                env.call_method(&socket, "connect", "()V", &[])
+                    .clear_ex()
+                    .map_err(|e| jerr(env, e))?;
+                env.new_global_ref(&socket).map_err(|e| jerr(env, e))
+            })?
+        };
+        let stream = RfcommStream::new(socket.into(), self.java.clone(), true)?;
+        Ok(super::BluetoothStream::Android(stream))
     }
 
-    fn get_paired_devices(&self) -> Option<Vec<crate::BluetoothDevice>> {
-        let bd = self.get_bonded_devices();
-        if let Some(bd) = bd {
-            let mut devs = Vec::new();
-            for b in bd {
-                devs.push(crate::BluetoothDevice::Android(b));
-            }
-            Some(devs)
-        } else {
-            None
-        }
+    fn advertise(
+        &self,
+        _settings: super::LeAdvertisementSettings,
+    ) -> Result<super::AdvertisementHandle, super::BluetoothError> {
+        Err(super::BluetoothError::Unsupported(
+            "Low energy advertising on android",
+        ))
     }
 
-    fn start_discovery(&self) -> crate::BluetoothDiscovery {
-        BluetoothDiscovery::new(self.adapter.clone(), self.java.clone()).into()
+    fn register_gatt_service(
+        &self,
+        _service: super::GattServiceDefinition,
+    ) -> Result<super::GattServiceHandle, crate::BluetoothError> {
+        Err(crate::BluetoothError::Unsupported(
+            "Gatt services on android",
+        ))
     }
 
     fn addresses(&self) -> Vec<super::BluetoothAdapterAddress> {
@@ -472,17 +2668,199 @@ impl crate::SyncBluetoothAdapterTrait for Bluetooth {
             action.get_string(env)
         });
         if let Ok(n) = n {
-            a.push(super::BluetoothAdapterAddress::String(n));
+            a.push(super::BluetoothAdapterAddress::String(n).normalized());
         }
         a
     }
-}
 
-type ReadCallback = Box<dyn Fn(Option<usize>) + 'static + Send>;
+    fn issue_command(&self, cmd: super::BluetoothCommand) -> Option<super::BluetoothResponse> {
+        match cmd {
+            super::BluetoothCommand::DetectAdapters => {
+                let Some(address) = self.addresses().pop() else {
+                    return Some(super::BluetoothResponse::Error(
+                        "Unable to get the address of the bluetooth adapter".to_string(),
+                    ));
+                };
+                let name = match self.alias() {
+                    Ok(n) => n,
+                    Err(e) => return Some(super::BluetoothResponse::Error(e.to_string())),
+                };
+                let discoverable = {
+                    let mut java = self.java.lock().unwrap();
+                    java.use_env(|env, _context| {
+                        env.call_method(&self.adapter, "getScanMode", "()I", &[])
+                            .get_int()
+                            .map(|m| m == SCAN_MODE_CONNECTABLE_DISCOVERABLE)
+                            .unwrap_or(false)
+                    })
+                };
+                Some(super::BluetoothResponse::AdapterList(vec![
+                    super::AdapterInfo {
+                        address,
+                        name,
+                        powered: self.is_powered().unwrap_or(false),
+                        discoverable,
+                    },
+                ]))
+            }
+            super::BluetoothCommand::QueryNumAdapters => {
+                Some(super::BluetoothResponse::Adapters(1))
+            }
+            super::BluetoothCommand::ListPairedDevices => {
+                use super::{BluetoothDeviceSyncTrait, BluetoothDeviceTrait};
+                let devices = match self.get_bonded_devices() {
+                    Ok(devices) => devices,
+                    Err(e) => return Some(super::BluetoothResponse::Error(e.to_string())),
+                };
+                let mut list = Vec::new();
+                for mut d in devices {
+                    if let Ok(address) = d.get_address() {
+                        list.push(super::DeviceSummary {
+                            address,
+                            name: d.get_name().ok(),
+                        });
+                    }
+                }
+                Some(super::BluetoothResponse::PairedDevices(list))
+            }
+            super::BluetoothCommand::StartDiscovery => {
+                // Stop the previous discovery first, so it does not stop the new one when dropped later
+                self.discovery.lock().unwrap().take();
+                match self.start_discovery() {
+                    Ok(d) => {
+                        *self.discovery.lock().unwrap() = Some(d);
+                        Some(super::BluetoothResponse::DiscoveryStarted)
+                    }
+                    Err(e) => Some(super::BluetoothResponse::Error(e.to_string())),
+                }
+            }
+            super::BluetoothCommand::StopDiscovery => {
+                self.discovery.lock().unwrap().take();
+                Some(super::BluetoothResponse::DiscoveryStopped)
+            }
+            super::BluetoothCommand::SetDiscoverable {
+                enable,
+                timeout_secs,
+            } => {
+                let timeout = timeout_secs.map(|t| std::time::Duration::from_secs(t as u64));
+                Some(match self.set_discoverable(enable, timeout) {
+                    Ok(d) => super::BluetoothResponse::Discoverable(d),
+                    Err(e) => super::BluetoothResponse::Error(e.to_string()),
+                })
+            }
+            super::BluetoothCommand::Pair { address } => {
+                Some(match self.pair_command(&address, true) {
+                    Ok(a) => super::BluetoothResponse::Paired(a),
+                    Err(e) => super::BluetoothResponse::Error(e.to_string()),
+                })
+            }
+            super::BluetoothCommand::Unpair { address } => {
+                Some(match self.pair_command(&address, false) {
+                    Ok(a) => super::BluetoothResponse::Unpaired(a),
+                    Err(e) => super::BluetoothResponse::Error(e.to_string()),
+                })
+            }
+            super::BluetoothCommand::ConnectRfcomm { address, uuid } => {
+                Some(match self.connect_command(&address, &uuid) {
+                    Ok(a) => super::BluetoothResponse::RfcommConnected(a),
+                    Err(e) => super::BluetoothResponse::Error(e.to_string()),
+                })
+            }
+        }
+    }
+}
 
 const BLUETOOTH_SERVICE: &str = "bluetooth";
 
+/// Convert a BluetoothDevice bond state into a pairing status
+fn pairing_status(state: i32) -> super::PairingStatus {
+    match state {
+        device::BOND_NONE => super::PairingStatus::NotPaired,
+        device::BOND_BONDING => super::PairingStatus::Pairing,
+        device::BOND_BONDED => super::PairingStatus::Paired,
+        _ => super::PairingStatus::Unknown,
+    }
+}
+
+/// BluetoothDevice.PAIRING_VARIANT_PIN
+const PAIRING_VARIANT_PIN: i32 = 0;
+
+/// Ask the bluetooth host for a pin code, waiting until the response timeout of the config. No answer in time
+/// cancels the request, and the host is told to stop displaying the request once there is an outcome.
+fn ask_pin_code(
+    host: &tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>,
+    config: &super::AgentConfig,
+    device: String,
+) -> super::PinCodeReply {
+    let reply = ask_host(host, config.response_timeout, |responder| {
+        super::MessageToBluetoothHost::RequestPinCode { device, responder }
+    })
+    .map_or(super::PinCodeReply::Cancel, |r| r.checked());
+    cancel_display(host);
+    reply
+}
+
+/// Give the pin code to the device, or cancel the pairing when the host did not give one
+fn answer_pin_request(
+    env: &mut jni::JNIEnv,
+    device: &jni::objects::JObject,
+    reply: super::PinCodeReply,
+) -> Result<(), std::io::Error> {
+    match reply {
+        super::PinCodeReply::Pin(pin) => {
+            let pin = env
+                .byte_array_from_slice(pin.as_bytes())
+                .map_err(|e| jerr(env, e))?;
+            let accepted = env
+                .call_method(device, "setPin", "([B)Z", &[(&pin).into()])
+                .get_boolean()
+                .map_err(|e| jerr(env, e))?;
+            if !accepted {
+                return Err(std::io::Error::other("The pin code was not accepted"));
+            }
+            Ok(())
+        }
+        super::PinCodeReply::Reject | super::PinCodeReply::Cancel => {
+            // cancelBondProcess is hidden from the public sdk, but has been present since api level 1
+            env.call_method(device, "cancelBondProcess", "()Z", &[])
+                .get_boolean()
+                .map(|_| ())
+                .map_err(|e| jerr(env, e))
+        }
+    }
+}
+
+/// BluetoothAdapter.STATE_OFF
+const STATE_OFF: i32 = 10;
+/// BluetoothAdapter.STATE_TURNING_ON
+const STATE_TURNING_ON: i32 = 11;
+/// BluetoothAdapter.STATE_ON
+const STATE_ON: i32 = 12;
+/// BluetoothAdapter.STATE_TURNING_OFF
+const STATE_TURNING_OFF: i32 = 13;
+/// BluetoothAdapter.SCAN_MODE_CONNECTABLE
+const SCAN_MODE_CONNECTABLE: i32 = 21;
+/// BluetoothAdapter.SCAN_MODE_CONNECTABLE_DISCOVERABLE
+const SCAN_MODE_CONNECTABLE_DISCOVERABLE: i32 = 23;
+
+/// Convert a BluetoothAdapter scan mode into the scan mode of this crate
+fn scan_mode(mode: i32) -> super::ScanMode {
+    match mode {
+        SCAN_MODE_CONNECTABLE => super::ScanMode::Connectable,
+        SCAN_MODE_CONNECTABLE_DISCOVERABLE => super::ScanMode::ConnectableDiscoverable,
+        _ => super::ScanMode::None,
+    }
+}
+
 impl Bluetooth {
+    /// Keep a receiver until the adapter is dropped, or until the event stream it feeds is closed. The receivers
+    /// of the streams closed since the last call are dropped here, so they do not pile up.
+    fn keep_receiver(&self, receiver: RegisteredReceiver) {
+        let mut receivers = self.receivers.lock().unwrap();
+        receivers.retain(|r| !r.stream_closed());
+        receivers.push(receiver);
+    }
+
     /// constructs a new Self with the protected java instance
     pub fn new(app: AndroidApp) -> Self {
         let java = Arc::new(Mutex::new(Java::make(app)));
@@ -490,12 +2868,203 @@ impl Bluetooth {
             let mut java2 = java.lock().unwrap();
             java2.use_env(|env, context| Self::get_adapter(env, &context).unwrap())
         };
+        let battery_levels = Arc::new(Mutex::new(std::collections::HashMap::new()));
+        let mut receivers = Vec::new();
+        match Self::watch_battery_levels(&java, battery_levels.clone()) {
+            Ok(r) => receivers.push(r),
+            Err(e) => log::warn!("Unable to watch device battery levels: {}", e),
+        }
+        let profile_proxies = ProfileProxyMap::default();
+        let profile_listener = match ProfileProxies::new(&adapter, &java, profile_proxies.clone()) {
+            Ok(p) => Some(p),
+            Err(e) => {
+                log::warn!("Unable to get the profile proxies: {}", e);
+                None
+            }
+        };
         Self {
             adapter,
             java,
             receiver: None,
             blue_uuid_receiver: None,
+            receivers: Mutex::new(receivers),
+            host: None,
+            agent_config: super::AgentConfig::default(),
+            pin_receiver: None,
+            battery_levels,
+            rssi_readings: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            profile_proxies,
+            discovery: Mutex::new(None),
+            connections: Mutex::new(std::collections::HashMap::new()),
+            _profile_listener: profile_listener,
+        }
+    }
+
+    /// Keep track of the battery levels reported by the hidden ACTION_BATTERY_LEVEL_CHANGED broadcast
+    fn watch_battery_levels(
+        java: &Arc<Mutex<super::Java>>,
+        levels: Arc<Mutex<std::collections::HashMap<String, u8>>>,
+    ) -> Result<RegisteredReceiver, std::io::Error> {
+        RegisteredReceiver::new(
+            java,
+            &["android.bluetooth.device.action.BATTERY_LEVEL_CHANGED"],
+            move |env, _context, intent| {
+                let extra = "android.bluetooth.device.extra.DEVICE".new_jobject(env)?;
+                let device = env
+                    .call_method(
+                        intent,
+                        "getParcelableExtra",
+                        "(Ljava/lang/String;)Landroid/os/Parcelable;",
+                        &[(&extra).into()],
+                    )
+                    .get_object(env)?;
+                if device.is_null() {
+                    return Ok(());
+                }
+                let address = env
+                    .call_method(&device, "getAddress", "()Ljava/lang/String;", &[])
+                    .get_object(env)?
+                    .get_string(env)?;
+                let extra = "android.bluetooth.device.extra.BATTERY_LEVEL".new_jobject(env)?;
+                let level = env
+                    .call_method(
+                        intent,
+                        "getIntExtra",
+                        "(Ljava/lang/String;I)I",
+                        &[(&extra).into(), (-1).into()],
+                    )
+                    .get_int()?;
+                let mut levels = levels.lock().unwrap();
+                match u8::try_from(level).ok().filter(|l| *l <= 100) {
+                    Some(l) => levels.insert(address, l),
+                    None => levels.remove(&address),
+                };
+                Ok(())
+            },
+        )
+    }
+
+    /// Listen for rfcomm connections with the service record api, which is available on all api levels
+    fn listen_rfcomm_legacy(
+        &self,
+        env: &mut jni::JNIEnv,
+        settings: &crate::BluetoothRfcommProfileSettings,
+    ) -> Result<jni::objects::GlobalRef, crate::BluetoothError> {
+        if settings.psm.is_some() {
+            return Err(crate::BluetoothError::Unsupported(
+                "Choosing the psm of an rfcomm profile before api level 36",
+            ));
+        }
+        if settings.sdp_record.is_some() {
+            return Err(crate::BluetoothError::Unsupported("A manual sdp record"));
+        }
+        let name = settings.name.as_deref().unwrap_or(&settings.uuid);
+        let name = name.new_jobject(env).map_err(|e| jerr(env, e))?;
+        let uuid = settings
+            .uuid
+            .as_str()
+            .new_jobject(env)
+            .map_err(|e| jerr(env, e))?;
+        let uuid = env
+            .call_static_method(
+                "java/util/UUID",
+                "fromString",
+                "(Ljava/lang/String;)Ljava/util/UUID;",
+                &[(&uuid).into()],
+            )
+            .get_object(env)
+            .map_err(|e| jerr(env, e))?;
+        let method = if settings.authenticate == Some(false) {
+            "listenUsingInsecureRfcommWithServiceRecord"
+        } else {
+            "listenUsingRfcommWithServiceRecord"
+        };
+        let server = env
+            .call_method(
+                &self.adapter,
+                method,
+                "(Ljava/lang/String;Ljava/util/UUID;)Landroid/bluetooth/BluetoothServerSocket;",
+                &[(&name).into(), (&uuid).into()],
+            )
+            .get_object(env)
+            .map_err(|e| permission_err(env, method, e))?;
+        env.new_global_ref(&server).map_err(|e| jerr(env, e))
+    }
+
+    /// Set where requests that need an answer from the user are sent, and how they are handled.
+    /// Without a host, incoming connections are handled according to the config defaults.
+    pub fn set_host(
+        &mut self,
+        host: Option<tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>>,
+        config: super::AgentConfig,
+    ) {
+        self.pin_receiver = None;
+        if let Some(host) = &host {
+            match Self::watch_pin_requests(&self.java, host.clone(), config.clone()) {
+                Ok(r) => self.pin_receiver = Some(r),
+                Err(e) => log::warn!("Unable to watch for pin code requests: {}", e),
+            }
         }
+        self.host = host;
+        self.agent_config = config;
+    }
+
+    /// Ask the bluetooth host for the pin code of legacy pairing requests, and give it to the device with
+    /// setPin. The other pairing variants are left to the system dialog.
+    fn watch_pin_requests(
+        java: &Arc<Mutex<super::Java>>,
+        host: tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>,
+        config: super::AgentConfig,
+    ) -> Result<RegisteredReceiver, std::io::Error> {
+        let app = java.lock().unwrap().get_app();
+        RegisteredReceiver::new(
+            java,
+            &["android.bluetooth.device.action.PAIRING_REQUEST"],
+            move |env, _context, intent| {
+                let extra = "android.bluetooth.device.extra.PAIRING_VARIANT".new_jobject(env)?;
+                let variant = env
+                    .call_method(
+                        intent,
+                        "getIntExtra",
+                        "(Ljava/lang/String;I)I",
+                        &[(&extra).into(), (-1).into()],
+                    )
+                    .get_int()?;
+                if variant != PAIRING_VARIANT_PIN {
+                    return Ok(());
+                }
+                let extra = "android.bluetooth.device.extra.DEVICE".new_jobject(env)?;
+                let device = env
+                    .call_method(
+                        intent,
+                        "getParcelableExtra",
+                        "(Ljava/lang/String;)Landroid/os/Parcelable;",
+                        &[(&extra).into()],
+                    )
+                    .get_object(env)?;
+                if device.is_null() {
+                    return Ok(());
+                }
+                let address = env
+                    .call_method(&device, "getAddress", "()Ljava/lang/String;", &[])
+                    .get_object(env)?
+                    .get_string(env)?;
+                let device = env.new_global_ref(&device)?;
+                let app = app.clone();
+                let host = host.clone();
+                let config = config.clone();
+                // The receiver runs on the main thread, so the host is waited for on another thread
+                std::thread::spawn(move || {
+                    let reply = ask_pin_code(&host, &config, address);
+                    let mut java = super::Java::make(app);
+                    let r = java.use_env(|env, _context| answer_pin_request(env, &device, reply));
+                    if let Err(e) = r {
+                        log::error!("Failed to answer the pin code request: {}", e);
+                    }
+                });
+                Ok(())
+            },
+        )
     }
 
     fn check_adapter(&mut self) {
@@ -511,7 +3080,7 @@ impl Bluetooth {
                 Ok(())
             })
             .unwrap();
-            let r = register_receiver(&self.java, &arg1, "android.bluetooth.device.action.UUID");
+            let r = register_receiver(&self.java, &arg1, &["android.bluetooth.device.action.UUID"]);
             self.blue_uuid_receiver.replace(arg1);
             if let Some(r) = r {
                 log::error!("Receiver is {:?}", r);
@@ -543,62 +3112,16 @@ impl Bluetooth {
         java.use_env(|env, context| self.check_permission2(env, &context, permission))
     }
 
-    /// Attempt to get the permisssion needed
+    /// Ask the user for the permission. This returns once the request is made, without waiting for the answer.
     pub fn get_permission2(
         &self,
         env: &mut jni::JNIEnv,
         context: &jni::objects::JObject,
         permission: &str,
-        app: AndroidApp,
+        _app: AndroidApp,
     ) -> Result<bool, std::io::Error> {
-        // Get ClassLoader instance from activity
-        let class_loader_obj = env
-            .call_method(&context, "getClassLoader", "()Ljava/lang/ClassLoader;", &[])
-            .expect("Failed to get ClassLoader")
-            .l()
-            .map_err(|e| jerr(env, e))?;
-
-        // Name of the class you want to load
-        let class_name = "com.example.android.JniBridge"
-            .new_jobject(env)
-            .map_err(|e| jerr(env, e))?;
-
-        // Call loadClass method (no need to get_method_id explicitly, call_method resolves it)
-        let jni_bridge_class_obj = env
-            .call_method(
-                class_loader_obj,
-                "loadClass",
-                "(Ljava/lang/String;)Ljava/lang/Class;",
-                &[(&class_name).into()],
-            )
-            .expect("Failed to call loadClass")
-            .l()
-            .map_err(|e| jerr(env, e))?;
-
-        // Convert JObject to JClass for new_object
-        let jni_bridge_class = jni::objects::JClass::from(jni_bridge_class_obj);
-
-        // Instantiate JniBridge using its no-arg constructor
-        let jni_bridge_obj = env
-            .new_object(jni_bridge_class, "()V", &[])
-            .map_err(|e| jerr(env, e))?;
-
-        let activity =
-            unsafe { jni::objects::JObject::from_raw(app.activity_as_ptr() as *mut _jobject) };
-
-        let arg2 = permission
-            .new_jobject(env)
-            .map_err(|e| jerr(env, e))
-            .unwrap();
-        let asdf = env
-            .call_method(
-                jni_bridge_obj,
-                "requestPermission",
-                "(Landroid/app/Activity;Ljava/lang/String;)I",
-                &[(&activity).try_into().unwrap(), (&arg2).try_into().unwrap()],
-            )
-            .map_err(|e| jerr(env, e))?;
-        asdf.i().map(|v| v == 0).map_err(|e| jerr(env, e))
+        request_permissions(env, context, &[permission], 0).map_err(|e| jerr(env, e))?;
+        Ok(true)
     }
 
     /// Check to see if we have the specified permission
@@ -608,84 +3131,18 @@ impl Bluetooth {
         context: &jni::objects::JObject,
         permission: &str,
     ) -> Result<bool, std::io::Error> {
-        // Get ClassLoader instance from activity
-        let class_loader_obj = env
-            .call_method(&context, "getClassLoader", "()Ljava/lang/ClassLoader;", &[])
-            .expect("Failed to get ClassLoader")
-            .l()
-            .map_err(|e| jerr(env, e))?;
-
-        // Name of the class you want to load
-        let class_name = "com.example.android.JniBridge"
-            .new_jobject(env)
-            .map_err(|e| jerr(env, e))?;
-
-        // Call loadClass method (no need to get_method_id explicitly, call_method resolves it)
-        let jni_bridge_class_obj = env
-            .call_method(
-                class_loader_obj,
-                "loadClass",
-                "(Ljava/lang/String;)Ljava/lang/Class;",
-                &[(&class_name).into()],
-            )
-            .expect("Failed to call loadClass")
-            .l()
-            .map_err(|e| jerr(env, e))?;
-
-        // Convert JObject to JClass for new_object
-        let jni_bridge_class = jni::objects::JClass::from(jni_bridge_class_obj);
-
-        // Instantiate JniBridge using its no-arg constructor
-        let jni_bridge_obj = env
-            .new_object(jni_bridge_class, "()V", &[])
-            .map_err(|e| jerr(env, e))?;
-
-        let arg2 = permission
-            .new_jobject(env)
-            .map_err(|e| jerr(env, e))
-            .unwrap();
-        let asdf = env
+        let permission = permission.new_jobject(env).map_err(|e| jerr(env, e))?;
+        let result = env
             .call_method(
-                jni_bridge_obj,
+                context,
                 "checkSelfPermission",
-                "(Landroid/content/Context;Ljava/lang/String;)I",
-                &[(&context).try_into().unwrap(), (&arg2).try_into().unwrap()],
+                "(Ljava/lang/String;)I",
+                &[(&permission).into()],
             )
+            .get_int()
             .map_err(|e| jerr(env, e))?;
-        asdf.i().map(|v| v == 0).map_err(|e| jerr(env, e))
-    }
-
-    /// Enables the bluetooth adapter
-    pub fn enable(&mut self) {
-        if !self.is_enabled() {
-            log::error!("Bluetooth not enabled. Requesting it to be enabled");
-            let mut java = self.java.lock().unwrap();
-            java.use_env(|env, context| {
-                let arg = "android.bluetooth.adapter.action.REQUEST_ENABLE"
-                    .new_jobject(env)
-                    .map_err(|e| jerr(env, e))
-                    .unwrap();
-                let intent = env
-                    .new_object(
-                        "android/content/Intent",
-                        "(Ljava/lang/String;)V",
-                        &[(&arg).into()],
-                    )
-                    .unwrap();
-                let mut args = Vec::new();
-                args.push(&intent);
-                let mut args2: Vec<jni::objects::JValueGen<&jni::objects::JObject>> =
-                    args.iter().map(|a| a.try_into().unwrap()).collect();
-                args2.push(1.into());
-                let a = env.call_method(
-                    context,
-                    "startActivityForResult",
-                    "(Landroid/content/Intent;I)V",
-                    args2.as_slice(),
-                );
-                log::error!("Results of bluetooth enable is {:?}", a);
-            })
-        }
+        // PackageManager.PERMISSION_GRANTED
+        Ok(result == 0)
     }
 
     /// Returns the enabled state of the bluetooth adapter
@@ -701,17 +3158,74 @@ impl Bluetooth {
         })
     }
 
-    /// Get the list of bonded devices for the bluetooth adapter
-    pub fn get_bonded_devices(&self) -> Option<Vec<BluetoothDevice>> {
+    /// Take the stream connected to the device by a `ConnectRfcomm` command
+    pub fn take_connection(
+        &self,
+        address: &super::BluetoothAdapterAddress,
+    ) -> Option<super::BluetoothStream> {
+        self.connections.lock().unwrap().remove(address)
+    }
+
+    /// Parse the address given to a command
+    fn command_address(
+        address: &str,
+    ) -> Result<super::BluetoothAdapterAddress, crate::BluetoothError> {
+        address
+            .parse()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e).into())
+    }
+
+    /// Pair with or remove the bond of the device for the `Pair` and `Unpair` commands
+    fn pair_command(
+        &self,
+        address: &str,
+        pair: bool,
+    ) -> Result<super::BluetoothAdapterAddress, crate::BluetoothError> {
+        use super::{BluetoothDeviceTrait, SyncBluetoothAdapterTrait};
+        let address = Self::command_address(address)?;
+        let mut device = self.device_by_address(&address)?;
+        let device = device
+            .supports_sync()
+            .ok_or(crate::BluetoothError::Unsupported("Pairing"))?;
+        if pair {
+            device.pair()?;
+        } else {
+            device.remove_bond()?;
+        }
+        Ok(address)
+    }
+
+    /// Connect to the service of the device for the `ConnectRfcomm` command, keeping the stream
+    fn connect_command(
+        &self,
+        address: &str,
+        uuid: &str,
+    ) -> Result<super::BluetoothAdapterAddress, crate::BluetoothError> {
+        use super::SyncBluetoothAdapterTrait;
+        use std::str::FromStr;
+        let address = Self::command_address(address)?;
+        let uuid = super::BluetoothUuid::from_str(uuid)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let stream = self.connect_rfcomm(&address, super::RfcommTarget::Uuid(uuid))?;
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(address.clone(), stream);
+        Ok(address)
+    }
+
+    /// Get the devices bonded with the adapter. Without the permission to get them, the error says which
+    /// permission is missing.
+    pub fn get_bonded_devices(&self) -> Result<Vec<BluetoothDevice>, crate::BluetoothError> {
         let mut java = self.java.lock().unwrap();
         java.use_env(
-            |env, _context| -> Result<Vec<BluetoothDevice>, std::io::Error> {
+            |env, _context| -> Result<Vec<BluetoothDevice>, crate::BluetoothError> {
                 let dev_set = env
                     .call_method(&self.adapter, "getBondedDevices", "()Ljava/util/Set;", &[])
                     .get_object(env)
-                    .map_err(|e| jerr(env, e))?;
+                    .map_err(|e| permission_err(env, "getBondedDevices", e))?;
                 if dev_set.is_null() {
-                    return Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+                    return Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied).into());
                 }
                 let jarr = env
                     .call_method(&dev_set, "toArray", "()[Ljava/lang/Object;", &[])
@@ -721,17 +3235,22 @@ impl Bluetooth {
                 let len = env.get_array_length(jarr).map_err(|e| jerr(env, e))?;
                 let mut vec = Vec::with_capacity(len as usize);
                 for i in 0..len {
-                    vec.push(BluetoothDevice::new(
-                        env.get_object_array_element(jarr, i)
-                            .global_ref(env)
-                            .map_err(|e| jerr(env, e))?,
-                        self.java.clone(),
-                    ));
+                    vec.push(
+                        BluetoothDevice::new(
+                            env.get_object_array_element(jarr, i)
+                                .global_ref(env)
+                                .map_err(|e| jerr(env, e))?,
+                            self.java.clone(),
+                        )
+                        .with_battery_levels(self.battery_levels.clone())
+                        .with_rssi_readings(self.rssi_readings.clone())
+                        .with_profile_proxies(self.profile_proxies.clone())
+                        .with_response_timeout(self.agent_config.response_timeout),
+                    );
                 }
                 Ok(vec)
             },
         )
-        .ok()
     }
 
     fn get_adapter<'a>(
@@ -776,10 +3295,114 @@ impl Bluetooth {
     }
 }
 
+/// Start the system activity for the given intent action, such as the dialog asking the user to enable bluetooth.
+/// An optional integer extra can be attached to the intent.
+fn start_activity(
+    env: &mut jni::JNIEnv,
+    context: &jni::objects::JObject,
+    action: &str,
+    extra: Option<(&str, i32)>,
+) -> Result<(), std::io::Error> {
+    let arg = action.new_jobject(env).map_err(|e| jerr(env, e))?;
+    let intent = env
+        .new_object(
+            "android/content/Intent",
+            "(Ljava/lang/String;)V",
+            &[(&arg).into()],
+        )
+        .map_err(|e| jerr(env, e))?;
+    if let Some((name, value)) = extra {
+        let name = name.new_jobject(env).map_err(|e| jerr(env, e))?;
+        env.call_method(
+            &intent,
+            "putExtra",
+            "(Ljava/lang/String;I)Landroid/content/Intent;",
+            &[(&name).into(), value.into()],
+        )
+        .map_err(|e| jerr(env, e))?;
+    }
+    env.call_method(
+        context,
+        "startActivityForResult",
+        "(Landroid/content/Intent;I)V",
+        &[(&intent).into(), 1.into()],
+    )
+    .map_err(|e| jerr(env, e))?;
+    Ok(())
+}
+
+/// A broadcast receiver registered with the activity. It is unregistered when dropped.
+pub(crate) struct RegisteredReceiver {
+    /// The receiver object
+    receiver: jni_min_helper::BroadcastReceiver,
+    /// The java instance
+    java: Arc<Mutex<super::Java>>,
+    /// Tells if the event stream fed by the receiver is closed, for receivers that feed one
+    stream_closed: Option<Box<dyn Fn() -> bool + Send + Sync>>,
+}
+
+impl RegisteredReceiver {
+    /// Build a receiver that runs the callback for every broadcast matching one of the actions
+    pub(crate) fn new<F>(
+        java: &Arc<Mutex<super::Java>>,
+        actions: &[&str],
+        f: F,
+    ) -> Result<Self, std::io::Error>
+    where
+        F: Fn(
+                &mut jni::JNIEnv<'_>,
+                &jni::objects::JObject<'_>,
+                &jni::objects::JObject<'_>,
+            ) -> Result<(), jni::errors::Error>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let receiver =
+            jni_min_helper::BroadcastReceiver::build(f).map_err(std::io::Error::other)?;
+        register_receiver(java, &receiver, actions).ok_or_else(|| {
+            std::io::Error::other(format!("Failed to register a receiver for {:?}", actions))
+        })?;
+        Ok(Self {
+            receiver,
+            java: java.clone(),
+            stream_closed: None,
+        })
+    }
+
+    /// The receiver feeds the event stream of the sender, it is no longer needed once the stream is closed
+    pub(crate) fn feeding<T: Send + 'static>(mut self, tx: tokio::sync::mpsc::Sender<T>) -> Self {
+        self.stream_closed = Some(Box::new(move || tx.is_closed()));
+        self
+    }
+
+    /// Is the event stream fed by the receiver closed
+    fn stream_closed(&self) -> bool {
+        self.stream_closed.as_ref().is_some_and(|closed| closed())
+    }
+}
+
+impl Drop for RegisteredReceiver {
+    fn drop(&mut self) {
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, context| {
+            let _ = env
+                .call_method(
+                    context,
+                    "unregisterReceiver",
+                    "(Landroid/content/BroadcastReceiver;)V",
+                    &[self.receiver.as_ref().into()],
+                )
+                .clear_ex();
+        });
+    }
+}
+
+/// Register the receiver for broadcasts matching any of the actions
 fn register_receiver(
     java: &Arc<Mutex<super::Java>>,
     arg1: &jni_min_helper::BroadcastReceiver,
-    intent_str: &str,
+    actions: &[&str],
 ) -> Option<jni::objects::GlobalRef> {
     let mut java2 = java.lock().unwrap();
     let mut sig = String::new();
@@ -789,13 +3412,21 @@ fn register_receiver(
     sig.push_str(")Landroid/content/Intent;");
     java2.use_env(|env, context| {
         let mut args = Vec::new();
-        let intent_str = intent_str.new_jobject(env).unwrap();
-        let arg2 = env.new_object(
-            "android/content/IntentFilter",
-            "(Ljava/lang/String;)V",
-            &[(&intent_str).into()],
-        );
-        let arg2 = arg2.unwrap();
+        let arg2 = env
+            .new_object("android/content/IntentFilter", "()V", &[])
+            .map_err(|e| jerr(env, e))
+            .ok()?;
+        for action in actions {
+            let action = action.new_jobject(env).ok()?;
+            env.call_method(
+                &arg2,
+                "addAction",
+                "(Ljava/lang/String;)V",
+                &[(&action).into()],
+            )
+            .map_err(|e| jerr(env, e))
+            .ok()?;
+        }
         args.push(arg1.as_ref());
         args.push(&arg2);
         let args2: Vec<jni::objects::JValueGen<&jni::objects::JObject>> =
@@ -808,3 +3439,70 @@ fn register_receiver(
         env.new_global_ref(&e).map_err(|e| jerr(env, e)).ok()
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jni::signature::{JavaType, Primitive, ReturnType, TypeSignature};
+    use std::str::FromStr;
+
+    /// Check that the signature takes a byte array, an offset and a length
+    fn assert_array_offset_length(signature: &TypeSignature) {
+        assert_eq!(signature.args.len(), 3);
+        assert!(matches!(&signature.args[0], JavaType::Array(t)
+            if matches!(**t, JavaType::Primitive(Primitive::Byte))));
+        for arg in &signature.args[1..] {
+            assert!(matches!(arg, JavaType::Primitive(Primitive::Int)));
+        }
+    }
+
+    #[test]
+    fn stream_signatures_match_the_java_methods() {
+        let read = TypeSignature::from_str(INPUT_STREAM_READ).unwrap();
+        assert_array_offset_length(&read);
+        assert!(matches!(read.ret, ReturnType::Primitive(Primitive::Int)));
+
+        let write = TypeSignature::from_str(OUTPUT_STREAM_WRITE).unwrap();
+        assert_array_offset_length(&write);
+        assert!(matches!(write.ret, ReturnType::Primitive(Primitive::Void)));
+
+        let flush = TypeSignature::from_str(OUTPUT_STREAM_FLUSH).unwrap();
+        assert!(flush.args.is_empty());
+        assert!(matches!(flush.ret, ReturnType::Primitive(Primitive::Void)));
+    }
+
+    #[test]
+    fn stream_methods_are_called_with_their_return_types() {
+        let read = TypeSignature::from_str(INPUT_STREAM_READ).unwrap();
+        assert_eq!(read.ret, StreamMethods::READ_RETURN);
+        let write = TypeSignature::from_str(OUTPUT_STREAM_WRITE).unwrap();
+        assert_eq!(write.ret, StreamMethods::WRITE_RETURN);
+        let flush = TypeSignature::from_str(OUTPUT_STREAM_FLUSH).unwrap();
+        assert_eq!(flush.ret, StreamMethods::WRITE_RETURN);
+    }
+
+    #[test]
+    fn read_count_follows_input_stream_read() {
+        assert_eq!(read_count(-1, 16).unwrap(), 0);
+        assert_eq!(read_count(0, 16).unwrap(), 0);
+        assert_eq!(read_count(5, 16).unwrap(), 5);
+        assert_eq!(read_count(16, 16).unwrap(), 16);
+        let err = read_count(17, 16).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn take_pending_copies_what_fits() {
+        let mut pending = b"hello world".to_vec();
+        let mut buf = [0u8; 5];
+        assert_eq!(take_pending(&mut pending, &mut buf), 5);
+        assert_eq!(&buf, b"hello");
+        assert_eq!(pending, b" world");
+
+        let mut buf = [0u8; 16];
+        assert_eq!(take_pending(&mut pending, &mut buf), 6);
+        assert_eq!(&buf[..6], b" world");
+        assert!(pending.is_empty());
+        assert_eq!(take_pending(&mut pending, &mut buf), 0);
+    }
+}
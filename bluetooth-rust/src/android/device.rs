@@ -5,33 +5,80 @@ use super::BluetoothSocket;
 use super::{ParcelUuid, jerr};
 use crate::BluetoothUuid;
 use jni_min_helper::*;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, Mutex};
 
+/// BluetoothDevice.ACTION_ACL_CONNECTED
+const ACTION_ACL_CONNECTED: &str = "android.bluetooth.device.action.ACL_CONNECTED";
+/// BluetoothDevice.ACTION_ACL_DISCONNECTED
+const ACTION_ACL_DISCONNECTED: &str = "android.bluetooth.device.action.ACL_DISCONNECTED";
+/// BluetoothDevice.ACTION_NAME_CHANGED
+const ACTION_NAME_CHANGED: &str = "android.bluetooth.device.action.NAME_CHANGED";
+/// BluetoothDevice.ACTION_BOND_STATE_CHANGED
+const ACTION_BOND_STATE_CHANGED: &str = "android.bluetooth.device.action.BOND_STATE_CHANGED";
+/// BluetoothDevice.ACTION_UUID
+const ACTION_UUID: &str = "android.bluetooth.device.action.UUID";
+/// BluetoothDevice.BOND_NONE
+pub(super) const BOND_NONE: i32 = 10;
+/// BluetoothDevice.BOND_BONDING
+pub(super) const BOND_BONDING: i32 = 11;
+/// BluetoothDevice.BOND_BONDED
+pub(super) const BOND_BONDED: i32 = 12;
+/// BluetoothStatusCodes.SUCCESS
+pub(super) const BLUETOOTH_STATUS_SUCCESS: i32 = 0;
+/// BluetoothStatusCodes.ERROR_MISSING_BLUETOOTH_CONNECT_PERMISSION
+const BLUETOOTH_STATUS_MISSING_CONNECT_PERMISSION: i32 = 6;
+
+/// Convert the value of BluetoothDevice.getType into the transport of the device
+pub(super) fn device_transport(t: i32) -> crate::DeviceTransport {
+    match t {
+        1 => crate::DeviceTransport::Classic,
+        2 => crate::DeviceTransport::Le,
+        3 => crate::DeviceTransport::Dual,
+        _ => crate::DeviceTransport::Unknown,
+    }
+}
+
+/// Build the error for functionality that android does not provide
+fn unsupported(what: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("{what} is not supported on android"),
+    )
+}
+
 pub struct BluetoothDevice {
     internal: jni::objects::GlobalRef,
     rfcomm_sockets: BTreeMap<String, BluetoothSocket>,
     java: Arc<Mutex<Java>>,
+    /// The signal strengths reported by discovery broadcasts, by device address
+    rssi_readings: Option<Arc<Mutex<HashMap<String, crate::RssiReading>>>>,
+    /// The profile proxies of the adapter, by android profile number
+    profile_proxies: Option<super::ProfileProxyMap>,
+    /// The state of the most recent gatt client, used to read the signal strength while connected
+    gatt: Mutex<std::sync::Weak<super::gatt::Shared>>,
+    /// The broadcast receivers used for watching the properties of the device
+    receivers: Vec<super::RegisteredReceiver>,
+    /// The battery levels reported by broadcasts, by device address
+    battery_levels: Option<Arc<Mutex<HashMap<String, u8>>>>,
+    /// The class of the device, which does not change once it is known
+    class: std::sync::OnceLock<crate::DeviceClass>,
+    /// The uuids from the most recent service discovery, which are fresher than the ones cached by android
+    uuids: Option<Vec<BluetoothUuid>>,
+    /// How long pairing and removing the bond wait for the bond state to settle
+    response_timeout: std::time::Duration,
 }
 
-impl crate::BluetoothDeviceTrait for BluetoothDevice {
-    fn run_sdp(&mut self) {
-        let mut java = self.java.lock().unwrap();
-        let _result = java.use_env(|env, _context| {
-            let dev_name = env
-                .call_method(&self.internal, "fetchUuidsWithSdp", "()Z", &[])
-                .get_boolean();
-            dev_name.map_err(|e| jerr(env, e))
-        });
-    }
-
+impl crate::BluetoothDeviceSyncTrait for BluetoothDevice {
+    /// The uuids from the last refresh_uuids are used when there are some, otherwise the uuids cached by android
+    /// are used
     fn get_uuids(&mut self) -> Result<Vec<BluetoothUuid>, std::io::Error> {
+        if let Some(uuids) = &self.uuids {
+            return Ok(uuids.clone());
+        }
         let p = self.get_parcel_uuids();
         match p {
-            Ok(p) => {
-                use std::convert::TryInto;
-                Ok(p.into_iter().map(|a| a.try_into().unwrap()).collect())
-            }
+            Ok(p) => Ok(p.into_iter().map(BluetoothUuid::from).collect()),
             Err(e) => Err(e),
         }
     }
@@ -50,18 +97,68 @@ impl crate::BluetoothDeviceTrait for BluetoothDevice {
         })
     }
 
-    fn get_address(&mut self) -> Result<String, std::io::Error> {
+    /// Aliases are only available from api level 30, the name is used on older versions
+    fn alias(&self) -> Result<String, std::io::Error> {
+        let alias = {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, _context| {
+                if super::sdk_int(env).map_err(|e| jerr(env, e))? < 30 {
+                    return Ok(None);
+                }
+                let alias = env
+                    .call_method(&self.internal, "getAlias", "()Ljava/lang/String;", &[])
+                    .get_object(env)
+                    .map_err(|e| jerr(env, e))?;
+                if alias.is_null() {
+                    return Ok(None);
+                }
+                alias.get_string(env).map(Some).map_err(|e| jerr(env, e))
+            })?
+        };
+        match alias {
+            Some(a) => Ok(a),
+            None => self.get_name(),
+        }
+    }
+
+    /// The callbacks of the connection are handled by the GattCallbackBridge class shipped with the crate, which
+    /// the application has to include
+    fn gatt(&self) -> Result<crate::GattClient, std::io::Error> {
         let mut java = self.java.lock().unwrap();
-        java.use_env(|env, _context| {
-            let dev_name = env
-                .call_method(&self.internal, "getAddress", "()Ljava/lang/String;", &[])
-                .get_object(env)
-                .map_err(|e| jerr(env, e))?;
-            if dev_name.is_null() {
-                return Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        let client = java.use_env(|env, context| {
+            super::GattClient::connect(env, &context, self.internal.as_obj())
+        })?;
+        *self.gatt.lock().unwrap() = client.shared();
+        Ok(crate::GattClient::Android(client))
+    }
+
+    /// Setting the alias requires api level 31, and the app must be associated with the device through the
+    /// companion device manager
+    fn set_alias(&self, alias: &str) -> Result<(), std::io::Error> {
+        let mut java = self.java.lock().unwrap();
+        let status = java.use_env(|env, _context| {
+            if super::sdk_int(env).map_err(|e| jerr(env, e))? < 31 {
+                return Err(unsupported("Setting the device alias before api level 31"));
             }
-            dev_name.get_string(env).map_err(|e| jerr(env, e))
-        })
+            let alias = alias.new_jobject(env).map_err(|e| jerr(env, e))?;
+            env.call_method(
+                &self.internal,
+                "setAlias",
+                "(Ljava/lang/String;)I",
+                &[(&alias).into()],
+            )
+            .get_int()
+            .map_err(|e| jerr(env, e))
+        })?;
+        match status {
+            BLUETOOTH_STATUS_SUCCESS => Ok(()),
+            BLUETOOTH_STATUS_MISSING_CONNECT_PERMISSION => {
+                Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+            }
+            s => Err(std::io::Error::other(format!(
+                "Failed to set the alias, status {s}"
+            ))),
+        }
     }
 
     fn get_pair_state(&self) -> Result<crate::PairingStatus, std::io::Error> {
@@ -72,112 +169,440 @@ impl crate::BluetoothDeviceTrait for BluetoothDevice {
                 .get_int();
             dev_name.map_err(|e| jerr(env, e))
         })?;
-        let s = match s {
-            10 => crate::PairingStatus::NotPaired,
-            11 => crate::PairingStatus::Pairing,
-            12 => crate::PairingStatus::Paired,
-            _ => crate::PairingStatus::Unknown,
+        Ok(super::pairing_status(s))
+    }
+
+    /// While a gatt client of the device is connected, the rssi is read over the connection. Otherwise android
+    /// only reports the rssi in the broadcast for a device found during discovery, so the most recent one is
+    /// used. This must not be called from the main thread, since it waits for the gatt callback.
+    fn get_rssi(&self) -> Result<Option<crate::RssiReading>, std::io::Error> {
+        let address = {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, _context| {
+                env.call_method(&self.internal, "getAddress", "()Ljava/lang/String;", &[])
+                    .get_object(env)
+                    .and_then(|a| a.get_string(env))
+                    .map_err(|e| jerr(env, e))
+            })?
         };
-        Ok(s)
+        let gatt = self.gatt.lock().unwrap().upgrade();
+        if let Some(gatt) = gatt {
+            match gatt.read_remote_rssi(&self.java, std::time::Duration::from_secs(2)) {
+                Ok(rssi) => {
+                    let reading = crate::RssiReading::new(rssi);
+                    if let Some(readings) = &self.rssi_readings {
+                        readings.lock().unwrap().insert(address, reading);
+                    }
+                    return Ok(Some(reading));
+                }
+                Err(e) => log::debug!("Unable to read the rssi over gatt: {e}"),
+            }
+        }
+        Ok(self
+            .rssi_readings
+            .as_ref()
+            .and_then(|r| r.lock().unwrap().get(&address).copied()))
     }
 
-    fn get_l2cap_socket(
+    /// Android only allows connecting individual profiles through their profile proxies
+    fn connect_device(&self) -> Result<(), std::io::Error> {
+        Err(unsupported("connecting all profiles of a device"))
+    }
+
+    /// Android only allows disconnecting individual profiles through their profile proxies
+    fn disconnect_device(&self) -> Result<(), std::io::Error> {
+        Err(unsupported("disconnecting all profiles of a device"))
+    }
+
+    /// This must not be called from the main thread, since the bond state broadcasts are delivered there. Fails
+    /// with `TimedOut` when the device is not bonded within the response timeout of the agent config.
+    fn pair(&mut self) -> Result<(), std::io::Error> {
+        let (_receiver, states) = self.watch_bond_state()?;
+        let deadline = std::time::Instant::now() + self.response_timeout;
+        let started = {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, _context| {
+                env.call_method(&self.internal, "createBond", "()Z", &[])
+                    .get_boolean()
+                    .map_err(|e| jerr(env, e))
+            })?
+        };
+        if !started {
+            // createBond fails when the device is already bonded
+            return match self.get_pair_state()? {
+                crate::PairingStatus::Paired => Ok(()),
+                _ => Err(std::io::Error::other("Failed to start pairing")),
+            };
+        }
+        loop {
+            match next_bond_state(&states, deadline)? {
+                BOND_BONDED => return Ok(()),
+                BOND_NONE => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::PermissionDenied,
+                        "Pairing was rejected or failed",
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// This must not be called from the main thread, since the bond state broadcasts are delivered there. Fails
+    /// with `TimedOut` when the bond is not removed within the response timeout of the agent config.
+    fn remove_bond(&mut self) -> Result<(), std::io::Error> {
+        let (_receiver, states) = self.watch_bond_state()?;
+        let deadline = std::time::Instant::now() + self.response_timeout;
+        let removed = {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, _context| {
+                // removeBond is hidden from the public sdk, but has been present since api level 1
+                env.call_method(&self.internal, "removeBond", "()Z", &[])
+                    .get_boolean()
+                    .map_err(|e| jerr(env, e))
+            })?
+        };
+        if !removed {
+            return Err(std::io::Error::other("failed to remove the bond"));
+        }
+        while next_bond_state(&states, deadline)? != BOND_NONE {}
+        // The sockets are closed when dropped
+        self.rfcomm_sockets.clear();
+        Ok(())
+    }
+
+    fn is_trusted(&self) -> Result<bool, std::io::Error> {
+        Err(unsupported("device trust"))
+    }
+
+    fn set_trusted(&self, _trusted: bool) -> Result<(), std::io::Error> {
+        Err(unsupported("device trust"))
+    }
+
+    fn is_blocked(&self) -> Result<bool, std::io::Error> {
+        Err(unsupported("blocking devices"))
+    }
+
+    fn set_blocked(&self, _blocked: bool) -> Result<(), std::io::Error> {
+        Err(unsupported("blocking devices"))
+    }
+
+    fn watch_properties(
         &mut self,
-        uuid: BluetoothUuid,
-        is_secure: bool,
-    ) -> Result<crate::BluetoothSocket, String> {
-        let uuid = uuid.as_str();
-        log::warn!("Checking rfcomm for {}", uuid);
-        let mut java = self.java.lock().unwrap();
-        if !self.rfcomm_sockets.contains_key(uuid) {
-            log::warn!("Building rfcomm for {}", uuid);
-            let socket = java
-                .use_env(|env, _context| {
-                    let uuid = uuid.new_jobject(env).map_err(|e| jerr(env, e))?;
-                    let uuid = env
-                        .call_static_method(
-                            "java/util/UUID",
-                            "fromString",
-                            "(Ljava/lang/String;)Ljava/util/UUID;",
-                            &[(&uuid).into()],
-                        )
-                        .get_object(env)
-                        .map_err(|e| jerr(env, e))?;
-
-                    let method_name = if is_secure {
-                        "createRfcommSocketToServiceRecord"
-                    } else {
-                        "createInsecureRfcommSocketToServiceRecord"
-                    };
-                    env.call_method(
-                        &self.internal,
-                        method_name,
-                        "(Ljava/util/UUID;)Landroid/bluetooth/BluetoothSocket;",
-                        &[(&uuid).into()],
-                    )
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::DevicePropertyEvent>, std::io::Error> {
+        let address = {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, _context| {
+                env.call_method(&self.internal, "getAddress", "()Ljava/lang/String;", &[])
                     .get_object(env)
-                    .globalize(env)
-                    // TODO: distinguish IOException and other unexpected exceptions
+                    .and_then(|a| a.get_string(env))
                     .map_err(|e| jerr(env, e))
-                })
-                .map_err(|e| e.to_string())?;
-            drop(java);
-            log::warn!("Building2 rfcomm for {}", uuid);
-            let socket = BluetoothSocket::build(socket, self.java.clone(), uuid);
-            if let Ok(a) = socket {
-                self.rfcomm_sockets.insert(uuid.to_string(), a);
+            })?
+        };
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let receiver = super::RegisteredReceiver::new(
+            &self.java,
+            &[
+                ACTION_ACL_CONNECTED,
+                ACTION_ACL_DISCONNECTED,
+                ACTION_NAME_CHANGED,
+                ACTION_BOND_STATE_CHANGED,
+            ],
+            move |env, _context, intent| {
+                let extra = "android.bluetooth.device.extra.DEVICE".new_jobject(env)?;
+                let device = env
+                    .call_method(
+                        intent,
+                        "getParcelableExtra",
+                        "(Ljava/lang/String;)Landroid/os/Parcelable;",
+                        &[(&extra).into()],
+                    )
+                    .get_object(env)?;
+                if device.is_null() {
+                    return Ok(());
+                }
+                let device_address = env
+                    .call_method(&device, "getAddress", "()Ljava/lang/String;", &[])
+                    .get_object(env)?
+                    .get_string(env)?;
+                if device_address != address {
+                    return Ok(());
+                }
+                let action = env
+                    .call_method(intent, "getAction", "()Ljava/lang/String;", &[])
+                    .get_object(env)?
+                    .get_string(env)?;
+                let event = match action.as_str() {
+                    ACTION_ACL_CONNECTED => crate::DevicePropertyEvent::Connected(true),
+                    ACTION_ACL_DISCONNECTED => crate::DevicePropertyEvent::Connected(false),
+                    ACTION_NAME_CHANGED => {
+                        let extra = "android.bluetooth.device.extra.NAME".new_jobject(env)?;
+                        let name = env
+                            .call_method(
+                                intent,
+                                "getStringExtra",
+                                "(Ljava/lang/String;)Ljava/lang/String;",
+                                &[(&extra).into()],
+                            )
+                            .get_object(env)?;
+                        if name.is_null() {
+                            return Ok(());
+                        }
+                        crate::DevicePropertyEvent::NameChanged(name.get_string(env)?)
+                    }
+                    ACTION_BOND_STATE_CHANGED => {
+                        let extra = "android.bluetooth.device.extra.BOND_STATE".new_jobject(env)?;
+                        let state = env
+                            .call_method(
+                                intent,
+                                "getIntExtra",
+                                "(Ljava/lang/String;I)I",
+                                &[(&extra).into(), (-1).into()],
+                            )
+                            .get_int()?;
+                        match state {
+                            BOND_NONE => crate::DevicePropertyEvent::Paired(false),
+                            BOND_BONDED => crate::DevicePropertyEvent::Paired(true),
+                            _ => return Ok(()),
+                        }
+                    }
+                    _ => return Ok(()),
+                };
+                let _ = tx.try_send(event);
+                Ok(())
+            },
+        )?;
+        self.receivers.push(receiver);
+        Ok(rx)
+    }
+
+    /// The level from the most recent battery broadcast is used when there is one, otherwise the hidden
+    /// getBatteryLevel method is asked.
+    fn battery_percent(&self) -> Result<Option<u8>, std::io::Error> {
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            let address = env
+                .call_method(&self.internal, "getAddress", "()Ljava/lang/String;", &[])
+                .get_object(env)
+                .and_then(|a| a.get_string(env))
+                .map_err(|e| jerr(env, e))?;
+            if let Some(levels) = &self.battery_levels {
+                if let Some(level) = levels.lock().unwrap().get(&address) {
+                    return Ok(Some(*level));
+                }
             }
-            log::warn!("Done building rfcomm for {}", uuid);
+            let level = env
+                .call_method(&self.internal, "getBatteryLevel", "()I", &[])
+                .get_int()
+                .map_err(|e| jerr(env, e))?;
+            // BluetoothDevice.BATTERY_LEVEL_UNKNOWN is -1
+            Ok(u8::try_from(level).ok().filter(|l| *l <= 100))
+        })
+    }
+
+    fn device_type(&self) -> Result<crate::DeviceTransport, std::io::Error> {
+        let mut java = self.java.lock().unwrap();
+        let t = java.use_env(|env, _context| {
+            env.call_method(&self.internal, "getType", "()I", &[])
+                .get_int()
+                .map_err(|e| jerr(env, e))
+        })?;
+        Ok(device_transport(t))
+    }
+
+    /// The profile proxies are requested when the adapter is built, and android connects them shortly after. The
+    /// state is not available until then.
+    fn profile_connection_state(
+        &self,
+        profile: crate::WellKnownProfile,
+    ) -> Result<crate::ProfileConnectionState, std::io::Error> {
+        let number = super::profile_number(profile);
+        let proxy = self
+            .profile_proxies
+            .as_ref()
+            .and_then(|p| p.lock().unwrap().get(&number).cloned())
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotConnected,
+                    "The profile proxy is not available",
+                )
+            })?;
+        let mut java = self.java.lock().unwrap();
+        let state = java.use_env(|env, _context| {
+            env.call_method(
+                &proxy,
+                "getConnectionState",
+                "(Landroid/bluetooth/BluetoothDevice;)I",
+                &[self.internal.as_obj().into()],
+            )
+            .get_int()
+            .map_err(|e| jerr(env, e))
+        })?;
+        Ok(match state {
+            1 => crate::ProfileConnectionState::Connecting,
+            2 => crate::ProfileConnectionState::Connected,
+            3 => crate::ProfileConnectionState::Disconnecting,
+            _ => crate::ProfileConnectionState::Disconnected,
+        })
+    }
+
+    fn device_class(&self) -> Result<Option<crate::DeviceClass>, std::io::Error> {
+        if let Some(class) = self.class.get() {
+            return Ok(Some(*class));
         }
-        self.rfcomm_sockets
-            .get_mut(uuid.into())
-            .map(|a| a.into())
-            .ok_or("Socket does not exist".to_string())
+        let mut java = self.java.lock().unwrap();
+        let class = java.use_env(|env, _context| {
+            let class = env
+                .call_method(
+                    &self.internal,
+                    "getBluetoothClass",
+                    "()Landroid/bluetooth/BluetoothClass;",
+                    &[],
+                )
+                .get_object(env)
+                .map_err(|e| jerr(env, e))?;
+            if class.is_null() {
+                return Ok(None);
+            }
+            let device = env
+                .call_method(&class, "getDeviceClass", "()I", &[])
+                .get_int()
+                .map_err(|e| jerr(env, e))?;
+            let raw = env
+                .call_method(&class, "hashCode", "()I", &[])
+                .get_int()
+                .map_err(|e| jerr(env, e))?;
+            Ok(Some(crate::DeviceClass::from_android(device, raw)))
+        })?;
+        if let Some(class) = class {
+            let _ = self.class.set(class);
+        }
+        Ok(class)
+    }
+
+    /// This must not be called from the main thread, since the broadcast with the uuids is delivered there
+    fn resolve_services(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<Vec<BluetoothUuid>, std::io::Error> {
+        self.refresh_uuids(timeout)
+    }
+}
+
+impl crate::BluetoothDeviceTrait for BluetoothDevice {
+    fn supports_async(&mut self) -> Option<&mut dyn crate::BluetoothDeviceAsyncTrait> {
+        None
+    }
+
+    fn supports_sync(&mut self) -> Option<&mut dyn crate::BluetoothDeviceSyncTrait> {
+        Some(self)
+    }
+
+    fn run_sdp(&mut self) {
+        let mut java = self.java.lock().unwrap();
+        let _result = java.use_env(|env, _context| {
+            let dev_name = env
+                .call_method(&self.internal, "fetchUuidsWithSdp", "()Z", &[])
+                .get_boolean();
+            dev_name.map_err(|e| jerr(env, e))
+        });
+    }
+
+    fn get_address(&mut self) -> Result<crate::BluetoothAdapterAddress, std::io::Error> {
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            let dev_name = env
+                .call_method(&self.internal, "getAddress", "()Ljava/lang/String;", &[])
+                .get_object(env)
+                .map_err(|e| jerr(env, e))?;
+            if dev_name.is_null() {
+                return Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+            }
+            dev_name
+                .get_string(env)
+                .map(|a| crate::BluetoothAdapterAddress::String(a).normalized())
+                .map_err(|e| jerr(env, e))
+        })
+    }
+
+    /// L2cap channels are only available from api level 29
+    fn get_l2cap_socket(
+        &mut self,
+        psm: u16,
+        is_secure: bool,
+    ) -> Result<crate::BluetoothSocket, crate::BluetoothError> {
+        let socket = {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, _context| {
+                if super::sdk_int(env).map_err(|e| jerr(env, e))? < 29 {
+                    return Err(unsupported("L2cap channels before api level 29"));
+                }
+                let method_name = if is_secure {
+                    "createL2capChannel"
+                } else {
+                    "createInsecureL2capChannel"
+                };
+                env.call_method(
+                    &self.internal,
+                    method_name,
+                    "(I)Landroid/bluetooth/BluetoothSocket;",
+                    &[(psm as i32).into()],
+                )
+                .get_object(env)
+                .globalize(env)
+                .map_err(|e| jerr(env, e))
+            })?
+        };
+        let socket = BluetoothSocket::build(
+            socket,
+            self.java.clone(),
+            &format!("l2cap psm {psm}"),
+            is_secure,
+        )?;
+        Ok(crate::BluetoothSocket::Android(socket))
     }
 
     fn get_rfcomm_socket(
         &mut self,
         uuid: BluetoothUuid,
         is_secure: bool,
-    ) -> Result<crate::BluetoothSocket, String> {
+    ) -> Result<crate::BluetoothSocket, crate::BluetoothError> {
         let uuid = uuid.as_str();
         log::warn!("Checking rfcomm for {}", uuid);
         let mut java = self.java.lock().unwrap();
         if !self.rfcomm_sockets.contains_key(uuid) {
             log::warn!("Building rfcomm for {}", uuid);
-            let socket = java
-                .use_env(|env, _context| {
-                    let uuid = uuid.new_jobject(env).map_err(|e| jerr(env, e))?;
-                    let uuid = env
-                        .call_static_method(
-                            "java/util/UUID",
-                            "fromString",
-                            "(Ljava/lang/String;)Ljava/util/UUID;",
-                            &[(&uuid).into()],
-                        )
-                        .get_object(env)
-                        .map_err(|e| jerr(env, e))?;
-
-                    let method_name = if is_secure {
-                        "createRfcommSocketToServiceRecord"
-                    } else {
-                        "createInsecureRfcommSocketToServiceRecord"
-                    };
-                    env.call_method(
-                        &self.internal,
-                        method_name,
-                        "(Ljava/util/UUID;)Landroid/bluetooth/BluetoothSocket;",
+            let socket = java.use_env(|env, _context| {
+                let uuid = uuid.new_jobject(env).map_err(|e| jerr(env, e))?;
+                let uuid = env
+                    .call_static_method(
+                        "java/util/UUID",
+                        "fromString",
+                        "(Ljava/lang/String;)Ljava/util/UUID;",
                         &[(&uuid).into()],
                     )
                     .get_object(env)
-                    .globalize(env)
-                    // TODO: distinguish IOException and other unexpected exceptions
-                    .map_err(|e| jerr(env, e))
-                })
-                .map_err(|e| e.to_string())?;
+                    .map_err(|e| jerr(env, e))?;
+
+                let method_name = if is_secure {
+                    "createRfcommSocketToServiceRecord"
+                } else {
+                    "createInsecureRfcommSocketToServiceRecord"
+                };
+                env.call_method(
+                    &self.internal,
+                    method_name,
+                    "(Ljava/util/UUID;)Landroid/bluetooth/BluetoothSocket;",
+                    &[(&uuid).into()],
+                )
+                .get_object(env)
+                .globalize(env)
+                // TODO: distinguish IOException and other unexpected exceptions
+                .map_err(|e| jerr(env, e))
+            })?;
             drop(java);
             log::warn!("Building2 rfcomm for {}", uuid);
-            let socket = BluetoothSocket::build(socket, self.java.clone(), uuid);
+            let socket = BluetoothSocket::build(socket, self.java.clone(), uuid, is_secure);
             if let Ok(a) = socket {
                 self.rfcomm_sockets.insert(uuid.to_string(), a);
             }
@@ -186,7 +611,27 @@ impl crate::BluetoothDeviceTrait for BluetoothDevice {
         self.rfcomm_sockets
             .get_mut(uuid.into())
             .map(|a| a.into())
-            .ok_or("Socket does not exist".to_string())
+            .ok_or_else(|| {
+                crate::BluetoothError::ConnectionFailed("Socket does not exist".to_string())
+            })
+    }
+}
+
+/// Wait for the next state sent by `watch_bond_state`, failing with `TimedOut` once the deadline passes
+fn next_bond_state(
+    states: &std::sync::mpsc::Receiver<i32>,
+    deadline: std::time::Instant,
+) -> Result<i32, std::io::Error> {
+    use std::sync::mpsc::RecvTimeoutError;
+    match states.recv_timeout(deadline.saturating_duration_since(std::time::Instant::now())) {
+        Ok(state) => Ok(state),
+        Err(RecvTimeoutError::Timeout) => Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "The bond state did not change in time",
+        )),
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(std::io::Error::other("Bond state broadcast stopped"))
+        }
     }
 }
 
@@ -196,11 +641,185 @@ impl BluetoothDevice {
             internal,
             rfcomm_sockets: BTreeMap::new(),
             java,
+            rssi_readings: None,
+            profile_proxies: None,
+            gatt: Mutex::new(std::sync::Weak::new()),
+            receivers: Vec::new(),
+            battery_levels: None,
+            class: std::sync::OnceLock::new(),
+            uuids: None,
+            response_timeout: crate::AgentConfig::default().response_timeout,
+        }
+    }
+
+    /// Use the battery levels collected by the adapter
+    pub(crate) fn with_battery_levels(mut self, levels: Arc<Mutex<HashMap<String, u8>>>) -> Self {
+        self.battery_levels = Some(levels);
+        self
+    }
+
+    /// Register a receiver that forwards the new bond states of this device
+    fn watch_bond_state(
+        &self,
+    ) -> Result<(super::RegisteredReceiver, std::sync::mpsc::Receiver<i32>), std::io::Error> {
+        let address = {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, _context| {
+                env.call_method(&self.internal, "getAddress", "()Ljava/lang/String;", &[])
+                    .get_object(env)
+                    .and_then(|a| a.get_string(env))
+                    .map_err(|e| jerr(env, e))
+            })?
+        };
+        let (tx, rx) = std::sync::mpsc::channel();
+        let receiver = super::RegisteredReceiver::new(
+            &self.java,
+            &[ACTION_BOND_STATE_CHANGED],
+            move |env, _context, intent| {
+                let extra = "android.bluetooth.device.extra.DEVICE".new_jobject(env)?;
+                let device = env
+                    .call_method(
+                        intent,
+                        "getParcelableExtra",
+                        "(Ljava/lang/String;)Landroid/os/Parcelable;",
+                        &[(&extra).into()],
+                    )
+                    .get_object(env)?;
+                if device.is_null() {
+                    return Ok(());
+                }
+                let device_address = env
+                    .call_method(&device, "getAddress", "()Ljava/lang/String;", &[])
+                    .get_object(env)?
+                    .get_string(env)?;
+                if device_address != address {
+                    return Ok(());
+                }
+                let extra = "android.bluetooth.device.extra.BOND_STATE".new_jobject(env)?;
+                let state = env
+                    .call_method(
+                        intent,
+                        "getIntExtra",
+                        "(Ljava/lang/String;I)I",
+                        &[(&extra).into(), (-1).into()],
+                    )
+                    .get_int()?;
+                let _ = tx.send(state);
+                Ok(())
+            },
+        )?;
+        Ok((receiver, rx))
+    }
+
+    /// Use the response timeout of the agent config of the adapter
+    pub(crate) fn with_response_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.response_timeout = timeout;
+        self
+    }
+
+    /// Use the profile proxies requested by the adapter
+    pub(crate) fn with_profile_proxies(mut self, proxies: super::ProfileProxyMap) -> Self {
+        self.profile_proxies = Some(proxies);
+        self
+    }
+
+    /// Use the signal strengths collected by discovery
+    pub(crate) fn with_rssi_readings(
+        mut self,
+        readings: Arc<Mutex<HashMap<String, crate::RssiReading>>>,
+    ) -> Self {
+        self.rssi_readings = Some(readings);
+        self
+    }
+
+    /// Run service discovery on the device and wait up to the timeout for the uuids it reports. The uuids are
+    /// remembered and returned by get_uuids afterwards. This must not be called from the main thread, since the
+    /// broadcast with the uuids is delivered there.
+    pub fn refresh_uuids(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<Vec<BluetoothUuid>, std::io::Error> {
+        let address = {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, _context| {
+                env.call_method(&self.internal, "getAddress", "()Ljava/lang/String;", &[])
+                    .get_object(env)
+                    .and_then(|a| a.get_string(env))
+                    .map_err(|e| jerr(env, e))
+            })?
+        };
+        let (tx, rx) = std::sync::mpsc::channel();
+        let _receiver = super::RegisteredReceiver::new(
+            &self.java,
+            &[ACTION_UUID],
+            move |env, _context, intent| {
+                let extra = "android.bluetooth.device.extra.DEVICE".new_jobject(env)?;
+                let device = env
+                    .call_method(
+                        intent,
+                        "getParcelableExtra",
+                        "(Ljava/lang/String;)Landroid/os/Parcelable;",
+                        &[(&extra).into()],
+                    )
+                    .get_object(env)?;
+                if device.is_null() {
+                    return Ok(());
+                }
+                let device_address = env
+                    .call_method(&device, "getAddress", "()Ljava/lang/String;", &[])
+                    .get_object(env)?
+                    .get_string(env)?;
+                if device_address != address {
+                    return Ok(());
+                }
+                let extra = "android.bluetooth.device.extra.UUID".new_jobject(env)?;
+                let uuids = env
+                    .call_method(
+                        intent,
+                        "getParcelableArrayExtra",
+                        "(Ljava/lang/String;)[Landroid/os/Parcelable;",
+                        &[(&extra).into()],
+                    )
+                    .get_object(env)?;
+                // The uuids are missing when service discovery failed
+                if uuids.is_null() {
+                    let _ = tx.send(None);
+                    return Ok(());
+                }
+                let uuids: &jni::objects::JObjectArray = uuids.as_ref().into();
+                let len = env.get_array_length(uuids)?;
+                let mut list = Vec::with_capacity(len as usize);
+                for i in 0..len {
+                    let uuid = env.get_object_array_element(uuids, i)?;
+                    list.push(ParcelUuid::new(env, &uuid)?.into());
+                }
+                let _ = tx.send(Some(list));
+                Ok(())
+            },
+        )?;
+        {
+            let mut java = self.java.lock().unwrap();
+            let started = java.use_env(|env, _context| {
+                env.call_method(&self.internal, "fetchUuidsWithSdp", "()Z", &[])
+                    .get_boolean()
+                    .map_err(|e| jerr(env, e))
+            })?;
+            if !started {
+                return Err(std::io::Error::other("Failed to start service discovery"));
+            }
         }
+        let uuids = rx.recv_timeout(timeout).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "Timed out waiting for service discovery",
+            )
+        })?;
+        let uuids = uuids.ok_or_else(|| std::io::Error::other("Service discovery failed"))?;
+        self.uuids = Some(uuids.clone());
+        Ok(uuids)
     }
 
     pub fn get_parcel_uuids(&mut self) -> Result<Vec<ParcelUuid>, std::io::Error> {
-        let java2 = self.java.clone();
         let mut java = self.java.lock().unwrap();
         java.use_env(|env, _context| {
             let objs = env
@@ -218,9 +837,9 @@ impl BluetoothDevice {
             for i in 0..len {
                 let uuid = env
                     .get_object_array_element(jarr, i)
-                    .global_ref(env)
+                    .auto_local(env)
                     .map_err(|e| jerr(env, e))?;
-                vec.push(ParcelUuid::new(uuid, java2.clone()));
+                vec.push(ParcelUuid::new(env, &uuid).map_err(|e| jerr(env, e))?);
             }
             Ok(vec)
         })
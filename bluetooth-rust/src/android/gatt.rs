@@ -0,0 +1,708 @@
+//! Gatt client for remote devices
+
+use super::super::Java;
+use super::jerr;
+use crate::BluetoothUuid;
+use jni::JNIEnv;
+use jni::objects::{GlobalRef, JByteArray, JClass, JObject};
+use jni::sys::{jint, jlong};
+use jni_min_helper::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The java class extending BluetoothGattCallback, which forwards the callbacks to the native functions here
+const CALLBACK_CLASS: &str = "com.github.uglyoldbob.bluetooth_rust.GattCallbackBridge";
+/// BluetoothDevice.TRANSPORT_LE
+const TRANSPORT_LE: i32 = 2;
+/// BluetoothProfile.STATE_CONNECTED
+const STATE_CONNECTED: i32 = 2;
+/// BluetoothGatt.GATT_SUCCESS
+const GATT_SUCCESS: i32 = 0;
+/// BluetoothGattService.SERVICE_TYPE_PRIMARY
+const SERVICE_TYPE_PRIMARY: i32 = 0;
+/// BluetoothGattCharacteristic.WRITE_TYPE_NO_RESPONSE
+const WRITE_TYPE_NO_RESPONSE: i32 = 1;
+/// BluetoothGattCharacteristic.WRITE_TYPE_DEFAULT
+const WRITE_TYPE_DEFAULT: i32 = 2;
+/// The uuid of the client characteristic configuration descriptor
+const CLIENT_CONFIG_UUID: &str = "00002902-0000-1000-8000-00805f9b34fb";
+/// How long to wait for the connection to the device
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Where the result of a gatt operation is sent by the callback
+type Reply<T> = tokio::sync::oneshot::Sender<Result<T, std::io::Error>>;
+
+/// The state shared between a client and the callbacks of its GattCallbackBridge. Requests are kept by the
+/// instance id of their characteristic, so that each callback finds the request it answers.
+pub(super) struct Shared {
+    /// The BluetoothGatt object, once connectGatt has returned it
+    gatt: std::sync::OnceLock<GlobalRef>,
+    /// True while the device is connected
+    connected: tokio::sync::watch::Sender<bool>,
+    /// The request waiting for service discovery
+    discovered: Mutex<Option<Reply<()>>>,
+    /// The reads waiting for a value
+    reads: Mutex<HashMap<u16, Reply<Vec<u8>>>>,
+    /// The writes waiting for confirmation
+    writes: Mutex<HashMap<u16, Reply<()>>>,
+    /// The client configuration writes waiting for confirmation
+    subscriptions: Mutex<HashMap<u16, Reply<()>>>,
+    /// Where the notifications of each characteristic are sent
+    notifications: Mutex<HashMap<u16, tokio::sync::mpsc::Sender<Vec<u8>>>>,
+    /// The request waiting for the signal strength, which blocks instead of being async
+    rssi: Mutex<Option<std::sync::mpsc::Sender<Result<i16, std::io::Error>>>>,
+}
+
+impl Shared {
+    /// Drop every pending request, so the requests fail instead of waiting for a disconnected device
+    fn disconnected(&self) {
+        self.connected.send_replace(false);
+        self.discovered.lock().unwrap().take();
+        self.reads.lock().unwrap().clear();
+        self.writes.lock().unwrap().clear();
+        self.subscriptions.lock().unwrap().clear();
+        self.notifications.lock().unwrap().clear();
+        self.rssi.lock().unwrap().take();
+    }
+
+    /// Ask the connected device for its signal strength and wait up to the timeout for it. Android reports the
+    /// answer on a binder thread, so this can be called from any thread except the main thread.
+    pub(super) fn read_remote_rssi(
+        &self,
+        java: &Arc<Mutex<Java>>,
+        timeout: std::time::Duration,
+    ) -> Result<i16, std::io::Error> {
+        let Some(gatt) = self.gatt.get() else {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        };
+        if !*self.connected.borrow() {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+        *self.rssi.lock().unwrap() = Some(tx);
+        let started = {
+            let mut java = java.lock().unwrap();
+            java.use_env(|env, _context| {
+                env.call_method(gatt, "readRemoteRssi", "()Z", &[])
+                    .get_boolean()
+                    .map_err(|e| jerr(env, e))
+            })?
+        };
+        if !started {
+            self.rssi.lock().unwrap().take();
+            return Err(std::io::Error::other("Failed to start reading the rssi"));
+        }
+        rx.recv_timeout(timeout).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "Timed out waiting for the rssi",
+            )
+        })?
+    }
+}
+
+/// Convert the status of a gatt callback into a result
+fn gatt_status(status: jint) -> Result<(), std::io::Error> {
+    if status == GATT_SUCCESS {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "The gatt operation failed with status {status}"
+        )))
+    }
+}
+
+/// Get the shared state of the client from the handle held by the java callback
+///
+/// # Safety
+/// The handle must come from a client that has not released it yet, the java callback guarantees this
+unsafe fn shared<'a>(handle: jlong) -> &'a Shared {
+    unsafe { &*(handle as *const Shared) }
+}
+
+/// Read a byte array given to a callback, which can be null for the callbacks used before android 13
+fn callback_bytes(env: &mut JNIEnv, value: &JByteArray) -> Vec<u8> {
+    if value.is_null() {
+        return Vec::new();
+    }
+    env.convert_byte_array(value).unwrap_or_default()
+}
+
+/// GattCallbackBridge.nativeConnectionStateChange
+extern "system" fn native_connection_state_change(
+    _env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    _status: jint,
+    new_state: jint,
+) {
+    let shared = unsafe { shared(handle) };
+    if new_state == STATE_CONNECTED {
+        shared.connected.send_replace(true);
+    } else {
+        shared.disconnected();
+    }
+}
+
+/// GattCallbackBridge.nativeServicesDiscovered
+extern "system" fn native_services_discovered(
+    _env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    status: jint,
+) {
+    let shared = unsafe { shared(handle) };
+    if let Some(reply) = shared.discovered.lock().unwrap().take() {
+        let _ = reply.send(gatt_status(status));
+    }
+}
+
+/// GattCallbackBridge.nativeCharacteristicRead
+extern "system" fn native_characteristic_read(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    id: jint,
+    value: JByteArray,
+    status: jint,
+) {
+    let shared = unsafe { shared(handle) };
+    if let Some(reply) = shared.reads.lock().unwrap().remove(&(id as u16)) {
+        let value = callback_bytes(&mut env, &value);
+        let _ = reply.send(gatt_status(status).map(|_| value));
+    }
+}
+
+/// GattCallbackBridge.nativeCharacteristicWrite
+extern "system" fn native_characteristic_write(
+    _env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    id: jint,
+    status: jint,
+) {
+    let shared = unsafe { shared(handle) };
+    if let Some(reply) = shared.writes.lock().unwrap().remove(&(id as u16)) {
+        let _ = reply.send(gatt_status(status));
+    }
+}
+
+/// GattCallbackBridge.nativeCharacteristicChanged
+extern "system" fn native_characteristic_changed(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    id: jint,
+    value: JByteArray,
+) {
+    let shared = unsafe { shared(handle) };
+    let mut notifications = shared.notifications.lock().unwrap();
+    let Some(tx) = notifications.get(&(id as u16)) else {
+        return;
+    };
+    if tx.is_closed() {
+        notifications.remove(&(id as u16));
+        return;
+    }
+    let _ = tx.try_send(callback_bytes(&mut env, &value));
+}
+
+/// GattCallbackBridge.nativeDescriptorWrite
+extern "system" fn native_descriptor_write(
+    _env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    id: jint,
+    status: jint,
+) {
+    let shared = unsafe { shared(handle) };
+    if let Some(reply) = shared.subscriptions.lock().unwrap().remove(&(id as u16)) {
+        let _ = reply.send(gatt_status(status));
+    }
+}
+
+/// GattCallbackBridge.nativeReadRemoteRssi
+extern "system" fn native_read_remote_rssi(
+    _env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    rssi: jint,
+    status: jint,
+) {
+    let shared = unsafe { shared(handle) };
+    if let Some(reply) = shared.rssi.lock().unwrap().take() {
+        let _ = reply.send(gatt_status(status).map(|_| rssi as i16));
+    }
+}
+
+/// Load the GattCallbackBridge class from the application and connect its native methods
+fn callback_class<'a>(
+    env: &mut JNIEnv<'a>,
+    context: &JObject,
+) -> Result<JClass<'a>, jni::errors::Error> {
+    let class = super::load_app_class(env, context, CALLBACK_CLASS)?;
+    let natives: [(&str, &str, *mut std::ffi::c_void); 7] = [
+        (
+            "nativeConnectionStateChange",
+            "(JII)V",
+            native_connection_state_change as *mut _,
+        ),
+        (
+            "nativeServicesDiscovered",
+            "(JI)V",
+            native_services_discovered as *mut _,
+        ),
+        (
+            "nativeCharacteristicRead",
+            "(JI[BI)V",
+            native_characteristic_read as *mut _,
+        ),
+        (
+            "nativeCharacteristicWrite",
+            "(JII)V",
+            native_characteristic_write as *mut _,
+        ),
+        (
+            "nativeCharacteristicChanged",
+            "(JI[B)V",
+            native_characteristic_changed as *mut _,
+        ),
+        (
+            "nativeDescriptorWrite",
+            "(JII)V",
+            native_descriptor_write as *mut _,
+        ),
+        (
+            "nativeReadRemoteRssi",
+            "(JII)V",
+            native_read_remote_rssi as *mut _,
+        ),
+    ];
+    let natives = natives.map(|(name, sig, fn_ptr)| jni::NativeMethod {
+        name: name.into(),
+        sig: sig.into(),
+        fn_ptr,
+    });
+    // Registering again replaces the methods with the same functions, so this is done for every client
+    env.register_native_methods(&class, &natives)?;
+    Ok(class)
+}
+
+/// A gatt client for a remote device, closed when dropped. Android runs one gatt operation at a time, so
+/// operations on the same client wait for each other.
+pub struct GattClient {
+    /// The BluetoothGatt object for the connection
+    gatt: GlobalRef,
+    /// The GattCallbackBridge given to connectGatt
+    callback: GlobalRef,
+    /// The java vm, attached to the thread of each operation since the futures of the client run on any thread
+    vm: jni::JavaVM,
+    /// The state shared with the callbacks
+    shared: Arc<Shared>,
+    /// The reference to the shared state held by the java callback, released on drop
+    handle: jlong,
+    /// The characteristics found by the last service discovery, by instance id
+    characteristics: Mutex<HashMap<u16, GlobalRef>>,
+    /// Held while an operation is running
+    op: tokio::sync::Mutex<()>,
+}
+
+impl GattClient {
+    /// Connect to the low energy gatt server of the device. The connection finishes in the background, the
+    /// operations of the client wait for it.
+    pub(super) fn connect(
+        env: &mut JNIEnv,
+        context: &JObject,
+        device: &JObject,
+    ) -> Result<Self, std::io::Error> {
+        let vm = env.get_java_vm().map_err(|e| jerr(env, e))?;
+        let class = callback_class(env, context).map_err(|e| jerr(env, e))?;
+        let (connected, _) = tokio::sync::watch::channel(false);
+        let shared = Arc::new(Shared {
+            gatt: std::sync::OnceLock::new(),
+            connected,
+            discovered: Mutex::new(None),
+            reads: Mutex::new(HashMap::new()),
+            writes: Mutex::new(HashMap::new()),
+            subscriptions: Mutex::new(HashMap::new()),
+            notifications: Mutex::new(HashMap::new()),
+            rssi: Mutex::new(None),
+        });
+        let handle = Arc::into_raw(shared.clone()) as jlong;
+        let connected = (|| -> Result<_, jni::errors::Error> {
+            let callback = env
+                .new_object(&class, "(J)V", &[handle.into()])
+                .globalize(env)?;
+            let gatt = env
+                .call_method(
+                    device,
+                    "connectGatt",
+                    "(Landroid/content/Context;ZLandroid/bluetooth/BluetoothGattCallback;I)Landroid/bluetooth/BluetoothGatt;",
+                    &[context.into(), false.into(), (&callback).into(), TRANSPORT_LE.into()],
+                )
+                .get_object(env)?;
+            Ok((callback, gatt))
+        })();
+        let (callback, gatt) = match connected {
+            Ok((callback, gatt)) if !gatt.is_null() => (callback, gatt),
+            r => {
+                // The callback was never registered, so nothing else holds the handle
+                unsafe { drop(Arc::from_raw(handle as *const Shared)) };
+                return Err(match r {
+                    Err(e) => jerr(env, e),
+                    Ok(_) => std::io::Error::other("Failed to start the gatt connection"),
+                });
+            }
+        };
+        let gatt = env.new_global_ref(gatt).map_err(|e| jerr(env, e))?;
+        let _ = shared.gatt.set(gatt.clone());
+        Ok(Self {
+            gatt,
+            callback,
+            vm,
+            shared,
+            handle,
+            characteristics: Mutex::new(HashMap::new()),
+            op: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// The state shared with the callbacks, for the device to read the signal strength over the connection
+    pub(super) fn shared(&self) -> std::sync::Weak<Shared> {
+        Arc::downgrade(&self.shared)
+    }
+
+    /// Run java code with the error converted
+    fn with_env<T>(
+        &self,
+        f: impl FnOnce(&mut JNIEnv) -> Result<T, jni::errors::Error>,
+    ) -> Result<T, std::io::Error> {
+        let mut env = self
+            .vm
+            .attach_current_thread()
+            .map_err(std::io::Error::other)?;
+        f(&mut env).map_err(|e| jerr(&mut env, e))
+    }
+
+    /// Wait for the device to be connected
+    async fn wait_connected(&self) -> Result<(), std::io::Error> {
+        let mut connected = self.shared.connected.subscribe();
+        tokio::time::timeout(CONNECT_TIMEOUT, connected.wait_for(|c| *c))
+            .await
+            .map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "Timed out connecting to the gatt server",
+                )
+            })?
+            .map(|_| ())
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::NotConnected))
+    }
+
+    /// Wait for the callback to answer a request
+    async fn reply<T>(
+        rx: tokio::sync::oneshot::Receiver<Result<T, std::io::Error>>,
+    ) -> Result<T, std::io::Error> {
+        rx.await
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::NotConnected))?
+    }
+
+    /// Find the java object for a characteristic returned by `services`
+    fn characteristic(&self, c: &crate::GattCharacteristic) -> Result<GlobalRef, std::io::Error> {
+        self.characteristics
+            .lock()
+            .unwrap()
+            .get(&c.id)
+            .cloned()
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "The characteristic was not found by the last service discovery",
+                )
+            })
+    }
+
+    /// List the services found by discovery, remembering their characteristics for later operations
+    fn read_services(&self) -> Result<Vec<crate::GattService>, std::io::Error> {
+        let mut found = HashMap::new();
+        let services = self.with_env(|env| {
+            let list = env
+                .call_method(&self.gatt, "getServices", "()Ljava/util/List;", &[])
+                .get_object(env)?;
+            let array = env
+                .call_method(&list, "toArray", "()[Ljava/lang/Object;", &[])
+                .get_object(env)?;
+            let array: &jni::objects::JObjectArray = array.as_ref().into();
+            let mut services = Vec::new();
+            for i in 0..env.get_array_length(array)? {
+                let service = env.get_object_array_element(array, i)?;
+                let kind = env.call_method(&service, "getType", "()I", &[]).get_int()?;
+                let uuid = object_uuid(env, &service)?;
+                let list = env
+                    .call_method(&service, "getCharacteristics", "()Ljava/util/List;", &[])
+                    .get_object(env)?;
+                let chars = env
+                    .call_method(&list, "toArray", "()[Ljava/lang/Object;", &[])
+                    .get_object(env)?;
+                let chars: &jni::objects::JObjectArray = chars.as_ref().into();
+                let mut characteristics = Vec::new();
+                for j in 0..env.get_array_length(chars)? {
+                    let c = env.get_object_array_element(chars, j)?;
+                    let id = env.call_method(&c, "getInstanceId", "()I", &[]).get_int()? as u16;
+                    let properties = env.call_method(&c, "getProperties", "()I", &[]).get_int()?;
+                    characteristics.push(crate::GattCharacteristic {
+                        uuid: object_uuid(env, &c)?,
+                        flags: crate::GattCharacteristicFlags {
+                            read: properties & 0x02 != 0,
+                            write_without_response: properties & 0x04 != 0,
+                            write: properties & 0x08 != 0,
+                            notify: properties & 0x10 != 0,
+                            indicate: properties & 0x20 != 0,
+                        },
+                        id,
+                    });
+                    found.insert(id, env.new_global_ref(&c)?);
+                }
+                services.push(crate::GattService {
+                    uuid,
+                    primary: kind == SERVICE_TYPE_PRIMARY,
+                    characteristics,
+                });
+            }
+            Ok(services)
+        })?;
+        *self.characteristics.lock().unwrap() = found;
+        Ok(services)
+    }
+}
+
+/// Get the uuid of a BluetoothGattService or BluetoothGattCharacteristic
+fn object_uuid(env: &mut JNIEnv, object: &JObject) -> Result<BluetoothUuid, jni::errors::Error> {
+    let uuid = env
+        .call_method(object, "getUuid", "()Ljava/util/UUID;", &[])
+        .get_object(env)?;
+    crate::bluetooth_uuid::java_uuid(env, &uuid)
+}
+
+#[async_trait::async_trait]
+impl crate::GattClientTrait for GattClient {
+    /// Service discovery runs every time, so the list reflects the current services of the device
+    async fn services(&self) -> Result<Vec<crate::GattService>, std::io::Error> {
+        let _op = self.op.lock().await;
+        self.wait_connected().await?;
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        *self.shared.discovered.lock().unwrap() = Some(tx);
+        let started = self.with_env(|env| {
+            env.call_method(&self.gatt, "discoverServices", "()Z", &[])
+                .get_boolean()
+        })?;
+        if !started {
+            self.shared.discovered.lock().unwrap().take();
+            return Err(std::io::Error::other(
+                "Failed to start gatt service discovery",
+            ));
+        }
+        Self::reply(rx).await?;
+        self.read_services()
+    }
+
+    async fn read(
+        &self,
+        characteristic: &crate::GattCharacteristic,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        let _op = self.op.lock().await;
+        self.wait_connected().await?;
+        let c = self.characteristic(characteristic)?;
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.shared
+            .reads
+            .lock()
+            .unwrap()
+            .insert(characteristic.id, tx);
+        let started = self.with_env(|env| {
+            env.call_method(
+                &self.gatt,
+                "readCharacteristic",
+                "(Landroid/bluetooth/BluetoothGattCharacteristic;)Z",
+                &[(&c).into()],
+            )
+            .get_boolean()
+        })?;
+        if !started {
+            self.shared.reads.lock().unwrap().remove(&characteristic.id);
+            return Err(std::io::Error::other(
+                "Failed to start the characteristic read",
+            ));
+        }
+        Self::reply(rx).await
+    }
+
+    async fn write(
+        &self,
+        characteristic: &crate::GattCharacteristic,
+        data: &[u8],
+        mode: crate::WriteMode,
+    ) -> Result<(), std::io::Error> {
+        let _op = self.op.lock().await;
+        self.wait_connected().await?;
+        let c = self.characteristic(characteristic)?;
+        let write_type = match mode {
+            crate::WriteMode::WithResponse => WRITE_TYPE_DEFAULT,
+            crate::WriteMode::WithoutResponse => WRITE_TYPE_NO_RESPONSE,
+        };
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.shared
+            .writes
+            .lock()
+            .unwrap()
+            .insert(characteristic.id, tx);
+        let started = self.with_env(|env| {
+            let value = env.byte_array_from_slice(data)?;
+            if super::sdk_int(env)? >= 33 {
+                let status = env
+                    .call_method(
+                        &self.gatt,
+                        "writeCharacteristic",
+                        "(Landroid/bluetooth/BluetoothGattCharacteristic;[BI)I",
+                        &[(&c).into(), (&value).into(), write_type.into()],
+                    )
+                    .get_int()?;
+                Ok(status == super::device::BLUETOOTH_STATUS_SUCCESS)
+            } else {
+                env.call_method(&c, "setWriteType", "(I)V", &[write_type.into()])?;
+                env.call_method(&c, "setValue", "([B)Z", &[(&value).into()])
+                    .get_boolean()?;
+                env.call_method(
+                    &self.gatt,
+                    "writeCharacteristic",
+                    "(Landroid/bluetooth/BluetoothGattCharacteristic;)Z",
+                    &[(&c).into()],
+                )
+                .get_boolean()
+            }
+        })?;
+        if !started {
+            self.shared
+                .writes
+                .lock()
+                .unwrap()
+                .remove(&characteristic.id);
+            return Err(std::io::Error::other(
+                "Failed to start the characteristic write",
+            ));
+        }
+        Self::reply(rx).await
+    }
+
+    /// Indications are used when the characteristic does not support notifications
+    async fn subscribe(
+        &self,
+        characteristic: &crate::GattCharacteristic,
+    ) -> Result<tokio::sync::mpsc::Receiver<Vec<u8>>, std::io::Error> {
+        let _op = self.op.lock().await;
+        self.wait_connected().await?;
+        let c = self.characteristic(characteristic)?;
+        let enable: [u8; 2] = if characteristic.flags.notify {
+            [1, 0]
+        } else {
+            [2, 0]
+        };
+        let (notify_tx, notify_rx) = tokio::sync::mpsc::channel(16);
+        self.shared
+            .notifications
+            .lock()
+            .unwrap()
+            .insert(characteristic.id, notify_tx);
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.shared
+            .subscriptions
+            .lock()
+            .unwrap()
+            .insert(characteristic.id, tx);
+        let started = self.with_env(|env| {
+            let enabled = env
+                .call_method(
+                    &self.gatt,
+                    "setCharacteristicNotification",
+                    "(Landroid/bluetooth/BluetoothGattCharacteristic;Z)Z",
+                    &[(&c).into(), true.into()],
+                )
+                .get_boolean()?;
+            if !enabled {
+                return Ok(false);
+            }
+            let uuid = CLIENT_CONFIG_UUID.new_jobject(env)?;
+            let uuid = env
+                .call_static_method(
+                    "java/util/UUID",
+                    "fromString",
+                    "(Ljava/lang/String;)Ljava/util/UUID;",
+                    &[(&uuid).into()],
+                )
+                .get_object(env)?;
+            let descriptor = env
+                .call_method(
+                    &c,
+                    "getDescriptor",
+                    "(Ljava/util/UUID;)Landroid/bluetooth/BluetoothGattDescriptor;",
+                    &[(&uuid).into()],
+                )
+                .get_object(env)?;
+            if descriptor.is_null() {
+                return Ok(false);
+            }
+            let value = env.byte_array_from_slice(&enable)?;
+            if super::sdk_int(env)? >= 33 {
+                let status = env
+                    .call_method(
+                        &self.gatt,
+                        "writeDescriptor",
+                        "(Landroid/bluetooth/BluetoothGattDescriptor;[B)I",
+                        &[(&descriptor).into(), (&value).into()],
+                    )
+                    .get_int()?;
+                Ok(status == super::device::BLUETOOTH_STATUS_SUCCESS)
+            } else {
+                env.call_method(&descriptor, "setValue", "([B)Z", &[(&value).into()])
+                    .get_boolean()?;
+                env.call_method(
+                    &self.gatt,
+                    "writeDescriptor",
+                    "(Landroid/bluetooth/BluetoothGattDescriptor;)Z",
+                    &[(&descriptor).into()],
+                )
+                .get_boolean()
+            }
+        })?;
+        if !started {
+            self.shared
+                .subscriptions
+                .lock()
+                .unwrap()
+                .remove(&characteristic.id);
+            self.shared
+                .notifications
+                .lock()
+                .unwrap()
+                .remove(&characteristic.id);
+            return Err(std::io::Error::other(
+                "Failed to enable notifications for the characteristic",
+            ));
+        }
+        Self::reply(rx).await?;
+        Ok(notify_rx)
+    }
+}
+
+impl Drop for GattClient {
+    fn drop(&mut self) {
+        if let Ok(mut env) = self.vm.attach_current_thread() {
+            let _ = env.call_method(&self.gatt, "close", "()V", &[]).clear_ex();
+            // Waits for a running callback to finish, no callbacks use the handle after this
+            let _ = env
+                .call_method(&self.callback, "release", "()V", &[])
+                .clear_ex();
+        }
+        unsafe { drop(Arc::from_raw(self.handle as *const Shared)) };
+    }
+}
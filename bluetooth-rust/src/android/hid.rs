@@ -0,0 +1,423 @@
+//! Acting as a bluetooth hid device
+
+use super::super::Java;
+use super::jerr;
+use crate::{BluetoothAdapterAddress, HidEvent, HidReportType};
+use jni::JNIEnv;
+use jni::objects::{GlobalRef, JByteArray, JClass, JObject};
+use jni::sys::{jboolean, jbyte, jint, jlong};
+use jni_min_helper::*;
+use std::sync::{Arc, Mutex};
+
+/// The java class extending BluetoothHidDevice.Callback, which forwards the callbacks to the native functions here
+const CALLBACK_CLASS: &str = "com.github.uglyoldbob.bluetooth_rust.HidCallbackBridge";
+/// BluetoothProfile.STATE_DISCONNECTED
+const STATE_DISCONNECTED: i32 = 0;
+/// BluetoothProfile.STATE_CONNECTED
+const STATE_CONNECTED: i32 = 2;
+/// BluetoothHidDevice.ERROR_RSP_SUCCESS
+const ERROR_RSP_SUCCESS: i8 = 0;
+
+/// The state shared between a hid device and the callbacks of its HidCallbackBridge
+struct Shared {
+    /// The BluetoothHidDevice profile proxy the app is registered with
+    proxy: GlobalRef,
+    /// Where the events are sent
+    events: tokio::sync::mpsc::Sender<HidEvent>,
+}
+
+/// Get the shared state of the hid device from the handle held by the java callback
+///
+/// # Safety
+/// The handle must come from a hid device that has not released it yet, the java callback guarantees this
+unsafe fn shared<'a>(handle: jlong) -> &'a Shared {
+    unsafe { &*(handle as *const Shared) }
+}
+
+/// Get the address of a BluetoothDevice given to a callback
+fn device_address(
+    env: &mut JNIEnv,
+    device: &JObject,
+) -> Result<BluetoothAdapterAddress, jni::errors::Error> {
+    let address = env
+        .call_method(device, "getAddress", "()Ljava/lang/String;", &[])
+        .get_object(env)?
+        .get_string(env)?;
+    Ok(BluetoothAdapterAddress::String(address).normalized())
+}
+
+/// Send an event for the device given to a callback. The callbacks run on the main thread, so the event is
+/// dropped instead of waiting when the receiver falls behind.
+fn send_event(
+    env: &mut JNIEnv,
+    handle: jlong,
+    device: &JObject,
+    event: impl FnOnce(BluetoothAdapterAddress) -> HidEvent,
+) {
+    let shared = unsafe { shared(handle) };
+    let Ok(address) = device_address(env, device).map_err(jni_clear_ex_ignore) else {
+        return;
+    };
+    let _ = shared.events.try_send(event(address));
+}
+
+/// Read a byte array given to a callback
+fn callback_bytes(env: &mut JNIEnv, value: &JByteArray) -> Vec<u8> {
+    if value.is_null() {
+        return Vec::new();
+    }
+    env.convert_byte_array(value).unwrap_or_default()
+}
+
+/// HidCallbackBridge.nativeAppStatusChanged
+extern "system" fn native_app_status_changed(
+    _env: JNIEnv,
+    _class: JClass,
+    _handle: jlong,
+    _device: JObject,
+    registered: jboolean,
+) {
+    log::info!("The hid device registration changed to {}", registered != 0);
+}
+
+/// HidCallbackBridge.nativeConnectionStateChanged
+extern "system" fn native_connection_state_changed(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    device: JObject,
+    state: jint,
+) {
+    if state == STATE_CONNECTED || state == STATE_DISCONNECTED {
+        send_event(&mut env, handle, &device, |device| {
+            HidEvent::ConnectionChanged {
+                device,
+                connected: state == STATE_CONNECTED,
+            }
+        });
+    }
+}
+
+/// HidCallbackBridge.nativeGetReport
+extern "system" fn native_get_report(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    device: JObject,
+    report_type: jbyte,
+    id: jbyte,
+    buffer_size: jint,
+) {
+    let Some(report_type) = HidReportType::from_value(report_type as u8) else {
+        return;
+    };
+    send_event(&mut env, handle, &device, |device| HidEvent::GetReport {
+        device,
+        report_type,
+        id: id as u8,
+        buffer_size: buffer_size.clamp(0, u16::MAX as i32) as u16,
+    });
+}
+
+/// HidCallbackBridge.nativeSetReport
+extern "system" fn native_set_report(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    device: JObject,
+    report_type: jbyte,
+    id: jbyte,
+    data: JByteArray,
+) {
+    if let Some(report_type) = HidReportType::from_value(report_type as u8) {
+        let data = callback_bytes(&mut env, &data);
+        send_event(&mut env, handle, &device, |device| HidEvent::SetReport {
+            device,
+            report_type,
+            id: id as u8,
+            data,
+        });
+    }
+    // The host waits for the report to be acknowledged
+    let shared = unsafe { shared(handle) };
+    let _ = env
+        .call_method(
+            &shared.proxy,
+            "reportError",
+            "(Landroid/bluetooth/BluetoothDevice;B)Z",
+            &[(&device).into(), ERROR_RSP_SUCCESS.into()],
+        )
+        .clear_ex();
+}
+
+/// HidCallbackBridge.nativeInterruptData
+extern "system" fn native_interrupt_data(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    device: JObject,
+    id: jbyte,
+    data: JByteArray,
+) {
+    let data = callback_bytes(&mut env, &data);
+    send_event(&mut env, handle, &device, |device| HidEvent::OutputReport {
+        device,
+        id: id as u8,
+        data,
+    });
+}
+
+/// HidCallbackBridge.nativeVirtualCableUnplug
+extern "system" fn native_virtual_cable_unplug(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    device: JObject,
+) {
+    send_event(&mut env, handle, &device, HidEvent::VirtualCableUnplug);
+}
+
+/// Load the HidCallbackBridge class from the application and connect its native methods
+fn callback_class<'a>(
+    env: &mut JNIEnv<'a>,
+    context: &JObject,
+) -> Result<JClass<'a>, jni::errors::Error> {
+    let class = super::load_app_class(env, context, CALLBACK_CLASS)?;
+    let natives: [(&str, &str, *mut std::ffi::c_void); 6] = [
+        (
+            "nativeAppStatusChanged",
+            "(JLandroid/bluetooth/BluetoothDevice;Z)V",
+            native_app_status_changed as *mut _,
+        ),
+        (
+            "nativeConnectionStateChanged",
+            "(JLandroid/bluetooth/BluetoothDevice;I)V",
+            native_connection_state_changed as *mut _,
+        ),
+        (
+            "nativeGetReport",
+            "(JLandroid/bluetooth/BluetoothDevice;BBI)V",
+            native_get_report as *mut _,
+        ),
+        (
+            "nativeSetReport",
+            "(JLandroid/bluetooth/BluetoothDevice;BB[B)V",
+            native_set_report as *mut _,
+        ),
+        (
+            "nativeInterruptData",
+            "(JLandroid/bluetooth/BluetoothDevice;B[B)V",
+            native_interrupt_data as *mut _,
+        ),
+        (
+            "nativeVirtualCableUnplug",
+            "(JLandroid/bluetooth/BluetoothDevice;)V",
+            native_virtual_cable_unplug as *mut _,
+        ),
+    ];
+    let natives = natives.map(|(name, sig, fn_ptr)| jni::NativeMethod {
+        name: name.into(),
+        sig: sig.into(),
+        fn_ptr,
+    });
+    env.register_native_methods(&class, &natives)?;
+    Ok(class)
+}
+
+/// The app registered as a hid device with the BluetoothHidDevice profile proxy, unregistered when dropped
+pub struct HidDevice {
+    /// The BluetoothHidDevice profile proxy
+    proxy: GlobalRef,
+    /// The adapter, used to get the remote devices to send reports to
+    adapter: GlobalRef,
+    /// The HidCallbackBridge given to registerApp
+    callback: GlobalRef,
+    /// The java instance
+    java: Arc<Mutex<Java>>,
+    /// The reference to the shared state held by the java callback, released on drop
+    handle: jlong,
+    /// The receiver for the events, until taken by the user
+    events: Option<tokio::sync::mpsc::Receiver<HidEvent>>,
+}
+
+impl HidDevice {
+    /// Register the app with the hid device profile. The callbacks are run by the main executor of the context.
+    pub(super) fn register(
+        env: &mut JNIEnv,
+        context: &JObject,
+        adapter: &GlobalRef,
+        proxy: GlobalRef,
+        settings: &crate::HidSettings,
+        java: Arc<Mutex<Java>>,
+    ) -> Result<Self, crate::BluetoothError> {
+        let class = callback_class(env, context).map_err(|e| jerr(env, e))?;
+        let (tx, rx) = tokio::sync::mpsc::channel(32);
+        let shared = Arc::new(Shared {
+            proxy: proxy.clone(),
+            events: tx,
+        });
+        let handle = Arc::into_raw(shared) as jlong;
+        let registered = (|| -> Result<_, jni::errors::Error> {
+            let callback = env
+                .new_object(&class, "(J)V", &[handle.into()])
+                .globalize(env)?;
+            let name = settings.name.as_str().new_jobject(env)?;
+            let description = settings.description.as_str().new_jobject(env)?;
+            let provider = settings.provider.as_str().new_jobject(env)?;
+            let descriptor = env.byte_array_from_slice(&settings.descriptor)?;
+            let sdp = env.new_object(
+                "android/bluetooth/BluetoothHidDeviceAppSdpSettings",
+                "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;B[B)V",
+                &[
+                    (&name).into(),
+                    (&description).into(),
+                    (&provider).into(),
+                    (settings.subclass as i8).into(),
+                    (&descriptor).into(),
+                ],
+            )?;
+            let executor = env
+                .call_method(
+                    context,
+                    "getMainExecutor",
+                    "()Ljava/util/concurrent/Executor;",
+                    &[],
+                )
+                .get_object(env)?;
+            let qos = JObject::null();
+            let registered = env
+                .call_method(
+                    &proxy,
+                    "registerApp",
+                    "(Landroid/bluetooth/BluetoothHidDeviceAppSdpSettings;Landroid/bluetooth/BluetoothHidDeviceAppQosSettings;Landroid/bluetooth/BluetoothHidDeviceAppQosSettings;Ljava/util/concurrent/Executor;Landroid/bluetooth/BluetoothHidDevice$Callback;)Z",
+                    &[
+                        (&sdp).into(),
+                        (&qos).into(),
+                        (&qos).into(),
+                        (&executor).into(),
+                        (&callback).into(),
+                    ],
+                )
+                .get_boolean()?;
+            Ok((callback, registered))
+        })();
+        let callback = match registered {
+            Ok((callback, true)) => callback,
+            r => {
+                // The callback was never registered, so nothing else holds the handle
+                unsafe { drop(Arc::from_raw(handle as *const Shared)) };
+                return Err(match r {
+                    Err(e) => super::permission_err(env, "registerApp", e),
+                    Ok(_) => std::io::Error::other(
+                        "Failed to register the hid device, another app may be registered",
+                    )
+                    .into(),
+                });
+            }
+        };
+        Ok(Self {
+            proxy,
+            adapter: adapter.clone(),
+            callback,
+            java,
+            handle,
+            events: Some(rx),
+        })
+    }
+
+    /// Call a method of the proxy for a remote device, which returns false when it fails
+    fn call_for_device(
+        &self,
+        device: &BluetoothAdapterAddress,
+        what: &str,
+        f: impl FnOnce(&mut JNIEnv, &JObject) -> Result<bool, jni::errors::Error>,
+    ) -> Result<(), crate::BluetoothError> {
+        let mut java = self.java.lock().unwrap();
+        let done = java.use_env(|env, _context| {
+            let done = (|| {
+                let address = device.to_string().new_jobject(env)?;
+                let device = env
+                    .call_method(
+                        &self.adapter,
+                        "getRemoteDevice",
+                        "(Ljava/lang/String;)Landroid/bluetooth/BluetoothDevice;",
+                        &[(&address).into()],
+                    )
+                    .get_object(env)?;
+                f(env, &device)
+            })();
+            done.map_err(|e| jerr(env, e))
+        })?;
+        if done {
+            Ok(())
+        } else {
+            Err(std::io::Error::other(format!("Failed to {what}")).into())
+        }
+    }
+}
+
+impl crate::HidDeviceTrait for HidDevice {
+    fn send_report(
+        &self,
+        device: &BluetoothAdapterAddress,
+        id: u8,
+        data: &[u8],
+    ) -> Result<(), crate::BluetoothError> {
+        self.call_for_device(device, "send the hid report", |env, device| {
+            let data = env.byte_array_from_slice(data)?;
+            env.call_method(
+                &self.proxy,
+                "sendReport",
+                "(Landroid/bluetooth/BluetoothDevice;I[B)Z",
+                &[device.into(), (id as i32).into(), (&data).into()],
+            )
+            .get_boolean()
+        })
+    }
+
+    fn reply_report(
+        &self,
+        device: &BluetoothAdapterAddress,
+        report_type: HidReportType,
+        id: u8,
+        data: &[u8],
+    ) -> Result<(), crate::BluetoothError> {
+        self.call_for_device(device, "reply with the hid report", |env, device| {
+            let data = env.byte_array_from_slice(data)?;
+            env.call_method(
+                &self.proxy,
+                "replyReport",
+                "(Landroid/bluetooth/BluetoothDevice;BB[B)Z",
+                &[
+                    device.into(),
+                    (report_type.value() as i8).into(),
+                    (id as i8).into(),
+                    (&data).into(),
+                ],
+            )
+            .get_boolean()
+        })
+    }
+
+    fn take_events(&mut self) -> Option<tokio::sync::mpsc::Receiver<HidEvent>> {
+        self.events.take()
+    }
+}
+
+impl Drop for HidDevice {
+    fn drop(&mut self) {
+        {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, _context| {
+                let _ = env
+                    .call_method(&self.proxy, "unregisterApp", "()Z", &[])
+                    .clear_ex();
+                // Waits for a running callback to finish, no callbacks use the handle after this
+                let _ = env
+                    .call_method(&self.callback, "release", "()V", &[])
+                    .clear_ex();
+            });
+        }
+        unsafe { drop(Arc::from_raw(self.handle as *const Shared)) };
+    }
+}
@@ -0,0 +1,226 @@
+//! Scanning for low energy devices
+
+use super::super::Java;
+use jni::JNIEnv;
+use jni::objects::{GlobalRef, JByteArray, JClass, JObject};
+use jni::sys::{jint, jlong};
+use jni_min_helper::*;
+use std::sync::{Arc, Mutex};
+
+/// The java class extending ScanCallback, which forwards the results to the native functions here
+const CALLBACK_CLASS: &str = "com.github.uglyoldbob.bluetooth_rust.ScanCallbackBridge";
+
+/// The state shared between a scan and the callbacks of its ScanCallbackBridge
+struct Shared {
+    /// Only the results that pass the filter are sent
+    filter: crate::LeScanFilter,
+    /// Where the results are sent, taken when the scan fails so that the results end
+    results: Mutex<Option<tokio::sync::mpsc::Sender<crate::LeScanResult>>>,
+}
+
+/// Get the shared state of the scan from the handle held by the java callback
+///
+/// # Safety
+/// The handle must come from a scan that has not released it yet, the java callback guarantees this
+unsafe fn shared<'a>(handle: jlong) -> &'a Shared {
+    unsafe { &*(handle as *const Shared) }
+}
+
+/// Build a scan result from the values given to ScanCallbackBridge.nativeScanResult
+fn scan_result(
+    env: &mut JNIEnv,
+    device: &JObject,
+    rssi: jint,
+    record: &JByteArray,
+) -> Result<crate::LeScanResult, jni::errors::Error> {
+    let address = env
+        .call_method(device, "getAddress", "()Ljava/lang/String;", &[])
+        .get_object(env)?
+        .get_string(env)?;
+    let record = if record.is_null() {
+        Vec::new()
+    } else {
+        env.convert_byte_array(record)?
+    };
+    let (name, service_uuids, manufacturer_data) = super::parse_advertisement(&record);
+    let name = match name {
+        Some(name) => Some(name),
+        // The advertisement does not always carry the name, android may know it from earlier
+        None => env
+            .call_method(device, "getName", "()Ljava/lang/String;", &[])
+            .get_object(env)
+            .ok()
+            .filter(|n| !n.is_null())
+            .and_then(|n| n.get_string(env).ok()),
+    };
+    Ok(crate::LeScanResult {
+        address: crate::BluetoothAdapterAddress::String(address).normalized(),
+        name,
+        rssi: Some(rssi as i16),
+        service_uuids,
+        manufacturer_data,
+    })
+}
+
+/// ScanCallbackBridge.nativeScanResult
+extern "system" fn native_scan_result(
+    mut env: JNIEnv,
+    _class: JClass,
+    handle: jlong,
+    device: JObject,
+    rssi: jint,
+    record: JByteArray,
+) {
+    let shared = unsafe { shared(handle) };
+    let Ok(result) = scan_result(&mut env, &device, rssi, &record).map_err(jni_clear_ex_ignore)
+    else {
+        return;
+    };
+    if !shared.filter.matches(&result) {
+        return;
+    }
+    // The callbacks run on the main thread, so the result is dropped instead of waiting when the receiver
+    // falls behind
+    if let Some(results) = &*shared.results.lock().unwrap() {
+        let _ = results.try_send(result);
+    }
+}
+
+/// ScanCallbackBridge.nativeScanFailed
+extern "system" fn native_scan_failed(_env: JNIEnv, _class: JClass, handle: jlong, error: jint) {
+    log::error!("The low energy scan failed with error {}", error);
+    let shared = unsafe { shared(handle) };
+    shared.results.lock().unwrap().take();
+}
+
+/// Load the ScanCallbackBridge class from the application and connect its native methods
+fn callback_class<'a>(
+    env: &mut JNIEnv<'a>,
+    context: &JObject,
+) -> Result<JClass<'a>, jni::errors::Error> {
+    let class = super::load_app_class(env, context, CALLBACK_CLASS)?;
+    let natives: [(&str, &str, *mut std::ffi::c_void); 2] = [
+        (
+            "nativeScanResult",
+            "(JLandroid/bluetooth/BluetoothDevice;I[B)V",
+            native_scan_result as *mut _,
+        ),
+        ("nativeScanFailed", "(JI)V", native_scan_failed as *mut _),
+    ];
+    let natives = natives.map(|(name, sig, fn_ptr)| jni::NativeMethod {
+        name: name.into(),
+        sig: sig.into(),
+        fn_ptr,
+    });
+    env.register_native_methods(&class, &natives)?;
+    Ok(class)
+}
+
+/// A low energy scan with BluetoothLeScanner, stopped when dropped
+pub struct LeScanSession {
+    /// The BluetoothLeScanner running the scan
+    scanner: GlobalRef,
+    /// The ScanCallbackBridge given to startScan, needed to stop the scan
+    callback: GlobalRef,
+    /// The java instance
+    java: Arc<Mutex<Java>>,
+    /// The reference to the shared state held by the java callback, released on drop
+    handle: jlong,
+    /// The scan results, until taken by the user
+    results: Option<tokio::sync::mpsc::Receiver<crate::LeScanResult>>,
+}
+
+impl LeScanSession {
+    /// Start a scan on the adapter, sending the results that pass the filter
+    pub(super) fn new(
+        adapter: &GlobalRef,
+        java: Arc<Mutex<Java>>,
+        filter: crate::LeScanFilter,
+    ) -> Result<Self, std::io::Error> {
+        let (tx, rx) = tokio::sync::mpsc::channel(32);
+        let shared = Arc::new(Shared {
+            filter,
+            results: Mutex::new(Some(tx)),
+        });
+        let handle = Arc::into_raw(shared) as jlong;
+        let started = {
+            let mut java2 = java.lock().unwrap();
+            java2.use_env(|env, context| {
+                let started = (|| -> Result<_, jni::errors::Error> {
+                    let scanner = env
+                        .call_method(
+                            adapter,
+                            "getBluetoothLeScanner",
+                            "()Landroid/bluetooth/le/BluetoothLeScanner;",
+                            &[],
+                        )
+                        .get_object(env)?;
+                    // There is no scanner while bluetooth is off
+                    if scanner.is_null() {
+                        return Ok(None);
+                    }
+                    let class = callback_class(env, &context)?;
+                    let callback = env
+                        .new_object(&class, "(J)V", &[handle.into()])
+                        .globalize(env)?;
+                    env.call_method(
+                        &scanner,
+                        "startScan",
+                        "(Landroid/bluetooth/le/ScanCallback;)V",
+                        &[(&callback).into()],
+                    )
+                    .clear_ex()?;
+                    Ok(Some((env.new_global_ref(&scanner)?, callback)))
+                })();
+                started.map_err(|e| super::permission_err(env, "startScan", e))
+            })
+        };
+        let (scanner, callback) = match started {
+            Ok(Some(started)) => started,
+            r => {
+                // The scan never started, so nothing else holds the handle
+                unsafe { drop(Arc::from_raw(handle as *const Shared)) };
+                return Err(match r {
+                    Err(e) => e.into(),
+                    Ok(_) => crate::BluetoothError::AdapterUnavailable.into(),
+                });
+            }
+        };
+        Ok(Self {
+            scanner,
+            callback,
+            java,
+            handle,
+            results: Some(rx),
+        })
+    }
+}
+
+impl crate::LeScanSessionTrait for LeScanSession {
+    fn take_results(&mut self) -> Option<tokio::sync::mpsc::Receiver<crate::LeScanResult>> {
+        self.results.take()
+    }
+}
+
+impl Drop for LeScanSession {
+    fn drop(&mut self) {
+        {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, _context| {
+                let _ = env
+                    .call_method(
+                        &self.scanner,
+                        "stopScan",
+                        "(Landroid/bluetooth/le/ScanCallback;)V",
+                        &[(&self.callback).into()],
+                    )
+                    .clear_ex();
+                // Waits for a running callback to finish, no callbacks use the handle after this
+                let _ = env
+                    .call_method(&self.callback, "release", "()V", &[])
+                    .clear_ex();
+            });
+        }
+        unsafe { drop(Arc::from_raw(self.handle as *const Shared)) };
+    }
+}
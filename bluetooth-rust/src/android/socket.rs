@@ -5,14 +5,73 @@ use super::jerr;
 use jni_min_helper::*;
 use std::{
     collections::VecDeque,
-    sync::{Arc, Mutex},
+    sync::{Arc, Condvar, Mutex},
     thread::JoinHandle,
-    time::{Duration, SystemTime},
+    time::{Duration, Instant},
 };
 
+/// The data received by the background read thread, waiting to be read
+struct ReadState {
+    /// The received data
+    data: VecDeque<u8>,
+    /// The read thread has stopped, because the socket disconnected or was closed
+    disconnected: bool,
+    /// The read thread stops reading from the socket when this much data is buffered
+    limit: usize,
+}
+
+impl Default for ReadState {
+    fn default() -> Self {
+        Self {
+            data: VecDeque::new(),
+            disconnected: false,
+            limit: BluetoothSocket::DEFAULT_RECEIVE_BUFFER_LIMIT,
+        }
+    }
+}
+
+/// The read buffer shared between the background read thread and the readers
+#[derive(Default)]
+struct ReadBuffer {
+    /// The buffer state
+    state: Mutex<ReadState>,
+    /// Notified when data arrives or the socket disconnects
+    changed: Condvar,
+}
+
+impl ReadBuffer {
+    /// Add received data, waking up the readers
+    fn push(&self, data: &[u8]) {
+        self.state.lock().unwrap().data.extend(data);
+        self.changed.notify_all();
+    }
+
+    /// Wait until there is room for more data, returning how much more fits under the limit. Once the limit is
+    /// reached, this waits until the readers have drained the buffer to half of the limit. Returns None when the
+    /// socket is disconnected.
+    fn wait_for_space(&self) -> Option<usize> {
+        let mut state = self.state.lock().unwrap();
+        if state.data.len() >= state.limit {
+            while !state.disconnected && state.data.len() > state.limit / 2 {
+                state = self.changed.wait(state).unwrap();
+            }
+        }
+        if state.disconnected {
+            return None;
+        }
+        Some(state.limit.saturating_sub(state.data.len()))
+    }
+
+    /// Mark the buffer as connected or disconnected, waking up the readers
+    fn set_disconnected(&self, disconnected: bool) {
+        self.state.lock().unwrap().disconnected = disconnected;
+        self.changed.notify_all();
+    }
+}
+
 /// Manages the Bluetooth socket and IO streams. It uses a read buffer and a background thread,
 /// because the timeout of the Java `InputStream` from the `BluetoothSocket` cannot be set.
-/// The read timeout defaults to 0 (it does not block).
+/// The read timeout defaults to 0 (it does not block), it can be changed with `set_read_timeout`.
 ///
 /// Reference:
 /// <https://developer.android.com/develop/connectivity/bluetooth/transfer-data>
@@ -20,10 +79,10 @@ pub struct BluetoothSocket {
     internal: jni::objects::GlobalRef,
 
     input_stream: jni::objects::GlobalRef,
-    buf_read: Arc<Mutex<VecDeque<u8>>>,
+    buf_read: Arc<ReadBuffer>,
     thread_read: Option<JoinHandle<Result<(), std::io::Error>>>, // the returned value is unused
-    read_callback: Arc<Mutex<Option<super::ReadCallback>>>,      // None by default
-    read_timeout: Duration,                                      // set for the standard Read trait
+    read_callback: Arc<Mutex<Option<crate::ReadCallback>>>,      // None by default
+    read_timeout: Option<Duration>,                              // set for the standard Read trait
 
     output_stream: jni::objects::GlobalRef,
     jmethod_write: jni::objects::JMethodID,
@@ -31,6 +90,10 @@ pub struct BluetoothSocket {
     array_write: jni::objects::GlobalRef,
     uuid: String,
     java: Arc<Mutex<Java>>,
+    /// The java socket was handed to a stream, which closes it instead
+    handed_over: bool,
+    /// The socket was created to require an authenticated and encrypted link
+    secure: bool,
 }
 
 impl std::fmt::Debug for BluetoothSocket {
@@ -39,13 +102,87 @@ impl std::fmt::Debug for BluetoothSocket {
     }
 }
 
-impl crate::BluetoothSocketTrait for &mut BluetoothSocket {
+#[async_trait::async_trait]
+impl crate::BluetoothSocketTrait for BluetoothSocket {
+    fn supports_sync(&mut self) -> Option<&mut dyn crate::SyncReadWrite> {
+        Some(self)
+    }
+
     fn is_connected(&self) -> Result<bool, std::io::Error> {
         let mut java2 = self.java.lock().unwrap();
         java2.use_env(|env, _context| self.is_connected2(env))
     }
 
-    fn connect(&mut self) -> Result<(), std::io::Error> {
+    fn read_timeout(&self) -> Result<Option<Duration>, std::io::Error> {
+        Ok(self.read_timeout)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), std::io::Error> {
+        self.read_timeout = crate::check_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// The callback is called from the background read thread
+    fn set_read_callback(&mut self, callback: crate::ReadCallback) -> Result<(), std::io::Error> {
+        self.read_callback.lock().unwrap().replace(callback);
+        Ok(())
+    }
+
+    fn clear_read_callback(&mut self) {
+        self.read_callback.lock().unwrap().take();
+    }
+
+    fn max_receive_packet_size(&self) -> Result<usize, std::io::Error> {
+        self.packet_size("getMaxReceivePacketSize")
+    }
+
+    fn max_transmit_packet_size(&self) -> Result<usize, std::io::Error> {
+        self.packet_size("getMaxTransmitPacketSize")
+    }
+
+    async fn async_connect(&mut self) -> Result<(), std::io::Error> {
+        Err(std::io::Error::other("async not supported"))
+    }
+
+    fn close(&mut self) -> Result<(), crate::BluetoothError> {
+        BluetoothSocket::close(self).map_err(Into::into)
+    }
+
+    /// The socket is connected here when it is not connected yet. A socket connected with `sync_connect` is
+    /// already read by its background thread, so it can not become a stream.
+    fn into_stream(mut self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        if self.thread_read.is_some() {
+            return Err(crate::BluetoothError::Unsupported(
+                "Turning a socket connected with sync_connect into a stream",
+            ));
+        }
+        let l2cap = {
+            let mut java = self.java.lock().unwrap();
+            java.use_env(|env, _context| {
+                if !self.is_connected2(env)? {
+                    env.call_method(&self.internal, "connect", "()V", &[])
+                        .map_err(|e| jerr(env, e))?;
+                }
+                // TYPE_L2CAP and TYPE_L2CAP_LE, the type is only available from api level 23
+                let kind = env
+                    .call_method(&self.internal, "getConnectionType", "()I", &[])
+                    .get_int()
+                    .map_err(|e| jerr(env, e));
+                Ok::<_, std::io::Error>(matches!(kind, Ok(3 | 4)))
+            })?
+        };
+        let stream =
+            super::RfcommStream::new(self.internal.clone().into(), self.java.clone(), self.secure)?;
+        // The stream closes the java socket from now on
+        self.handed_over = true;
+        Ok(if l2cap {
+            crate::BluetoothStream::AndroidL2cap(stream)
+        } else {
+            crate::BluetoothStream::Android(stream)
+        })
+    }
+
+    fn sync_connect(&mut self) -> Result<(), std::io::Error> {
         use crate::BluetoothSocketTrait;
         if self.is_connected()? {
             return Ok(());
@@ -65,15 +202,19 @@ impl crate::BluetoothSocketTrait for &mut BluetoothSocket {
             let input_stream = self.input_stream.clone();
             let arc_buf_read = self.buf_read.clone();
             let arc_callback = self.read_callback.clone();
+            self.buf_read.set_disconnected(false);
             self.thread_read.replace(std::thread::spawn(move || {
                 let mut java = Java::make(app);
-                BluetoothSocket::read_loop(
+                let result = BluetoothSocket::read_loop(
                     &mut java,
                     socket,
                     input_stream,
-                    arc_buf_read,
+                    arc_buf_read.clone(),
                     arc_callback,
-                )
+                );
+                // wake up any blocked readers, however the loop ended
+                arc_buf_read.set_disconnected(true);
+                result
             }));
             log::warn!("Done connecting");
             Ok(())
@@ -83,13 +224,35 @@ impl crate::BluetoothSocketTrait for &mut BluetoothSocket {
     }
 }
 
+/// How much to read from the socket when `room` more bytes fit in the read buffer. The read is kept to whole packets
+/// so a packet is never cut short, and to at most `read_size` bytes.
+fn read_length(room: usize, read_size: usize, packet: usize) -> usize {
+    room.next_multiple_of(packet).min(read_size)
+}
+
 impl BluetoothSocket {
     const ARRAY_SIZE: usize = 32 * 1024;
+    /// The default limit for the amount of received data waiting to be read
+    pub const DEFAULT_RECEIVE_BUFFER_LIMIT: usize = 1024 * 1024;
+
+    /// Set how much received data can wait to be read before the socket stops receiving. Once the limit is
+    /// reached, receiving resumes when the buffer has been drained to half of the limit. Reads from the socket are
+    /// kept to whole packets, so the buffer can go over the limit by less than one packet.
+    pub fn set_receive_buffer_limit(&mut self, limit: usize) {
+        self.buf_read.state.lock().unwrap().limit = limit.max(1);
+        self.buf_read.changed.notify_all();
+    }
+
+    /// The amount of received data waiting to be read
+    pub fn buffered_bytes(&self) -> usize {
+        self.buf_read.state.lock().unwrap().data.len()
+    }
 
     pub fn build(
         obj: jni::objects::GlobalRef,
         java: Arc<Mutex<Java>>,
         uuid: &str,
+        secure: bool,
     ) -> Result<Self, std::io::Error> {
         let mut java2 = java.lock().unwrap();
         let input_stream = java2.use_env(|env, _context| {
@@ -106,11 +269,11 @@ impl BluetoothSocket {
                 .map_err(|e| jerr(env, e))
         })?;
         let jmethod_write = java2.use_env(|env, _context| {
-            env.get_method_id("java/io/OutputStream", "write", "([BII)V")
+            env.get_method_id("java/io/OutputStream", "write", super::OUTPUT_STREAM_WRITE)
                 .map_err(|e| jerr(env, e))
         })?;
         let jmethod_flush = java2.use_env(|env, _context| {
-            env.get_method_id("java/io/OutputStream", "flush", "()V")
+            env.get_method_id("java/io/OutputStream", "flush", super::OUTPUT_STREAM_FLUSH)
                 .map_err(|e| jerr(env, e))
         })?;
 
@@ -125,10 +288,10 @@ impl BluetoothSocket {
             internal: obj,
 
             input_stream,
-            buf_read: Arc::new(Mutex::new(VecDeque::new())),
+            buf_read: Arc::new(ReadBuffer::default()),
             thread_read: None,
             read_callback: Arc::new(Mutex::new(None)),
-            read_timeout: Duration::from_millis(0),
+            read_timeout: Some(Duration::from_millis(0)),
 
             output_stream,
             jmethod_write,
@@ -136,6 +299,19 @@ impl BluetoothSocket {
             array_write,
             uuid: uuid.to_string(),
             java,
+            handed_over: false,
+            secure,
+        })
+    }
+
+    /// Call one of the packet size methods of the socket
+    fn packet_size(&self, method: &str) -> Result<usize, std::io::Error> {
+        let mut java = self.java.lock().unwrap();
+        java.use_env(|env, _context| {
+            env.call_method(&self.internal, method, "()I", &[])
+                .get_int()
+                .map(|size| size.max(0) as usize)
+                .map_err(|e| jerr(env, e))
         })
     }
 
@@ -151,25 +327,20 @@ impl BluetoothSocket {
         java: &mut Java,
         socket: jni::objects::GlobalRef,
         input_stream: jni::objects::GlobalRef,
-        buf_read: Arc<Mutex<VecDeque<u8>>>,
-        read_callback: Arc<Mutex<Option<super::ReadCallback>>>,
+        buf_read: Arc<ReadBuffer>,
+        read_callback: Arc<Mutex<Option<crate::ReadCallback>>>,
     ) -> Result<(), std::io::Error> {
         java.use_env(|env, _context| {
             let jmethod_read = env
-                .get_method_id("java/io/InputStream", "read", "([BII)I")
+                .get_method_id("java/io/InputStream", "read", super::INPUT_STREAM_READ)
                 .map_err(|e| jerr(env, e))?;
-            let read_size = env
+            let packet = env
                 .call_method(&socket, "getMaxReceivePacketSize", "()I", &[])
                 .get_int()
-                .map(|i| {
-                    if i > 0 {
-                        let sz = i as usize;
-                        (Self::ARRAY_SIZE / sz) * sz
-                    } else {
-                        Self::ARRAY_SIZE
-                    }
-                })
-                .unwrap_or(Self::ARRAY_SIZE);
+                .ok()
+                .filter(|i| *i > 0)
+                .map_or(1, |i| i as usize);
+            let read_size = (Self::ARRAY_SIZE / packet) * packet;
 
             let mut vec_read = vec![0u8; read_size];
             let array_read = env
@@ -180,8 +351,13 @@ impl BluetoothSocket {
 
             loop {
                 use jni::signature::*;
+                // stop pulling from the socket while the buffer is full, so flow control pushes back on the peer
+                let Some(room) = buf_read.wait_for_space() else {
+                    return Ok(());
+                };
+                let read_len = read_length(room, read_size, packet);
                 // Safety: arguments passed to `call_method_unchecked` are correct.
-                let read_len = unsafe {
+                let ret = unsafe {
                     env.call_method_unchecked(
                         &input_stream,
                         jmethod_read,
@@ -194,31 +370,25 @@ impl BluetoothSocket {
                                 i: 0 as jni::sys::jint,
                             },
                             jni::sys::jvalue {
-                                i: read_size as jni::sys::jint,
+                                i: read_len as jni::sys::jint,
                             },
                         ],
                     )
                 }
                 .get_int();
-                if let Ok(len) = read_len {
-                    use std::io::Write;
-                    let len = if len > 0 {
-                        len as usize
-                    } else {
-                        continue;
+                if let Ok(len) = ret {
+                    let len = match super::read_count(len, read_len)? {
+                        0 => continue,
+                        len => len,
                     };
                     // Safety: casts `&mut [u8]` to `&mut [i8]` for `get_byte_array_region`,
-                    // `input_stream.read(..)` = `len` <= `read_size` = `vec_read.len()`.
+                    // `read_count` checked that `len` <= `read_len` <= `read_size` = `vec_read.len()`.
                     let tmp_read = unsafe {
                         std::slice::from_raw_parts_mut(vec_read.as_mut_ptr() as *mut i8, len)
                     };
                     env.get_byte_array_region(array_read, 0, tmp_read)
                         .map_err(|e| jerr(env, e))?;
-                    buf_read
-                        .lock()
-                        .unwrap()
-                        .write_all(&vec_read[..len])
-                        .unwrap();
+                    buf_read.push(&vec_read[..len]);
                     Self::read_callback(&read_callback, Some(len));
                 } else {
                     if let Some(ex) = jni_last_cleared_ex() {
@@ -245,7 +415,7 @@ impl BluetoothSocket {
         })
     }
 
-    fn read_callback(cb: impl AsRef<Mutex<Option<super::ReadCallback>>>, val: Option<usize>) {
+    fn read_callback(cb: impl AsRef<Mutex<Option<crate::ReadCallback>>>, val: Option<usize>) {
         let mut lck = cb.as_ref().lock().unwrap();
         if let Some(callback) = lck.take() {
             drop(lck);
@@ -262,7 +432,7 @@ impl BluetoothSocket {
     pub fn close(&mut self) -> Result<(), std::io::Error> {
         use crate::BluetoothSocketTrait;
         use std::io::Write;
-        if !self.is_connected()? {
+        if self.handed_over || !self.is_connected()? {
             return Ok(());
         }
         let _ = self.flush();
@@ -272,6 +442,8 @@ impl BluetoothSocket {
                 .clear_ex()
                 .map_err(|e| jerr(env, e))
         })?;
+        // the read thread may be waiting for room in the buffer
+        self.buf_read.set_disconnected(true);
         if let Some(th) = self.thread_read.take() {
             let _ = th.join();
         }
@@ -280,47 +452,48 @@ impl BluetoothSocket {
 }
 
 impl std::io::Read for BluetoothSocket {
+    /// Returns as soon as some data is available, waiting up to the read timeout for it to arrive
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        use crate::BluetoothSocketTrait;
+        use std::io::Read;
         if buf.is_empty() {
             return Ok(0);
         }
+        if self.thread_read.is_none() {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        }
 
-        let t_timeout = SystemTime::now() + self.read_timeout;
-
-        let mut cnt_read = 0;
-        let mut disconnected = false;
-        while cnt_read < buf.len() {
-            let mut lck_buf_read = self.buf_read.lock().unwrap();
-            if let Ok(cnt) = lck_buf_read.read(&mut buf[cnt_read..]) {
-                cnt_read += cnt;
+        let t_timeout = self.read_timeout.map(|t| Instant::now() + t);
+        let mut state = self.buf_read.state.lock().unwrap();
+        loop {
+            if !state.data.is_empty() {
+                let cnt = state.data.read(buf)?;
+                // the read thread may be waiting for room in the buffer
+                self.buf_read.changed.notify_all();
+                return Ok(cnt);
+            } else if state.disconnected {
+                return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
             }
-            drop(lck_buf_read);
-            if cnt_read >= buf.len() {
-                break;
-            } else if !self.is_connected()? {
-                disconnected = true;
-                break;
-            } else if let Ok(dur_rem) = t_timeout.duration_since(SystemTime::now()) {
-                std::thread::sleep(Duration::from_millis(100).min(dur_rem));
-            } else {
-                break;
+            match t_timeout {
+                Some(t_timeout) => {
+                    let now = Instant::now();
+                    if now >= t_timeout {
+                        return Err(std::io::Error::from(std::io::ErrorKind::TimedOut));
+                    }
+                    state = self
+                        .buf_read
+                        .changed
+                        .wait_timeout(state, t_timeout - now)
+                        .unwrap()
+                        .0;
+                }
+                None => state = self.buf_read.changed.wait(state).unwrap(),
             }
         }
-
-        if cnt_read > 0 {
-            Ok(cnt_read)
-        } else if !disconnected {
-            Err(std::io::Error::from(std::io::ErrorKind::TimedOut))
-        } else {
-            Err(std::io::Error::from(std::io::ErrorKind::NotConnected))
-        }
     }
 }
 
 impl std::io::Write for BluetoothSocket {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        use crate::BluetoothSocketTrait;
         if buf.is_empty() {
             return Ok(0);
         }
@@ -370,10 +543,12 @@ impl std::io::Write for BluetoothSocket {
             }
             .clear_ex()
             .map_err(|e| {
-                if !self.is_connected().unwrap_or(false) {
+                // the java instance is already locked, so the connection is checked with this env
+                let err = jerr(env, e);
+                if !self.is_connected2(env).unwrap_or(false) {
                     std::io::Error::from(std::io::ErrorKind::NotConnected)
                 } else {
-                    jerr(env, e)
+                    err
                 }
             })
             .map(|_| buf.len())
@@ -404,3 +579,93 @@ impl Drop for BluetoothSocket {
         let _ = self.close();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A buffer with the given limit, filled by a fake producer thread in pushes of `chunk` bytes until the buffer
+    /// is disconnected. The number of finished pushes is counted.
+    fn produce(
+        limit: usize,
+        chunk: usize,
+    ) -> (
+        Arc<ReadBuffer>,
+        Arc<std::sync::atomic::AtomicUsize>,
+        JoinHandle<()>,
+    ) {
+        let buf = Arc::new(ReadBuffer::default());
+        buf.state.lock().unwrap().limit = limit;
+        let pushes = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let producer = {
+            let buf = buf.clone();
+            let pushes = pushes.clone();
+            std::thread::spawn(move || {
+                while let Some(room) = buf.wait_for_space() {
+                    buf.push(&vec![0; read_length(room, chunk, 1)]);
+                    pushes.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                }
+            })
+        };
+        (buf, pushes, producer)
+    }
+
+    /// Wait for the producer to settle
+    fn settle() {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    /// Remove buffered data like a reader does, leaving `left` bytes
+    fn drain_to(buf: &ReadBuffer, left: usize) {
+        let mut state = buf.state.lock().unwrap();
+        let n = state.data.len() - left;
+        state.data.drain(..n);
+        buf.changed.notify_all();
+    }
+
+    #[test]
+    fn producer_blocks_at_the_limit() {
+        let (buf, pushes, producer) = produce(10, 4);
+        settle();
+        assert_eq!(buf.state.lock().unwrap().data.len(), 10);
+        let done = pushes.load(std::sync::atomic::Ordering::SeqCst);
+        settle();
+        assert_eq!(pushes.load(std::sync::atomic::Ordering::SeqCst), done);
+        buf.set_disconnected(true);
+        producer.join().unwrap();
+    }
+
+    #[test]
+    fn producer_resumes_at_half_the_limit() {
+        let (buf, pushes, producer) = produce(10, 4);
+        settle();
+        let done = pushes.load(std::sync::atomic::Ordering::SeqCst);
+        drain_to(&buf, 6);
+        settle();
+        assert_eq!(pushes.load(std::sync::atomic::Ordering::SeqCst), done);
+        assert_eq!(buf.state.lock().unwrap().data.len(), 6);
+        drain_to(&buf, 5);
+        settle();
+        assert!(pushes.load(std::sync::atomic::Ordering::SeqCst) > done);
+        assert_eq!(buf.state.lock().unwrap().data.len(), 10);
+        buf.set_disconnected(true);
+        producer.join().unwrap();
+    }
+
+    #[test]
+    fn disconnecting_wakes_the_producer() {
+        let (buf, _pushes, producer) = produce(10, 4);
+        settle();
+        buf.set_disconnected(true);
+        producer.join().unwrap();
+        assert_eq!(buf.wait_for_space(), None);
+    }
+
+    #[test]
+    fn reads_stay_under_the_limit_in_whole_packets() {
+        assert_eq!(read_length(100, 32 * 1024, 1), 100);
+        assert_eq!(read_length(100_000, 32 * 1024, 1), 32 * 1024);
+        assert_eq!(read_length(100, 990 * 33, 990), 990);
+        assert_eq!(read_length(1000, 990 * 33, 990), 1980);
+    }
+}
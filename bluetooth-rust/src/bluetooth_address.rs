@@ -0,0 +1,225 @@
+//! Addresses of bluetooth adapters
+
+/// The address of a bluetooth adapter
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub enum BluetoothAdapterAddress {
+    /// The address in string form
+    String(String),
+    /// The address in byte form
+    Byte([u8; 6]),
+}
+
+/// The error returned when a string is not a valid bluetooth address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddressParseError(String);
+
+impl std::fmt::Display for AddressParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid bluetooth address: {}", self.0)
+    }
+}
+
+impl std::error::Error for AddressParseError {}
+
+impl BluetoothAdapterAddress {
+    /// Parse an address such as `00:11:22:AA:BB:CC`. The hex digits can be upper or lower case, and the bytes
+    /// can be separated by `:`, `-`, or nothing at all. The result is always in byte form.
+    pub fn parse(s: &str) -> Result<Self, AddressParseError> {
+        let err = || AddressParseError(s.to_string());
+        let digits: Vec<char> = if s.len() == 12 {
+            s.chars().collect()
+        } else {
+            let separator = if s.contains(':') { ':' } else { '-' };
+            let parts: Vec<&str> = s.split(separator).collect();
+            if parts.len() != 6 || parts.iter().any(|p| p.len() != 2) {
+                return Err(err());
+            }
+            parts.iter().flat_map(|p| p.chars()).collect()
+        };
+        if digits.len() != 12 {
+            return Err(err());
+        }
+        let mut bytes = [0u8; 6];
+        for (b, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+            let hi = pair[0].to_digit(16).ok_or_else(err)?;
+            let lo = pair[1].to_digit(16).ok_or_else(err)?;
+            *b = (hi * 16 + lo) as u8;
+        }
+        Ok(Self::Byte(bytes))
+    }
+
+    /// Get the address in byte form. This is `None` for a string address that is not valid.
+    pub fn as_bytes(&self) -> Option<[u8; 6]> {
+        match self {
+            Self::Byte(b) => Some(*b),
+            Self::String(s) => match Self::parse(s) {
+                Ok(Self::Byte(b)) => Some(b),
+                _ => None,
+            },
+        }
+    }
+
+    /// Convert the address to byte form when possible, leaving invalid string addresses unchanged
+    pub fn normalized(self) -> Self {
+        match self.as_bytes() {
+            Some(b) => Self::Byte(b),
+            None => self,
+        }
+    }
+}
+
+impl From<[u8; 6]> for BluetoothAdapterAddress {
+    fn from(value: [u8; 6]) -> Self {
+        Self::Byte(value)
+    }
+}
+
+impl std::str::FromStr for BluetoothAdapterAddress {
+    type Err = AddressParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// Valid addresses are formatted as upper case hex separated by `:`, invalid string addresses are shown as-is
+impl std::fmt::Display for BluetoothAdapterAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.as_bytes() {
+            Some(b) => write!(
+                f,
+                "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
+                b[0], b[1], b[2], b[3], b[4], b[5]
+            ),
+            None => match self {
+                Self::String(s) => f.write_str(s),
+                Self::Byte(_) => unreachable!(),
+            },
+        }
+    }
+}
+
+/// Addresses are equal when they refer to the same bytes, regardless of the form they are stored in
+impl PartialEq for BluetoothAdapterAddress {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.as_bytes(), other.as_bytes()) {
+            (Some(a), Some(b)) => a == b,
+            (None, None) => self.to_string() == other.to_string(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for BluetoothAdapterAddress {}
+
+impl std::hash::Hash for BluetoothAdapterAddress {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        match self.as_bytes() {
+            Some(b) => b.hash(state),
+            None => self.to_string().hash(state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bytes of the address used by the tests
+    const BYTES: [u8; 6] = [0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc];
+
+    #[test]
+    fn parse_accepts_any_case_and_separator() {
+        for s in [
+            "00:11:22:AA:BB:CC",
+            "00:11:22:aa:bb:cc",
+            "00:11:22:aA:Bb:cC",
+            "00-11-22-AA-BB-CC",
+            "001122AABBCC",
+            "001122aabbcc",
+        ] {
+            assert_eq!(
+                BluetoothAdapterAddress::parse(s),
+                Ok(BluetoothAdapterAddress::Byte(BYTES)),
+                "{s}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_rejects_invalid_strings() {
+        for s in [
+            "",
+            "00:11:22:AA:BB",
+            "00:11:22:AA:BB:CC:DD",
+            "001122AABB",
+            "001122AABBCCDD",
+            "0:11:22:AA:BB:CCC",
+            "00:11-22:AA:BB:CC",
+            "00:11:22:AA:BB:CG",
+            "00112 AABBCC",
+            "not an address",
+        ] {
+            assert!(BluetoothAdapterAddress::parse(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn display_uses_upper_case_and_colons() {
+        assert_eq!(
+            BluetoothAdapterAddress::Byte(BYTES).to_string(),
+            "00:11:22:AA:BB:CC"
+        );
+        assert_eq!(
+            BluetoothAdapterAddress::String("00-11-22-aa-bb-cc".to_string()).to_string(),
+            "00:11:22:AA:BB:CC"
+        );
+        assert_eq!(
+            BluetoothAdapterAddress::String("invalid".to_string()).to_string(),
+            "invalid"
+        );
+    }
+
+    #[test]
+    fn round_trips_through_strings() {
+        let address = BluetoothAdapterAddress::from(BYTES);
+        let parsed: BluetoothAdapterAddress = address.to_string().parse().unwrap();
+        assert_eq!(parsed, address);
+        assert_eq!(parsed.as_bytes(), Some(BYTES));
+        let json = serde_json::to_string(&address).unwrap();
+        let from_json: BluetoothAdapterAddress = serde_json::from_str(&json).unwrap();
+        assert_eq!(from_json, address);
+    }
+
+    #[test]
+    fn byte_and_string_forms_are_equal() {
+        use std::collections::HashSet;
+        let byte = BluetoothAdapterAddress::Byte(BYTES);
+        let set: HashSet<BluetoothAdapterAddress> = [
+            BluetoothAdapterAddress::Byte(BYTES),
+            BluetoothAdapterAddress::String("00:11:22:AA:BB:CC".to_string()),
+            BluetoothAdapterAddress::String("00-11-22-aa-bb-cc".to_string()),
+            BluetoothAdapterAddress::String("001122aabbcc".to_string()),
+        ]
+        .into_iter()
+        .inspect(|a| assert_eq!(*a, byte, "{a:?}"))
+        .collect();
+        assert_eq!(set.len(), 1);
+        assert_eq!(
+            BluetoothAdapterAddress::String("00:11:22:aa:bb:cc".to_string()).normalized(),
+            BluetoothAdapterAddress::Byte(BYTES)
+        );
+    }
+
+    #[test]
+    fn invalid_string_addresses_only_equal_themselves() {
+        let invalid = BluetoothAdapterAddress::String("00:11:22:AA:BB".to_string());
+        assert_eq!(invalid.as_bytes(), None);
+        assert_eq!(invalid, invalid.clone());
+        assert_ne!(invalid, BluetoothAdapterAddress::Byte(BYTES));
+        assert!(matches!(
+            invalid.clone().normalized(),
+            BluetoothAdapterAddress::String(s) if s == "00:11:22:AA:BB"
+        ));
+    }
+}
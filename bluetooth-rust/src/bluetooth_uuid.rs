@@ -1,16 +1,32 @@
 //! UUID stuff for android bluetooth
 
-#[cfg(target_os = "android")]
-use super::android::Java;
-#[cfg(target_os = "android")]
-use super::android::jerr;
 #[cfg(target_os = "android")]
 use jni_min_helper::*;
-#[cfg(target_os = "android")]
-use std::sync::{Arc, Mutex};
+
+/// The error returned when a string is not a valid bluetooth uuid
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UuidParseError(String);
+
+impl std::fmt::Display for UuidParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid bluetooth uuid: {}", self.0)
+    }
+}
+
+impl std::error::Error for UuidParseError {}
+
+/// A uuid that does not have a dedicated variant in `BluetoothUuid`. It can only be created from a valid uuid, with
+/// `BluetoothUuid::custom` or one of the other constructors.
+#[derive(Clone, Debug)]
+pub struct CustomUuid {
+    /// The 128-bit value
+    value: u128,
+    /// The canonical string form of the value
+    text: String,
+}
 
 /// Represents the uuid for a bluetooth service
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub enum BluetoothUuid {
     /// Android auto
     AndroidAuto,
@@ -46,8 +62,28 @@ pub enum BluetoothUuid {
     AvrcpRemote,
     /// Network nap protocol for bluetooth networking
     NetworkingNap,
-    /// An unknown bluetooth uuid
-    Unknown(String),
+    /// Human interface device
+    Hid,
+    /// Human interface device over gatt, for low energy devices
+    HidOverGatt,
+    /// Obex pce protocol, the client side of phonebook access
+    ObexPce,
+    /// Obex map protocol, message access
+    ObexMap,
+    /// Dial up networking
+    Dun,
+    /// Sim access protocol
+    Sap,
+    /// Network panu protocol, a user of a personal area network
+    NetworkingPanu,
+    /// Network gn protocol, a group ad-hoc network
+    NetworkingGn,
+    /// Gatt battery service
+    BatteryService,
+    /// Gatt device information service
+    DeviceInformation,
+    /// Any other bluetooth uuid, such as a vendor specific service
+    Custom(CustomUuid),
 }
 
 impl BluetoothUuid {
@@ -71,14 +107,24 @@ impl BluetoothUuid {
             BluetoothUuid::HspHs => 0x1108,
             BluetoothUuid::HspAg => 0x1112,
             BluetoothUuid::AndroidAuto => 0x7a00,
-            BluetoothUuid::Unknown(s) => u16::from_str_radix(&s[4..8], 16).unwrap(),
+            BluetoothUuid::Hid => 0x1124,
+            BluetoothUuid::HidOverGatt => 0x1812,
+            BluetoothUuid::ObexPce => 0x112e,
+            BluetoothUuid::ObexMap => 0x1134,
+            BluetoothUuid::Dun => 0x1103,
+            BluetoothUuid::Sap => 0x112d,
+            BluetoothUuid::NetworkingPanu => 0x1115,
+            BluetoothUuid::NetworkingGn => 0x1117,
+            BluetoothUuid::BatteryService => 0x180f,
+            BluetoothUuid::DeviceInformation => 0x180a,
+            BluetoothUuid::Custom(c) => (c.value >> 96) as u16,
         }
     }
 
     /// Get the uuid as a str reference
     pub fn as_str(&self) -> &str {
         match self {
-            BluetoothUuid::SPP => "00001101-0000-1000-8000-00805F9B34FB",
+            BluetoothUuid::SPP => "00001101-0000-1000-8000-00805f9b34fb",
             BluetoothUuid::A2dpSource => "0000110a-0000-1000-8000-00805f9b34fb",
             BluetoothUuid::HfpHs => "0000111e-0000-1000-8000-00805f9b34fb",
             BluetoothUuid::ObexOpp => "00001105-0000-1000-8000-00805f9b34fb",
@@ -95,81 +141,650 @@ impl BluetoothUuid {
             BluetoothUuid::HspHs => "00001108-0000-1000-8000-00805f9b34fb",
             BluetoothUuid::HspAg => "00001112-0000-1000-8000-00805f9b34fb",
             BluetoothUuid::AndroidAuto => "4de17a00-52cb-11e6-bdf4-0800200c9a66",
-            BluetoothUuid::Unknown(s) => s,
+            BluetoothUuid::Hid => "00001124-0000-1000-8000-00805f9b34fb",
+            BluetoothUuid::HidOverGatt => "00001812-0000-1000-8000-00805f9b34fb",
+            BluetoothUuid::ObexPce => "0000112e-0000-1000-8000-00805f9b34fb",
+            BluetoothUuid::ObexMap => "00001134-0000-1000-8000-00805f9b34fb",
+            BluetoothUuid::Dun => "00001103-0000-1000-8000-00805f9b34fb",
+            BluetoothUuid::Sap => "0000112d-0000-1000-8000-00805f9b34fb",
+            BluetoothUuid::NetworkingPanu => "00001115-0000-1000-8000-00805f9b34fb",
+            BluetoothUuid::NetworkingGn => "00001117-0000-1000-8000-00805f9b34fb",
+            BluetoothUuid::BatteryService => "0000180f-0000-1000-8000-00805f9b34fb",
+            BluetoothUuid::DeviceInformation => "0000180a-0000-1000-8000-00805f9b34fb",
+            BluetoothUuid::Custom(c) => &c.text,
         }
     }
 }
 
 impl std::str::FromStr for BluetoothUuid {
-    type Err = ();
+    type Err = UuidParseError;
+    /// Parses a uuid regardless of case, surrounding braces or missing dashes. A bare 16-bit or 32-bit id like
+    /// `1101` or `0x1101` is expanded against the bluetooth base uuid.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s {
-            "00001101-0000-1000-8000-00805F9B34FB" => BluetoothUuid::SPP,
-            "0000110a-0000-1000-8000-00805f9b34fb" => BluetoothUuid::A2dpSource,
-            "0000111e-0000-1000-8000-00805f9b34fb" => BluetoothUuid::HfpHs,
-            "00001105-0000-1000-8000-00805f9b34fb" => BluetoothUuid::ObexOpp,
-            "00001106-0000-1000-8000-00805f9b34fb" => BluetoothUuid::ObexFtp,
-            "00001104-0000-1000-8000-00805f9b34fb" => BluetoothUuid::ObexSync,
-            "0000110b-0000-1000-8000-00805f9b34fb" => BluetoothUuid::A2dpSink,
-            "0000110e-0000-1000-8000-00805f9b34fb" => BluetoothUuid::AvrcpRemote,
-            "0000112f-0000-1000-8000-00805f9b34fb" => BluetoothUuid::ObexPse,
-            "0000111f-0000-1000-8000-00805f9b34fb" => BluetoothUuid::HfpAg,
-            "00001132-0000-1000-8000-00805f9b34fb" => BluetoothUuid::ObexMas,
-            "00001133-0000-1000-8000-00805f9b34fb" => BluetoothUuid::ObexMns,
-            "00000000-0000-1000-8000-00805f9b34fb" => BluetoothUuid::Base,
-            "00001116-0000-1000-8000-00805f9b34fb" => BluetoothUuid::NetworkingNap,
-            "00001108-0000-1000-8000-00805f9b34fb" => BluetoothUuid::HspHs,
-            "00001112-0000-1000-8000-00805f9b34fb" => BluetoothUuid::HspAg,
-            "4de17a00-52cb-11e6-bdf4-0800200c9a66" => BluetoothUuid::AndroidAuto,
-            _ => BluetoothUuid::Unknown(s.to_string()),
+        parse_uuid(s)
+            .map(Self::from_u128)
+            .ok_or_else(|| UuidParseError(s.to_string()))
+    }
+}
+
+/// The general kind of service a uuid represents
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ServiceCategory {
+    /// Audio streaming, remote control, headset and handsfree profiles
+    Audio,
+    /// Personal area networking and dial up networking
+    Networking,
+    /// Serial port emulation
+    Serial,
+    /// Obex based transfer of files, objects, phonebooks and messages
+    ObjectTransfer,
+    /// Keyboards, mice, gamepads and other input devices
+    Input,
+    /// Sim card access
+    Telephony,
+    /// Gatt services describing the device, such as its battery level
+    DeviceInformation,
+    /// Anything else, including custom uuids
+    Other,
+}
+
+/// What is known about a uuid, as returned by `identify`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceInfo {
+    /// The uuid
+    pub uuid: BluetoothUuid,
+    /// The 16-bit assigned number, when the uuid is derived from the bluetooth base uuid
+    pub short: Option<u16>,
+    /// A human readable name for the service
+    pub name: Option<&'static str>,
+    /// The kind of service
+    pub category: ServiceCategory,
+}
+
+/// Identify the service for a uuid string. Any form accepted by `BluetoothUuid::from_str` can be used.
+pub fn identify(uuid_str: &str) -> Result<ServiceInfo, UuidParseError> {
+    use std::str::FromStr;
+    let uuid = BluetoothUuid::from_str(uuid_str)?;
+    Ok(ServiceInfo {
+        short: uuid.as_u16(),
+        name: uuid.description(),
+        category: uuid.category(),
+        uuid,
+    })
+}
+
+/// The bluetooth base uuid, 16-bit and 32-bit ids are shorthand for a uuid derived from this one
+const BASE_UUID: u128 = 0x00000000_0000_1000_8000_00805f9b34fb;
+
+/// The bits of a uuid that are not part of a 16-bit or 32-bit id
+const BASE_UUID_MASK: u128 = (1 << 96) - 1;
+
+/// Parse a 128-bit uuid, accepting any case, optional surrounding braces and optional dashes. A 16-bit or 32-bit id,
+/// with or without a 0x prefix, is expanded against the base uuid.
+fn parse_uuid(s: &str) -> Option<u128> {
+    let s = s.trim();
+    let short = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    if (short.len() == 4 || short.len() == 8) && short.chars().all(|c| c.is_ascii_hexdigit()) {
+        return u32::from_str_radix(short, 16)
+            .ok()
+            .map(|id| ((id as u128) << 96) | BASE_UUID);
+    }
+    let s = s
+        .strip_prefix('{')
+        .and_then(|s| s.strip_suffix('}'))
+        .unwrap_or(s);
+    let hex: String = s.chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u128::from_str_radix(&hex, 16).ok()
+}
+
+/// Format a 128-bit uuid in the canonical lowercase form with dashes
+fn format_uuid(value: u128) -> String {
+    let hex = format!("{:032x}", value);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+impl BluetoothUuid {
+    /// All of the uuids that have a dedicated variant
+    const KNOWN: [BluetoothUuid; 27] = [
+        BluetoothUuid::AndroidAuto,
+        BluetoothUuid::SPP,
+        BluetoothUuid::A2dpSource,
+        BluetoothUuid::A2dpSink,
+        BluetoothUuid::Base,
+        BluetoothUuid::HspHs,
+        BluetoothUuid::HspAg,
+        BluetoothUuid::HfpAg,
+        BluetoothUuid::HfpHs,
+        BluetoothUuid::ObexOpp,
+        BluetoothUuid::ObexFtp,
+        BluetoothUuid::ObexMas,
+        BluetoothUuid::ObexMns,
+        BluetoothUuid::ObexPse,
+        BluetoothUuid::ObexSync,
+        BluetoothUuid::AvrcpRemote,
+        BluetoothUuid::NetworkingNap,
+        BluetoothUuid::Hid,
+        BluetoothUuid::HidOverGatt,
+        BluetoothUuid::ObexPce,
+        BluetoothUuid::ObexMap,
+        BluetoothUuid::Dun,
+        BluetoothUuid::Sap,
+        BluetoothUuid::NetworkingPanu,
+        BluetoothUuid::NetworkingGn,
+        BluetoothUuid::BatteryService,
+        BluetoothUuid::DeviceInformation,
+    ];
+
+    /// Build a uuid from its 128-bit value, using the dedicated variant when there is one
+    pub fn from_u128(value: u128) -> Self {
+        Self::KNOWN
+            .iter()
+            .find(|u| u.as_u128() == value)
+            .cloned()
+            .unwrap_or_else(|| {
+                BluetoothUuid::Custom(CustomUuid {
+                    value,
+                    text: format_uuid(value),
+                })
+            })
+    }
+
+    /// Build a uuid from a string in the `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` layout, in any case. The dedicated
+    /// variant is returned when the uuid has one.
+    pub fn custom(s: &str) -> Result<Self, UuidParseError> {
+        let valid = s.len() == 36
+            && s.char_indices().all(|(i, c)| match i {
+                8 | 13 | 18 | 23 => c == '-',
+                _ => c.is_ascii_hexdigit(),
+            });
+        if !valid {
+            return Err(UuidParseError(s.to_string()));
+        }
+        parse_uuid(s)
+            .map(Self::from_u128)
+            .ok_or_else(|| UuidParseError(s.to_string()))
+    }
+
+    /// Build a uuid from its 16 bytes, most significant byte first
+    pub fn custom_from_bytes(bytes: [u8; 16]) -> Self {
+        Self::from_u128(u128::from_be_bytes(bytes))
+    }
+
+    /// Get a human readable name for the uuid, this is None for a `Custom` uuid
+    pub fn description(&self) -> Option<&'static str> {
+        Some(match self {
+            BluetoothUuid::SPP => "Serial Port Profile (SPP)",
+            BluetoothUuid::A2dpSource => "A2DP Source",
+            BluetoothUuid::HfpHs => "Hands-Free (HFP)",
+            BluetoothUuid::ObexOpp => "OBEX Object Push (OPP)",
+            BluetoothUuid::ObexFtp => "OBEX File Transfer (FTP)",
+            BluetoothUuid::ObexSync => "OBEX Synchronization (SYNC)",
+            BluetoothUuid::A2dpSink => "A2DP Sink",
+            BluetoothUuid::AvrcpRemote => "AVRCP Remote Control",
+            BluetoothUuid::ObexPse => "Phonebook Access Server (PBAP PSE)",
+            BluetoothUuid::HfpAg => "Hands-Free Audio Gateway (HFP AG)",
+            BluetoothUuid::ObexMas => "Message Access Server (MAP MAS)",
+            BluetoothUuid::ObexMns => "Message Notification Server (MAP MNS)",
+            BluetoothUuid::Base => "Bluetooth Base UUID",
+            BluetoothUuid::NetworkingNap => "NAP (Network Access Point)",
+            BluetoothUuid::HspHs => "Headset (HSP)",
+            BluetoothUuid::HspAg => "Headset Audio Gateway (HSP AG)",
+            BluetoothUuid::AndroidAuto => "Android Auto",
+            BluetoothUuid::Hid => "Human Interface Device (HID)",
+            BluetoothUuid::HidOverGatt => "HID over GATT (HOGP)",
+            BluetoothUuid::ObexPce => "Phonebook Access Client (PBAP PCE)",
+            BluetoothUuid::ObexMap => "Message Access Profile (MAP)",
+            BluetoothUuid::Dun => "Dial-up Networking (DUN)",
+            BluetoothUuid::Sap => "SIM Access (SAP)",
+            BluetoothUuid::NetworkingPanu => "PANU (Personal Area Network User)",
+            BluetoothUuid::NetworkingGn => "GN (Group Ad-hoc Network)",
+            BluetoothUuid::BatteryService => "Battery Service",
+            BluetoothUuid::DeviceInformation => "Device Information",
+            BluetoothUuid::Custom(_) => return None,
         })
     }
+
+    /// Get the kind of service the uuid represents
+    pub fn category(&self) -> ServiceCategory {
+        match self {
+            BluetoothUuid::A2dpSource
+            | BluetoothUuid::A2dpSink
+            | BluetoothUuid::AvrcpRemote
+            | BluetoothUuid::HspHs
+            | BluetoothUuid::HspAg
+            | BluetoothUuid::HfpHs
+            | BluetoothUuid::HfpAg => ServiceCategory::Audio,
+            BluetoothUuid::NetworkingNap
+            | BluetoothUuid::NetworkingPanu
+            | BluetoothUuid::NetworkingGn
+            | BluetoothUuid::Dun => ServiceCategory::Networking,
+            BluetoothUuid::SPP => ServiceCategory::Serial,
+            BluetoothUuid::ObexOpp
+            | BluetoothUuid::ObexFtp
+            | BluetoothUuid::ObexSync
+            | BluetoothUuid::ObexPse
+            | BluetoothUuid::ObexPce
+            | BluetoothUuid::ObexMas
+            | BluetoothUuid::ObexMns
+            | BluetoothUuid::ObexMap => ServiceCategory::ObjectTransfer,
+            BluetoothUuid::Hid | BluetoothUuid::HidOverGatt => ServiceCategory::Input,
+            BluetoothUuid::Sap => ServiceCategory::Telephony,
+            BluetoothUuid::BatteryService | BluetoothUuid::DeviceInformation => {
+                ServiceCategory::DeviceInformation
+            }
+            BluetoothUuid::Base | BluetoothUuid::AndroidAuto | BluetoothUuid::Custom(_) => {
+                ServiceCategory::Other
+            }
+        }
+    }
+
+    /// Build a uuid from a 16-bit id, expanded against the bluetooth base uuid
+    pub fn from_u16(id: u16) -> Self {
+        Self::from_u32(id as u32)
+    }
+
+    /// Build a uuid from a 32-bit id, expanded against the bluetooth base uuid
+    pub fn from_u32(id: u32) -> Self {
+        Self::from_u128(((id as u128) << 96) | BASE_UUID)
+    }
+
+    /// Get the 16-bit id when the uuid is derived from the bluetooth base uuid
+    pub fn as_u16(&self) -> Option<u16> {
+        let value = self.as_u128();
+        if value & BASE_UUID_MASK != BASE_UUID {
+            return None;
+        }
+        u16::try_from(value >> 96).ok()
+    }
+
+    /// Get the 128-bit value of the uuid
+    pub fn as_u128(&self) -> u128 {
+        match self {
+            BluetoothUuid::SPP => 0x00001101_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::A2dpSource => 0x0000110a_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::HfpHs => 0x0000111e_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::ObexOpp => 0x00001105_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::ObexFtp => 0x00001106_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::ObexSync => 0x00001104_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::A2dpSink => 0x0000110b_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::AvrcpRemote => 0x0000110e_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::ObexPse => 0x0000112f_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::HfpAg => 0x0000111f_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::ObexMas => 0x00001132_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::ObexMns => 0x00001133_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::Base => 0x00000000_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::NetworkingNap => 0x00001116_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::HspHs => 0x00001108_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::HspAg => 0x00001112_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::AndroidAuto => 0x4de17a00_52cb_11e6_bdf4_0800200c9a66,
+            BluetoothUuid::Hid => 0x00001124_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::HidOverGatt => 0x00001812_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::ObexPce => 0x0000112e_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::ObexMap => 0x00001134_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::Dun => 0x00001103_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::Sap => 0x0000112d_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::NetworkingPanu => 0x00001115_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::NetworkingGn => 0x00001117_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::BatteryService => 0x0000180f_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::DeviceInformation => 0x0000180a_0000_1000_8000_00805f9b34fb,
+            BluetoothUuid::Custom(c) => c.value,
+        }
+    }
+}
+
+impl PartialEq for BluetoothUuid {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_u128() == other.as_u128()
+    }
+}
+
+impl Eq for BluetoothUuid {}
+
+impl PartialOrd for BluetoothUuid {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BluetoothUuid {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_u128().cmp(&other.as_u128())
+    }
+}
+
+impl std::hash::Hash for BluetoothUuid {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.as_u128().hash(state);
+    }
+}
+
+/// Uuids are displayed as their canonical string, use `description` for a human readable name
+impl std::fmt::Display for BluetoothUuid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Uuids are serialized as their canonical string
+impl serde::Serialize for BluetoothUuid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for BluetoothUuid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use std::str::FromStr;
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl From<uuid::Uuid> for BluetoothUuid {
+    fn from(value: uuid::Uuid) -> Self {
+        Self::from_u128(value.as_u128())
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl From<&BluetoothUuid> for uuid::Uuid {
+    fn from(value: &BluetoothUuid) -> Self {
+        uuid::Uuid::from_u128(value.as_u128())
+    }
+}
+
+/// Read a java.util.UUID object
+#[cfg(target_os = "android")]
+pub(crate) fn java_uuid(
+    env: &mut jni::JNIEnv,
+    uuid: &jni::objects::JObject,
+) -> Result<BluetoothUuid, jni::errors::Error> {
+    let most = env
+        .call_method(uuid, "getMostSignificantBits", "()J", &[])?
+        .j()?;
+    let least = env
+        .call_method(uuid, "getLeastSignificantBits", "()J", &[])?
+        .j()?;
+    Ok(BluetoothUuid::from_u128(
+        ((most as u64 as u128) << 64) | least as u64 as u128,
+    ))
 }
 
 #[cfg(target_os = "android")]
 impl From<ParcelUuid> for BluetoothUuid {
     fn from(value: ParcelUuid) -> Self {
-        use std::str::FromStr;
-        BluetoothUuid::from_str(&value.to_string().unwrap()).unwrap()
+        value.uuid
     }
 }
 
+/// An android ParcelUuid. The uuid is read when it is created, so using it does not call into java.
 #[cfg(target_os = "android")]
 pub struct ParcelUuid {
-    internal: jni::objects::GlobalRef,
-    java: Arc<Mutex<Java>>,
+    /// The uuid held by the ParcelUuid
+    uuid: BluetoothUuid,
 }
 
 #[cfg(target_os = "android")]
 impl std::fmt::Display for ParcelUuid {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self.to_string() {
-            Ok(s) => f.write_str(&s),
-            Err(e) => f.write_str(&format!("ERR: {}", e)),
-        }
+        std::fmt::Display::fmt(&self.uuid, f)
     }
 }
 
 #[cfg(target_os = "android")]
 impl ParcelUuid {
-    pub fn new(uuid: jni::objects::GlobalRef, java: Arc<Mutex<Java>>) -> Self {
-        Self {
-            internal: uuid,
-            java,
+    /// Read the uuid of a ParcelUuid object
+    pub fn new(
+        env: &mut jni::JNIEnv,
+        uuid: &jni::objects::JObject,
+    ) -> Result<Self, jni::errors::Error> {
+        let uuid = env
+            .call_method(uuid, "getUuid", "()Ljava/util/UUID;", &[])
+            .get_object(env)?;
+        Ok(Self {
+            uuid: java_uuid(env, &uuid)?,
+        })
+    }
+
+    /// Get the uuid as a str reference
+    pub fn as_str(&self) -> &str {
+        self.uuid.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn parse_ignores_case() {
+        for s in [
+            "00001101-0000-1000-8000-00805f9b34fb",
+            "00001101-0000-1000-8000-00805F9B34FB",
+            "00001101-0000-1000-8000-00805f9B34Fb",
+        ] {
+            assert_eq!(BluetoothUuid::from_str(s), Ok(BluetoothUuid::SPP), "{s}");
+        }
+        let custom = BluetoothUuid::from_str("ABCDEF01-2345-6789-ABCD-EF0123456789").unwrap();
+        assert_eq!(custom.as_str(), "abcdef01-2345-6789-abcd-ef0123456789");
+    }
+
+    #[test]
+    fn parse_accepts_braces_and_missing_dashes() {
+        for s in [
+            "{00001101-0000-1000-8000-00805f9b34fb}",
+            "0000110100001000800000805f9b34fb",
+            "{0000110100001000800000805F9B34FB}",
+            " 00001101-0000-1000-8000-00805f9b34fb ",
+        ] {
+            assert_eq!(BluetoothUuid::from_str(s), Ok(BluetoothUuid::SPP), "{s}");
+        }
+    }
+
+    #[test]
+    fn parse_expands_short_ids() {
+        for s in ["1101", "0x1101", "0X1101", "00001101"] {
+            assert_eq!(BluetoothUuid::from_str(s), Ok(BluetoothUuid::SPP), "{s}");
         }
+        let long = BluetoothUuid::from_str("0x12345678").unwrap();
+        assert_eq!(long.as_str(), "12345678-0000-1000-8000-00805f9b34fb");
     }
 
-    pub fn to_string(&self) -> Result<String, std::io::Error> {
-        let mut java = self.java.lock().unwrap();
-        java.use_env(|env, _context| {
-            let dev_name = env
-                .call_method(&self.internal, "toString", "()Ljava/lang/String;", &[])
-                .get_object(env)
-                .map_err(|e| jerr(env, e))?;
-            if dev_name.is_null() {
-                return Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+    #[test]
+    fn short_ids_round_trip() {
+        assert_eq!(BluetoothUuid::from_u16(0x1101), BluetoothUuid::SPP);
+        assert_eq!(
+            BluetoothUuid::from_u16(0x180f),
+            BluetoothUuid::BatteryService
+        );
+        assert_eq!(BluetoothUuid::SPP.as_u16(), Some(0x1101));
+        let custom = BluetoothUuid::from_u16(0xfeed);
+        assert!(matches!(custom, BluetoothUuid::Custom(_)));
+        assert_eq!(custom.as_u16(), Some(0xfeed));
+        let long = BluetoothUuid::from_u32(0x12345678);
+        assert_eq!(long.as_u128() >> 96, 0x12345678);
+        assert_eq!(long.as_u16(), None);
+        assert_eq!(BluetoothUuid::from_u32(0x1101), BluetoothUuid::SPP);
+    }
+
+    #[test]
+    fn as_u16_is_none_off_the_base_uuid() {
+        assert_eq!(BluetoothUuid::AndroidAuto.as_u16(), None);
+        let custom = BluetoothUuid::from_str("00001101-0000-1000-8000-00805f9b34fc").unwrap();
+        assert_eq!(custom.as_u16(), None);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_uuids() {
+        for s in [
+            "",
+            "00001101-0000-1000-8000-00805f9b34f",
+            "00001101-0000-1000-8000-00805f9b34fbb",
+            "00001101-0000-1000-8000-00805f9b34fg",
+            "{00001101-0000-1000-8000-00805f9b34fb",
+            "110",
+        ] {
+            assert!(BluetoothUuid::from_str(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn equal_values_compare_and_hash_equal() {
+        use std::collections::HashSet;
+        let parsed = BluetoothUuid::from_str("0000110100001000800000805F9B34FB").unwrap();
+        let custom = BluetoothUuid::Custom(CustomUuid {
+            value: BluetoothUuid::SPP.as_u128(),
+            text: format_uuid(BluetoothUuid::SPP.as_u128()),
+        });
+        assert_eq!(parsed, BluetoothUuid::SPP);
+        assert_eq!(custom, BluetoothUuid::SPP);
+        assert_eq!(custom.cmp(&BluetoothUuid::SPP), std::cmp::Ordering::Equal);
+        let set: HashSet<BluetoothUuid> =
+            [parsed, custom, BluetoothUuid::SPP].into_iter().collect();
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn every_variant_parses_back_to_itself() {
+        let variants = [
+            BluetoothUuid::AndroidAuto,
+            BluetoothUuid::SPP,
+            BluetoothUuid::A2dpSource,
+            BluetoothUuid::A2dpSink,
+            BluetoothUuid::Base,
+            BluetoothUuid::HspHs,
+            BluetoothUuid::HspAg,
+            BluetoothUuid::HfpAg,
+            BluetoothUuid::HfpHs,
+            BluetoothUuid::ObexOpp,
+            BluetoothUuid::ObexFtp,
+            BluetoothUuid::ObexMas,
+            BluetoothUuid::ObexMns,
+            BluetoothUuid::ObexPse,
+            BluetoothUuid::ObexSync,
+            BluetoothUuid::AvrcpRemote,
+            BluetoothUuid::NetworkingNap,
+            BluetoothUuid::Hid,
+            BluetoothUuid::HidOverGatt,
+            BluetoothUuid::ObexPce,
+            BluetoothUuid::ObexMap,
+            BluetoothUuid::Dun,
+            BluetoothUuid::Sap,
+            BluetoothUuid::NetworkingPanu,
+            BluetoothUuid::NetworkingGn,
+            BluetoothUuid::BatteryService,
+            BluetoothUuid::DeviceInformation,
+        ];
+        for u in &variants {
+            // A new variant makes this match fail to compile, so that it is added to the list above
+            match u {
+                BluetoothUuid::AndroidAuto
+                | BluetoothUuid::SPP
+                | BluetoothUuid::A2dpSource
+                | BluetoothUuid::A2dpSink
+                | BluetoothUuid::Base
+                | BluetoothUuid::HspHs
+                | BluetoothUuid::HspAg
+                | BluetoothUuid::HfpAg
+                | BluetoothUuid::HfpHs
+                | BluetoothUuid::ObexOpp
+                | BluetoothUuid::ObexFtp
+                | BluetoothUuid::ObexMas
+                | BluetoothUuid::ObexMns
+                | BluetoothUuid::ObexPse
+                | BluetoothUuid::ObexSync
+                | BluetoothUuid::AvrcpRemote
+                | BluetoothUuid::NetworkingNap
+                | BluetoothUuid::Hid
+                | BluetoothUuid::HidOverGatt
+                | BluetoothUuid::ObexPce
+                | BluetoothUuid::ObexMap
+                | BluetoothUuid::Dun
+                | BluetoothUuid::Sap
+                | BluetoothUuid::NetworkingPanu
+                | BluetoothUuid::NetworkingGn
+                | BluetoothUuid::BatteryService
+                | BluetoothUuid::DeviceInformation => {}
+                BluetoothUuid::Custom(_) => unreachable!(),
             }
-            dev_name.get_string(env).map_err(|e| jerr(env, e))
-        })
+            let parsed = BluetoothUuid::from_str(u.as_str()).unwrap();
+            assert_eq!(
+                std::mem::discriminant(&parsed),
+                std::mem::discriminant(u),
+                "{u} is missing from BluetoothUuid::KNOWN"
+            );
+            assert_eq!(parsed.as_str(), u.as_str());
+            assert_eq!(parse_uuid(u.as_str()), Some(u.as_u128()), "{u}");
+        }
+        assert_eq!(variants.len(), BluetoothUuid::KNOWN.len());
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let uuids = [
+            BluetoothUuid::SPP,
+            BluetoothUuid::from_str("abcdef01-2345-6789-abcd-ef0123456789").unwrap(),
+        ];
+        for u in uuids {
+            let json = serde_json::to_string(&u).unwrap();
+            assert_eq!(json, format!("\"{}\"", u.as_str()));
+            let from_json: BluetoothUuid = serde_json::from_str(&json).unwrap();
+            assert_eq!(from_json, u);
+            assert_eq!(from_json.as_str(), u.as_str());
+
+            let bytes = bincode::serialize(&u).unwrap();
+            let from_bincode: BluetoothUuid = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(from_bincode, u);
+            assert_eq!(from_bincode.as_str(), u.as_str());
+        }
+        let mixed: BluetoothUuid =
+            serde_json::from_str("\"{00001101-0000-1000-8000-00805F9B34FB}\"").unwrap();
+        assert!(matches!(mixed, BluetoothUuid::SPP));
+    }
+
+    #[test]
+    fn serde_rejects_invalid_uuids() {
+        assert!(serde_json::from_str::<BluetoothUuid>("\"not a uuid\"").is_err());
+        assert!(serde_json::from_str::<BluetoothUuid>("1101").is_err());
+        let bytes = bincode::serialize("00001101-0000-1000-8000-00805f9b34").unwrap();
+        assert!(bincode::deserialize::<BluetoothUuid>(&bytes).is_err());
+    }
+}
+
+#[cfg(all(test, feature = "uuid"))]
+mod uuid_tests {
+    use super::*;
+
+    #[test]
+    fn spp_matches_uuid_in_both_directions() {
+        let spp = uuid::Uuid::parse_str("00001101-0000-1000-8000-00805f9b34fb").unwrap();
+        let from_uuid = BluetoothUuid::from(spp);
+        assert!(matches!(from_uuid, BluetoothUuid::SPP));
+        assert_eq!(from_uuid, BluetoothUuid::SPP);
+        assert_eq!(uuid::Uuid::from(&BluetoothUuid::SPP), spp);
+        assert_eq!(uuid::Uuid::from(&from_uuid), spp);
+    }
+
+    #[test]
+    fn custom_uuid_round_trips() {
+        let value = uuid::Uuid::parse_str("ABCDEF01-2345-6789-ABCD-EF0123456789").unwrap();
+        let custom = BluetoothUuid::from(value);
+        assert_eq!(custom.as_str(), "abcdef01-2345-6789-abcd-ef0123456789");
+        assert_eq!(uuid::Uuid::from(&custom), value);
     }
 }
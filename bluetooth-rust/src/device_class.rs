@@ -0,0 +1,301 @@
+//! Decoding of the bluetooth class of device
+
+/// The major class of a bluetooth device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MajorDeviceClass {
+    /// Miscellaneous device
+    Miscellaneous,
+    /// Desktop, laptop, pda and similar
+    Computer,
+    /// Cellular, cordless and similar phones
+    Phone,
+    /// Network access point
+    NetworkAccessPoint,
+    /// Headsets, speakers, car audio and similar
+    AudioVideo,
+    /// Mouse, keyboard, joystick and similar
+    Peripheral,
+    /// Printers, scanners, cameras and displays
+    Imaging,
+    /// Watches, glasses and similar
+    Wearable,
+    /// Toys and games
+    Toy,
+    /// Health devices
+    Health,
+    /// The device did not specify a class
+    Uncategorized,
+    /// A major class reserved by the specification
+    Reserved(u8),
+}
+
+impl From<u8> for MajorDeviceClass {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Miscellaneous,
+            1 => Self::Computer,
+            2 => Self::Phone,
+            3 => Self::NetworkAccessPoint,
+            4 => Self::AudioVideo,
+            5 => Self::Peripheral,
+            6 => Self::Imaging,
+            7 => Self::Wearable,
+            8 => Self::Toy,
+            9 => Self::Health,
+            0x1f => Self::Uncategorized,
+            v => Self::Reserved(v),
+        }
+    }
+}
+
+/// The minor class of a bluetooth device. The meaning depends on the major class, so only the common
+/// ones are decoded, the rest are kept as the raw value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MinorDeviceClass {
+    /// A cellular phone
+    Cellular,
+    /// A cordless phone
+    Cordless,
+    /// A smartphone
+    Smartphone,
+    /// A desktop computer
+    Desktop,
+    /// A laptop computer
+    Laptop,
+    /// A wearable headset
+    Headset,
+    /// A handsfree device
+    Handsfree,
+    /// A loudspeaker
+    Loudspeaker,
+    /// Headphones
+    Headphones,
+    /// Car audio
+    CarAudio,
+    /// A minor class that is not decoded
+    Other(u8),
+}
+
+impl MinorDeviceClass {
+    /// Decode the minor class for the given major class
+    fn new(major: MajorDeviceClass, minor: u8) -> Self {
+        match (major, minor) {
+            (MajorDeviceClass::Phone, 1) => Self::Cellular,
+            (MajorDeviceClass::Phone, 2) => Self::Cordless,
+            (MajorDeviceClass::Phone, 3) => Self::Smartphone,
+            (MajorDeviceClass::Computer, 1) => Self::Desktop,
+            (MajorDeviceClass::Computer, 3) => Self::Laptop,
+            (MajorDeviceClass::AudioVideo, 1) => Self::Headset,
+            (MajorDeviceClass::AudioVideo, 2) => Self::Handsfree,
+            (MajorDeviceClass::AudioVideo, 5) => Self::Loudspeaker,
+            (MajorDeviceClass::AudioVideo, 6) => Self::Headphones,
+            (MajorDeviceClass::AudioVideo, 8) => Self::CarAudio,
+            (_, v) => Self::Other(v),
+        }
+    }
+}
+
+/// The major service classes a bluetooth device can advertise in its class of device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServiceClass {
+    /// The device is in limited discoverable mode
+    LimitedDiscoverable,
+    /// Location identification
+    Positioning,
+    /// LAN and ad hoc networking
+    Networking,
+    /// Printing and speakers
+    Rendering,
+    /// Scanning and microphones
+    Capturing,
+    /// File transfer and similar
+    ObjectTransfer,
+    /// Speakers, microphones and headsets
+    Audio,
+    /// Cordless telephony and modems
+    Telephony,
+    /// Web servers and similar
+    Information,
+}
+
+impl ServiceClass {
+    /// All of the service classes
+    pub const ALL: [Self; 9] = [
+        Self::LimitedDiscoverable,
+        Self::Positioning,
+        Self::Networking,
+        Self::Rendering,
+        Self::Capturing,
+        Self::ObjectTransfer,
+        Self::Audio,
+        Self::Telephony,
+        Self::Information,
+    ];
+
+    /// The bit of the class of device for the service class
+    fn bit(&self) -> u32 {
+        match self {
+            Self::LimitedDiscoverable => 13,
+            Self::Positioning => 16,
+            Self::Networking => 17,
+            Self::Rendering => 18,
+            Self::Capturing => 19,
+            Self::ObjectTransfer => 20,
+            Self::Audio => 21,
+            Self::Telephony => 22,
+            Self::Information => 23,
+        }
+    }
+}
+
+/// The 24 bit class of device of a bluetooth device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceClass(u32);
+
+impl DeviceClass {
+    /// Build from the raw 24 bit value, the upper byte is ignored
+    pub fn new(raw: u32) -> Self {
+        Self(raw & 0xff_ffff)
+    }
+
+    /// Build from the values of android's `BluetoothClass`. `getDeviceClass` gives the major and minor class
+    /// bits, and `hashCode` gives the full class, of which only the service class bits are used.
+    pub fn from_android(device_class: i32, class_hash: i32) -> Self {
+        Self::new((class_hash as u32 & 0xff_e000) | (device_class as u32 & 0x1ffc))
+    }
+
+    /// The raw 24 bit value
+    pub fn raw(&self) -> u32 {
+        self.0
+    }
+
+    /// The major device class, from bits 8 to 12
+    pub fn major(&self) -> MajorDeviceClass {
+        (((self.0 >> 8) & 0x1f) as u8).into()
+    }
+
+    /// The minor device class, from bits 2 to 7
+    pub fn minor(&self) -> MinorDeviceClass {
+        MinorDeviceClass::new(self.major(), ((self.0 >> 2) & 0x3f) as u8)
+    }
+
+    /// The major service class bits, from bits 13 to 23
+    pub fn service_classes(&self) -> u16 {
+        (self.0 >> 13) as u16
+    }
+
+    /// Does the device advertise the service class
+    pub fn has_service(&self, service: ServiceClass) -> bool {
+        self.0 & (1 << service.bit()) != 0
+    }
+
+    /// The service classes advertised by the device
+    pub fn services(&self) -> Vec<ServiceClass> {
+        ServiceClass::ALL
+            .into_iter()
+            .filter(|s| self.has_service(*s))
+            .collect()
+    }
+
+    /// Is the device some kind of phone
+    pub fn is_phone(&self) -> bool {
+        self.major() == MajorDeviceClass::Phone
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_major_classes() {
+        let major = |raw| DeviceClass::new(raw).major();
+        assert_eq!(major(0x000100), MajorDeviceClass::Computer);
+        assert_eq!(major(0x000200), MajorDeviceClass::Phone);
+        assert_eq!(major(0x000400), MajorDeviceClass::AudioVideo);
+        assert_eq!(major(0x000900), MajorDeviceClass::Health);
+        assert_eq!(major(0x001f00), MajorDeviceClass::Uncategorized);
+        assert_eq!(major(0x000a00), MajorDeviceClass::Reserved(10));
+        assert!(DeviceClass::new(0x5a020c).is_phone());
+        assert!(!DeviceClass::new(0x240404).is_phone());
+    }
+
+    #[test]
+    fn decodes_minor_classes_for_the_major_class() {
+        let minor = |raw| DeviceClass::new(raw).minor();
+        assert_eq!(minor(0x00020c), MinorDeviceClass::Smartphone);
+        assert_eq!(minor(0x00010c), MinorDeviceClass::Laptop);
+        assert_eq!(minor(0x000418), MinorDeviceClass::Headphones);
+        // The same minor bits mean different things for each major class
+        assert_eq!(minor(0x000104), MinorDeviceClass::Desktop);
+        assert_eq!(minor(0x000204), MinorDeviceClass::Cellular);
+        assert_eq!(minor(0x000504), MinorDeviceClass::Other(1));
+    }
+
+    #[test]
+    fn decodes_service_classes() {
+        let class = DeviceClass::new(0x5a020c);
+        assert_eq!(class.service_classes(), 0x2d0);
+        assert_eq!(
+            class.services(),
+            vec![
+                ServiceClass::Networking,
+                ServiceClass::Capturing,
+                ServiceClass::ObjectTransfer,
+                ServiceClass::Telephony,
+            ]
+        );
+        assert!(!class.has_service(ServiceClass::Audio));
+        assert!(DeviceClass::new(0x002000).has_service(ServiceClass::LimitedDiscoverable));
+        assert!(DeviceClass::new(0x00020c).services().is_empty());
+    }
+
+    #[test]
+    fn decodes_android_classes() {
+        // The values of BluetoothClass.Device and BluetoothClass.Service
+        const PHONE_SMART: i32 = 0x020c;
+        const AUDIO_VIDEO_WEARABLE_HEADSET: i32 = 0x0404;
+        const AUDIO_VIDEO_CAR_AUDIO: i32 = 0x0420;
+        const SERVICE_AUDIO: i32 = 0x200000;
+        const SERVICE_TELEPHONY: i32 = 0x400000;
+
+        let phone = DeviceClass::from_android(PHONE_SMART, 0x5a020c);
+        assert_eq!(phone.major(), MajorDeviceClass::Phone);
+        assert_eq!(phone.minor(), MinorDeviceClass::Smartphone);
+        assert!(phone.has_service(ServiceClass::Telephony));
+
+        let headset = DeviceClass::from_android(
+            AUDIO_VIDEO_WEARABLE_HEADSET,
+            SERVICE_AUDIO | AUDIO_VIDEO_WEARABLE_HEADSET,
+        );
+        assert_eq!(headset.major(), MajorDeviceClass::AudioVideo);
+        assert_eq!(headset.minor(), MinorDeviceClass::Headset);
+        assert_eq!(headset.services(), vec![ServiceClass::Audio]);
+
+        let car = DeviceClass::from_android(
+            AUDIO_VIDEO_CAR_AUDIO,
+            SERVICE_AUDIO | SERVICE_TELEPHONY | AUDIO_VIDEO_CAR_AUDIO,
+        );
+        assert_eq!(car.major(), MajorDeviceClass::AudioVideo);
+        assert_eq!(car.minor(), MinorDeviceClass::CarAudio);
+        assert_eq!(
+            car.services(),
+            vec![ServiceClass::Audio, ServiceClass::Telephony]
+        );
+        assert!(!car.is_phone());
+    }
+
+    #[test]
+    fn android_class_bits_come_from_their_own_value() {
+        // The format bits and any device bits in the hash are not used
+        let class = DeviceClass::from_android(0x0404 | 0x3, 0x200000 | 0x020c);
+        assert_eq!(class.raw(), 0x200404);
+    }
+
+    #[test]
+    fn ignores_the_upper_byte() {
+        let class = DeviceClass::new(0xff5a_020c);
+        assert_eq!(class.raw(), 0x5a020c);
+        assert_eq!(class, DeviceClass::new(0x5a020c));
+    }
+}
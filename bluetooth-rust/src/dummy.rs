@@ -0,0 +1,1539 @@
+//! An in-memory loopback bluetooth backend. It needs no bluetooth radio, so application and protocol code can
+//! be run and tested on any machine. Connections made with the adapter go to the profiles registered on the
+//! same adapter, and the data travels over in-memory duplex streams.
+
+use std::sync::{Arc, Mutex};
+
+/// The size of the in-memory buffer in each direction of a connection
+const STREAM_BUFFER: usize = 64 * 1024;
+
+/// The mtu reported for l2cap connections, the default l2cap mtu
+pub(crate) const L2CAP_MTU: usize = 672;
+
+/// The first dynamically allocated psm
+const FIRST_DYNAMIC_PSM: u16 = 0x1001;
+
+/// The answer to a connection attempt, the stream for the connecting side when it is accepted
+type ConnectionAnswer = tokio::sync::oneshot::Sender<tokio::io::DuplexStream>;
+
+/// An rfcomm profile registered on the adapter
+struct RfcommListener {
+    /// The channel of the profile
+    channel: u8,
+    /// The uuid of the profile
+    uuid: crate::BluetoothUuid,
+    /// Where connections for the profile are sent
+    sender: tokio::sync::mpsc::Sender<Connectable>,
+}
+
+/// The state shared by a dummy adapter and the objects created from it
+struct State {
+    /// The address of the adapter
+    address: [u8; 6],
+    /// The name of the adapter shown to remote devices
+    alias: String,
+    /// Is the adapter turned on
+    powered: bool,
+    /// Can the adapter be discovered
+    discoverable: bool,
+    /// Can devices pair with the adapter
+    pairable: bool,
+    /// How long the adapter stays pairable
+    pairable_timeout: std::time::Duration,
+    /// The registered rfcomm profiles that accept connections
+    rfcomm: Vec<RfcommListener>,
+    /// The registered l2cap profiles, by psm
+    l2cap: std::collections::HashMap<u16, tokio::sync::mpsc::Sender<Connectable>>,
+    /// The receivers of power state changes
+    state_subscribers: Vec<tokio::sync::mpsc::Sender<crate::AdapterState>>,
+    /// The receivers of scan mode changes
+    scan_mode_subscribers: Vec<tokio::sync::mpsc::Sender<crate::ScanMode>>,
+    /// The receivers of adapter hotplug events, kept so the receivers stay open
+    hotplug_subscribers: Vec<tokio::sync::mpsc::Sender<crate::AdapterHotplugEvent>>,
+    /// The receivers of pairing events, kept so the receivers stay open
+    pairing_subscribers: Vec<tokio::sync::mpsc::Sender<crate::PairingEvent>>,
+    /// The receivers of connection events
+    connection_subscribers: Vec<tokio::sync::mpsc::Sender<crate::ConnectionEvent>>,
+}
+
+impl State {
+    /// The current scan mode
+    fn scan_mode(&self) -> crate::ScanMode {
+        if !self.powered {
+            crate::ScanMode::None
+        } else if self.discoverable {
+            crate::ScanMode::ConnectableDiscoverable
+        } else {
+            crate::ScanMode::Connectable
+        }
+    }
+
+    /// Send the event to the subscribers, forgetting the ones whose receiver is gone
+    fn notify<T: Clone>(subscribers: &mut Vec<tokio::sync::mpsc::Sender<T>>, event: T) {
+        subscribers.retain(|s| !s.is_closed());
+        for s in subscribers.iter() {
+            let _ = s.try_send(event.clone());
+        }
+    }
+}
+
+/// A bluetooth adapter that lives entirely in memory. Clones of the adapter share the same state.
+///
+/// Profiles registered on the adapter receive the connections made with `connect_rfcomm` on the same adapter,
+/// and with [`DummyAdapter::connect_l2cap`] for l2cap profiles. Both sides of the connection see the address of
+/// the adapter as the address of the peer. There are no remote devices, so discovery and scans finish without
+/// results.
+#[derive(Clone)]
+pub struct DummyAdapter {
+    /// The shared state of the adapter
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for DummyAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DummyAdapter {
+    /// Construct a new adapter with the address 00:00:00:00:00:01, turned on
+    pub fn new() -> Self {
+        Self::with_address([0, 0, 0, 0, 0, 1])
+    }
+
+    /// Construct a new adapter with the given address, turned on
+    pub fn with_address(address: [u8; 6]) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                address,
+                alias: "Dummy bluetooth adapter".to_string(),
+                powered: true,
+                discoverable: false,
+                pairable: false,
+                pairable_timeout: std::time::Duration::ZERO,
+                rfcomm: Vec::new(),
+                l2cap: std::collections::HashMap::new(),
+                state_subscribers: Vec::new(),
+                scan_mode_subscribers: Vec::new(),
+                hotplug_subscribers: Vec::new(),
+                pairing_subscribers: Vec::new(),
+                connection_subscribers: Vec::new(),
+            })),
+        }
+    }
+
+    /// The address of the adapter
+    fn address(&self) -> crate::BluetoothAdapterAddress {
+        crate::BluetoothAdapterAddress::Byte(self.state.lock().unwrap().address)
+    }
+
+    /// Fail when the adapter is turned off
+    fn check_powered(state: &State) -> Result<(), crate::BluetoothError> {
+        if state.powered {
+            Ok(())
+        } else {
+            Err(crate::BluetoothError::AdapterUnavailable)
+        }
+    }
+
+    /// Register an rfcomm profile, on the requested channel or the lowest free channel
+    fn rfcomm_profile(
+        &self,
+        settings: crate::BluetoothRfcommProfileSettings,
+    ) -> Result<RfcommProfile, crate::BluetoothError> {
+        use std::str::FromStr;
+        settings.validate()?;
+        let mut state = self.state.lock().unwrap();
+        Self::check_powered(&state)?;
+        if settings.role == Some(crate::ProfileRole::Client) {
+            // Client profiles never listen, connections are made with connect_rfcomm instead
+            return Ok(RfcommProfile {
+                listener: None,
+                state: self.state.clone(),
+            });
+        }
+        let uuid = crate::BluetoothUuid::from_str(&settings.uuid)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let used = |c: u8| state.rfcomm.iter().any(|l| l.channel == c);
+        let channel = match settings.channel {
+            Some(c) if used(c as u8) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AddrInUse,
+                    format!("The rfcomm channel {} is already in use", c),
+                )
+                .into());
+            }
+            Some(c) => c as u8,
+            None => (1..=30).find(|c| !used(*c)).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::AddrInUse,
+                    "Every rfcomm channel is in use",
+                )
+            })?,
+        };
+        let (sender, receiver) = tokio::sync::mpsc::channel(16);
+        state.rfcomm.push(RfcommListener {
+            channel,
+            uuid,
+            sender,
+        });
+        Ok(RfcommProfile {
+            listener: Some(Listener {
+                receiver,
+                key: ListenerKey::Rfcomm(channel),
+            }),
+            state: self.state.clone(),
+        })
+    }
+
+    /// Register an l2cap profile, on the requested psm or the lowest free dynamic psm
+    fn l2cap_profile(
+        &self,
+        settings: crate::BluetoothL2capProfileSettings,
+    ) -> Result<L2capProfile, crate::BluetoothError> {
+        if settings.role == Some(crate::ProfileRole::Client) {
+            // l2cap profiles only accept connections, connect_l2cap makes outgoing ones
+            return Err(crate::BluetoothError::Unsupported(
+                "A client role l2cap profile",
+            ));
+        }
+        let mut state = self.state.lock().unwrap();
+        Self::check_powered(&state)?;
+        let psm = match settings.psm {
+            Some(p) if state.l2cap.contains_key(&p) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AddrInUse,
+                    format!("The l2cap psm {:#06x} is already in use", p),
+                )
+                .into());
+            }
+            Some(p) => p,
+            None => (FIRST_DYNAMIC_PSM..=u16::MAX)
+                .step_by(2)
+                .find(|p| !state.l2cap.contains_key(p))
+                .ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::AddrInUse, "Every l2cap psm is in use")
+                })?,
+        };
+        let (sender, receiver) = tokio::sync::mpsc::channel(16);
+        state.l2cap.insert(psm, sender);
+        Ok(L2capProfile {
+            listener: Listener {
+                receiver,
+                key: ListenerKey::L2cap(psm),
+            },
+            psm,
+            state: self.state.clone(),
+        })
+    }
+
+    /// Hand a new connection to the profile listening on the key. The returned receiver gets the stream for
+    /// the connecting side when the profile accepts the connection, and is closed when it is rejected.
+    fn request_connection(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        key: ListenerKey,
+    ) -> Result<tokio::sync::oneshot::Receiver<tokio::io::DuplexStream>, std::io::Error> {
+        let (own, alias) = {
+            let state = self.state.lock().unwrap();
+            (state.address, state.alias.clone())
+        };
+        if address.as_bytes() != Some(own) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::HostUnreachable,
+                format!(
+                    "The dummy adapter can only connect to itself, not {}",
+                    address
+                ),
+            ));
+        }
+        self.connect_from(own, alias, key)
+    }
+
+    /// Hand a new connection from the peer to the profile listening on the key, see `request_connection`
+    pub(crate) fn connect_from(
+        &self,
+        peer: [u8; 6],
+        peer_name: String,
+        key: ListenerKey,
+    ) -> Result<tokio::sync::oneshot::Receiver<tokio::io::DuplexStream>, std::io::Error> {
+        let state = self.state.lock().unwrap();
+        Self::check_powered(&state)?;
+        let (sender, channel) = match &key {
+            ListenerKey::Rfcomm(c) => {
+                let listener = state.rfcomm.iter().find(|l| l.channel == *c);
+                (listener.map(|l| l.sender.clone()), *c)
+            }
+            ListenerKey::RfcommUuid(u) => {
+                let listener = state.rfcomm.iter().find(|l| &l.uuid == u);
+                (
+                    listener.map(|l| l.sender.clone()),
+                    listener.map(|l| l.channel).unwrap_or(0),
+                )
+            }
+            ListenerKey::L2cap(p) => (state.l2cap.get(p).cloned(), 0),
+        };
+        let Some(sender) = sender else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                "No profile is listening for the connection",
+            ));
+        };
+        let (answer, receiver) = tokio::sync::oneshot::channel();
+        let connectable = Connectable {
+            answer: Some(answer),
+            peer,
+            peer_name,
+            channel,
+            l2cap: matches!(key, ListenerKey::L2cap(_)),
+            connection_subscribers: state.connection_subscribers.clone(),
+        };
+        sender.try_send(connectable).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                "The profile is not accepting connections",
+            )
+        })?;
+        Ok(receiver)
+    }
+
+    /// The stream for the connecting side, once the connection is answered
+    fn answered(
+        answer: Result<tokio::io::DuplexStream, tokio::sync::oneshot::error::RecvError>,
+        l2cap: bool,
+    ) -> Result<crate::BluetoothStream, std::io::Error> {
+        match answer {
+            Ok(s) if l2cap => Ok(crate::BluetoothStream::DummyL2cap(Stream::new(s))),
+            Ok(s) => Ok(crate::BluetoothStream::Dummy(Stream::new(s))),
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                "The connection was rejected",
+            )),
+        }
+    }
+
+    /// The key for the rfcomm profile of the target
+    pub(crate) fn rfcomm_key(target: crate::RfcommTarget) -> ListenerKey {
+        match target {
+            crate::RfcommTarget::Channel(c) => ListenerKey::Rfcomm(c),
+            crate::RfcommTarget::Uuid(u) => ListenerKey::RfcommUuid(u),
+        }
+    }
+
+    /// Connect to the l2cap profile listening on the psm, waiting until the profile accepts or rejects the
+    /// connection
+    pub async fn connect_l2cap(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        psm: u16,
+    ) -> Result<crate::BluetoothStream, std::io::Error> {
+        let answer = self.request_connection(address, ListenerKey::L2cap(psm))?;
+        Self::answered(answer.await, true)
+    }
+
+    /// Turn the adapter on or off, notifying the subscribers
+    fn power(&self, powered: bool) {
+        let mut state = self.state.lock().unwrap();
+        if state.powered == powered {
+            return;
+        }
+        state.powered = powered;
+        let event = if powered {
+            crate::AdapterState::On
+        } else {
+            crate::AdapterState::Off
+        };
+        State::notify(&mut state.state_subscribers, event);
+        let mode = state.scan_mode();
+        State::notify(&mut state.scan_mode_subscribers, mode);
+    }
+
+    /// Send a pairing event to the subscribers
+    #[cfg(feature = "mock")]
+    pub(crate) fn pairing_event(&self, event: crate::PairingEvent) {
+        State::notify(&mut self.state.lock().unwrap().pairing_subscribers, event);
+    }
+
+    /// Change the discoverable property, notifying the subscribers
+    fn discoverable(&self, discoverable: bool) -> Result<bool, std::io::Error> {
+        let mut state = self.state.lock().unwrap();
+        Self::check_powered(&state)?;
+        if state.discoverable != discoverable {
+            state.discoverable = discoverable;
+            let mode = state.scan_mode();
+            State::notify(&mut state.scan_mode_subscribers, mode);
+        }
+        Ok(true)
+    }
+
+    /// Add a subscriber to the list chosen by the closure
+    fn subscribe<T>(
+        &self,
+        list: impl FnOnce(&mut State) -> &mut Vec<tokio::sync::mpsc::Sender<T>>,
+    ) -> tokio::sync::mpsc::Receiver<T> {
+        let (sender, receiver) = tokio::sync::mpsc::channel(16);
+        list(&mut self.state.lock().unwrap()).push(sender);
+        receiver
+    }
+
+    /// An lescan session that finishes without results
+    fn le_scan() -> crate::LeScanSession {
+        let (_, receiver) = tokio::sync::mpsc::channel(1);
+        crate::LeScanSession::Dummy(LeScanSession {
+            results: Some(receiver),
+        })
+    }
+
+    /// A discovery that finishes without finding any devices
+    fn discovery() -> crate::BluetoothDiscovery {
+        let (_, receiver) = tokio::sync::mpsc::channel(1);
+        let (_, finished) = tokio::sync::watch::channel(true);
+        crate::BluetoothDiscovery::Dummy(BluetoothDiscovery {
+            events: Some(receiver),
+            finished,
+        })
+    }
+
+    /// Get the adapter itself as a device, the only device the dummy adapter knows
+    fn device(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+    ) -> Result<crate::BluetoothDevice, std::io::Error> {
+        if address.as_bytes() == Some(self.state.lock().unwrap().address) {
+            Ok(crate::BluetoothDevice::Dummy(BluetoothDevice {
+                adapter: self.clone(),
+            }))
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("The dummy adapter has no device {}", address),
+            ))
+        }
+    }
+}
+
+impl crate::BluetoothAdapterTrait for DummyAdapter {
+    fn supports_async(&self) -> Option<&dyn crate::AsyncBluetoothAdapterTrait> {
+        Some(self)
+    }
+
+    fn supports_sync(&self) -> Option<&dyn crate::SyncBluetoothAdapterTrait> {
+        Some(self)
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::AsyncBluetoothAdapterTrait for DummyAdapter {
+    async fn register_rfcomm_profile(
+        &self,
+        settings: crate::BluetoothRfcommProfileSettings,
+    ) -> Result<crate::BluetoothRfcommProfileAsync, crate::BluetoothError> {
+        self.rfcomm_profile(settings)
+            .map(crate::BluetoothRfcommProfileAsync::Dummy)
+    }
+
+    async fn register_l2cap_profile(
+        &self,
+        settings: crate::BluetoothL2capProfileSettings,
+    ) -> Result<crate::BluetoothL2capProfileAsync, crate::BluetoothError> {
+        self.l2cap_profile(settings)
+            .map(crate::BluetoothL2capProfileAsync::Dummy)
+    }
+
+    async fn get_paired_devices(
+        &self,
+    ) -> Result<Vec<crate::BluetoothDevice>, crate::BluetoothError> {
+        Ok(Vec::new())
+    }
+
+    async fn start_discovery(&self) -> Result<crate::BluetoothDiscovery, crate::BluetoothError> {
+        Ok(Self::discovery())
+    }
+
+    async fn start_le_scan(
+        &self,
+        _filter: crate::LeScanFilter,
+    ) -> Result<crate::LeScanSession, crate::BluetoothError> {
+        Ok(Self::le_scan())
+    }
+
+    async fn register_hid_device(
+        &self,
+        _settings: crate::HidSettings,
+    ) -> Result<crate::HidDeviceHandle, crate::BluetoothError> {
+        Err(crate::BluetoothError::Unsupported(
+            "A hid device on the dummy adapter",
+        ))
+    }
+
+    async fn addresses(&self) -> Vec<crate::BluetoothAdapterAddress> {
+        vec![self.address()]
+    }
+
+    fn has_permission(&self, _permission: crate::BluetoothPermission) -> bool {
+        true
+    }
+
+    async fn request_permissions(
+        &self,
+        _permissions: &[crate::BluetoothPermission],
+    ) -> crate::PermissionOutcome {
+        crate::PermissionOutcome::Granted
+    }
+
+    /// The timeout is not used, the adapter stays discoverable until it is changed again
+    async fn set_discoverable(
+        &self,
+        d: bool,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<bool, crate::BluetoothError> {
+        Ok(self.discoverable(d)?)
+    }
+
+    async fn set_powered(
+        &self,
+        powered: bool,
+    ) -> Result<crate::PowerOutcome, crate::BluetoothError> {
+        self.power(powered);
+        Ok(crate::PowerOutcome::Completed)
+    }
+
+    async fn is_powered(&self) -> Result<bool, crate::BluetoothError> {
+        Ok(self.state.lock().unwrap().powered)
+    }
+
+    async fn alias(&self) -> Result<String, crate::BluetoothError> {
+        Ok(self.state.lock().unwrap().alias.clone())
+    }
+
+    async fn set_alias(&self, alias: &str) -> Result<(), crate::BluetoothError> {
+        self.state.lock().unwrap().alias = alias.to_string();
+        Ok(())
+    }
+
+    async fn set_pairable(&self, pairable: bool) -> Result<(), crate::BluetoothError> {
+        self.state.lock().unwrap().pairable = pairable;
+        Ok(())
+    }
+
+    async fn set_pairable_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<(), crate::BluetoothError> {
+        self.state.lock().unwrap().pairable_timeout = timeout;
+        Ok(())
+    }
+
+    /// There is only one dummy adapter, so no events are sent
+    async fn adapter_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::AdapterHotplugEvent>, crate::BluetoothError>
+    {
+        Ok(self.subscribe(|s| &mut s.hotplug_subscribers))
+    }
+
+    async fn adapter_state_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::AdapterState>, crate::BluetoothError> {
+        Ok(self.subscribe(|s| &mut s.state_subscribers))
+    }
+
+    async fn scan_mode(&self) -> Result<crate::ScanMode, crate::BluetoothError> {
+        Ok(self.state.lock().unwrap().scan_mode())
+    }
+
+    async fn scan_mode_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::ScanMode>, crate::BluetoothError> {
+        Ok(self.subscribe(|s| &mut s.scan_mode_subscribers))
+    }
+
+    /// There are no remote devices to pair with, so no events are sent
+    async fn pairing_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::PairingEvent>, crate::BluetoothError> {
+        Ok(self.subscribe(|s| &mut s.pairing_subscribers))
+    }
+
+    /// An event is sent when a profile accepts a connection
+    async fn connection_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::ConnectionEvent>, crate::BluetoothError> {
+        Ok(self.subscribe(|s| &mut s.connection_subscribers))
+    }
+
+    /// Only the address of the adapter itself is known, connecting to the device connects to the profiles of the
+    /// adapter
+    async fn device_by_address(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+    ) -> Result<crate::BluetoothDevice, crate::BluetoothError> {
+        Ok(self.device(address)?)
+    }
+
+    /// Connect to an rfcomm profile of the adapter, waiting until the profile accepts or rejects the connection
+    async fn connect_rfcomm(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        target: crate::RfcommTarget,
+    ) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        let answer = self.request_connection(address, Self::rfcomm_key(target))?;
+        Ok(Self::answered(answer.await, false)?)
+    }
+
+    async fn advertise(
+        &self,
+        _settings: crate::LeAdvertisementSettings,
+    ) -> Result<crate::AdvertisementHandle, crate::BluetoothError> {
+        Ok(crate::AdvertisementHandle::Dummy(crate::Dummy {}))
+    }
+
+    async fn register_gatt_service(
+        &self,
+        _service: crate::GattServiceDefinition,
+    ) -> Result<crate::GattServiceHandle, crate::BluetoothError> {
+        Ok(crate::GattServiceHandle::Dummy(crate::Dummy {}))
+    }
+}
+
+impl crate::SyncBluetoothAdapterTrait for DummyAdapter {
+    fn register_rfcomm_profile(
+        &self,
+        settings: crate::BluetoothRfcommProfileSettings,
+    ) -> Result<crate::BluetoothRfcommProfileSync, crate::BluetoothError> {
+        self.rfcomm_profile(settings)
+            .map(crate::BluetoothRfcommProfileSync::Dummy)
+    }
+
+    fn register_l2cap_profile(
+        &self,
+        settings: crate::BluetoothL2capProfileSettings,
+    ) -> Result<crate::BluetoothL2capProfileSync, crate::BluetoothError> {
+        self.l2cap_profile(settings)
+            .map(crate::BluetoothL2capProfileSync::Dummy)
+    }
+
+    fn get_paired_devices(&self) -> Result<Vec<crate::BluetoothDevice>, crate::BluetoothError> {
+        Ok(Vec::new())
+    }
+
+    fn start_discovery(&self) -> Result<crate::BluetoothDiscovery, crate::BluetoothError> {
+        Ok(Self::discovery())
+    }
+
+    fn start_le_scan(
+        &self,
+        _filter: crate::LeScanFilter,
+    ) -> Result<crate::LeScanSession, crate::BluetoothError> {
+        Ok(Self::le_scan())
+    }
+
+    fn register_hid_device(
+        &self,
+        _settings: crate::HidSettings,
+    ) -> Result<crate::HidDeviceHandle, crate::BluetoothError> {
+        Err(crate::BluetoothError::Unsupported(
+            "A hid device on the dummy adapter",
+        ))
+    }
+
+    fn addresses(&self) -> Vec<crate::BluetoothAdapterAddress> {
+        vec![self.address()]
+    }
+
+    fn has_permission(&self, _permission: crate::BluetoothPermission) -> bool {
+        true
+    }
+
+    fn request_permissions(
+        &self,
+        _permissions: &[crate::BluetoothPermission],
+    ) -> crate::PermissionRequest {
+        Box::pin(std::future::ready(crate::PermissionOutcome::Granted))
+    }
+
+    /// The timeout is not used, the adapter stays discoverable until it is changed again
+    fn set_discoverable(
+        &self,
+        d: bool,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<bool, crate::BluetoothError> {
+        Ok(self.discoverable(d)?)
+    }
+
+    fn set_powered(&self, powered: bool) -> Result<crate::PowerOutcome, crate::BluetoothError> {
+        self.power(powered);
+        Ok(crate::PowerOutcome::Completed)
+    }
+
+    fn is_powered(&self) -> Result<bool, crate::BluetoothError> {
+        Ok(self.state.lock().unwrap().powered)
+    }
+
+    fn alias(&self) -> Result<String, crate::BluetoothError> {
+        Ok(self.state.lock().unwrap().alias.clone())
+    }
+
+    fn set_alias(&self, alias: &str) -> Result<(), crate::BluetoothError> {
+        self.state.lock().unwrap().alias = alias.to_string();
+        Ok(())
+    }
+
+    fn set_pairable(&self, pairable: bool) -> Result<(), crate::BluetoothError> {
+        self.state.lock().unwrap().pairable = pairable;
+        Ok(())
+    }
+
+    fn set_pairable_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<(), crate::BluetoothError> {
+        self.state.lock().unwrap().pairable_timeout = timeout;
+        Ok(())
+    }
+
+    /// There is only one dummy adapter, so no events are sent
+    fn adapter_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::AdapterHotplugEvent>, crate::BluetoothError>
+    {
+        Ok(self.subscribe(|s| &mut s.hotplug_subscribers))
+    }
+
+    fn adapter_state_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::AdapterState>, crate::BluetoothError> {
+        Ok(self.subscribe(|s| &mut s.state_subscribers))
+    }
+
+    fn scan_mode(&self) -> Result<crate::ScanMode, crate::BluetoothError> {
+        Ok(self.state.lock().unwrap().scan_mode())
+    }
+
+    fn scan_mode_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::ScanMode>, crate::BluetoothError> {
+        Ok(self.subscribe(|s| &mut s.scan_mode_subscribers))
+    }
+
+    /// There are no remote devices to pair with, so no events are sent
+    fn pairing_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::PairingEvent>, crate::BluetoothError> {
+        Ok(self.subscribe(|s| &mut s.pairing_subscribers))
+    }
+
+    /// An event is sent when a profile accepts a connection
+    fn connection_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::ConnectionEvent>, crate::BluetoothError> {
+        Ok(self.subscribe(|s| &mut s.connection_subscribers))
+    }
+
+    /// Only the address of the adapter itself is known, connecting to the device connects to the profiles of the
+    /// adapter
+    fn device_by_address(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+    ) -> Result<crate::BluetoothDevice, crate::BluetoothError> {
+        Ok(self.device(address)?)
+    }
+
+    /// Connect to an rfcomm profile of the adapter, blocking until the profile accepts or rejects the
+    /// connection. This must not be called from an async context, or from the thread that accepts the
+    /// connection.
+    fn connect_rfcomm(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        target: crate::RfcommTarget,
+    ) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        let answer = self.request_connection(address, Self::rfcomm_key(target))?;
+        Ok(Self::answered(answer.blocking_recv(), false)?)
+    }
+
+    fn advertise(
+        &self,
+        _settings: crate::LeAdvertisementSettings,
+    ) -> Result<crate::AdvertisementHandle, crate::BluetoothError> {
+        Ok(crate::AdvertisementHandle::Dummy(crate::Dummy {}))
+    }
+
+    fn register_gatt_service(
+        &self,
+        _service: crate::GattServiceDefinition,
+    ) -> Result<crate::GattServiceHandle, crate::BluetoothError> {
+        Ok(crate::GattServiceHandle::Dummy(crate::Dummy {}))
+    }
+}
+
+/// Identifies the profile a connection is made to
+#[derive(Clone)]
+pub(crate) enum ListenerKey {
+    /// The rfcomm profile on a channel
+    Rfcomm(u8),
+    /// The rfcomm profile with a uuid
+    RfcommUuid(crate::BluetoothUuid),
+    /// The l2cap profile on a psm
+    L2cap(u16),
+}
+
+/// The receiving end of the connections for a profile. The profile is removed from the adapter when this is
+/// dropped.
+struct Listener {
+    /// The connections made to the profile
+    receiver: tokio::sync::mpsc::Receiver<Connectable>,
+    /// The key of the profile in the adapter
+    key: ListenerKey,
+}
+
+impl Listener {
+    /// Wait for the next connection
+    async fn next(&mut self) -> Result<Connectable, crate::BluetoothError> {
+        self.receiver
+            .recv()
+            .await
+            .ok_or(crate::BluetoothError::AdapterUnavailable)
+    }
+
+    /// Wait up to the timeout for the next connection, blocking the thread
+    fn next_blocking(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<Connectable, crate::BluetoothError> {
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(c) => return Ok(c),
+                Err(tokio::sync::mpsc::error::TryRecvError::Disconnected) => {
+                    return Err(crate::BluetoothError::AdapterUnavailable);
+                }
+                Err(tokio::sync::mpsc::error::TryRecvError::Empty) => {}
+            }
+            if std::time::Instant::now() >= deadline {
+                return Err(crate::BluetoothError::Timeout);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    /// Remove the profile from the adapter
+    fn unregister(&self, state: &Mutex<State>) {
+        let mut state = state.lock().unwrap();
+        match self.key {
+            ListenerKey::Rfcomm(c) => state.rfcomm.retain(|l| l.channel != c),
+            ListenerKey::L2cap(p) => {
+                state.l2cap.remove(&p);
+            }
+            ListenerKey::RfcommUuid(_) => {}
+        }
+    }
+}
+
+/// An rfcomm profile registered on a dummy adapter. The profile is removed when this is dropped.
+pub struct RfcommProfile {
+    /// The connections for the profile, `None` for client profiles
+    listener: Option<Listener>,
+    /// The state of the adapter the profile is registered on
+    state: Arc<Mutex<State>>,
+}
+
+impl RfcommProfile {
+    /// The listener of the profile, client profiles do not have one
+    fn listener(&mut self) -> Result<&mut Listener, crate::BluetoothError> {
+        self.listener
+            .as_mut()
+            .ok_or(crate::BluetoothError::Unsupported(
+                "Accepting connections on a client profile",
+            ))
+    }
+}
+
+impl Drop for RfcommProfile {
+    fn drop(&mut self) {
+        if let Some(listener) = &self.listener {
+            listener.unregister(&self.state);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::BluetoothRfcommProfileAsyncTrait for RfcommProfile {
+    async fn connectable(
+        &mut self,
+    ) -> Result<crate::BluetoothRfcommConnectableAsync, crate::BluetoothError> {
+        self.listener()?
+            .next()
+            .await
+            .map(crate::BluetoothRfcommConnectableAsync::Dummy)
+    }
+}
+
+impl crate::BluetoothRfcommProfileSyncTrait for RfcommProfile {
+    fn connectable(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<crate::BluetoothRfcommConnectableSync, crate::BluetoothError> {
+        self.listener()?
+            .next_blocking(timeout)
+            .map(crate::BluetoothRfcommConnectableSync::Dummy)
+    }
+}
+
+/// An l2cap profile registered on a dummy adapter. The profile is removed when this is dropped.
+pub struct L2capProfile {
+    /// The connections for the profile
+    listener: Listener,
+    /// The psm the profile listens on
+    psm: u16,
+    /// The state of the adapter the profile is registered on
+    state: Arc<Mutex<State>>,
+}
+
+impl Drop for L2capProfile {
+    fn drop(&mut self) {
+        self.listener.unregister(&self.state);
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::BluetoothL2capProfileAsyncTrait for L2capProfile {
+    async fn connectable(
+        &mut self,
+    ) -> Result<crate::BluetoothL2capConnectableAsync, crate::BluetoothError> {
+        self.listener
+            .next()
+            .await
+            .map(crate::BluetoothL2capConnectableAsync::Dummy)
+    }
+
+    fn psm(&self) -> Result<u16, crate::BluetoothError> {
+        Ok(self.psm)
+    }
+}
+
+impl crate::BluetoothL2capProfileSyncTrait for L2capProfile {
+    fn connectable(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<crate::BluetoothL2capConnectableSync, crate::BluetoothError> {
+        self.listener
+            .next_blocking(timeout)
+            .map(crate::BluetoothL2capConnectableSync::Dummy)
+    }
+
+    fn psm(&self) -> Result<u16, crate::BluetoothError> {
+        Ok(self.psm)
+    }
+}
+
+/// A connection to a profile of a dummy adapter that has not been accepted or rejected yet. The connection is
+/// rejected if this is dropped without accepting it.
+pub struct Connectable {
+    /// Where the stream for the connecting side is sent when the connection is accepted
+    answer: Option<ConnectionAnswer>,
+    /// The address of the connecting adapter
+    peer: [u8; 6],
+    /// The name of the connecting adapter
+    peer_name: String,
+    /// The rfcomm channel of the connection, 0 for l2cap connections
+    channel: u8,
+    /// Is this an l2cap connection
+    l2cap: bool,
+    /// The receivers of connection events
+    connection_subscribers: Vec<tokio::sync::mpsc::Sender<crate::ConnectionEvent>>,
+}
+
+impl Connectable {
+    /// Accept the connection, giving the other end of the stream to the connecting side
+    fn accept_stream(mut self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        let answer = self.answer.take().ok_or_else(|| {
+            crate::BluetoothError::ConnectionFailed(
+                "The connection was already answered".to_string(),
+            )
+        })?;
+        let (local, remote) = tokio::io::duplex(STREAM_BUFFER);
+        answer.send(remote).map_err(|_| {
+            crate::BluetoothError::ConnectionFailed(
+                "The connecting side gave up on the connection".to_string(),
+            )
+        })?;
+        let event = crate::ConnectionEvent {
+            address: crate::BluetoothAdapterAddress::Byte(self.peer),
+            connected: true,
+        };
+        State::notify(&mut self.connection_subscribers, event);
+        Ok(if self.l2cap {
+            crate::BluetoothStream::DummyL2cap(Stream::new(local))
+        } else {
+            crate::BluetoothStream::Dummy(Stream::new(local))
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::BluetoothRfcommConnectableAsyncTrait for Connectable {
+    async fn accept(self) -> Result<(crate::BluetoothStream, [u8; 6], u8), crate::BluetoothError> {
+        let (peer, channel) = (self.peer, self.channel);
+        self.accept_stream().map(|s| (s, peer, channel))
+    }
+
+    async fn reject(self) {}
+
+    fn peer_address(&self) -> crate::BluetoothAdapterAddress {
+        crate::BluetoothAdapterAddress::Byte(self.peer)
+    }
+
+    async fn peer_name(&self) -> Option<String> {
+        Some(self.peer_name.clone())
+    }
+}
+
+impl crate::BluetoothRfcommConnectableSyncTrait for Connectable {
+    fn accept(self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        self.accept_stream()
+    }
+
+    fn reject(self) {}
+
+    fn peer_address(&self) -> crate::BluetoothAdapterAddress {
+        crate::BluetoothAdapterAddress::Byte(self.peer)
+    }
+
+    fn peer_name(&self) -> Option<String> {
+        Some(self.peer_name.clone())
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::BluetoothL2capConnectableAsyncTrait for Connectable {
+    async fn accept(self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        self.accept_stream()
+    }
+}
+
+impl crate::BluetoothL2capConnectableSyncTrait for Connectable {
+    fn accept(self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        self.accept_stream()
+    }
+}
+
+/// The dummy adapter seen as a remote device, so that sockets made from it connect to the profiles of the adapter
+pub struct BluetoothDevice {
+    /// The adapter
+    adapter: DummyAdapter,
+}
+
+impl BluetoothDevice {
+    /// A socket that connects to the profile listening on the key
+    fn socket(&self, key: ListenerKey) -> crate::BluetoothSocket {
+        crate::BluetoothSocket::Dummy(BluetoothSocket {
+            adapter: self.adapter.clone(),
+            key,
+            stream: None,
+        })
+    }
+}
+
+impl crate::BluetoothDeviceTrait for BluetoothDevice {
+    fn supports_async(&mut self) -> Option<&mut dyn crate::BluetoothDeviceAsyncTrait> {
+        None
+    }
+
+    fn supports_sync(&mut self) -> Option<&mut dyn crate::BluetoothDeviceSyncTrait> {
+        None
+    }
+
+    fn get_address(&mut self) -> Result<crate::BluetoothAdapterAddress, std::io::Error> {
+        Ok(self.adapter.address())
+    }
+
+    /// The security setting is not used
+    fn get_rfcomm_socket(
+        &mut self,
+        channel: u8,
+        _is_secure: bool,
+    ) -> Result<crate::BluetoothSocket, crate::BluetoothError> {
+        Ok(self.socket(ListenerKey::Rfcomm(channel)))
+    }
+
+    /// The security setting is not used
+    fn get_l2cap_socket(
+        &mut self,
+        psm: u16,
+        _is_secure: bool,
+    ) -> Result<crate::BluetoothSocket, crate::BluetoothError> {
+        Ok(self.socket(ListenerKey::L2cap(psm)))
+    }
+
+    fn run_sdp(
+        &mut self,
+        _uuid: crate::BluetoothUuid,
+    ) -> Result<crate::sdp::ServiceRecord, crate::BluetoothError> {
+        Err(crate::BluetoothError::Unsupported(
+            "The service discovery protocol on the dummy adapter",
+        ))
+    }
+}
+
+/// A socket to a profile of the dummy adapter
+pub struct BluetoothSocket {
+    /// The adapter the profile is registered on
+    adapter: DummyAdapter,
+    /// The profile to connect to
+    key: ListenerKey,
+    /// The stream once the socket is connected
+    stream: Option<tokio::io::DuplexStream>,
+}
+
+impl BluetoothSocket {
+    /// Ask the profile for a connection
+    fn request(
+        &self,
+    ) -> Result<tokio::sync::oneshot::Receiver<tokio::io::DuplexStream>, std::io::Error> {
+        let address = self.adapter.address();
+        self.adapter.request_connection(&address, self.key.clone())
+    }
+
+    /// Keep the stream of an accepted connection
+    fn connected(
+        &mut self,
+        answer: Result<tokio::io::DuplexStream, tokio::sync::oneshot::error::RecvError>,
+    ) -> Result<(), std::io::Error> {
+        let stream = answer.map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                "The connection was rejected",
+            )
+        })?;
+        self.stream = Some(stream);
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::BluetoothSocketTrait for BluetoothSocket {
+    fn is_connected(&self) -> Result<bool, std::io::Error> {
+        Ok(self.stream.is_some())
+    }
+
+    async fn async_connect(&mut self) -> Result<(), std::io::Error> {
+        let answer = self.request()?;
+        self.connected(answer.await)
+    }
+
+    /// This blocks until the profile accepts or rejects the connection, so it must not be called from an async
+    /// context, or from the thread that accepts the connection.
+    fn sync_connect(&mut self) -> Result<(), std::io::Error> {
+        let answer = self.request()?;
+        self.connected(answer.blocking_recv())
+    }
+
+    fn close(&mut self) -> Result<(), crate::BluetoothError> {
+        self.stream = None;
+        Ok(())
+    }
+
+    fn into_stream(self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        let stream = self
+            .stream
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotConnected))?;
+        Ok(match self.key {
+            ListenerKey::L2cap(_) => crate::BluetoothStream::DummyL2cap(Stream::new(stream)),
+            _ => crate::BluetoothStream::Dummy(Stream::new(stream)),
+        })
+    }
+
+    fn supports_async(&mut self) -> Option<&mut dyn crate::AsyncReadWrite> {
+        self.stream
+            .as_mut()
+            .map(|s| s as &mut dyn crate::AsyncReadWrite)
+    }
+
+    fn max_receive_packet_size(&self) -> Result<usize, std::io::Error> {
+        Ok(match self.key {
+            ListenerKey::L2cap(_) => L2CAP_MTU,
+            _ => crate::RFCOMM_DEFAULT_MTU,
+        })
+    }
+
+    fn max_transmit_packet_size(&self) -> Result<usize, std::io::Error> {
+        self.max_receive_packet_size()
+    }
+}
+
+/// A connected stream of the dummy adapter. Closing it drops the in-memory stream, so the peer sees the end of
+/// the stream and reads and writes on this side fail.
+pub struct Stream {
+    /// The in-memory stream, until the stream is closed
+    inner: Option<tokio::io::DuplexStream>,
+}
+
+impl Stream {
+    /// Wrap one end of an in-memory stream
+    fn new(inner: tokio::io::DuplexStream) -> Self {
+        Self { inner: Some(inner) }
+    }
+
+    /// Close the stream, closing a closed stream does nothing
+    pub(crate) fn close(&mut self) {
+        self.inner = None;
+    }
+
+    /// Is the stream still open
+    pub(crate) fn is_open(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// The in-memory stream, or an error when the stream is closed
+    fn open(&mut self) -> Result<std::pin::Pin<&mut tokio::io::DuplexStream>, std::io::Error> {
+        self.inner
+            .as_mut()
+            .map(std::pin::Pin::new)
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotConnected))
+    }
+}
+
+impl tokio::io::AsyncRead for Stream {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        tokio::io::AsyncRead::poll_read(self.get_mut().open()?, cx, buf)
+    }
+}
+
+impl tokio::io::AsyncWrite for Stream {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        tokio::io::AsyncWrite::poll_write(self.get_mut().open()?, cx, buf)
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        tokio::io::AsyncWrite::poll_flush(self.get_mut().open()?, cx)
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        tokio::io::AsyncWrite::poll_shutdown(self.get_mut().open()?, cx)
+    }
+
+    fn poll_write_vectored(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        tokio::io::AsyncWrite::poll_write_vectored(self.get_mut().open()?, cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner
+            .as_ref()
+            .is_some_and(tokio::io::AsyncWrite::is_write_vectored)
+    }
+}
+
+/// A discovery on a dummy adapter, which finishes right away without finding any devices
+pub struct BluetoothDiscovery {
+    /// The events of the discovery, the sender is already gone
+    events: Option<tokio::sync::mpsc::Receiver<crate::DiscoveryEvent>>,
+    /// Always true
+    finished: tokio::sync::watch::Receiver<bool>,
+}
+
+#[async_trait::async_trait]
+impl crate::BluetoothDiscoveryTrait for BluetoothDiscovery {
+    fn take_events(&mut self) -> Option<tokio::sync::mpsc::Receiver<crate::DiscoveryEvent>> {
+        self.events.take()
+    }
+
+    fn try_recv(&mut self) -> Option<crate::DiscoveryEvent> {
+        self.events.as_mut()?.try_recv().ok()
+    }
+
+    async fn next(&mut self) -> Option<crate::DiscoveryEvent> {
+        self.events.as_mut()?.recv().await
+    }
+
+    fn is_running(&self) -> bool {
+        false
+    }
+
+    fn finished(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.finished.clone()
+    }
+}
+
+/// A low energy scan on a dummy adapter, which finishes right away without results
+pub struct LeScanSession {
+    /// The results of the scan, the sender is already gone
+    results: Option<tokio::sync::mpsc::Receiver<crate::LeScanResult>>,
+}
+
+impl crate::LeScanSessionTrait for LeScanSession {
+    fn take_results(&mut self) -> Option<tokio::sync::mpsc::Receiver<crate::LeScanResult>> {
+        self.results.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AsyncBluetoothAdapterTrait, BluetoothDeviceTrait, BluetoothL2capConnectableAsyncTrait,
+        BluetoothL2capProfileAsyncTrait, BluetoothRfcommConnectableAsyncTrait,
+        BluetoothRfcommProfileAsyncTrait, BluetoothSocketTrait,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Send a message each way over a connection
+    async fn exchange(mut a: crate::BluetoothStream, mut b: crate::BluetoothStream) {
+        a.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        b.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+        b.write_all(b"pong").await.unwrap();
+        a.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+    }
+
+    /// The settings for an rfcomm server profile
+    fn rfcomm_settings() -> crate::BluetoothRfcommProfileSettings {
+        crate::BluetoothRfcommProfileSettings::builder()
+            .uuid(crate::BluetoothUuid::SPP)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn rfcomm_loopback() {
+        let adapter = DummyAdapter::new();
+        let mut profile =
+            AsyncBluetoothAdapterTrait::register_rfcomm_profile(&adapter, rfcomm_settings())
+                .await
+                .unwrap();
+        let client = adapter.clone();
+        let connect = tokio::spawn(async move {
+            let address = client.address();
+            AsyncBluetoothAdapterTrait::connect_rfcomm(
+                &client,
+                &address,
+                crate::RfcommTarget::Uuid(crate::BluetoothUuid::SPP),
+            )
+            .await
+        });
+        let connectable = profile.connectable().await.unwrap();
+        let (server, peer, channel) = connectable.accept().await.unwrap();
+        assert_eq!(peer, [0, 0, 0, 0, 0, 1]);
+        assert_eq!(channel, 1);
+        exchange(connect.await.unwrap().unwrap(), server).await;
+    }
+
+    #[tokio::test]
+    async fn closed_streams_fail_reads_and_writes() {
+        let adapter = DummyAdapter::new();
+        let mut profile =
+            AsyncBluetoothAdapterTrait::register_rfcomm_profile(&adapter, rfcomm_settings())
+                .await
+                .unwrap();
+        let client = adapter.clone();
+        let connect = tokio::spawn(async move {
+            let address = client.address();
+            AsyncBluetoothAdapterTrait::connect_rfcomm(
+                &client,
+                &address,
+                crate::RfcommTarget::Uuid(crate::BluetoothUuid::SPP),
+            )
+            .await
+        });
+        let connectable = profile.connectable().await.unwrap();
+        let (mut server, _, _) = connectable.accept().await.unwrap();
+        let mut client = connect.await.unwrap().unwrap();
+        assert!(client.is_open());
+        client.close().unwrap();
+        client.close().unwrap();
+        assert!(!client.is_open());
+        let mut buf = [0u8; 4];
+        assert!(client.read(&mut buf).await.is_err());
+        assert!(client.write_all(b"ping").await.is_err());
+        // The peer sees the end of the stream
+        assert_eq!(server.read(&mut buf).await.unwrap(), 0);
+        assert!(server.is_open());
+    }
+
+    #[tokio::test]
+    async fn throughput_over_a_loopback_connection() {
+        // This only measures the in-memory stream of the dummy adapter, the java calls of the android stream are
+        // checked by the signature tests in android.rs
+        /// The amount of data sent through the connection
+        const TOTAL: usize = 4 * 1024 * 1024;
+        let adapter = DummyAdapter::new();
+        let mut profile =
+            AsyncBluetoothAdapterTrait::register_rfcomm_profile(&adapter, rfcomm_settings())
+                .await
+                .unwrap();
+        let client = adapter.clone();
+        let connect = tokio::spawn(async move {
+            let address = client.address();
+            let target = crate::RfcommTarget::Channel(1);
+            AsyncBluetoothAdapterTrait::connect_rfcomm(&client, &address, target).await
+        });
+        let (mut server, _, _) = profile.connectable().await.unwrap().accept().await.unwrap();
+        let mut client = connect.await.unwrap().unwrap();
+
+        let start = std::time::Instant::now();
+        let writer = tokio::spawn(async move {
+            let chunk: Vec<u8> = (0..=255).cycle().take(1000).collect();
+            let mut sent = 0;
+            while sent < TOTAL {
+                let n = chunk.len().min(TOTAL - sent);
+                client.write_all(&chunk[..n]).await.unwrap();
+                sent += n;
+            }
+            client.shutdown().await.unwrap();
+        });
+        let mut received = 0;
+        let mut buf = vec![0u8; 4096];
+        loop {
+            let n = server.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            // The data is the repeating pattern 0..=255 restarted every 1000 bytes
+            for (i, b) in buf[..n].iter().enumerate() {
+                assert_eq!(*b, ((received + i) % 1000 % 256) as u8);
+            }
+            received += n;
+        }
+        writer.await.unwrap();
+        assert_eq!(received, TOTAL);
+        assert!(
+            start.elapsed() < std::time::Duration::from_secs(20),
+            "Sending {} bytes took {:?}",
+            TOTAL,
+            start.elapsed()
+        );
+    }
+
+    #[tokio::test]
+    async fn l2cap_loopback_through_a_device_socket() {
+        let adapter = DummyAdapter::new();
+        let settings = crate::BluetoothL2capProfileSettings {
+            uuid: crate::BluetoothUuid::SPP.to_string(),
+            name: None,
+            service_uuid: None,
+            channel: None,
+            psm: None,
+            authenticate: None,
+            authorize: None,
+            auto_connect: None,
+            sdp_record: None,
+            sdp_version: None,
+            sdp_features: None,
+            role: None,
+        };
+        let mut profile = AsyncBluetoothAdapterTrait::register_l2cap_profile(&adapter, settings)
+            .await
+            .unwrap();
+        let psm = profile.psm().unwrap();
+        assert_eq!(psm, FIRST_DYNAMIC_PSM);
+        let mut device =
+            AsyncBluetoothAdapterTrait::device_by_address(&adapter, &adapter.address())
+                .await
+                .unwrap();
+        let mut socket = device.get_l2cap_socket(psm, false).unwrap();
+        let connect = tokio::spawn(async move {
+            socket.async_connect().await.unwrap();
+            socket.into_stream().unwrap()
+        });
+        let server = profile.connectable().await.unwrap().accept().await.unwrap();
+        let client = connect.await.unwrap();
+        assert!(matches!(client, crate::BluetoothStream::DummyL2cap(_)));
+        exchange(client, server).await;
+    }
+
+    #[tokio::test]
+    async fn rejected_connections_fail() {
+        let adapter = DummyAdapter::new();
+        let mut profile =
+            AsyncBluetoothAdapterTrait::register_rfcomm_profile(&adapter, rfcomm_settings())
+                .await
+                .unwrap();
+        let client = adapter.clone();
+        let connect = tokio::spawn(async move {
+            let address = client.address();
+            AsyncBluetoothAdapterTrait::connect_rfcomm(
+                &client,
+                &address,
+                crate::RfcommTarget::Channel(1),
+            )
+            .await
+        });
+        profile.connectable().await.unwrap().reject().await;
+        let err = connect.await.unwrap().err().unwrap();
+        assert!(matches!(err, crate::BluetoothError::ConnectionFailed(_)));
+    }
+
+    #[tokio::test]
+    async fn connections_need_a_registered_profile() {
+        let adapter = DummyAdapter::new();
+        let address = adapter.address();
+        let target = crate::RfcommTarget::Channel(1);
+        let err = AsyncBluetoothAdapterTrait::connect_rfcomm(&adapter, &address, target)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, crate::BluetoothError::ConnectionFailed(_)));
+
+        let profile =
+            AsyncBluetoothAdapterTrait::register_rfcomm_profile(&adapter, rfcomm_settings())
+                .await
+                .unwrap();
+        drop(profile);
+        let target = crate::RfcommTarget::Channel(1);
+        let err = AsyncBluetoothAdapterTrait::connect_rfcomm(&adapter, &address, target)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, crate::BluetoothError::ConnectionFailed(_)));
+    }
+
+    #[tokio::test]
+    async fn incoming_ends_when_the_profile_cannot_accept() {
+        use futures::StreamExt;
+        let adapter = DummyAdapter::new();
+        let settings = crate::BluetoothRfcommProfileSettings::builder()
+            .uuid(crate::BluetoothUuid::SPP)
+            .role(crate::ProfileRole::Client)
+            .build()
+            .unwrap();
+        let mut profile = AsyncBluetoothAdapterTrait::register_rfcomm_profile(&adapter, settings)
+            .await
+            .unwrap();
+        let mut incoming = std::pin::pin!(profile.incoming());
+        let err = incoming.next().await.unwrap().err().unwrap();
+        assert!(matches!(err, crate::BluetoothError::Unsupported(_)));
+        assert!(incoming.next().await.is_none());
+    }
+
+    #[test]
+    fn sync_incoming_continues_after_a_timeout() {
+        let adapter = DummyAdapter::new();
+        let mut profile =
+            crate::SyncBluetoothAdapterTrait::register_rfcomm_profile(&adapter, rfcomm_settings())
+                .unwrap();
+        let mut incoming = profile.incoming(std::time::Duration::from_millis(10));
+        for _ in 0..2 {
+            let next = incoming.next();
+            assert!(matches!(next, Some(Err(crate::BluetoothError::Timeout))));
+        }
+    }
+}
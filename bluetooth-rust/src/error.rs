@@ -0,0 +1,149 @@
+//! Errors reported by the bluetooth library
+
+/// An error from a bluetooth operation
+#[derive(Debug)]
+pub enum BluetoothError {
+    /// The operation failed because the app has not been granted the permission. Request it with
+    /// `request_permissions` and try the operation again.
+    MissingPermission(crate::BluetoothPermission),
+    /// The operating system refused the operation
+    PermissionDenied,
+    /// There is no bluetooth adapter, or it is turned off
+    AdapterUnavailable,
+    /// The operation requires the device to be paired first
+    NotPaired,
+    /// A connection to the peer could not be made or was refused
+    ConnectionFailed(String),
+    /// The operation did not finish in time
+    Timeout,
+    /// The operation is not supported on this platform
+    Unsupported(&'static str),
+    /// The adapter builder is missing something it needs to build the adapter
+    BuilderMisconfigured(&'static str),
+    /// The settings for a profile are not valid
+    InvalidSettings(crate::ProfileSettingsError),
+    /// An io error
+    Io(std::io::Error),
+    /// Any other error reported by the platform bluetooth stack
+    Platform(String),
+}
+
+impl BluetoothError {
+    /// The io error kind used when the error is converted into a `std::io::Error`
+    fn io_kind(&self) -> std::io::ErrorKind {
+        match self {
+            Self::MissingPermission(_) | Self::PermissionDenied => {
+                std::io::ErrorKind::PermissionDenied
+            }
+            Self::AdapterUnavailable | Self::NotPaired => std::io::ErrorKind::NotConnected,
+            Self::ConnectionFailed(_) => std::io::ErrorKind::ConnectionRefused,
+            Self::Timeout => std::io::ErrorKind::TimedOut,
+            Self::Unsupported(_) => std::io::ErrorKind::Unsupported,
+            Self::BuilderMisconfigured(_) | Self::InvalidSettings(_) => {
+                std::io::ErrorKind::InvalidInput
+            }
+            Self::Io(e) => e.kind(),
+            Self::Platform(_) => std::io::ErrorKind::Other,
+        }
+    }
+}
+
+impl std::fmt::Display for BluetoothError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingPermission(p) => write!(f, "Missing the {:?} bluetooth permission", p),
+            Self::PermissionDenied => f.write_str("Permission denied"),
+            Self::AdapterUnavailable => f.write_str("The bluetooth adapter is not available"),
+            Self::NotPaired => f.write_str("The device is not paired"),
+            Self::ConnectionFailed(e) => write!(f, "Connection failed: {}", e),
+            Self::Timeout => f.write_str("The operation timed out"),
+            Self::Unsupported(what) => write!(f, "{} is not supported", what),
+            Self::BuilderMisconfigured(what) => {
+                write!(f, "The adapter builder is misconfigured: {}", what)
+            }
+            Self::InvalidSettings(e) => e.fmt(f),
+            Self::Io(e) => e.fmt(f),
+            Self::Platform(e) => f.write_str(e),
+        }
+    }
+}
+
+impl std::error::Error for BluetoothError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::InvalidSettings(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Errors created from a `BluetoothError` are converted back to it, the common io error kinds map to their variants.
+/// Permission errors that carry a message stay io errors, so the message is kept.
+impl From<std::io::Error> for BluetoothError {
+    fn from(value: std::io::Error) -> Self {
+        let value = match value.downcast::<BluetoothError>() {
+            Ok(e) => return e,
+            Err(value) => value,
+        };
+        match value.kind() {
+            std::io::ErrorKind::PermissionDenied if value.get_ref().is_none() => {
+                Self::PermissionDenied
+            }
+            std::io::ErrorKind::TimedOut => Self::Timeout,
+            std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted => Self::ConnectionFailed(value.to_string()),
+            _ => Self::Io(value),
+        }
+    }
+}
+
+/// The error becomes an io error of the matching kind that still contains the `BluetoothError`, so it can be
+/// recovered with `std::io::Error::downcast` by methods that return `std::io::Error`.
+impl From<BluetoothError> for std::io::Error {
+    fn from(value: BluetoothError) -> Self {
+        match value {
+            BluetoothError::Io(e) => e,
+            _ => std::io::Error::new(value.io_kind(), value),
+        }
+    }
+}
+
+impl From<crate::ProfileSettingsError> for BluetoothError {
+    fn from(value: crate::ProfileSettingsError) -> Self {
+        Self::InvalidSettings(value)
+    }
+}
+
+impl From<String> for BluetoothError {
+    fn from(value: String) -> Self {
+        Self::Platform(value)
+    }
+}
+
+impl From<BluetoothError> for String {
+    fn from(value: BluetoothError) -> Self {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permission_errors_keep_their_message() {
+        let err = std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "Need BLUETOOTH_CONNECT",
+        );
+        let err = BluetoothError::from(err);
+        assert!(matches!(err, BluetoothError::Io(_)));
+        assert_eq!(err.to_string(), "Need BLUETOOTH_CONNECT");
+        let err = std::io::Error::from(err);
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+        let err = BluetoothError::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert!(matches!(err, BluetoothError::PermissionDenied));
+    }
+}
@@ -14,6 +14,8 @@ mod android;
 #[cfg(target_os = "android")]
 pub use android::Bluetooth;
 #[cfg(target_os = "android")]
+pub use android::DiscoveredDevice;
+#[cfg(target_os = "android")]
 pub use android::Java;
 #[cfg(target_os = "android")]
 use winit::platform::android::activity::AndroidApp;
@@ -24,28 +26,152 @@ mod linux;
 #[cfg(target_os = "windows")]
 mod windows;
 
+mod dummy;
+pub use dummy::DummyAdapter;
+
+#[cfg(feature = "mock")]
+mod mock;
+#[cfg(feature = "mock")]
+pub use mock::MockAdapter;
+
 mod bluetooth_uuid;
-pub use bluetooth_uuid::BluetoothUuid;
+pub use bluetooth_uuid::{
+    BluetoothUuid, CustomUuid, ServiceCategory, ServiceInfo, UuidParseError, identify,
+};
+
+mod bluetooth_address;
+pub use bluetooth_address::{AddressParseError, BluetoothAdapterAddress};
+
+mod device_class;
+pub use device_class::{DeviceClass, MajorDeviceClass, MinorDeviceClass, ServiceClass};
+
+mod error;
+pub use error::BluetoothError;
 
 mod sdp;
 
 /// Commands issued to the library
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub enum BluetoothCommand {
     /// Detect all bluetooth adapters present on the system
     DetectAdapters,
     /// Find out how many bluetooth adapters are detected
     QueryNumAdapters,
+    /// List the devices paired with the bluetooth adapters
+    ListPairedDevices,
+    /// Start discovering nearby devices, it runs until `StopDiscovery` is issued
+    StartDiscovery,
+    /// Stop the discovery started by `StartDiscovery`
+    StopDiscovery,
+    /// Make the adapter visible to remote devices, or hide it again
+    SetDiscoverable {
+        /// Whether the adapter should be discoverable
+        enable: bool,
+        /// How many seconds the adapter stays discoverable, the adapter setting is used when not given
+        timeout_secs: Option<u32>,
+    },
+    /// Pair with a remote device
+    Pair {
+        /// The address of the device, formatted as `00:11:22:33:44:55`
+        address: String,
+    },
+    /// Remove the pairing with a remote device
+    Unpair {
+        /// The address of the device, formatted as `00:11:22:33:44:55`
+        address: String,
+    },
+    /// Connect to a service of a remote device over rfcomm
+    ConnectRfcomm {
+        /// The address of the device, formatted as `00:11:22:33:44:55`
+        address: String,
+        /// The uuid of the service to connect to
+        uuid: String,
+    },
 }
 
 /// Messages that can be sent specifically to the app user hosting the bluetooth controls
 pub enum MessageToBluetoothHost {
     /// The passkey used for pairing devices
-    DisplayPasskey(u32, tokio::sync::mpsc::Sender<ResponseToPasskey>),
+    DisplayPasskey {
+        /// The address of the device
+        device: BluetoothAdapterAddress,
+        /// The name of the device, if it is known
+        name: Option<String>,
+        /// The passkey to display
+        passkey: u32,
+        /// Where to send the response
+        responder: tokio::sync::mpsc::Sender<ResponseToPasskey>,
+    },
     /// The passkey to confirm for pairing
-    ConfirmPasskey(u32, tokio::sync::mpsc::Sender<ResponseToPasskey>),
+    ConfirmPasskey {
+        /// The address of the device
+        device: BluetoothAdapterAddress,
+        /// The name of the device, if it is known
+        name: Option<String>,
+        /// The passkey to confirm
+        passkey: u32,
+        /// Where to send the response
+        responder: tokio::sync::mpsc::Sender<ResponseToPasskey>,
+    },
     /// Cancal the passkey display
     CancelDisplayPasskey,
+    /// A pin code is needed to pair with a legacy device
+    RequestPinCode {
+        /// The address of the device, formatted like `00:11:22:AA:BB:CC`
+        device: String,
+        /// Where to send the pin code
+        responder: tokio::sync::mpsc::Sender<PinCodeReply>,
+    },
+    /// A passkey needs to be entered to pair with a device
+    RequestPasskey {
+        /// The address of the device
+        device: BluetoothAdapterAddress,
+        /// The name of the device, if it is known
+        name: Option<String>,
+        /// Where to send the passkey
+        responder: tokio::sync::mpsc::Sender<PasskeyEntryResponse>,
+    },
+    /// A device wants to connect to a service, send true to allow the connection
+    AuthorizeService {
+        /// The address of the device
+        device: BluetoothAdapterAddress,
+        /// The name of the device, if it is known
+        name: Option<String>,
+        /// The service the device is connecting to
+        service: BluetoothUuid,
+        /// Where to send the decision
+        responder: tokio::sync::mpsc::Sender<bool>,
+    },
+    /// A device without input or output capabilities wants to pair, send true to allow the pairing
+    AuthorizePairing {
+        /// The address of the device
+        device_address: BluetoothAdapterAddress,
+        /// The name of the device, if it is known
+        name: Option<String>,
+        /// Where to send the decision
+        responder: tokio::sync::mpsc::Sender<bool>,
+    },
+}
+
+/// Settings for how requests that need an answer from the bluetooth host are handled
+#[derive(Clone, Debug)]
+pub struct AgentConfig {
+    /// How long to wait for the host to answer a request
+    pub response_timeout: std::time::Duration,
+    /// The answer used for passkey display and confirmation when the host does not answer in time
+    pub default_on_timeout: ResponseToPasskey,
+    /// Whether a service connection is allowed when the host does not answer in time
+    pub authorize_service_default: bool,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            response_timeout: std::time::Duration::from_secs(30),
+            default_on_timeout: ResponseToPasskey::Cancel,
+            authorize_service_default: false,
+        }
+    }
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -53,6 +179,8 @@ pub enum MessageToBluetoothHost {
 pub enum MessageFromBluetoothHost {
     /// A response about the active pairing passkey
     PasskeyMessage(ResponseToPasskey),
+    /// A response to the active request for a pin code
+    PinCodeMessage(PinCodeReply),
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -68,14 +196,97 @@ pub enum ResponseToPasskey {
     Waiting,
 }
 
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+/// The user response to a request for a pin code
+pub enum PinCodeReply {
+    /// The pin code to use for pairing, 1 to 16 ascii characters
+    Pin(String),
+    /// The pairing is rejected
+    Reject,
+    /// The process is canceled by the user
+    Cancel,
+}
+
+impl PinCodeReply {
+    /// Check that the pin code can be used for pairing, it must be 1 to 16 ascii characters
+    pub fn is_valid_pin(pin: &str) -> bool {
+        (1..=16).contains(&pin.len()) && pin.is_ascii()
+    }
+
+    /// Turn a pin code that can not be used for pairing into a rejection, before it is given to the stack
+    pub(crate) fn checked(self) -> Self {
+        match self {
+            Self::Pin(pin) if !Self::is_valid_pin(&pin) => {
+                log::error!("Rejecting a pin code that is not 1 to 16 ascii characters");
+                Self::Reject
+            }
+            r => r,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+/// The user response to a request for a passkey
+pub enum PasskeyEntryResponse {
+    /// The passkey entered by the user, must be in the range 0..=999999
+    Passkey(u32),
+    /// The pairing is rejected
+    Reject,
+    /// The process is canceled by the user
+    Cancel,
+}
+
+/// Details about a bluetooth adapter
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AdapterInfo {
+    /// The address of the adapter
+    pub address: BluetoothAdapterAddress,
+    /// The name of the adapter
+    pub name: String,
+    /// Is the adapter powered on
+    pub powered: bool,
+    /// Is the adapter discoverable
+    pub discoverable: bool,
+}
+
+/// A short description of a remote bluetooth device
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct DeviceSummary {
+    /// The address of the device
+    pub address: BluetoothAdapterAddress,
+    /// The name of the device, if it is known
+    pub name: Option<String>,
+}
+
 /// Responses issued by the library
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub enum BluetoothResponse {
     /// The number of bluetooth adapters detected
     Adapters(usize),
+    /// The adapters detected
+    AdapterList(Vec<AdapterInfo>),
+    /// The devices paired with the adapters
+    PairedDevices(Vec<DeviceSummary>),
+    /// Discovery of nearby devices was started
+    DiscoveryStarted,
+    /// Discovery of nearby devices was stopped
+    DiscoveryStopped,
+    /// Whether the adapter is now in the requested discoverable state, false when the user refused the change
+    Discoverable(bool),
+    /// The device was paired
+    Paired(BluetoothAdapterAddress),
+    /// The pairing with the device was removed
+    Unpaired(BluetoothAdapterAddress),
+    /// A connection to the device was made, the stream is kept by the adapter until it is taken
+    RfcommConnected(BluetoothAdapterAddress),
+    /// The platform can not carry out the command
+    Unsupported(BluetoothCommand),
+    /// The command failed
+    Error(String),
 }
 
 /// Settings for an rfcomm profile
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct BluetoothRfcommProfileSettings {
     /// The uuid for the profile
     pub uuid: String,
@@ -99,6 +310,173 @@ pub struct BluetoothRfcommProfileSettings {
     pub sdp_version: Option<u16>,
     /// SDP profile features
     pub sdp_features: Option<u16>,
+    /// Whether the profile initiates or accepts connections
+    pub role: Option<ProfileRole>,
+}
+
+/// The role of a profile
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileRole {
+    /// The profile initiates connections to remote devices
+    Client,
+    /// The profile accepts connections from remote devices
+    Server,
+}
+
+/// The error returned when the settings for a profile are not valid
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProfileSettingsError {
+    /// The builder was not given a uuid for the profile
+    MissingUuid,
+    /// The uuid of the profile is not valid
+    InvalidUuid(UuidParseError),
+    /// The service uuid of the profile is not valid
+    InvalidServiceUuid(UuidParseError),
+    /// Rfcomm channels must be in the range 1 to 30
+    InvalidChannel(u16),
+    /// Psm values must be odd and in the dynamic range starting at 0x1001
+    InvalidPsm(u16),
+}
+
+impl std::fmt::Display for ProfileSettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingUuid => f.write_str("The profile does not have a uuid"),
+            Self::InvalidUuid(e) => write!(f, "Invalid profile uuid: {}", e),
+            Self::InvalidServiceUuid(e) => write!(f, "Invalid profile service uuid: {}", e),
+            Self::InvalidChannel(c) => write!(f, "Invalid rfcomm channel {}, expected 1 to 30", c),
+            Self::InvalidPsm(p) => write!(f, "Invalid psm {:#06x}, expected an odd dynamic psm", p),
+        }
+    }
+}
+
+impl std::error::Error for ProfileSettingsError {}
+
+/// Check that a uuid string from the settings of a profile can be parsed
+fn check_profile_uuid(uuid: &str) -> Result<(), UuidParseError> {
+    use std::str::FromStr;
+    BluetoothUuid::from_str(uuid).map(|_| ())
+}
+
+impl BluetoothRfcommProfileSettings {
+    /// Start building the settings for an rfcomm profile
+    pub fn builder() -> BluetoothRfcommProfileSettingsBuilder {
+        BluetoothRfcommProfileSettingsBuilder::default()
+    }
+
+    /// Check the uuids, channel and psm of the settings. This is done by every platform before the profile is
+    /// registered, so invalid settings are reported the same way everywhere.
+    pub fn validate(&self) -> Result<(), ProfileSettingsError> {
+        check_profile_uuid(&self.uuid).map_err(ProfileSettingsError::InvalidUuid)?;
+        if let Some(service) = &self.service_uuid {
+            check_profile_uuid(service).map_err(ProfileSettingsError::InvalidServiceUuid)?;
+        }
+        if let Some(channel) = self.channel
+            && !(1..=30).contains(&channel)
+        {
+            return Err(ProfileSettingsError::InvalidChannel(channel));
+        }
+        if let Some(psm) = self.psm
+            && (psm < 0x1001 || psm & 0x0101 != 0x0001)
+        {
+            return Err(ProfileSettingsError::InvalidPsm(psm));
+        }
+        Ok(())
+    }
+}
+
+/// A builder for `BluetoothRfcommProfileSettings`
+#[derive(Default)]
+pub struct BluetoothRfcommProfileSettingsBuilder {
+    /// The uuid for the profile
+    uuid: Option<BluetoothUuid>,
+    /// The settings being built, the uuid is filled in by build
+    settings: BluetoothRfcommProfileSettings,
+}
+
+impl BluetoothRfcommProfileSettingsBuilder {
+    /// Set the uuid for the profile, this is required
+    pub fn uuid(mut self, uuid: impl Into<BluetoothUuid>) -> Self {
+        self.uuid = Some(uuid.into());
+        self
+    }
+
+    /// Set the service uuid for the profile
+    pub fn service_uuid(mut self, uuid: impl Into<BluetoothUuid>) -> Self {
+        self.settings.service_uuid = Some(uuid.into().to_string());
+        self
+    }
+
+    /// Set the user readable name for the profile
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.settings.name = Some(name.into());
+        self
+    }
+
+    /// Set the rfcomm channel to use, from 1 to 30
+    pub fn channel(mut self, channel: u16) -> Self {
+        self.settings.channel = Some(channel);
+        self
+    }
+
+    /// Set the psm to use
+    pub fn psm(mut self, psm: u16) -> Self {
+        self.settings.psm = Some(psm);
+        self
+    }
+
+    /// Set whether authentication is required for a connection
+    pub fn authenticate(mut self, authenticate: bool) -> Self {
+        self.settings.authenticate = Some(authenticate);
+        self
+    }
+
+    /// Set whether authorization is required for a connection
+    pub fn authorize(mut self, authorize: bool) -> Self {
+        self.settings.authorize = Some(authorize);
+        self
+    }
+
+    /// For client profiles, set whether the channel is connected when a remote device is connected
+    pub fn auto_connect(mut self, auto_connect: bool) -> Self {
+        self.settings.auto_connect = Some(auto_connect);
+        self
+    }
+
+    /// Set a manual sdp record
+    pub fn sdp_record(mut self, record: impl Into<String>) -> Self {
+        self.settings.sdp_record = Some(record.into());
+        self
+    }
+
+    /// Set the sdp version
+    pub fn sdp_version(mut self, version: u16) -> Self {
+        self.settings.sdp_version = Some(version);
+        self
+    }
+
+    /// Set the sdp profile features
+    pub fn sdp_features(mut self, features: u16) -> Self {
+        self.settings.sdp_features = Some(features);
+        self
+    }
+
+    /// Set whether the profile initiates or accepts connections
+    pub fn role(mut self, role: ProfileRole) -> Self {
+        self.settings.role = Some(role);
+        self
+    }
+
+    /// Build and validate the settings
+    pub fn build(self) -> Result<BluetoothRfcommProfileSettings, ProfileSettingsError> {
+        let uuid = self.uuid.ok_or(ProfileSettingsError::MissingUuid)?;
+        let settings = BluetoothRfcommProfileSettings {
+            uuid: uuid.to_string(),
+            ..self.settings
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
 }
 
 /// Settings for an rfcomm profile
@@ -126,11 +504,71 @@ pub struct BluetoothL2capProfileSettings {
     pub sdp_version: Option<u16>,
     /// SDP profile features
     pub sdp_features: Option<u16>,
+    /// Whether the profile initiates or accepts connections
+    pub role: Option<ProfileRole>,
+}
+
+/// Settings for a bluetooth low energy advertisement
+#[derive(Debug, Default)]
+pub struct LeAdvertisementSettings {
+    /// The service uuids to advertise
+    pub service_uuids: Vec<BluetoothUuid>,
+    /// The local name to advertise
+    pub local_name: Option<String>,
+    /// Manufacturer specific data, indexed by the company identifier
+    pub manufacturer_data: std::collections::BTreeMap<u16, Vec<u8>>,
+    /// Should the advertisement make the adapter discoverable
+    pub discoverable: bool,
+}
+
+/// A running low energy advertisement. Advertising stops when this is dropped.
+pub enum AdvertisementHandle {
+    /// The advertisement registered with the bluez library in linux
+    #[cfg(target_os = "linux")]
+    Bluez(linux::AdvertisementHandle),
+    /// A dummy handler
+    Dummy(Dummy),
 }
 
 /// The trait that implements managing when bluetooth discovery is enabled
+#[async_trait::async_trait]
 #[enum_dispatch::enum_dispatch]
-pub trait BluetoothDiscoveryTrait {}
+pub trait BluetoothDiscoveryTrait {
+    /// Take the receiver for the devices found while discovery is running. This can only be taken once,
+    /// later calls return `None`. Events stop when the discovery object is dropped.
+    fn take_events(&mut self) -> Option<tokio::sync::mpsc::Receiver<DiscoveryEvent>>;
+    /// Get the next event if one is waiting, without waiting for one. Returns `None` when no event is waiting or
+    /// the receiver was taken with `take_events`.
+    fn try_recv(&mut self) -> Option<DiscoveryEvent>;
+    /// Wait for the next event. Returns `None` once discovery has finished and every event has been received, or
+    /// when the receiver was taken with `take_events`.
+    async fn next(&mut self) -> Option<DiscoveryEvent>;
+    /// Is discovery still running
+    fn is_running(&self) -> bool;
+    /// Get a receiver whose value becomes true when discovery finishes. Use `wait_for` on it to wait for
+    /// discovery to finish.
+    fn finished(&self) -> tokio::sync::watch::Receiver<bool>;
+}
+
+/// Events generated while discovering bluetooth devices
+pub enum DiscoveryEvent {
+    /// A device was found
+    DeviceAdded {
+        /// The device that was found
+        device: BluetoothDevice,
+        /// The radio transports of the device, so devices can be filtered before connecting to them
+        transport: DeviceTransport,
+    },
+    /// A device is no longer known
+    DeviceRemoved(BluetoothAdapterAddress),
+    /// A property of a device that was found has changed
+    DeviceUpdated {
+        /// The address of the device
+        address: BluetoothAdapterAddress,
+        /// The property that changed
+        property: DevicePropertyEvent,
+    },
+}
 
 /// The trait for the object that manages bluetooth discovery
 #[enum_dispatch::enum_dispatch(BluetoothDiscoveryTrait)]
@@ -144,14 +582,210 @@ pub enum BluetoothDiscovery {
     /// Windows implementation
     #[cfg(target_os = "windows")]
     Windows(windows::BluetoothDiscovery),
+    /// The in-memory dummy adapter, which finds no devices
+    Dummy(dummy::BluetoothDiscovery),
+    /// The mock adapter, which finds the scripted devices
+    #[cfg(feature = "mock")]
+    Mock(mock::BluetoothDiscovery),
+}
+
+/// Limits which devices are reported by a low energy scan
+#[derive(Clone, Debug, Default)]
+pub struct LeScanFilter {
+    /// Only report devices advertising one of these services. Every device is reported when this is empty.
+    pub service_uuids: Vec<BluetoothUuid>,
+    /// Only report devices with a name starting with this
+    pub name_prefix: Option<String>,
+}
+
+impl LeScanFilter {
+    /// Does the scan result pass the filter
+    pub fn matches(&self, result: &LeScanResult) -> bool {
+        let name = match &self.name_prefix {
+            Some(prefix) => result
+                .name
+                .as_ref()
+                .is_some_and(|n| n.starts_with(prefix.as_str())),
+            None => true,
+        };
+        let service = self.service_uuids.is_empty()
+            || self.service_uuids.iter().any(|u| {
+                result
+                    .service_uuids
+                    .iter()
+                    .any(|r| r.as_str().eq_ignore_ascii_case(u.as_str()))
+            });
+        name && service
+    }
+}
+
+/// A low energy device found by a scan
+#[derive(Clone, Debug)]
+pub struct LeScanResult {
+    /// The address of the device
+    pub address: BluetoothAdapterAddress,
+    /// The name of the device, if it is known
+    pub name: Option<String>,
+    /// The signal strength of the advertisement in dBm, if it is known
+    pub rssi: Option<i16>,
+    /// The services advertised by the device
+    pub service_uuids: Vec<BluetoothUuid>,
+    /// The manufacturer specific data advertised by the device, by company identifier
+    pub manufacturer_data: std::collections::HashMap<u16, Vec<u8>>,
+}
+
+/// The trait for a running low energy scan
+#[enum_dispatch::enum_dispatch]
+pub trait LeScanSessionTrait {
+    /// Take the receiver for the scan results. This can only be taken once, later calls return `None`.
+    /// Results stop when the session is dropped.
+    fn take_results(&mut self) -> Option<tokio::sync::mpsc::Receiver<LeScanResult>>;
+}
+
+/// A running low energy scan. The scan stops when this is dropped.
+#[enum_dispatch::enum_dispatch(LeScanSessionTrait)]
+pub enum LeScanSession {
+    /// The android version
+    #[cfg(target_os = "android")]
+    Android(android::LeScanSession),
+    /// Linux bluez library implementation
+    #[cfg(target_os = "linux")]
+    Bluez(linux::LeScanSession),
+    /// The in-memory dummy adapter, which finds no devices
+    Dummy(dummy::LeScanSession),
+}
+
+/// Events about bluetooth adapters being added to or removed from the system
+#[derive(Clone, Debug)]
+pub enum AdapterHotplugEvent {
+    /// An adapter became available
+    AdapterAdded(BluetoothAdapterAddress),
+    /// An adapter is no longer available
+    AdapterRemoved(BluetoothAdapterAddress),
+}
+
+/// The radio transports supported by a bluetooth device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceTransport {
+    /// Bluetooth classic (BR/EDR) only
+    Classic,
+    /// Bluetooth low energy only
+    Le,
+    /// Both bluetooth classic and low energy
+    Dual,
+    /// The transport is not known
+    Unknown,
 }
 
-/// The address of a bluetooth adapter
-pub enum BluetoothAdapterAddress {
-    /// The address in string form
-    String(String),
-    /// The address in byte form
-    Byte([u8; 6]),
+/// Profiles whose connection state can be queried for a device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WellKnownProfile {
+    /// Advanced audio distribution, for media audio
+    A2dp,
+    /// Hands free, for calls
+    Hfp,
+}
+
+/// The connection state of a profile of a device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileConnectionState {
+    /// The profile is not connected
+    Disconnected,
+    /// The profile is connecting
+    Connecting,
+    /// The profile is connected
+    Connected,
+    /// The profile is disconnecting
+    Disconnecting,
+}
+
+/// Changes to the properties of a bluetooth device
+#[derive(Clone, Debug)]
+pub enum DevicePropertyEvent {
+    /// The device connected or disconnected
+    Connected(bool),
+    /// The services of the device have been resolved, or are no longer resolved
+    ServicesResolved(bool),
+    /// The signal strength of the device changed, in dBm
+    RssiChanged(i16),
+    /// The name of the device changed
+    NameChanged(String),
+    /// The device was paired or unpaired
+    Paired(bool),
+    /// The device was trusted or untrusted
+    Trusted(bool),
+    /// The device was blocked or unblocked
+    Blocked(bool),
+}
+
+/// The rfcomm channel to connect to on a remote device
+#[derive(Debug)]
+pub enum RfcommTarget {
+    /// A specific channel number
+    Channel(u8),
+    /// The channel advertised for a service
+    Uuid(BluetoothUuid),
+}
+
+/// The power state of a bluetooth adapter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdapterState {
+    /// The adapter is off
+    Off,
+    /// The adapter is turning on
+    TurningOn,
+    /// The adapter is on
+    On,
+    /// The adapter is turning off
+    TurningOff,
+}
+
+/// The permissions an application may need for bluetooth operations
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BluetoothPermission {
+    /// Connecting to and communicating with paired devices
+    Connect,
+    /// Discovering nearby devices
+    Scan,
+    /// Advertising to nearby devices
+    Advertise,
+    /// The bluetooth permission used before the connect and scan permissions existed
+    LegacyBluetooth,
+    /// Precise location, which some platforms require for scanning
+    FineLocation,
+}
+
+/// The result of requesting permissions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PermissionOutcome {
+    /// All of the permissions are granted
+    Granted,
+    /// These permissions were not granted
+    Denied(Vec<BluetoothPermission>),
+}
+
+/// A request for permissions that finishes when the user has answered it
+pub type PermissionRequest =
+    std::pin::Pin<Box<dyn std::future::Future<Output = PermissionOutcome> + Send>>;
+
+/// Whether remote devices can connect to and discover a bluetooth adapter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScanMode {
+    /// Remote devices can neither connect nor discover the adapter
+    None,
+    /// Remote devices can connect, but not discover the adapter
+    Connectable,
+    /// Remote devices can connect and discover the adapter
+    ConnectableDiscoverable,
+}
+
+/// The result of a request to change the power state of a bluetooth adapter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerOutcome {
+    /// The adapter is now in the requested power state
+    Completed,
+    /// The user has been asked to change the power state. Wait for the state to change before using the adapter.
+    PromptShown,
 }
 
 /// Common async functionality for the bluetooth adapter
@@ -162,20 +796,100 @@ pub trait AsyncBluetoothAdapterTrait {
     async fn register_rfcomm_profile(
         &self,
         settings: BluetoothRfcommProfileSettings,
-    ) -> Result<BluetoothRfcommProfileAsync, String>;
+    ) -> Result<BluetoothRfcommProfileAsync, BluetoothError>;
     /// Attempt to register a new l2cap profile
     async fn register_l2cap_profile(
         &self,
         settings: BluetoothL2capProfileSettings,
-    ) -> Result<BluetoothL2capProfileAsync, String>;
+    ) -> Result<BluetoothL2capProfileAsync, BluetoothError>;
     ///Get a list of paired bluetooth devices
-    async fn get_paired_devices(&self) -> Option<Vec<BluetoothDevice>>;
+    async fn get_paired_devices(&self) -> Result<Vec<BluetoothDevice>, BluetoothError>;
     /// Start discovery of bluetooth devices. Run this and drop the result to cancel discovery
-    fn start_discovery(&self) -> BluetoothDiscovery;
+    async fn start_discovery(&self) -> Result<BluetoothDiscovery, BluetoothError>;
+    /// Start scanning for low energy devices that pass the filter. Drop the session to stop scanning.
+    async fn start_le_scan(&self, filter: LeScanFilter) -> Result<LeScanSession, BluetoothError>;
+    /// Register as a hid device, so hosts can use the app as a keyboard, gamepad, or other input device
+    async fn register_hid_device(
+        &self,
+        settings: HidSettings,
+    ) -> Result<HidDeviceHandle, BluetoothError>;
     /// Get the mac addresses of all bluetooth adapters for the system
     async fn addresses(&self) -> Vec<BluetoothAdapterAddress>;
-    /// Set the discoverable property
-    async fn set_discoverable(&self, d: bool) -> Result<(), ()>;
+    /// Does the application have the permission
+    fn has_permission(&self, permission: BluetoothPermission) -> bool;
+    /// Ask the user for the permissions the application does not have yet
+    async fn request_permissions(&self, permissions: &[BluetoothPermission]) -> PermissionOutcome;
+    /// Set the discoverable property, turning it off again after the timeout if one is given.
+    /// When no timeout is given, the current timeout setting of the adapter is used.
+    /// Returns true when the adapter is in the requested state, and false when the user refused the change.
+    async fn set_discoverable(
+        &self,
+        d: bool,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<bool, BluetoothError>;
+    /// Turn the bluetooth adapter on or off
+    async fn set_powered(&self, powered: bool) -> Result<PowerOutcome, BluetoothError>;
+    /// Is the bluetooth adapter turned on
+    async fn is_powered(&self) -> Result<bool, BluetoothError>;
+    /// Get the name of the bluetooth adapter that is shown to remote devices
+    async fn alias(&self) -> Result<String, BluetoothError>;
+    /// Set the name of the bluetooth adapter that is shown to remote devices
+    async fn set_alias(&self, alias: &str) -> Result<(), BluetoothError>;
+    /// Allow or disallow remote devices to pair with the adapter
+    async fn set_pairable(&self, pairable: bool) -> Result<(), BluetoothError>;
+    /// Set how long the adapter stays pairable after pairing is allowed
+    async fn set_pairable_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<(), BluetoothError>;
+    /// Get a receiver for events about adapters being added to or removed from the system
+    async fn adapter_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<AdapterHotplugEvent>, BluetoothError>;
+    /// Get a receiver for events about the power state of the adapter changing
+    async fn adapter_state_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<AdapterState>, BluetoothError>;
+    /// Get whether remote devices can connect to and discover the adapter
+    async fn scan_mode(&self) -> Result<ScanMode, BluetoothError>;
+    /// Get a receiver for events about the scan mode of the adapter changing
+    async fn scan_mode_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<ScanMode>, BluetoothError>;
+    /// Get a receiver for events about the pairing status of devices changing
+    async fn pairing_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<PairingEvent>, BluetoothError>;
+    /// Get a receiver for events about remote devices connecting and disconnecting
+    async fn connection_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<ConnectionEvent>, BluetoothError>;
+    /// Get a device by its address, without it being discovered or paired first
+    async fn device_by_address(
+        &self,
+        address: &BluetoothAdapterAddress,
+    ) -> Result<BluetoothDevice, BluetoothError>;
+    /// Connect to an rfcomm channel of a remote device
+    async fn connect_rfcomm(
+        &self,
+        address: &BluetoothAdapterAddress,
+        target: RfcommTarget,
+    ) -> Result<BluetoothStream, BluetoothError>;
+    /// Start a low energy advertisement, which runs until the returned handle is dropped
+    async fn advertise(
+        &self,
+        settings: LeAdvertisementSettings,
+    ) -> Result<AdvertisementHandle, BluetoothError>;
+    /// Register a local gatt service, which is available until the returned handle is dropped
+    async fn register_gatt_service(
+        &self,
+        service: GattServiceDefinition,
+    ) -> Result<GattServiceHandle, BluetoothError>;
+    /// Issue the specified bluetooth command, with an optional response for the command.
+    /// Commands the platform can not carry out are answered with `BluetoothResponse::Unsupported`.
+    async fn issue_command(&self, cmd: BluetoothCommand) -> Option<BluetoothResponse> {
+        Some(BluetoothResponse::Unsupported(cmd))
+    }
 }
 
 /// Common sync functionality for the bluetooth adapter
@@ -185,20 +899,92 @@ pub trait SyncBluetoothAdapterTrait {
     fn register_rfcomm_profile(
         &self,
         settings: BluetoothRfcommProfileSettings,
-    ) -> Result<BluetoothRfcommProfileSync, String>;
+    ) -> Result<BluetoothRfcommProfileSync, BluetoothError>;
     /// Attempt to register a new lc2ap profile
     fn register_l2cap_profile(
         &self,
         settings: BluetoothL2capProfileSettings,
-    ) -> Result<BluetoothL2capProfileAsync, String>;
+    ) -> Result<BluetoothL2capProfileSync, BluetoothError>;
     ///Get a list of paired bluetooth devices
-    fn get_paired_devices(&self) -> Option<Vec<BluetoothDevice>>;
+    fn get_paired_devices(&self) -> Result<Vec<BluetoothDevice>, BluetoothError>;
     /// Start discovery of bluetooth devices. Run this and drop the result to cancel discovery
-    fn start_discovery(&self) -> BluetoothDiscovery;
+    fn start_discovery(&self) -> Result<BluetoothDiscovery, BluetoothError>;
+    /// Start scanning for low energy devices that pass the filter. Drop the session to stop scanning.
+    fn start_le_scan(&self, filter: LeScanFilter) -> Result<LeScanSession, BluetoothError>;
+    /// Register as a hid device, so hosts can use the app as a keyboard, gamepad, or other input device
+    fn register_hid_device(&self, settings: HidSettings)
+    -> Result<HidDeviceHandle, BluetoothError>;
     /// Get the mac addresses of all bluetooth adapters for the system
     fn addresses(&self) -> Vec<BluetoothAdapterAddress>;
-    /// Set the discoverable property
-    fn set_discoverable(&self, d: bool) -> Result<(), ()>;
+    /// Does the application have the permission
+    fn has_permission(&self, permission: BluetoothPermission) -> bool;
+    /// Ask the user for the permissions the application does not have yet. The request is made before this
+    /// returns, the returned future finishes when the user has answered it.
+    fn request_permissions(&self, permissions: &[BluetoothPermission]) -> PermissionRequest;
+    /// Set the discoverable property, turning it off again after the timeout if one is given.
+    /// When no timeout is given, the current timeout setting of the adapter is used.
+    /// Returns true when the adapter is in the requested state, and false when the user refused the change.
+    fn set_discoverable(
+        &self,
+        d: bool,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<bool, BluetoothError>;
+    /// Turn the bluetooth adapter on or off
+    fn set_powered(&self, powered: bool) -> Result<PowerOutcome, BluetoothError>;
+    /// Is the bluetooth adapter turned on
+    fn is_powered(&self) -> Result<bool, BluetoothError>;
+    /// Get the name of the bluetooth adapter that is shown to remote devices
+    fn alias(&self) -> Result<String, BluetoothError>;
+    /// Set the name of the bluetooth adapter that is shown to remote devices
+    fn set_alias(&self, alias: &str) -> Result<(), BluetoothError>;
+    /// Allow or disallow remote devices to pair with the adapter
+    fn set_pairable(&self, pairable: bool) -> Result<(), BluetoothError>;
+    /// Set how long the adapter stays pairable after pairing is allowed
+    fn set_pairable_timeout(&self, timeout: std::time::Duration) -> Result<(), BluetoothError>;
+    /// Get a receiver for events about adapters being added to or removed from the system
+    fn adapter_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<AdapterHotplugEvent>, BluetoothError>;
+    /// Get a receiver for events about the power state of the adapter changing
+    fn adapter_state_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<AdapterState>, BluetoothError>;
+    /// Get whether remote devices can connect to and discover the adapter
+    fn scan_mode(&self) -> Result<ScanMode, BluetoothError>;
+    /// Get a receiver for events about the scan mode of the adapter changing
+    fn scan_mode_events(&self) -> Result<tokio::sync::mpsc::Receiver<ScanMode>, BluetoothError>;
+    /// Get a receiver for events about the pairing status of devices changing
+    fn pairing_events(&self) -> Result<tokio::sync::mpsc::Receiver<PairingEvent>, BluetoothError>;
+    /// Get a receiver for events about remote devices connecting and disconnecting
+    fn connection_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<ConnectionEvent>, BluetoothError>;
+    /// Get a device by its address, without it being discovered or paired first
+    fn device_by_address(
+        &self,
+        address: &BluetoothAdapterAddress,
+    ) -> Result<BluetoothDevice, BluetoothError>;
+    /// Connect to an rfcomm channel of a remote device
+    fn connect_rfcomm(
+        &self,
+        address: &BluetoothAdapterAddress,
+        target: RfcommTarget,
+    ) -> Result<BluetoothStream, BluetoothError>;
+    /// Start a low energy advertisement, which runs until the returned handle is dropped
+    fn advertise(
+        &self,
+        settings: LeAdvertisementSettings,
+    ) -> Result<AdvertisementHandle, BluetoothError>;
+    /// Register a local gatt service, which is available until the returned handle is dropped
+    fn register_gatt_service(
+        &self,
+        service: GattServiceDefinition,
+    ) -> Result<GattServiceHandle, BluetoothError>;
+    /// Issue the specified bluetooth command, with an optional response for the command.
+    /// Commands the platform can not carry out are answered with `BluetoothResponse::Unsupported`.
+    fn issue_command(&self, cmd: BluetoothCommand) -> Option<BluetoothResponse> {
+        Some(BluetoothResponse::Unsupported(cmd))
+    }
 }
 
 /// Common functionality for the bluetooth adapter
@@ -211,6 +997,7 @@ pub trait BluetoothAdapterTrait {
 }
 
 /// The pairing status of a bluetooth device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PairingStatus {
     /// The device is not paired
     NotPaired,
@@ -222,6 +1009,50 @@ pub enum PairingStatus {
     Unknown,
 }
 
+/// A signal strength measurement of a remote device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RssiReading {
+    /// The signal strength in dBm
+    pub rssi: i16,
+    /// When the signal strength was measured
+    pub time: std::time::Instant,
+}
+
+impl RssiReading {
+    /// A reading measured now
+    pub fn new(rssi: i16) -> Self {
+        Self {
+            rssi,
+            time: std::time::Instant::now(),
+        }
+    }
+
+    /// How long ago the signal strength was measured
+    pub fn age(&self) -> std::time::Duration {
+        self.time.elapsed()
+    }
+}
+
+/// A change of the pairing status of a bluetooth device
+#[derive(Clone, Debug)]
+pub struct PairingEvent {
+    /// The address of the device
+    pub address: BluetoothAdapterAddress,
+    /// The status before the change
+    pub old: PairingStatus,
+    /// The status after the change
+    pub new: PairingStatus,
+}
+
+/// A remote device connecting to or disconnecting from the adapter
+#[derive(Clone, Debug)]
+pub struct ConnectionEvent {
+    /// The address of the device
+    pub address: BluetoothAdapterAddress,
+    /// True when the device connected, false when it disconnected
+    pub connected: bool,
+}
+
 fn uuid16(uuid: u16) -> Vec<u8> {
     let mut v = Vec::new();
     v.push(0x19); // UUID-16 type
@@ -286,8 +1117,56 @@ pub trait BluetoothDeviceAsyncTrait {
     async fn get_uuids(&mut self) -> Result<Vec<BluetoothUuid>, std::io::Error>;
     /// Retrieve the device name
     async fn get_name(&self) -> Result<String, std::io::Error>;
+    /// Retrieve the name assigned to the device by the user, which is the device name when none was assigned
+    async fn alias(&self) -> Result<String, std::io::Error>;
+    /// Assign a name to the device
+    async fn set_alias(&self, alias: &str) -> Result<(), std::io::Error>;
     /// Retrieve the device pairing status
     async fn get_pair_state(&self) -> Result<PairingStatus, std::io::Error>;
+    /// Retrieve the signal strength of the device. This is `None` when the signal strength is not known,
+    /// such as when the device has not been seen during discovery. The value may be stale when discovery is not
+    /// active, the time of the reading tells how fresh it is.
+    async fn get_rssi(&self) -> Result<Option<RssiReading>, std::io::Error>;
+    /// Connect all of the profiles the device supports that are marked as auto-connectable
+    async fn connect_device(&self) -> Result<(), std::io::Error>;
+    /// Disconnect all of the profiles connected on the device
+    async fn disconnect_device(&self) -> Result<(), std::io::Error>;
+    /// Pair with the device, completing when the pairing succeeds or fails. The state is reported as
+    /// `PairingStatus::Pairing` by get_pair_state while the pairing is in progress.
+    async fn pair(&mut self) -> Result<(), std::io::Error>;
+    /// Remove the pairing with the device. The device will no longer be returned by get_paired_devices.
+    async fn remove_bond(&mut self) -> Result<(), std::io::Error>;
+    /// Check if the device is trusted. Trusted devices can connect profiles without the agent being asked.
+    async fn is_trusted(&self) -> Result<bool, std::io::Error>;
+    /// Set the device as trusted or untrusted
+    async fn set_trusted(&self, trusted: bool) -> Result<(), std::io::Error>;
+    /// Check if the device is blocked. Blocked devices are not allowed to connect.
+    async fn is_blocked(&self) -> Result<bool, std::io::Error>;
+    /// Set the device as blocked or unblocked
+    async fn set_blocked(&self, blocked: bool) -> Result<(), std::io::Error>;
+    /// Receive events when the properties of the device change. Events stop when the receiver is dropped.
+    async fn watch_properties(
+        &mut self,
+    ) -> Result<tokio::sync::mpsc::Receiver<DevicePropertyEvent>, std::io::Error>;
+    /// Retrieve the battery level of the device in percent, `None` when the device does not report it
+    async fn battery_percent(&self) -> Result<Option<u8>, std::io::Error>;
+    /// Retrieve the class of the device, `None` when the device did not report it
+    async fn device_class(&self) -> Result<Option<DeviceClass>, std::io::Error>;
+    /// Retrieve the radio transports supported by the device
+    async fn device_type(&self) -> Result<DeviceTransport, std::io::Error>;
+    /// Retrieve whether the profile is connected for the device, not just supported
+    async fn profile_connection_state(
+        &self,
+        profile: WellKnownProfile,
+    ) -> Result<ProfileConnectionState, std::io::Error>;
+    /// Connect to the device if needed and wait up to the timeout for its services to be resolved, then get
+    /// the fresh list of uuids
+    async fn resolve_services(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<Vec<BluetoothUuid>, std::io::Error>;
+    /// Get a client for the gatt services of the device, connecting to the device when it is not connected
+    async fn gatt(&self) -> Result<GattClient, std::io::Error>;
 }
 
 #[enum_dispatch::enum_dispatch]
@@ -297,8 +1176,58 @@ pub trait BluetoothDeviceSyncTrait {
     fn get_uuids(&mut self) -> Result<Vec<BluetoothUuid>, std::io::Error>;
     /// Retrieve the device name
     fn get_name(&self) -> Result<String, std::io::Error>;
+    /// Retrieve the name assigned to the device by the user, which is the device name when none was assigned
+    fn alias(&self) -> Result<String, std::io::Error>;
+    /// Get a client for the low energy gatt services of the device. The connection is made in the background,
+    /// the operations of the client wait for it.
+    fn gatt(&self) -> Result<GattClient, std::io::Error>;
+    /// Assign a name to the device
+    fn set_alias(&self, alias: &str) -> Result<(), std::io::Error>;
     /// Retrieve the device pairing status
     fn get_pair_state(&self) -> Result<PairingStatus, std::io::Error>;
+    /// Retrieve the signal strength of the device. This is `None` when the signal strength is not known,
+    /// such as when the device has not been seen during discovery. The value may be stale when discovery is not
+    /// active, the time of the reading tells how fresh it is.
+    fn get_rssi(&self) -> Result<Option<RssiReading>, std::io::Error>;
+    /// Connect all of the profiles the device supports that are marked as auto-connectable
+    fn connect_device(&self) -> Result<(), std::io::Error>;
+    /// Disconnect all of the profiles connected on the device
+    fn disconnect_device(&self) -> Result<(), std::io::Error>;
+    /// Pair with the device, blocking until the pairing succeeds or fails. The state is reported as
+    /// `PairingStatus::Pairing` by get_pair_state while the pairing is in progress.
+    fn pair(&mut self) -> Result<(), std::io::Error>;
+    /// Remove the pairing with the device, blocking until it is removed. The device will no longer be returned
+    /// by get_paired_devices, and any sockets previously retrieved for it are closed.
+    fn remove_bond(&mut self) -> Result<(), std::io::Error>;
+    /// Check if the device is trusted. Trusted devices can connect profiles without the agent being asked.
+    fn is_trusted(&self) -> Result<bool, std::io::Error>;
+    /// Set the device as trusted or untrusted
+    fn set_trusted(&self, trusted: bool) -> Result<(), std::io::Error>;
+    /// Check if the device is blocked. Blocked devices are not allowed to connect.
+    fn is_blocked(&self) -> Result<bool, std::io::Error>;
+    /// Set the device as blocked or unblocked
+    fn set_blocked(&self, blocked: bool) -> Result<(), std::io::Error>;
+    /// Receive events when the properties of the device change. Events continue for as long as the device exists.
+    fn watch_properties(
+        &mut self,
+    ) -> Result<tokio::sync::mpsc::Receiver<DevicePropertyEvent>, std::io::Error>;
+    /// Retrieve the battery level of the device in percent, `None` when the device does not report it
+    fn battery_percent(&self) -> Result<Option<u8>, std::io::Error>;
+    /// Retrieve the class of the device, `None` when the device did not report it
+    fn device_class(&self) -> Result<Option<DeviceClass>, std::io::Error>;
+    /// Retrieve the radio transports supported by the device
+    fn device_type(&self) -> Result<DeviceTransport, std::io::Error>;
+    /// Retrieve whether the profile is connected for the device, not just supported
+    fn profile_connection_state(
+        &self,
+        profile: WellKnownProfile,
+    ) -> Result<ProfileConnectionState, std::io::Error>;
+    /// Run service discovery on the device and wait up to the timeout for it to complete, then get the fresh
+    /// list of uuids
+    fn resolve_services(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<Vec<BluetoothUuid>, std::io::Error>;
 }
 
 /// The trait that all bluetooth devices must implement
@@ -309,23 +1238,29 @@ pub trait BluetoothDeviceTrait {
     /// Does the device support sync?
     fn supports_sync(&mut self) -> Option<&mut dyn BluetoothDeviceSyncTrait>;
     /// Retrieve the device address
-    fn get_address(&mut self) -> Result<String, std::io::Error>;
+    fn get_address(&mut self) -> Result<BluetoothAdapterAddress, std::io::Error>;
+    /// Retrieve the device address as text, formatted like `00:11:22:AA:BB:CC`
+    fn get_address_string(&mut self) -> Result<String, std::io::Error> {
+        self.get_address().map(|a| a.to_string())
+    }
     /// Attempt to get an rfcomm socket for the given uuid and security setting
     fn get_rfcomm_socket(
         &mut self,
         channel: u8,
         is_secure: bool,
-    ) -> Result<BluetoothSocket, String>;
+    ) -> Result<BluetoothSocket, BluetoothError>;
 
     /// Attempt to get an l2cap socket for the given uuid and security setting
-    fn get_l2cap_socket(&mut self, psm: u16, is_secure: bool) -> Result<BluetoothSocket, String>;
+    fn get_l2cap_socket(
+        &mut self,
+        psm: u16,
+        is_secure: bool,
+    ) -> Result<BluetoothSocket, BluetoothError>;
 
     /// Run the service discovery protocol
-    fn run_sdp(&mut self, uuid: BluetoothUuid) -> Result<sdp::ServiceRecord, String> {
-        if let Ok(a) = self.get_address() {
-            return sdp::run_sdp(&a, uuid.get_16_bit_id()).map_err(|e| e.to_string());
-        }
-        Err("Sdp failed".to_string())
+    fn run_sdp(&mut self, uuid: BluetoothUuid) -> Result<sdp::ServiceRecord, BluetoothError> {
+        let a = self.get_address_string()?;
+        Ok(sdp::run_sdp(&a, &uuid)?)
     }
 }
 
@@ -341,6 +1276,11 @@ pub enum BluetoothDevice {
     /// Bluetooth device on Windows
     #[cfg(target_os = "windows")]
     Windows(windows::BluetoothDevice),
+    /// The in-memory dummy adapter, connecting to itself
+    Dummy(dummy::BluetoothDevice),
+    /// A device declared on the mock adapter
+    #[cfg(feature = "mock")]
+    Mock(mock::BluetoothDevice),
 }
 
 /// Represents a bluetooth adapter that communicates to bluetooth devices
@@ -355,6 +1295,11 @@ pub enum BluetoothAdapter {
     /// On Windows, bluetooth adapter using the windows crate
     #[cfg(target_os = "windows")]
     Windows(windows::BluetoothHandler),
+    /// An in-memory adapter that connects to its own profiles, for running without a bluetooth radio
+    Dummy(dummy::DummyAdapter),
+    /// A scriptable adapter for testing applications
+    #[cfg(feature = "mock")]
+    Mock(mock::MockAdapter),
 }
 
 /// A builder for `BluetoothAdapter`
@@ -364,6 +1309,19 @@ pub struct BluetoothAdapterBuilder {
     app: Option<AndroidApp>,
     /// The sender to send messages to the bluetooth host
     s: Option<tokio::sync::mpsc::Sender<MessageToBluetoothHost>>,
+    /// The address of the only adapter to use
+    adapter_address: Option<BluetoothAdapterAddress>,
+    /// The name of the only adapter to use
+    adapter_name: Option<String>,
+    /// How requests to the bluetooth host are handled
+    agent_config: AgentConfig,
+    /// How long discovery runs before it is stopped
+    discovery_duration: Option<std::time::Duration>,
+    /// How pairing requests are answered when there is no sender
+    auto_confirm: bool,
+    /// The mock adapter to build instead of the adapter of the platform
+    #[cfg(feature = "mock")]
+    mock: Option<mock::MockAdapter>,
 }
 
 impl Default for BluetoothAdapterBuilder {
@@ -379,6 +1337,13 @@ impl BluetoothAdapterBuilder {
             #[cfg(target_os = "android")]
             app: None,
             s: None,
+            adapter_address: None,
+            adapter_name: None,
+            agent_config: AgentConfig::default(),
+            discovery_duration: None,
+            auto_confirm: false,
+            #[cfg(feature = "mock")]
+            mock: None,
         }
     }
 
@@ -388,24 +1353,103 @@ impl BluetoothAdapterBuilder {
         self.app = Some(app);
     }
 
-    /// Add the sender to the builder
+    /// Add the sender to the builder. The sender is optional, without it requests that need an answer from
+    /// the bluetooth host are answered automatically, see `with_auto_confirm`.
     pub fn with_sender(&mut self, s: tokio::sync::mpsc::Sender<MessageToBluetoothHost>) {
         self.s = Some(s);
     }
 
+    /// Accept pairing confirmations and pairing authorizations when there is no sender, instead of rejecting
+    /// them. Pin codes and passkeys cannot be answered without a sender, so those are always rejected.
+    /// Service authorizations use `AgentConfig::authorize_service_default`. This is currently only used on
+    /// linux, android shows its own pairing dialogs.
+    pub fn with_auto_confirm(&mut self, auto_confirm: bool) {
+        self.auto_confirm = auto_confirm;
+    }
+
+    /// Only use the adapter with the given address, instead of every adapter on the system.
+    /// This is currently only used on linux, the other platforms have a single adapter. Building fails with
+    /// `BluetoothError::BuilderMisconfigured` when the address is not a valid bluetooth address.
+    pub fn with_adapter(&mut self, address: BluetoothAdapterAddress) {
+        self.adapter_address = Some(address);
+    }
+
+    /// Only use the adapter with the given name (such as hci0), instead of every adapter on the system.
+    /// This is currently only used on linux, the other platforms have a single adapter.
+    pub fn with_adapter_name(&mut self, name: &str) {
+        self.adapter_name = Some(name.to_string());
+    }
+
+    /// Set how requests that need an answer from the bluetooth host are handled
+    pub fn with_agent_config(&mut self, config: AgentConfig) {
+        self.agent_config = config;
+    }
+
+    /// Stop discovery after the given duration, instead of running until the discovery object is dropped.
+    /// This is currently only used on linux, android stops discovery by itself after about 12 seconds.
+    pub fn with_discovery_duration(&mut self, duration: std::time::Duration) {
+        self.discovery_duration = Some(duration);
+    }
+
+    /// Build the mock adapter instead of the adapter of the platform. The sender, agent config, and auto confirm
+    /// setting of the builder are used for the pairing requests sent by the mock.
+    #[cfg(feature = "mock")]
+    pub fn with_mock(&mut self, mock: MockAdapter) {
+        self.mock = Some(mock);
+    }
+
+    /// Check the settings of the builder, before anything is built
+    fn validate(&self) -> Result<(), BluetoothError> {
+        if self
+            .adapter_address
+            .as_ref()
+            .is_some_and(|a| a.as_bytes().is_none())
+        {
+            return Err(BluetoothError::BuilderMisconfigured(
+                "the adapter address given to with_adapter is not a valid bluetooth address",
+            ));
+        }
+        if self.agent_config.response_timeout.is_zero() {
+            return Err(BluetoothError::BuilderMisconfigured(
+                "the response timeout of the agent config must not be zero",
+            ));
+        }
+        Ok(())
+    }
+
     /// Do the build
-    pub fn build(self) -> Result<BluetoothAdapter, String> {
+    pub fn build(self) -> Result<BluetoothAdapter, BluetoothError> {
+        self.validate()?;
+        #[cfg(feature = "mock")]
+        if let Some(mock) = self.mock {
+            mock.set_host(self.s, self.agent_config, self.auto_confirm);
+            return Ok(BluetoothAdapter::Mock(mock));
+        }
         #[cfg(target_os = "android")]
         {
-            return Ok(BluetoothAdapter::Android(android::Bluetooth::new(
-                self.app.unwrap(),
-            )));
+            let Some(app) = self.app else {
+                return Err(BluetoothError::BuilderMisconfigured(
+                    "an AndroidApp is required, set it with with_android_app",
+                ));
+            };
+            let mut b = android::Bluetooth::new(app);
+            b.set_host(self.s, self.agent_config);
+            return Ok(BluetoothAdapter::Android(b));
         }
-        Err("No synchronous builders available".to_string())
+        #[cfg(not(any(target_os = "android", target_os = "linux", target_os = "windows")))]
+        {
+            return Ok(BluetoothAdapter::Dummy(dummy::DummyAdapter::new()));
+        }
+        Err(BluetoothError::Unsupported("A synchronous adapter"))
     }
 
     /// Do the build
-    pub async fn async_build(self) -> Result<BluetoothAdapter, String> {
+    pub async fn async_build(self) -> Result<BluetoothAdapter, BluetoothError> {
+        self.validate()?;
+        #[cfg(feature = "mock")]
+        if self.mock.is_some() {
+            return self.build();
+        }
         #[cfg(target_os = "android")]
         {
             return self.build();
@@ -413,16 +1457,28 @@ impl BluetoothAdapterBuilder {
         #[cfg(target_os = "linux")]
         {
             return Ok(BluetoothAdapter::Bluez(
-                linux::BluetoothHandler::new(self.s.unwrap()).await?,
+                linux::BluetoothHandler::new(
+                    self.s,
+                    self.adapter_address,
+                    self.adapter_name,
+                    self.agent_config,
+                    self.auto_confirm,
+                    self.discovery_duration,
+                )
+                .await?,
             ));
         }
         #[cfg(target_os = "windows")]
         {
             return Ok(BluetoothAdapter::Windows(
-                windows::BluetoothHandler::new(self.s.unwrap()).await?,
+                windows::BluetoothHandler::new(self.s).await?,
             ));
         }
-        Err("No async builders available".to_string())
+        #[cfg(not(any(target_os = "android", target_os = "linux", target_os = "windows")))]
+        {
+            return self.build();
+        }
+        Err(BluetoothError::Unsupported("An async adapter"))
     }
 }
 
@@ -431,12 +1487,22 @@ pub enum BluetoothStream {
     /// On linux, a stream using the bluez library
     #[cfg(target_os = "linux")]
     Bluez(std::pin::Pin<Box<bluer::rfcomm::Stream>>),
+    /// On linux, an l2cap stream using the bluez library
+    #[cfg(target_os = "linux")]
+    BluezL2cap(std::pin::Pin<Box<bluer::l2cap::Stream>>),
     /// Android code for a bluetooth stream
     #[cfg(target_os = "android")]
     Android(android::RfcommStream),
+    /// Android code for an l2cap stream, android uses the same socket type for rfcomm and l2cap
+    #[cfg(target_os = "android")]
+    AndroidL2cap(android::RfcommStream),
     /// Windows RFCOMM stream
     #[cfg(target_os = "windows")]
     Windows(windows::WindowsRfcommStream),
+    /// An rfcomm stream of the in-memory dummy adapter
+    Dummy(dummy::Stream),
+    /// An l2cap stream of the in-memory dummy adapter
+    DummyL2cap(dummy::Stream),
 }
 
 macro_rules! pin_match {
@@ -445,11 +1511,21 @@ macro_rules! pin_match {
             #[cfg(target_os = "linux")]
             BluetoothStream::Bluez($s) => $body,
 
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap($s) => $body,
+
             #[cfg(target_os = "android")]
             BluetoothStream::Android($s) => $body,
 
+            #[cfg(target_os = "android")]
+            BluetoothStream::AndroidL2cap($s) => $body,
+
             #[cfg(target_os = "windows")]
             BluetoothStream::Windows($s) => $body,
+
+            BluetoothStream::Dummy($s) => $body,
+
+            BluetoothStream::DummyL2cap($s) => $body,
         }
     };
 }
@@ -483,6 +1559,33 @@ impl tokio::io::AsyncWrite for BluetoothStream {
             tokio::io::AsyncWrite::poll_shutdown(std::pin::Pin::new(s), cx)
         })
     }
+
+    fn poll_write_vectored(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        pin_match!(self, s => {
+            tokio::io::AsyncWrite::poll_write_vectored(std::pin::Pin::new(s), cx, bufs)
+        })
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(s) => tokio::io::AsyncWrite::is_write_vectored(s),
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(s) => tokio::io::AsyncWrite::is_write_vectored(s),
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(s) => tokio::io::AsyncWrite::is_write_vectored(s),
+            #[cfg(target_os = "android")]
+            BluetoothStream::AndroidL2cap(s) => tokio::io::AsyncWrite::is_write_vectored(s),
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(_) => false,
+            BluetoothStream::Dummy(s) => tokio::io::AsyncWrite::is_write_vectored(s),
+            BluetoothStream::DummyL2cap(s) => tokio::io::AsyncWrite::is_write_vectored(s),
+        }
+    }
 }
 
 impl tokio::io::AsyncRead for BluetoothStream {
@@ -497,28 +1600,200 @@ impl tokio::io::AsyncRead for BluetoothStream {
     }
 }
 
+/// The transport a bluetooth stream runs over
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    /// An rfcomm channel
+    Rfcomm,
+    /// An l2cap connection oriented channel
+    L2cap,
+}
+
+/// The parameters of the link a bluetooth stream runs over
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinkInfo {
+    /// The transport of the stream
+    pub transport: Transport,
+    /// The largest packet that can be sent at once, when it is known. Messages batched up to this size fit in one
+    /// packet, larger writes to an l2cap channel fail.
+    pub mtu: Option<u16>,
+    /// Is the link authenticated and encrypted
+    pub secure: bool,
+}
+
 impl BluetoothStream {
-    /// Used to check to see if the object supports async read, and then use the functionality
-    pub fn supports_async_read(&mut self) -> Option<&mut dyn tokio::io::AsyncRead> {
+    /// Get the transport the stream runs over. L2cap channels preserve message boundaries and can have a
+    /// different mtu than rfcomm channels.
+    pub fn transport(&self) -> Transport {
         match self {
             #[cfg(target_os = "linux")]
-            BluetoothStream::Bluez(pin) => Some(pin),
+            BluetoothStream::Bluez(_) => Transport::Rfcomm,
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(_) => Transport::L2cap,
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(_) => Transport::Rfcomm,
             #[cfg(target_os = "android")]
-            BluetoothStream::Android(_pin) => None,
+            BluetoothStream::AndroidL2cap(_) => Transport::L2cap,
             #[cfg(target_os = "windows")]
-            BluetoothStream::Windows(_pin) => None,
+            BluetoothStream::Windows(_) => Transport::Rfcomm,
+            BluetoothStream::Dummy(_) => Transport::Rfcomm,
+            BluetoothStream::DummyL2cap(_) => Transport::L2cap,
         }
     }
 
-    /// Used to check to see if the object supports async write, and then use the functionality
-    pub fn supports_async_write(&mut self) -> Option<&mut dyn tokio::io::AsyncWrite> {
+    /// Get the address of the remote device of the stream
+    pub fn peer_address(&self) -> Result<BluetoothAdapterAddress, BluetoothError> {
         match self {
             #[cfg(target_os = "linux")]
-            BluetoothStream::Bluez(pin) => Some(pin),
+            BluetoothStream::Bluez(s) => Ok(BluetoothAdapterAddress::Byte(s.peer_addr()?.addr.0)),
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(s) => {
+                Ok(BluetoothAdapterAddress::Byte(s.peer_addr()?.addr.0))
+            }
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(s) => Ok(s.peer_address()),
+            #[cfg(target_os = "android")]
+            BluetoothStream::AndroidL2cap(s) => Ok(s.peer_address()),
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(s) => Ok(s.peer_address()),
+            BluetoothStream::Dummy(_) | BluetoothStream::DummyL2cap(_) => Err(
+                BluetoothError::Unsupported("The peer address of a dummy stream"),
+            ),
+        }
+    }
+
+    /// Get the name of the remote device of the stream, if it was known when the connection was made. The name
+    /// is not known for linux and dummy streams.
+    pub fn peer_name(&self) -> Option<String> {
+        match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(_) | BluetoothStream::BluezL2cap(_) => None,
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(s) => s.peer_name(),
+            #[cfg(target_os = "android")]
+            BluetoothStream::AndroidL2cap(s) => s.peer_name(),
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(s) => s.peer_name(),
+            BluetoothStream::Dummy(_) | BluetoothStream::DummyL2cap(_) => None,
+        }
+    }
+
+    /// Get the transport, mtu, and security of the link. On android the security is how the socket was created,
+    /// because android does not report the security of a connection.
+    pub fn link_info(&self) -> LinkInfo {
+        let secure = match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(s) => {
+                bluer::rfcomm::Stream::as_ref(s).security().is_ok_and(|s| {
+                    matches!(
+                        s.level,
+                        bluer::rfcomm::SecurityLevel::Medium
+                            | bluer::rfcomm::SecurityLevel::High
+                            | bluer::rfcomm::SecurityLevel::Fips
+                    )
+                })
+            }
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(s) => {
+                bluer::l2cap::Stream::as_ref(s).security().is_ok_and(|s| {
+                    matches!(
+                        s.level,
+                        bluer::l2cap::SecurityLevel::Medium
+                            | bluer::l2cap::SecurityLevel::High
+                            | bluer::l2cap::SecurityLevel::Fips
+                    )
+                })
+            }
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(s) | BluetoothStream::AndroidL2cap(s) => s.is_secure(),
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(_) => false,
+            BluetoothStream::Dummy(_) | BluetoothStream::DummyL2cap(_) => false,
+        };
+        LinkInfo {
+            transport: self.transport(),
+            mtu: self
+                .max_transmit_packet_size()
+                .ok()
+                .map(|m| m.min(u16::MAX as usize) as u16),
+            secure,
+        }
+    }
+
+    /// The largest packet that can be received at once. For l2cap this is the mtu of the channel.
+    pub fn max_receive_packet_size(&self) -> Result<usize, std::io::Error> {
+        match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(_) => Ok(RFCOMM_DEFAULT_MTU),
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(s) => {
+                bluer::l2cap::Stream::as_ref(s).recv_mtu().map(usize::from)
+            }
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(s) => s.max_receive_packet_size(),
+            #[cfg(target_os = "android")]
+            BluetoothStream::AndroidL2cap(s) => s.max_receive_packet_size(),
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(_) => Ok(RFCOMM_DEFAULT_MTU),
+            BluetoothStream::Dummy(_) => Ok(RFCOMM_DEFAULT_MTU),
+            BluetoothStream::DummyL2cap(_) => Ok(dummy::L2CAP_MTU),
+        }
+    }
+
+    /// The largest packet that can be sent at once. For l2cap this is the mtu of the remote device, and larger
+    /// writes fail, so frames need to be split to fit.
+    pub fn max_transmit_packet_size(&self) -> Result<usize, std::io::Error> {
+        match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(_) => Ok(RFCOMM_DEFAULT_MTU),
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(s) => {
+                bluer::l2cap::Stream::as_ref(s).send_mtu().map(usize::from)
+            }
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(s) => s.max_transmit_packet_size(),
+            #[cfg(target_os = "android")]
+            BluetoothStream::AndroidL2cap(s) => s.max_transmit_packet_size(),
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(_) => Ok(RFCOMM_DEFAULT_MTU),
+            BluetoothStream::Dummy(_) => Ok(RFCOMM_DEFAULT_MTU),
+            BluetoothStream::DummyL2cap(_) => Ok(dummy::L2CAP_MTU),
+        }
+    }
+
+    /// Used to check to see if the object supports async read, and then use the functionality
+    pub fn supports_async_read(&mut self) -> Option<&mut dyn tokio::io::AsyncRead> {
+        match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(pin) => Some(pin),
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(pin) => Some(pin),
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(pin) => Some(pin),
             #[cfg(target_os = "android")]
-            BluetoothStream::Android(_pin) => None,
+            BluetoothStream::AndroidL2cap(pin) => Some(pin),
             #[cfg(target_os = "windows")]
             BluetoothStream::Windows(_pin) => None,
+            BluetoothStream::Dummy(pin) => Some(pin),
+            BluetoothStream::DummyL2cap(pin) => Some(pin),
+        }
+    }
+
+    /// Used to check to see if the object supports async write, and then use the functionality
+    pub fn supports_async_write(&mut self) -> Option<&mut dyn tokio::io::AsyncWrite> {
+        match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(pin) => Some(pin),
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(pin) => Some(pin),
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(pin) => Some(pin),
+            #[cfg(target_os = "android")]
+            BluetoothStream::AndroidL2cap(pin) => Some(pin),
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(_pin) => None,
+            BluetoothStream::Dummy(pin) => Some(pin),
+            BluetoothStream::DummyL2cap(pin) => Some(pin),
         }
     }
 
@@ -527,10 +1802,16 @@ impl BluetoothStream {
         match self {
             #[cfg(target_os = "linux")]
             BluetoothStream::Bluez(_pin) => None,
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(_pin) => None,
             #[cfg(target_os = "android")]
             BluetoothStream::Android(pin) => Some(pin),
+            #[cfg(target_os = "android")]
+            BluetoothStream::AndroidL2cap(pin) => Some(pin),
             #[cfg(target_os = "windows")]
             BluetoothStream::Windows(pin) => Some(pin),
+            BluetoothStream::Dummy(_pin) => None,
+            BluetoothStream::DummyL2cap(_pin) => None,
         }
     }
 
@@ -539,12 +1820,333 @@ impl BluetoothStream {
         match self {
             #[cfg(target_os = "linux")]
             BluetoothStream::Bluez(_pin) => None,
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(_pin) => None,
             #[cfg(target_os = "android")]
             BluetoothStream::Android(pin) => Some(pin),
+            #[cfg(target_os = "android")]
+            BluetoothStream::AndroidL2cap(pin) => Some(pin),
             #[cfg(target_os = "windows")]
             BluetoothStream::Windows(pin) => Some(pin),
+            BluetoothStream::Dummy(_pin) => None,
+            BluetoothStream::DummyL2cap(_pin) => None,
         }
     }
+
+    /// Flush the stream and shut down its writing half. The peer sees the end of the stream, and data can still be
+    /// read until the peer closes its side. Android sockets cannot be half closed, so this only flushes them, use
+    /// `close` to end the connection.
+    pub async fn shutdown(&mut self) -> Result<(), BluetoothError> {
+        tokio::io::AsyncWriteExt::shutdown(self).await?;
+        Ok(())
+    }
+
+    /// Close the stream, ending the connection in both directions. Reads and writes fail afterwards, and closing
+    /// a closed stream does nothing. Dropping the stream also closes it, ignoring any error.
+    pub fn close(&mut self) -> Result<(), BluetoothError> {
+        match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(s) => Ok(linux::shutdown_socket(&**s)?),
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(s) => Ok(linux::shutdown_socket(&**s)?),
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(s) => s.close(),
+            #[cfg(target_os = "android")]
+            BluetoothStream::AndroidL2cap(s) => s.close(),
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(s) => s.close(),
+            BluetoothStream::Dummy(s) | BluetoothStream::DummyL2cap(s) => {
+                s.close();
+                Ok(())
+            }
+        }
+    }
+
+    /// Check if the stream is still connected, without reading from it. This is false after `close`, or once the
+    /// peer has disconnected, when the platform reports it. Dummy streams only report `close` on this side. This is
+    /// also the connection check, there is no separate `is_connected`.
+    pub fn is_open(&self) -> bool {
+        match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(s) => linux::socket_is_open(&**s),
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(s) => linux::socket_is_open(&**s),
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(s) => s.is_open(),
+            #[cfg(target_os = "android")]
+            BluetoothStream::AndroidL2cap(s) => s.is_open(),
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(s) => s.is_open(),
+            BluetoothStream::Dummy(s) | BluetoothStream::DummyL2cap(s) => s.is_open(),
+        }
+    }
+
+    /// Set how long `read_timeout_aware` waits for data, `None` waits forever. Android streams also apply it to
+    /// sync reads. On linux the kernel only keeps the timeout for the non-blocking socket, reads through
+    /// `AsyncRead` ignore it. A zero duration is an error. Timeouts are not supported by windows and dummy streams.
+    pub fn set_read_timeout(
+        &mut self,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<(), BluetoothError> {
+        check_timeout(timeout)?;
+        match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(s) => {
+                Ok(linux::set_socket_timeout(&**s, libc::SO_RCVTIMEO, timeout)?)
+            }
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(s) => {
+                Ok(linux::set_socket_timeout(&**s, libc::SO_RCVTIMEO, timeout)?)
+            }
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(s) | BluetoothStream::AndroidL2cap(s) => {
+                s.set_read_timeout(timeout);
+                Ok(())
+            }
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(_) => {
+                Err(BluetoothError::Unsupported("A timeout on a windows stream"))
+            }
+            BluetoothStream::Dummy(_) | BluetoothStream::DummyL2cap(_) => {
+                Err(BluetoothError::Unsupported("A timeout on a dummy stream"))
+            }
+        }
+    }
+
+    /// Set how long `write_timeout_aware` waits, `None` waits forever. Android streams also apply it to sync
+    /// writes and flushes. On linux the kernel only keeps the timeout for the non-blocking socket, writes through
+    /// `AsyncWrite` ignore it. A zero duration is an error. Timeouts are not supported by windows and dummy
+    /// streams.
+    pub fn set_write_timeout(
+        &mut self,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<(), BluetoothError> {
+        check_timeout(timeout)?;
+        match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(s) => {
+                Ok(linux::set_socket_timeout(&**s, libc::SO_SNDTIMEO, timeout)?)
+            }
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(s) => {
+                Ok(linux::set_socket_timeout(&**s, libc::SO_SNDTIMEO, timeout)?)
+            }
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(s) | BluetoothStream::AndroidL2cap(s) => {
+                s.set_write_timeout(timeout);
+                Ok(())
+            }
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(_) => {
+                Err(BluetoothError::Unsupported("A timeout on a windows stream"))
+            }
+            BluetoothStream::Dummy(_) | BluetoothStream::DummyL2cap(_) => {
+                Err(BluetoothError::Unsupported("A timeout on a dummy stream"))
+            }
+        }
+    }
+
+    /// The read timeout of the stream, `None` means reads wait forever. On linux the kernel keeps the timeout,
+    /// rounded up to its clock tick.
+    pub fn read_timeout(&self) -> Result<Option<std::time::Duration>, BluetoothError> {
+        match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(s) => Ok(linux::socket_timeout(&**s, libc::SO_RCVTIMEO)?),
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(s) => Ok(linux::socket_timeout(&**s, libc::SO_RCVTIMEO)?),
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(s) | BluetoothStream::AndroidL2cap(s) => Ok(s.read_timeout()),
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(_) => {
+                Err(BluetoothError::Unsupported("A timeout on a windows stream"))
+            }
+            BluetoothStream::Dummy(_) | BluetoothStream::DummyL2cap(_) => {
+                Err(BluetoothError::Unsupported("A timeout on a dummy stream"))
+            }
+        }
+    }
+
+    /// The write timeout of the stream, `None` means writes wait forever. On linux the kernel keeps the timeout,
+    /// rounded up to its clock tick.
+    pub fn write_timeout(&self) -> Result<Option<std::time::Duration>, BluetoothError> {
+        match self {
+            #[cfg(target_os = "linux")]
+            BluetoothStream::Bluez(s) => Ok(linux::socket_timeout(&**s, libc::SO_SNDTIMEO)?),
+            #[cfg(target_os = "linux")]
+            BluetoothStream::BluezL2cap(s) => Ok(linux::socket_timeout(&**s, libc::SO_SNDTIMEO)?),
+            #[cfg(target_os = "android")]
+            BluetoothStream::Android(s) | BluetoothStream::AndroidL2cap(s) => Ok(s.write_timeout()),
+            #[cfg(target_os = "windows")]
+            BluetoothStream::Windows(_) => {
+                Err(BluetoothError::Unsupported("A timeout on a windows stream"))
+            }
+            BluetoothStream::Dummy(_) | BluetoothStream::DummyL2cap(_) => {
+                Err(BluetoothError::Unsupported("A timeout on a dummy stream"))
+            }
+        }
+    }
+
+    /// Read from the stream, failing with [`BluetoothError::Timeout`] when no data arrives before the read timeout
+    /// passes. The timeout error is an io error of kind `TimedOut`, which converts back to the bluetooth error.
+    pub async fn read_timeout_aware(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        let timeout = self.read_timeout().ok().flatten();
+        with_timeout(timeout, tokio::io::AsyncReadExt::read(self, buf)).await
+    }
+
+    /// Write to the stream, failing with [`BluetoothError::Timeout`] when the write does not finish before the
+    /// write timeout passes. Part of the data may have been sent when it times out.
+    pub async fn write_timeout_aware(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
+        let timeout = self.write_timeout().ok().flatten();
+        with_timeout(timeout, tokio::io::AsyncWriteExt::write(self, buf)).await
+    }
+
+    /// Write all of the parts of a frame. The parts are written together with vectored writes when the stream
+    /// supports them, otherwise they are copied into one buffer, so a frame is not split into a write per part.
+    pub async fn write_frame(&mut self, parts: &[&[u8]]) -> Result<(), std::io::Error> {
+        use tokio::io::AsyncWriteExt;
+        if !tokio::io::AsyncWrite::is_write_vectored(self) {
+            return self.write_all(&parts.concat()).await;
+        }
+        let mut slices: Vec<std::io::IoSlice<'_>> =
+            parts.iter().map(|p| std::io::IoSlice::new(p)).collect();
+        let mut slices = &mut slices[..];
+        // Skip the empty parts at the start, so that a write of 0 bytes means the stream is closed
+        std::io::IoSlice::advance_slices(&mut slices, 0);
+        while !slices.is_empty() {
+            let n = self.write_vectored(slices).await?;
+            if n == 0 {
+                return Err(std::io::ErrorKind::WriteZero.into());
+            }
+            std::io::IoSlice::advance_slices(&mut slices, n);
+        }
+        Ok(())
+    }
+
+    /// Wrap the stream so it can be used with `std::io::Read` and `std::io::Write` on every platform, by blocking
+    /// on the async operations with the given tokio runtime. The wrapper starts with the timeouts of the stream.
+    pub fn into_blocking(self, runtime: tokio::runtime::Handle) -> BlockingBluetoothStream {
+        BlockingBluetoothStream {
+            read_timeout: self.read_timeout().ok().flatten(),
+            write_timeout: self.write_timeout().ok().flatten(),
+            stream: self,
+            runtime,
+        }
+    }
+}
+
+/// Reject zero timeouts, like `std::net::TcpStream` does
+fn check_timeout(
+    timeout: Option<std::time::Duration>,
+) -> Result<Option<std::time::Duration>, std::io::Error> {
+    if timeout.is_some_and(|t| t.is_zero()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "cannot set a 0 duration timeout",
+        ));
+    }
+    Ok(timeout)
+}
+
+/// Run an io operation, failing with [`BluetoothError::Timeout`] when the timeout passes first
+async fn with_timeout<T>(
+    timeout: Option<std::time::Duration>,
+    f: impl std::future::Future<Output = Result<T, std::io::Error>>,
+) -> Result<T, std::io::Error> {
+    match timeout {
+        Some(t) => tokio::time::timeout(t, f)
+            .await
+            .map_err(|_| BluetoothError::Timeout)?,
+        None => f.await,
+    }
+}
+
+/// A bluetooth stream that implements `std::io::Read` and `std::io::Write` by blocking on the async stream. Made
+/// with [`BluetoothStream::into_blocking`]. It must not be used from a thread that is running the async runtime,
+/// and the runtime needs the time driver enabled for timeouts to work.
+pub struct BlockingBluetoothStream {
+    /// The stream that does the work
+    stream: BluetoothStream,
+    /// The runtime used to run the async operations of the stream
+    runtime: tokio::runtime::Handle,
+    /// How long a read can wait for data, forever when `None`
+    read_timeout: Option<std::time::Duration>,
+    /// How long a write or flush can wait, forever when `None`
+    write_timeout: Option<std::time::Duration>,
+}
+
+impl BlockingBluetoothStream {
+    /// Set how long a read waits for data before failing with `std::io::ErrorKind::TimedOut`. `None` waits
+    /// forever. A zero duration is an error, like it is for `std::net::TcpStream`.
+    pub fn set_read_timeout(
+        &mut self,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<(), std::io::Error> {
+        self.read_timeout = check_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Set how long a write or flush waits before failing with `std::io::ErrorKind::TimedOut`. `None` waits
+    /// forever. A zero duration is an error, like it is for `std::net::TcpStream`.
+    pub fn set_write_timeout(
+        &mut self,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<(), std::io::Error> {
+        self.write_timeout = check_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// The read timeout, `None` means reads wait forever
+    pub fn read_timeout(&self) -> Option<std::time::Duration> {
+        self.read_timeout
+    }
+
+    /// The write timeout, `None` means writes wait forever
+    pub fn write_timeout(&self) -> Option<std::time::Duration> {
+        self.write_timeout
+    }
+
+    /// Get a reference to the wrapped stream
+    pub fn get_ref(&self) -> &BluetoothStream {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the wrapped stream
+    pub fn get_mut(&mut self) -> &mut BluetoothStream {
+        &mut self.stream
+    }
+
+    /// Get the wrapped stream back, to use it asynchronously
+    pub fn into_inner(self) -> BluetoothStream {
+        self.stream
+    }
+
+    /// Run an operation of the stream on the runtime, giving up after the timeout
+    fn block_on<T>(
+        runtime: &tokio::runtime::Handle,
+        timeout: Option<std::time::Duration>,
+        f: impl std::future::Future<Output = Result<T, std::io::Error>>,
+    ) -> Result<T, std::io::Error> {
+        runtime.block_on(with_timeout(timeout, f))
+    }
+}
+
+impl std::io::Read for BlockingBluetoothStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = tokio::io::AsyncReadExt::read(&mut self.stream, buf);
+        Self::block_on(&self.runtime, self.read_timeout, read)
+    }
+}
+
+impl std::io::Write for BlockingBluetoothStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let write = tokio::io::AsyncWriteExt::write(&mut self.stream, buf);
+        Self::block_on(&self.runtime, self.write_timeout, write)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let flush = tokio::io::AsyncWriteExt::flush(&mut self.stream);
+        Self::block_on(&self.runtime, self.write_timeout, flush)
+    }
 }
 
 /// The trait for bluetooth rfcomm objects that can be connected or accepted
@@ -552,29 +2154,39 @@ impl BluetoothStream {
 #[enum_dispatch::enum_dispatch]
 pub trait BluetoothRfcommConnectableAsyncTrait {
     /// Accept a connection from a bluetooth peer, returns the stream, bluetooth address, and port
-    async fn accept(self) -> Result<(BluetoothStream, [u8; 6], u8), String>;
+    async fn accept(self) -> Result<(BluetoothStream, [u8; 6], u8), BluetoothError>;
+    /// Refuse the connection from the bluetooth peer. Dropping the object also refuses the connection.
+    async fn reject(self);
+    /// The address of the bluetooth peer that wants to connect
+    fn peer_address(&self) -> BluetoothAdapterAddress;
+    /// The name of the bluetooth peer that wants to connect, if it is known
+    async fn peer_name(&self) -> Option<String>;
 }
 
 /// A bluetooth profile for rfcomm channels
 #[enum_dispatch::enum_dispatch(BluetoothRfcommConnectableAsyncTrait)]
 pub enum BluetoothRfcommConnectableAsync {
-    /// The android object for the profile
-    #[cfg(target_os = "android")]
-    Android(android::BluetoothRfcommConnectable),
     /// The bluez library in linux is responsible for the profile
     #[cfg(target_os = "linux")]
-    Bluez(bluer::rfcomm::ConnectRequest),
+    Bluez(linux::RfcommConnectable),
     /// Windows RFCOMM connectable
     #[cfg(target_os = "windows")]
     Windows(windows::BluetoothRfcommConnectable),
+    /// A connection to the in-memory dummy adapter
+    Dummy(dummy::Connectable),
 }
 
 /// The trait for bluetooth rfcomm objects that can be connected or accepted
 #[enum_dispatch::enum_dispatch]
 pub trait BluetoothRfcommConnectableSyncTrait {
-    /// Accept a connection from a bluetooth peer
-    fn accept(self, timeout: std::time::Duration)
-    -> Result<(BluetoothStream, [u8; 6], u8), String>;
+    /// Accept the connection from the bluetooth peer
+    fn accept(self) -> Result<BluetoothStream, BluetoothError>;
+    /// Refuse the connection from the bluetooth peer. Dropping the object also refuses the connection.
+    fn reject(self);
+    /// The address of the bluetooth peer that wants to connect
+    fn peer_address(&self) -> BluetoothAdapterAddress;
+    /// The name of the bluetooth peer that wants to connect, if it is known
+    fn peer_name(&self) -> Option<String>;
 }
 
 /// A bluetooth profile for rfcomm channels
@@ -583,53 +2195,83 @@ pub enum BluetoothRfcommConnectableSync {
     /// The android object for the profile
     #[cfg(target_os = "android")]
     Android(android::BluetoothRfcommConnectable),
+    /// A connection to the in-memory dummy adapter
+    Dummy(dummy::Connectable),
 }
 
-/// The trait for bluetooth rfcomm objects that can be connected or accepted
+/// The trait for bluetooth l2cap objects that can be connected or accepted
+#[async_trait::async_trait]
 #[enum_dispatch::enum_dispatch]
 pub trait BluetoothL2capConnectableAsyncTrait {
     /// Accept a connection from a bluetooth peer
-    async fn accept(self) -> Result<BluetoothStream, String>;
+    async fn accept(self) -> Result<BluetoothStream, BluetoothError>;
 }
 
-/// A bluetooth profile for rfcomm channels
-#[enum_dispatch::enum_dispatch(BluetoothL2capConnectableTrait)]
+/// An incoming connection for an l2cap profile
+#[enum_dispatch::enum_dispatch(BluetoothL2capConnectableAsyncTrait)]
 pub enum BluetoothL2capConnectableAsync {
-    /// The android object for the profile
-    #[cfg(target_os = "android")]
-    Android(android::BluetoothRfcommConnectable),
-    /// The bluez library in linux is responsible for the profile
+    /// The bluez library in linux is responsible for the connection
     #[cfg(target_os = "linux")]
-    Bluez(bluer::rfcomm::ConnectRequest),
+    Bluez(linux::L2capConnectable),
+    /// A connection to the in-memory dummy adapter
+    Dummy(dummy::Connectable),
 }
 
-/// The trait for bluetooth rfcomm objects that can be connected or accepted
+/// The trait for bluetooth l2cap objects that can be connected or accepted
 #[enum_dispatch::enum_dispatch]
 pub trait BluetoothL2capConnectableSyncTrait {
-    /// Accept a connection from a bluetooth peer
-    fn accept(self, timeout: std::time::Duration) -> Result<BluetoothStream, String>;
+    /// Accept the connection from the bluetooth peer
+    fn accept(self) -> Result<BluetoothStream, BluetoothError>;
 }
 
-/// A bluetooth profile for rfcomm channels
+/// An incoming connection for an l2cap profile
 #[enum_dispatch::enum_dispatch(BluetoothL2capConnectableSyncTrait)]
 pub enum BluetoothL2capConnectableSync {
     /// The android object for the profile
     #[cfg(target_os = "android")]
     Android(android::BluetoothRfcommConnectable),
+    /// A connection to the in-memory dummy adapter
+    Dummy(dummy::Connectable),
 }
 
 /// Allows building an object to connect to bluetooth devices
+#[async_trait::async_trait]
 #[enum_dispatch::enum_dispatch]
 pub trait BluetoothRfcommProfileAsyncTrait {
     /// Get an object in order to accept a connection from or connect to a bluetooth peer
-    async fn connectable(&mut self) -> Result<BluetoothRfcommConnectableAsync, String>;
+    async fn connectable(&mut self) -> Result<BluetoothRfcommConnectableAsync, BluetoothError>;
 }
 
 /// Allows building an object to connect to bluetooth devices
 #[enum_dispatch::enum_dispatch]
 pub trait BluetoothRfcommProfileSyncTrait {
-    /// Get an object in order to accept a connection from or connect to a bluetooth peer
-    fn connectable(&mut self) -> Result<BluetoothRfcommConnectableSync, String>;
+    /// Wait up to the timeout for a bluetooth peer to connect, and get an object in order to accept the connection
+    fn connectable(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<BluetoothRfcommConnectableSync, BluetoothError>;
+}
+
+/// Allows building an object to accept l2cap connections from bluetooth devices
+#[async_trait::async_trait]
+#[enum_dispatch::enum_dispatch]
+pub trait BluetoothL2capProfileAsyncTrait {
+    /// Wait for a bluetooth peer to connect and get an object in order to accept the connection
+    async fn connectable(&mut self) -> Result<BluetoothL2capConnectableAsync, BluetoothError>;
+    /// The psm the profile is listening on. This is the dynamically allocated psm when no psm was requested.
+    fn psm(&self) -> Result<u16, BluetoothError>;
+}
+
+/// Allows building an object to accept l2cap connections from bluetooth devices
+#[enum_dispatch::enum_dispatch]
+pub trait BluetoothL2capProfileSyncTrait {
+    /// Wait up to the timeout for a bluetooth peer to connect, and get an object in order to accept the connection
+    fn connectable(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<BluetoothL2capConnectableSync, BluetoothError>;
+    /// The psm the profile is listening on. This is the dynamically allocated psm when no psm was requested.
+    fn psm(&self) -> Result<u16, BluetoothError>;
 }
 
 /// A bluetooth profile for rfcomm channels
@@ -637,12 +2279,12 @@ pub trait BluetoothRfcommProfileSyncTrait {
 pub enum BluetoothRfcommProfileAsync {
     /// The bluez library in linux is responsible for the profile
     #[cfg(target_os = "linux")]
-    Bluez(bluer::rfcomm::ProfileHandle),
+    Bluez(linux::RfcommProfile),
     /// Windows RFCOMM profile
     #[cfg(target_os = "windows")]
     Windows(windows::BluetoothRfcommProfile),
-    /// A dummy handler
-    Dummy(Dummy),
+    /// A profile on the in-memory dummy adapter
+    Dummy(dummy::RfcommProfile),
 }
 
 /// A bluetooth profile for rfcomm channels
@@ -651,26 +2293,350 @@ pub enum BluetoothRfcommProfileSync {
     /// Android rfcomm profile
     #[cfg(target_os = "android")]
     Android(android::BluetoothRfcommProfile),
-    /// A dummy handler
-    Dummy(Dummy),
+    /// A profile on the in-memory dummy adapter
+    Dummy(dummy::RfcommProfile),
 }
 
-/// A bluetooth profile for rfcomm channels
+impl BluetoothRfcommProfileAsync {
+    /// A stream of the connections accepted by the profile, so that several peers can be served one after
+    /// another without registering the profile again. Connections refused by the authorizer show up as errors.
+    /// When the profile can no longer wait for connections, that error is the last item of the stream.
+    pub fn incoming(
+        &mut self,
+    ) -> impl futures::Stream<Item = Result<(BluetoothStream, [u8; 6], u8), BluetoothError>> + '_
+    {
+        futures::stream::unfold(Some(self), |profile| async move {
+            let profile = profile?;
+            match profile.connectable().await {
+                Ok(c) => Some((c.accept().await, Some(profile))),
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+}
+
+impl BluetoothRfcommProfileSync {
+    /// An iterator of the connections accepted by the profile, so that several peers can be served one after
+    /// another without registering the profile again. Each accept waits up to the timeout, a timeout or a
+    /// connection refused by the authorizer shows up as an error and the iterator can be used again. Any other
+    /// error from waiting for a connection is the last item of the iterator.
+    pub fn incoming(&mut self, timeout: std::time::Duration) -> RfcommIncomingSync<'_> {
+        RfcommIncomingSync {
+            profile: Some(self),
+            timeout,
+        }
+    }
+}
+
+/// The connections accepted by a [`BluetoothRfcommProfileSync`], see [`BluetoothRfcommProfileSync::incoming`]
+pub struct RfcommIncomingSync<'a> {
+    /// The profile accepting the connections, `None` once the profile can no longer wait for connections
+    profile: Option<&'a mut BluetoothRfcommProfileSync>,
+    /// How long to wait for each connection
+    timeout: std::time::Duration,
+}
+
+impl Iterator for RfcommIncomingSync<'_> {
+    type Item = Result<BluetoothStream, BluetoothError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.profile.as_mut()?.connectable(self.timeout) {
+            Ok(c) => Some(c.accept()),
+            Err(BluetoothError::Timeout) => Some(Err(BluetoothError::Timeout)),
+            Err(e) => {
+                self.profile = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl std::iter::FusedIterator for RfcommIncomingSync<'_> {}
+
+/// A bluetooth profile for l2cap channels
 #[enum_dispatch::enum_dispatch(BluetoothL2capProfileAsyncTrait)]
 pub enum BluetoothL2capProfileAsync {
-    /// The bluez library in linux is responsible for the profile
+    /// An l2cap listener using the bluez library in linux
     #[cfg(target_os = "linux")]
-    Bluez(bluer::rfcomm::ProfileHandle),
-    /// A dummy handler
-    Dummy(Dummy),
+    Bluez(linux::L2capProfile),
+    /// A profile on the in-memory dummy adapter
+    Dummy(dummy::L2capProfile),
 }
 
-/// A bluetooth profile for rfcomm channels
+/// A bluetooth profile for l2cap channels
 #[enum_dispatch::enum_dispatch(BluetoothL2capProfileSyncTrait)]
 pub enum BluetoothL2capProfileSync {
-    /// Android rfcomm profile
+    /// Android l2cap profile
     #[cfg(target_os = "android")]
-    Android(android::BluetoothRfcommProfile),
+    Android(android::BluetoothL2capProfile),
+    /// A profile on the in-memory dummy adapter
+    Dummy(dummy::L2capProfile),
+}
+
+/// How a value is written to a gatt characteristic
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteMode {
+    /// The remote device acknowledges the write
+    WithResponse,
+    /// The write is not acknowledged
+    WithoutResponse,
+}
+
+/// The operations supported by a gatt characteristic
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GattCharacteristicFlags {
+    /// The value can be read
+    pub read: bool,
+    /// The value can be written with a response
+    pub write: bool,
+    /// The value can be written without a response
+    pub write_without_response: bool,
+    /// Changes to the value can be sent as notifications
+    pub notify: bool,
+    /// Changes to the value can be sent as indications
+    pub indicate: bool,
+}
+
+/// A characteristic of a gatt service on a remote device
+#[derive(Clone, Debug)]
+pub struct GattCharacteristic {
+    /// The uuid of the characteristic
+    pub uuid: BluetoothUuid,
+    /// The operations supported by the characteristic
+    pub flags: GattCharacteristicFlags,
+    /// The platform identifier of the service containing the characteristic
+    #[cfg(target_os = "linux")]
+    service_id: u16,
+    /// The platform identifier of the characteristic within the service
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    id: u16,
+}
+
+/// A gatt service on a remote device
+#[derive(Clone, Debug)]
+pub struct GattService {
+    /// The uuid of the service
+    pub uuid: BluetoothUuid,
+    /// Is this a primary service
+    pub primary: bool,
+    /// The characteristics of the service
+    pub characteristics: Vec<GattCharacteristic>,
+}
+
+impl GattService {
+    /// Find the characteristic of the service with the given uuid
+    pub fn characteristic(&self, uuid: &BluetoothUuid) -> Option<&GattCharacteristic> {
+        self.characteristics.iter().find(|c| &c.uuid == uuid)
+    }
+}
+
+/// The functionality of a gatt client for a remote device
+#[async_trait::async_trait]
+#[enum_dispatch::enum_dispatch]
+pub trait GattClientTrait {
+    /// List the services of the remote device, along with their characteristics
+    async fn services(&self) -> Result<Vec<GattService>, std::io::Error>;
+    /// Read the value of a characteristic
+    async fn read(&self, characteristic: &GattCharacteristic) -> Result<Vec<u8>, std::io::Error>;
+    /// Write the value of a characteristic
+    async fn write(
+        &self,
+        characteristic: &GattCharacteristic,
+        data: &[u8],
+        mode: WriteMode,
+    ) -> Result<(), std::io::Error>;
+    /// Receive the notifications or indications of a characteristic. Notifications stop when the receiver is dropped.
+    async fn subscribe(
+        &self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<tokio::sync::mpsc::Receiver<Vec<u8>>, std::io::Error>;
+}
+
+/// A gatt client for a remote device
+#[enum_dispatch::enum_dispatch(GattClientTrait)]
+pub enum GattClient {
+    /// A gatt client using the bluez library in linux
+    #[cfg(target_os = "linux")]
+    Bluez(linux::GattClient),
+    /// A gatt client using the android api
+    #[cfg(target_os = "android")]
+    Android(android::GattClient),
+    /// A dummy handler
+    Dummy(Dummy),
+}
+
+/// The future returned by the read callback of a local gatt characteristic
+pub type GattReadFuture =
+    std::pin::Pin<Box<dyn std::future::Future<Output = Result<Vec<u8>, std::io::Error>> + Send>>;
+
+/// The future returned by the write callback of a local gatt characteristic
+pub type GattWriteFuture =
+    std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), std::io::Error>> + Send>>;
+
+/// Called when a remote device reads a local gatt characteristic
+pub type GattReadCallback = std::sync::Arc<dyn Fn() -> GattReadFuture + Send + Sync>;
+
+/// Called with the value when a remote device writes a local gatt characteristic
+pub type GattWriteCallback = std::sync::Arc<dyn Fn(Vec<u8>) -> GattWriteFuture + Send + Sync>;
+
+/// A characteristic of a local gatt service. The properties of the characteristic are determined by which
+/// callbacks are present.
+#[derive(Clone)]
+pub struct GattCharacteristicDefinition {
+    /// The uuid of the characteristic
+    pub uuid: BluetoothUuid,
+    /// The characteristic can be read, the value is provided by this callback
+    pub read: Option<GattReadCallback>,
+    /// The characteristic can be written, with or without a response
+    pub write: Option<GattWriteCallback>,
+    /// The characteristic supports notifications, every value sent is notified to the subscribed devices
+    pub notify: Option<tokio::sync::broadcast::Sender<Vec<u8>>>,
+}
+
+/// A local gatt service
+#[derive(Clone)]
+pub struct GattServiceDefinition {
+    /// The uuid of the service
+    pub uuid: BluetoothUuid,
+    /// Is this a primary service
+    pub primary: bool,
+    /// The characteristics of the service
+    pub characteristics: Vec<GattCharacteristicDefinition>,
+}
+
+/// A registered local gatt service. The service is unregistered when this is dropped.
+pub enum GattServiceHandle {
+    /// The gatt application registered with the bluez library in linux
+    #[cfg(target_os = "linux")]
+    Bluez(linux::GattServiceHandle),
+    /// A dummy handler
+    Dummy(Dummy),
+}
+
+/// Settings for acting as a bluetooth hid device, such as a keyboard or a gamepad
+#[derive(Clone, Debug, Default)]
+pub struct HidSettings {
+    /// The name of the device
+    pub name: String,
+    /// A description of the device
+    pub description: String,
+    /// The name of the provider of the device
+    pub provider: String,
+    /// The hid device subclass, the minor device class of a peripheral. For example 0x08 is a gamepad and 0x40
+    /// is a keyboard.
+    pub subclass: u8,
+    /// The hid report descriptor, describing the reports that are sent and received
+    pub descriptor: Vec<u8>,
+}
+
+/// The types of hid reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HidReportType {
+    /// Reports sent from the device to the host
+    Input,
+    /// Reports sent from the host to the device
+    Output,
+    /// Configuration reports, read and written by the host
+    Feature,
+}
+
+impl HidReportType {
+    /// The value of the report type in the hid protocol
+    pub fn value(self) -> u8 {
+        match self {
+            HidReportType::Input => 1,
+            HidReportType::Output => 2,
+            HidReportType::Feature => 3,
+        }
+    }
+
+    /// Get the report type from its value in the hid protocol
+    pub fn from_value(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(HidReportType::Input),
+            2 => Some(HidReportType::Output),
+            3 => Some(HidReportType::Feature),
+            _ => None,
+        }
+    }
+}
+
+/// Events for a registered hid device. A report id of 0 means the report descriptor does not use report ids.
+#[derive(Clone, Debug)]
+pub enum HidEvent {
+    /// A host connected or disconnected. Reports can be sent to connected hosts.
+    ConnectionChanged {
+        /// The address of the host
+        device: BluetoothAdapterAddress,
+        /// Is the host connected now
+        connected: bool,
+    },
+    /// The host asks for a report, answer with [`HidDeviceTrait::reply_report`]
+    GetReport {
+        /// The address of the host
+        device: BluetoothAdapterAddress,
+        /// The type of report requested
+        report_type: HidReportType,
+        /// The id of the report requested
+        id: u8,
+        /// The largest report the host accepts, 0 when the host did not say
+        buffer_size: u16,
+    },
+    /// The host set a report. Receiving the report is acknowledged to the host automatically.
+    SetReport {
+        /// The address of the host
+        device: BluetoothAdapterAddress,
+        /// The type of report set
+        report_type: HidReportType,
+        /// The id of the report set
+        id: u8,
+        /// The contents of the report
+        data: Vec<u8>,
+    },
+    /// The host sent an output report, such as the state of keyboard leds
+    OutputReport {
+        /// The address of the host
+        device: BluetoothAdapterAddress,
+        /// The id of the report
+        id: u8,
+        /// The contents of the report
+        data: Vec<u8>,
+    },
+    /// The host unplugged the virtual cable, it should be forgotten
+    VirtualCableUnplug(BluetoothAdapterAddress),
+}
+
+/// The functionality of a registered hid device
+#[enum_dispatch::enum_dispatch]
+pub trait HidDeviceTrait {
+    /// Send an input report to a connected host. Use an id of 0 when the report descriptor does not use report ids.
+    fn send_report(
+        &self,
+        device: &BluetoothAdapterAddress,
+        id: u8,
+        data: &[u8],
+    ) -> Result<(), BluetoothError>;
+    /// Answer a [`HidEvent::GetReport`] from the host
+    fn reply_report(
+        &self,
+        device: &BluetoothAdapterAddress,
+        report_type: HidReportType,
+        id: u8,
+        data: &[u8],
+    ) -> Result<(), BluetoothError>;
+    /// Take the receiver for the events of the device. This can only be taken once, later calls return `None`.
+    fn take_events(&mut self) -> Option<tokio::sync::mpsc::Receiver<HidEvent>>;
+}
+
+/// A registered hid device. The device is unregistered when this is dropped.
+#[enum_dispatch::enum_dispatch(HidDeviceTrait)]
+pub enum HidDeviceHandle {
+    /// The hid device using the android api
+    #[cfg(target_os = "android")]
+    Android(android::HidDevice),
+    /// The hid device using the bluez library and l2cap sockets in linux
+    #[cfg(target_os = "linux")]
+    Bluez(linux::HidDevice),
     /// A dummy handler
     Dummy(Dummy),
 }
@@ -678,15 +2644,59 @@ pub enum BluetoothL2capProfileSync {
 /// A dummy struct for ensuring enums are not empty
 pub struct Dummy {}
 
-impl BluetoothRfcommProfileSyncTrait for Dummy {
-    fn connectable(&mut self) -> Result<BluetoothRfcommConnectableSync, String> {
-        unimplemented!()
+impl HidDeviceTrait for Dummy {
+    fn send_report(
+        &self,
+        _device: &BluetoothAdapterAddress,
+        _id: u8,
+        _data: &[u8],
+    ) -> Result<(), BluetoothError> {
+        Err(BluetoothError::Unsupported(
+            "A hid report on a dummy device",
+        ))
+    }
+
+    fn reply_report(
+        &self,
+        _device: &BluetoothAdapterAddress,
+        _report_type: HidReportType,
+        _id: u8,
+        _data: &[u8],
+    ) -> Result<(), BluetoothError> {
+        Err(BluetoothError::Unsupported(
+            "A hid report on a dummy device",
+        ))
+    }
+
+    fn take_events(&mut self) -> Option<tokio::sync::mpsc::Receiver<HidEvent>> {
+        None
     }
 }
 
-impl BluetoothRfcommProfileAsyncTrait for Dummy {
-    async fn connectable(&mut self) -> Result<BluetoothRfcommConnectableAsync, String> {
-        unimplemented!()
+#[async_trait::async_trait]
+impl GattClientTrait for Dummy {
+    async fn services(&self) -> Result<Vec<GattService>, std::io::Error> {
+        Err(BluetoothError::Unsupported("A gatt client on a dummy device").into())
+    }
+
+    async fn read(&self, _characteristic: &GattCharacteristic) -> Result<Vec<u8>, std::io::Error> {
+        Err(BluetoothError::Unsupported("A gatt client on a dummy device").into())
+    }
+
+    async fn write(
+        &self,
+        _characteristic: &GattCharacteristic,
+        _data: &[u8],
+        _mode: WriteMode,
+    ) -> Result<(), std::io::Error> {
+        Err(BluetoothError::Unsupported("A gatt client on a dummy device").into())
+    }
+
+    async fn subscribe(
+        &self,
+        _characteristic: &GattCharacteristic,
+    ) -> Result<tokio::sync::mpsc::Receiver<Vec<u8>>, std::io::Error> {
+        Err(BluetoothError::Unsupported("A gatt client on a dummy device").into())
     }
 }
 
@@ -699,7 +2709,7 @@ pub trait SyncReadWrite: std::io::Read + std::io::Write + Send {}
 impl<T: std::io::Read + std::io::Write + Unpin + Send> SyncReadWrite for T {}
 
 
-/// The common functions for all bluetooth rfcomm sockets
+/// The common functions for all bluetooth rfcomm and l2cap sockets
 #[async_trait::async_trait]
 #[enum_dispatch::enum_dispatch]
 pub trait BluetoothSocketTrait {
@@ -709,6 +2719,12 @@ pub trait BluetoothSocketTrait {
     async fn async_connect(&mut self) -> Result<(), std::io::Error>;
     /// connect the socket
     fn sync_connect(&mut self) -> Result<(), std::io::Error>;
+    /// Close the socket, closing a socket that is not connected does nothing
+    fn close(&mut self) -> Result<(), BluetoothError>;
+    /// Turn the socket into a stream for reading and writing. The socket must be connected with `async_connect`
+    /// first, except on android where this connects the socket instead, because a socket connected with
+    /// `sync_connect` is already read by its own background thread.
+    fn into_stream(self) -> Result<BluetoothStream, BluetoothError>;
     /// Does the socket support async?
     fn supports_async(&mut self) -> Option<&mut dyn AsyncReadWrite> {
         None
@@ -717,18 +2733,174 @@ pub trait BluetoothSocketTrait {
     fn supports_sync(&mut self) -> Option<&mut dyn SyncReadWrite> {
         None
     }
+    /// How long a sync read waits for data, `None` means it waits until data arrives or the socket disconnects
+    fn read_timeout(&self) -> Result<Option<std::time::Duration>, std::io::Error> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+    }
+    /// Set how long a sync read waits for data, `None` means it waits until data arrives or the socket
+    /// disconnects. A zero duration is an error, like it is for [`BluetoothStream::set_read_timeout`].
+    fn set_read_timeout(
+        &mut self,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<(), std::io::Error> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+    }
+    /// Set a callback for when data arrives on the socket. It is called with the number of bytes received, and
+    /// with `None` when the socket disconnects. The callback runs on a background thread or task, so it should
+    /// only be used to wake up the code that reads the socket.
+    fn set_read_callback(&mut self, _callback: ReadCallback) -> Result<(), std::io::Error> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+    }
+    /// Remove the callback for when data arrives on the socket
+    fn clear_read_callback(&mut self) {}
+    /// The largest packet that can be received at once. For l2cap this is the mtu of the channel.
+    fn max_receive_packet_size(&self) -> Result<usize, std::io::Error> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+    }
+    /// The largest packet that can be sent at once. For l2cap this is the mtu of the remote device, and larger
+    /// writes fail, so frames need to be split to fit.
+    fn max_transmit_packet_size(&self) -> Result<usize, std::io::Error> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+    }
 }
 
-/// A bluetooth rfcomm socket
+/// The rfcomm frame size that every device supports. It is reported as the packet size of rfcomm links when the
+/// negotiated size is not available.
+pub const RFCOMM_DEFAULT_MTU: usize = 127;
+
+/// A callback for when data arrives on a socket, see [`BluetoothSocketTrait::set_read_callback`]
+pub type ReadCallback = Box<dyn Fn(Option<usize>) + Send + 'static>;
+
+/// A bluetooth rfcomm or l2cap socket
 #[enum_dispatch::enum_dispatch(BluetoothSocketTrait)]
 pub enum BluetoothSocket {
     /// The android based rfcomm socket
     #[cfg(target_os = "android")]
     Android(android::BluetoothSocket),
-    /// Linux using bluez library
+    /// Linux rfcomm socket using bluez library
     #[cfg(target_os = "linux")]
     Bluez(linux::BluetoothRfcommSocket),
+    /// Linux l2cap socket using bluez library
+    #[cfg(target_os = "linux")]
+    BluezL2cap(linux::BluetoothL2capSocket),
     /// Windows bluetooth socket
     #[cfg(target_os = "windows")]
     Windows(windows::BluetoothRfcommSocket),
+    /// A socket to a profile of the in-memory dummy adapter
+    Dummy(dummy::BluetoothSocket),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that a build failed because the builder was misconfigured
+    fn assert_misconfigured(r: Result<BluetoothAdapter, BluetoothError>) {
+        match r {
+            Err(BluetoothError::BuilderMisconfigured(_)) => {}
+            Err(e) => panic!("Expected BuilderMisconfigured, got {e:?}"),
+            Ok(_) => panic!("Expected BuilderMisconfigured, the build succeeded"),
+        }
+    }
+
+    #[test]
+    fn builder_rejects_invalid_adapter_address() {
+        let mut b = BluetoothAdapterBuilder::new();
+        b.with_adapter(BluetoothAdapterAddress::String("nope".to_string()));
+        assert_misconfigured(b.build());
+    }
+
+    #[test]
+    fn builder_rejects_zero_response_timeout() {
+        let mut b = BluetoothAdapterBuilder::new();
+        b.with_agent_config(AgentConfig {
+            response_timeout: std::time::Duration::ZERO,
+            ..AgentConfig::default()
+        });
+        assert_misconfigured(b.build());
+    }
+
+    #[tokio::test]
+    async fn async_builder_rejects_invalid_adapter_address() {
+        let mut b = BluetoothAdapterBuilder::new();
+        b.with_adapter(BluetoothAdapterAddress::String("00:11:22".to_string()));
+        assert_misconfigured(b.async_build().await);
+    }
+
+    #[cfg(target_os = "android")]
+    #[test]
+    fn builder_requires_android_app() {
+        assert_misconfigured(BluetoothAdapterBuilder::new().build());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn builder_validates_before_building_a_mock() {
+        let mut b = BluetoothAdapterBuilder::new();
+        b.with_mock(MockAdapter::new());
+        b.with_adapter(BluetoothAdapterAddress::String("nope".to_string()));
+        assert_misconfigured(b.build());
+
+        let mut b = BluetoothAdapterBuilder::new();
+        b.with_mock(MockAdapter::new());
+        b.with_adapter(BluetoothAdapterAddress::Byte([0x55; 6]));
+        assert!(matches!(b.build(), Ok(BluetoothAdapter::Mock(_))));
+    }
+
+    /// The settings of an spp profile with the given changes
+    fn spp_settings(
+        f: impl FnOnce(BluetoothRfcommProfileSettingsBuilder) -> BluetoothRfcommProfileSettingsBuilder,
+    ) -> Result<BluetoothRfcommProfileSettings, ProfileSettingsError> {
+        f(BluetoothRfcommProfileSettings::builder().uuid(BluetoothUuid::SPP)).build()
+    }
+
+    #[test]
+    fn settings_reject_channels_outside_1_to_30() {
+        assert_eq!(
+            spp_settings(|b| b.channel(0)).err(),
+            Some(ProfileSettingsError::InvalidChannel(0))
+        );
+        assert_eq!(
+            spp_settings(|b| b.channel(31)).err(),
+            Some(ProfileSettingsError::InvalidChannel(31))
+        );
+        assert!(spp_settings(|b| b.channel(1)).is_ok());
+        assert!(spp_settings(|b| b.channel(30)).is_ok());
+    }
+
+    #[test]
+    fn settings_reject_invalid_psms() {
+        assert_eq!(
+            spp_settings(|b| b.psm(0x1002)).err(),
+            Some(ProfileSettingsError::InvalidPsm(0x1002))
+        );
+        assert_eq!(
+            spp_settings(|b| b.psm(0x0003)).err(),
+            Some(ProfileSettingsError::InvalidPsm(0x0003))
+        );
+        assert!(spp_settings(|b| b.psm(0x1001)).is_ok());
+    }
+
+    #[test]
+    fn settings_reject_an_invalid_service_uuid() {
+        let mut settings = spp_settings(|b| b).unwrap();
+        settings.service_uuid = Some("nope".to_string());
+        assert!(matches!(
+            settings.validate(),
+            Err(ProfileSettingsError::InvalidServiceUuid(_))
+        ));
+    }
+
+    #[test]
+    fn settings_builder_needs_a_uuid() {
+        let err = BluetoothRfcommProfileSettings::builder()
+            .channel(1)
+            .build()
+            .unwrap_err();
+        assert_eq!(err, ProfileSettingsError::MissingUuid);
+        assert!(matches!(
+            BluetoothError::from(err),
+            BluetoothError::InvalidSettings(ProfileSettingsError::MissingUuid)
+        ));
+    }
 }
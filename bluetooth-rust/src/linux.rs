@@ -1,84 +1,296 @@
 //! Linux specific bluetooth code
 
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
 
 use bluer::AdapterEvent;
 use futures::FutureExt;
 use futures::StreamExt;
 
+/// Maps bluer errors to `std::io::Error`, keeping the error kinds that
+/// callers are likely to act on.
+pub(crate) fn berr(e: bluer::Error) -> std::io::Error {
+    use bluer::ErrorKind::*;
+    let err = match e.kind {
+        NotAuthorized | NotPermitted | AuthenticationRejected => {
+            crate::BluetoothError::PermissionDenied
+        }
+        NotReady => crate::BluetoothError::AdapterUnavailable,
+        AuthenticationTimeout => crate::BluetoothError::Timeout,
+        ConnectionAttemptFailed | AuthenticationFailed | AuthenticationCanceled => {
+            crate::BluetoothError::ConnectionFailed(e.message)
+        }
+        NotSupported => crate::BluetoothError::Unsupported("The operation"),
+        InvalidArguments => {
+            return std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string());
+        }
+        DoesNotExist => return std::io::Error::new(std::io::ErrorKind::NotFound, e.to_string()),
+        _ => return std::io::Error::other(e.to_string()),
+    };
+    err.into()
+}
+
 // ────────────────────────────────────────────────────────────────────────────
-// BluetoothRfcommConnectableAsyncTrait for bluer::rfcomm::ConnectRequest
+// RfcommConnectable – incoming rfcomm connections
 // ────────────────────────────────────────────────────────────────────────────
 
+/// An incoming rfcomm connection that has not been accepted or rejected yet.
+/// The connection is rejected if this is dropped without accepting it.
+pub struct RfcommConnectable {
+    /// The request from BlueZ, taken when the request is answered
+    request: Option<bluer::rfcomm::ConnectRequest>,
+    /// The session, used to look up the device making the request
+    session: bluer::Session,
+}
+
 #[async_trait::async_trait]
-impl super::BluetoothRfcommConnectableAsyncTrait for bluer::rfcomm::ConnectRequest {
-    async fn accept(self) -> Result<(crate::BluetoothStream, [u8; 6], u8), String> {
-        let s = bluer::rfcomm::ConnectRequest::accept(self);
-        match s {
-            Ok(s) => {
-                let addr = s.peer_addr().map_err(|e| e.to_string())?;
-                Ok((
-                    crate::BluetoothStream::Bluez(Box::pin(s)),
-                    *addr.addr,
-                    addr.channel,
-                ))
+impl super::BluetoothRfcommConnectableAsyncTrait for RfcommConnectable {
+    async fn accept(
+        mut self,
+    ) -> Result<(crate::BluetoothStream, [u8; 6], u8), crate::BluetoothError> {
+        let Some(request) = self.request.take() else {
+            return Err(crate::BluetoothError::ConnectionFailed(
+                "The connection was already answered".to_string(),
+            ));
+        };
+        let s = bluer::rfcomm::ConnectRequest::accept(request).map_err(berr)?;
+        let addr = s.peer_addr()?;
+        Ok((
+            crate::BluetoothStream::Bluez(Box::pin(s)),
+            *addr.addr,
+            addr.channel,
+        ))
+    }
+
+    async fn reject(mut self) {
+        if let Some(request) = self.request.take() {
+            request.reject(bluer::agent::ReqError::Rejected);
+        }
+    }
+
+    fn peer_address(&self) -> super::BluetoothAdapterAddress {
+        super::BluetoothAdapterAddress::Byte(self.device().0)
+    }
+
+    /// The request does not say which adapter it arrived on, so every adapter is checked for the device
+    async fn peer_name(&self) -> Option<String> {
+        let names = self.session.adapter_names().await.ok()?;
+        for name in names {
+            let Ok(adapter) = self.session.adapter(&name) else {
+                continue;
+            };
+            let Ok(device) = adapter.device(self.device()) else {
+                continue;
+            };
+            if let Ok(Some(n)) = device.name().await {
+                return Some(n);
             }
-            Err(e) => Err(e.to_string()),
+        }
+        None
+    }
+}
+
+impl RfcommConnectable {
+    /// The address of the device making the request
+    fn device(&self) -> bluer::Address {
+        self.request
+            .as_ref()
+            .map(|r| r.device())
+            .unwrap_or_else(bluer::Address::any)
+    }
+}
+
+impl Drop for RfcommConnectable {
+    fn drop(&mut self) {
+        if let Some(request) = self.request.take() {
+            request.reject(bluer::agent::ReqError::Rejected);
         }
     }
 }
 
 // ────────────────────────────────────────────────────────────────────────────
-// BluetoothRfcommProfileAsyncTrait for bluer::rfcomm::ProfileHandle
+// RfcommProfile – rfcomm profiles registered with BlueZ
 // ────────────────────────────────────────────────────────────────────────────
 
-impl super::BluetoothRfcommProfileAsyncTrait for bluer::rfcomm::ProfileHandle {
-    async fn connectable(&mut self) -> Result<crate::BluetoothRfcommConnectableAsync, String> {
-        self.next()
+/// An rfcomm profile registered with the BlueZ ProfileManager1. The profile
+/// is unregistered when this is dropped.
+pub struct RfcommProfile {
+    /// The handle for the registered profile
+    handle: bluer::rfcomm::ProfileHandle,
+    /// The session the profile is registered with
+    session: bluer::Session,
+}
+
+#[async_trait::async_trait]
+impl super::BluetoothRfcommProfileAsyncTrait for RfcommProfile {
+    async fn connectable(
+        &mut self,
+    ) -> Result<crate::BluetoothRfcommConnectableAsync, crate::BluetoothError> {
+        self.handle
+            .next()
             .await
-            .map(|a| crate::BluetoothRfcommConnectableAsync::Bluez(a))
-            .ok_or_else(|| "Failed to get bluetooth connection".to_string())
+            .map(|request| {
+                crate::BluetoothRfcommConnectableAsync::Bluez(RfcommConnectable {
+                    request: Some(request),
+                    session: self.session.clone(),
+                })
+            })
+            .ok_or_else(|| {
+                crate::BluetoothError::ConnectionFailed(
+                    "Failed to get bluetooth connection".to_string(),
+                )
+            })
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// L2capProfile – listening L2CAP sockets
+// ────────────────────────────────────────────────────────────────────────────
+
+/// A listening l2cap socket that accepts connection oriented channels from
+/// bluetooth peers.
+///
+/// Unlike rfcomm profiles this is not registered with the BlueZ
+/// ProfileManager1, which only hands out rfcomm channels. No SDP record is
+/// published for the psm, so peers must know the psm in advance or find it
+/// through a record registered separately.
+pub struct L2capProfile {
+    /// The listening socket
+    listener: bluer::l2cap::StreamListener,
+}
+
+impl L2capProfile {
+    /// Bind and listen on the psm from the settings, or on a dynamically
+    /// allocated psm when the settings do not specify one. The local address
+    /// selects the adapter to listen on, `Address::any()` listens on all of them.
+    fn new(
+        settings: &super::BluetoothL2capProfileSettings,
+        local: bluer::Address,
+    ) -> Result<Self, crate::BluetoothError> {
+        if settings.role == Some(super::ProfileRole::Client) {
+            // l2cap profiles only accept connections, get_l2cap_socket makes outgoing ones
+            return Err(crate::BluetoothError::Unsupported(
+                "A client role l2cap profile",
+            ));
+        }
+        let socket = bluer::l2cap::Socket::<bluer::l2cap::Stream>::new_stream()?;
+        if settings.authenticate.unwrap_or(false) {
+            socket.set_security(bluer::l2cap::Security {
+                level: bluer::l2cap::SecurityLevel::Medium,
+                key_size: 0,
+            })?;
+        }
+        let addr = bluer::l2cap::SocketAddr::new(
+            local,
+            bluer::AddressType::BrEdr,
+            settings.psm.unwrap_or(0),
+        );
+        socket.bind(addr)?;
+        let listener = socket.listen(1)?;
+        Ok(Self { listener })
+    }
+}
+
+#[async_trait::async_trait]
+impl super::BluetoothL2capProfileAsyncTrait for L2capProfile {
+    async fn connectable(
+        &mut self,
+    ) -> Result<crate::BluetoothL2capConnectableAsync, crate::BluetoothError> {
+        let (stream, addr) = self.listener.accept().await?;
+        Ok(crate::BluetoothL2capConnectableAsync::Bluez(
+            L2capConnectable { stream, addr },
+        ))
+    }
+
+    fn psm(&self) -> Result<u16, crate::BluetoothError> {
+        Ok(self.listener.as_ref().local_addr()?.psm)
+    }
+}
+
+/// An incoming l2cap connection that has not been handed to the user yet
+pub struct L2capConnectable {
+    /// The stream for the connection
+    stream: bluer::l2cap::Stream,
+    /// The address of the peer
+    addr: bluer::l2cap::SocketAddr,
+}
+
+#[async_trait::async_trait]
+impl super::BluetoothL2capConnectableAsyncTrait for L2capConnectable {
+    async fn accept(self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        log::info!("Accepted l2cap connection from {:?}", self.addr);
+        Ok(crate::BluetoothStream::BluezL2cap(Box::pin(self.stream)))
     }
 }
 
 // ────────────────────────────────────────────────────────────────────────────
-// Internal active-connection holder (RFCOMM or L2CAP stream)
+// BluetoothRfcommSocket – outgoing RFCOMM connections
 // ────────────────────────────────────────────────────────────────────────────
 
-/// Holds the active stream for either an RFCOMM or L2CAP connection.
-enum BluetoothConnection {
-    /// An active RFCOMM stream
-    Rfcomm(bluer::rfcomm::Stream),
-    /// An active L2CAP stream
-    L2cap(bluer::l2cap::Stream),
+/// An outgoing RFCOMM bluetooth socket. The socket is created lazily; call
+/// `connect()` before doing any I/O.
+pub struct BluetoothRfcommSocket {
+    /// Address of the remote device
+    device_addr: bluer::Address,
+    /// RFCOMM channel to connect on.
+    ///
+    /// BlueZ does not expose a direct SDP query API through the bluer crate, so
+    /// callers are responsible for resolving the correct channel via
+    /// `run_sdp` / `get_uuids` before creating the socket, or by relying on
+    /// a well-known channel number for the service.
+    rfcomm_channel: u8,
+    /// Whether to request an encrypted / authenticated link
+    is_secure: bool,
+    /// The live connection, present after a successful `connect()` call
+    connection: Option<bluer::rfcomm::Stream>,
+    /// Calls the read callback when data arrives
+    read_watcher: Option<ReadWatcher>,
+}
+
+impl BluetoothRfcommSocket {
+    /// Create a new (unconnected) RFCOMM socket.
+    fn new(device_addr: bluer::Address, channel: u8, is_secure: bool) -> Self {
+        Self {
+            device_addr,
+            rfcomm_channel: channel,
+            is_secure,
+            connection: None,
+            read_watcher: None,
+        }
+    }
 }
 
-impl tokio::io::AsyncRead for BluetoothConnection {
+impl tokio::io::AsyncRead for BluetoothRfcommSocket {
     fn poll_read(
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
         buf: &mut tokio::io::ReadBuf<'_>,
     ) -> std::task::Poll<std::io::Result<()>> {
-        match self.get_mut() {
-            Self::Rfcomm(s) => {
-                tokio::io::AsyncRead::poll_read(std::pin::Pin::new(s), cx, buf)
-            }
-            Self::L2cap(s) => {
-                tokio::io::AsyncRead::poll_read(std::pin::Pin::new(s), cx, buf)
-            }
+        if let Some(conn) = &mut self.get_mut().connection {
+            tokio::io::AsyncRead::poll_read(std::pin::Pin::new(conn), cx, buf)
+        } else {
+            std::task::Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "not connected",
+            )))
         }
     }
 }
 
-impl tokio::io::AsyncWrite for BluetoothConnection {
+impl tokio::io::AsyncWrite for BluetoothRfcommSocket {
     fn poll_write(
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
         buf: &[u8],
     ) -> std::task::Poll<std::io::Result<usize>> {
-        match self.get_mut() {
-            Self::Rfcomm(s) => tokio::io::AsyncWrite::poll_write(std::pin::Pin::new(s), cx, buf),
-            Self::L2cap(s) => tokio::io::AsyncWrite::poll_write(std::pin::Pin::new(s), cx, buf),
+        if let Some(conn) = &mut self.get_mut().connection {
+            tokio::io::AsyncWrite::poll_write(std::pin::Pin::new(conn), cx, buf)
+        } else {
+            std::task::Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "not connected",
+            )))
         }
     }
 
@@ -86,9 +298,10 @@ impl tokio::io::AsyncWrite for BluetoothConnection {
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<std::io::Result<()>> {
-        match self.get_mut() {
-            Self::Rfcomm(s) => tokio::io::AsyncWrite::poll_flush(std::pin::Pin::new(s), cx),
-            Self::L2cap(s) => tokio::io::AsyncWrite::poll_flush(std::pin::Pin::new(s), cx),
+        if let Some(conn) = &mut self.get_mut().connection {
+            tokio::io::AsyncWrite::poll_flush(std::pin::Pin::new(conn), cx)
+        } else {
+            std::task::Poll::Ready(Ok(()))
         }
     }
 
@@ -96,64 +309,305 @@ impl tokio::io::AsyncWrite for BluetoothConnection {
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<std::io::Result<()>> {
-        match self.get_mut() {
-            Self::Rfcomm(s) => tokio::io::AsyncWrite::poll_shutdown(std::pin::Pin::new(s), cx),
-            Self::L2cap(s) => tokio::io::AsyncWrite::poll_shutdown(std::pin::Pin::new(s), cx),
+        if let Some(conn) = &mut self.get_mut().connection {
+            tokio::io::AsyncWrite::poll_shutdown(std::pin::Pin::new(conn), cx)
+        } else {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::BluetoothSocketTrait for BluetoothRfcommSocket {
+    fn supports_async(&mut self) -> Option<&mut (dyn super::AsyncReadWrite)> {
+        Some(self)
+    }
+
+    /// The callback is called from a task watching the socket
+    fn set_read_callback(&mut self, callback: crate::ReadCallback) -> Result<(), std::io::Error> {
+        use std::os::fd::AsRawFd;
+        let Some(conn) = &self.connection else {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        };
+        self.read_watcher = Some(ReadWatcher::new(conn.as_raw_fd(), callback)?);
+        Ok(())
+    }
+
+    fn clear_read_callback(&mut self) {
+        self.read_watcher = None;
+    }
+
+    fn is_connected(&self) -> Result<bool, std::io::Error> {
+        Ok(self.connection.is_some())
+    }
+
+    /// The kernel does not report the negotiated rfcomm frame size, so this is the size every device supports
+    fn max_receive_packet_size(&self) -> Result<usize, std::io::Error> {
+        if self.connection.is_none() {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        }
+        Ok(crate::RFCOMM_DEFAULT_MTU)
+    }
+
+    /// The kernel does not report the negotiated rfcomm frame size, so this is the size every device supports
+    fn max_transmit_packet_size(&self) -> Result<usize, std::io::Error> {
+        if self.connection.is_none() {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        }
+        Ok(crate::RFCOMM_DEFAULT_MTU)
+    }
+
+    /// The kernel keeps the timeout with the socket, rounded up to its clock tick. It is applied by the
+    /// timeout aware reads of the stream the socket turns into.
+    fn read_timeout(&self) -> Result<Option<std::time::Duration>, std::io::Error> {
+        let Some(conn) = &self.connection else {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        };
+        socket_timeout(conn, libc::SO_RCVTIMEO)
+    }
+
+    fn set_read_timeout(
+        &mut self,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<(), std::io::Error> {
+        let Some(conn) = &self.connection else {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        };
+        set_socket_timeout(conn, libc::SO_RCVTIMEO, crate::check_timeout(timeout)?)
+    }
+
+    fn sync_connect(&mut self) -> Result<(), std::io::Error> {
+        Err(std::io::Error::other("sync not supported"))
+    }
+
+    /// Dropping the connection closes the socket
+    fn close(&mut self) -> Result<(), crate::BluetoothError> {
+        self.read_watcher = None;
+        self.connection = None;
+        Ok(())
+    }
+
+    fn into_stream(self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        let stream = self
+            .connection
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotConnected))?;
+        Ok(crate::BluetoothStream::Bluez(Box::pin(stream)))
+    }
+
+    async fn async_connect(&mut self) -> Result<(), std::io::Error> {
+        if self.connection.is_some() {
+            return Ok(());
+        }
+        let addr = bluer::rfcomm::SocketAddr::new(self.device_addr, self.rfcomm_channel);
+        let socket = bluer::rfcomm::Socket::new()?;
+        if self.is_secure {
+            socket.set_security(bluer::rfcomm::Security {
+                level: bluer::rfcomm::SecurityLevel::Medium,
+                key_size: 0,
+            })?;
+        }
+        let stream = socket.connect(addr).await?;
+        log::info!(
+            "STREAM {:?} to {:?}",
+            stream.as_ref().local_addr(),
+            stream.peer_addr()
+        );
+        self.connection = Some(stream);
+        log::info!("Got an rfcomm stream");
+        Ok(())
+    }
+}
+
+/// Shut down both directions of a socket, ending the connection. The descriptor stays open until its owner
+/// drops it. Shutting down a socket that is no longer connected does nothing.
+pub(crate) fn shutdown_socket(socket: &impl std::os::fd::AsRawFd) -> Result<(), std::io::Error> {
+    // Safety: shutdown only changes the state of the connection, the descriptor stays valid
+    if unsafe { libc::shutdown(socket.as_raw_fd(), libc::SHUT_RDWR) } < 0 {
+        let e = std::io::Error::last_os_error();
+        if e.kind() != std::io::ErrorKind::NotConnected {
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Check if a socket is still connected without waiting or consuming any data. A peek that finds the end of the
+/// stream or an error other than having no data means the connection is gone.
+pub(crate) fn socket_is_open(socket: &impl std::os::fd::AsRawFd) -> bool {
+    let mut byte = 0u8;
+    // Safety: peeks at most one byte into a valid buffer, the data stays in the socket
+    let peeked = unsafe {
+        libc::recv(
+            socket.as_raw_fd(),
+            &mut byte as *mut u8 as *mut libc::c_void,
+            1,
+            libc::MSG_PEEK | libc::MSG_DONTWAIT,
+        )
+    };
+    peeked > 0
+        || (peeked < 0 && std::io::Error::last_os_error().kind() == std::io::ErrorKind::WouldBlock)
+}
+
+/// Keep a timeout in the SO_RCVTIMEO or SO_SNDTIMEO option of a socket. The sockets of bluer are non-blocking,
+/// so the kernel never applies the option, it only keeps the timeout with the socket for the stream to apply.
+/// The kernel rounds the timeout up to its clock tick.
+pub(crate) fn set_socket_timeout(
+    socket: &impl std::os::fd::AsRawFd,
+    option: libc::c_int,
+    timeout: Option<std::time::Duration>,
+) -> Result<(), std::io::Error> {
+    let timeout = timeout.unwrap_or_default();
+    let mut tv = libc::timeval {
+        tv_sec: timeout.as_secs() as libc::time_t,
+        tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+    };
+    // A zero timeval means no timeout, so shorter timeouts are rounded up
+    if tv.tv_sec == 0 && tv.tv_usec == 0 && !timeout.is_zero() {
+        tv.tv_usec = 1;
+    }
+    // Safety: the option value is a timeval of the given size
+    let r = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            option,
+            &tv as *const libc::timeval as *const libc::c_void,
+            std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+        )
+    };
+    if r < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Get the timeout kept in the SO_RCVTIMEO or SO_SNDTIMEO option of a socket, `None` when there is no timeout
+pub(crate) fn socket_timeout(
+    socket: &impl std::os::fd::AsRawFd,
+    option: libc::c_int,
+) -> Result<Option<std::time::Duration>, std::io::Error> {
+    let mut tv = libc::timeval {
+        tv_sec: 0,
+        tv_usec: 0,
+    };
+    let mut len = std::mem::size_of::<libc::timeval>() as libc::socklen_t;
+    // Safety: the option value is written to a timeval of the given size
+    let r = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            option,
+            &mut tv as *mut libc::timeval as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if r < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let timeout = std::time::Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000);
+    Ok((!timeout.is_zero()).then_some(timeout))
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// ReadWatcher
+// ────────────────────────────────────────────────────────────────────────────
+
+/// A task that calls a read callback when data arrives on a socket. The task
+/// stops when this is dropped.
+struct ReadWatcher(tokio::task::JoinHandle<()>);
+
+impl ReadWatcher {
+    /// Watch a duplicate of the socket, so the stream can still be read normally
+    fn new(fd: std::os::fd::RawFd, callback: crate::ReadCallback) -> Result<Self, std::io::Error> {
+        use std::os::fd::{AsRawFd, FromRawFd};
+        // Safety: dup does not affect the original descriptor
+        let dup = unsafe { libc::dup(fd) };
+        if dup < 0 {
+            return Err(std::io::Error::last_os_error());
         }
+        // Safety: the duplicate is a new descriptor that nothing else owns
+        let fd = unsafe { std::os::fd::OwnedFd::from_raw_fd(dup) };
+        let fd = tokio::io::unix::AsyncFd::new(fd)?;
+        Ok(Self(tokio::spawn(async move {
+            loop {
+                let Ok(mut guard) = fd.readable().await else {
+                    callback(None);
+                    break;
+                };
+                let mut byte = 0u8;
+                // Safety: peeks at most one byte into a valid buffer, the data stays in the socket
+                let peeked = unsafe {
+                    libc::recv(
+                        fd.as_raw_fd(),
+                        &mut byte as *mut u8 as *mut libc::c_void,
+                        1,
+                        libc::MSG_PEEK | libc::MSG_DONTWAIT,
+                    )
+                };
+                if peeked == 0 {
+                    callback(None);
+                    break;
+                } else if peeked > 0 {
+                    let mut available: libc::c_int = 0;
+                    // Safety: FIONREAD writes the number of bytes waiting in the socket
+                    unsafe { libc::ioctl(fd.as_raw_fd(), libc::FIONREAD, &mut available) };
+                    callback(Some(available.max(1) as usize));
+                } else if std::io::Error::last_os_error().kind() != std::io::ErrorKind::WouldBlock {
+                    callback(None);
+                    break;
+                }
+                // Readiness is edge triggered, so the callback runs again when more data arrives
+                guard.clear_ready();
+            }
+        })))
+    }
+}
+
+impl Drop for ReadWatcher {
+    fn drop(&mut self) {
+        self.0.abort();
     }
 }
 
 // ────────────────────────────────────────────────────────────────────────────
-// BluetoothRfcommSocket – used for both RFCOMM and L2CAP outgoing connections
+// BluetoothL2capSocket – outgoing L2CAP connections
 // ────────────────────────────────────────────────────────────────────────────
 
-/// An outgoing bluetooth socket that may carry either an RFCOMM or L2CAP
-/// connection.  The socket is created lazily; call `connect()` before doing
-/// any I/O.
-pub struct BluetoothRfcommSocket {
+/// An outgoing L2CAP bluetooth socket using connection oriented (stream)
+/// channels. The socket is created lazily; call `connect()` before doing any
+/// I/O.
+pub struct BluetoothL2capSocket {
     /// Address of the remote device
     device_addr: bluer::Address,
-    /// RFCOMM channel to connect on (mutually exclusive with `l2cap_psm`).
-    ///
-    /// BlueZ does not expose a direct SDP query API through the bluer crate, so
-    /// callers are responsible for resolving the correct channel via
-    /// `run_sdp` / `get_uuids` before creating the socket, or by relying on
-    /// a well-known channel number for the service.  Channel 1 is used as a
-    /// default placeholder when the channel is not otherwise known.
-    rfcomm_channel: Option<u8>,
-    /// L2CAP PSM to connect on (mutually exclusive with `rfcomm_channel`).
-    l2cap_psm: Option<u16>,
+    /// The protocol service multiplexer to connect to
+    psm: u16,
     /// Whether to request an encrypted / authenticated link
     is_secure: bool,
     /// The live connection, present after a successful `connect()` call
-    connection: Option<BluetoothConnection>,
+    connection: Option<bluer::l2cap::Stream>,
+    /// Calls the read callback when data arrives
+    read_watcher: Option<ReadWatcher>,
 }
 
-impl BluetoothRfcommSocket {
-    /// Create a new (unconnected) RFCOMM socket.
-    fn new_rfcomm(device_addr: bluer::Address, channel: u8, is_secure: bool) -> Self {
+impl BluetoothL2capSocket {
+    /// Create a new (unconnected) L2CAP socket.
+    fn new(device_addr: bluer::Address, psm: u16, is_secure: bool) -> Self {
         Self {
             device_addr,
-            rfcomm_channel: Some(channel),
-            l2cap_psm: None,
+            psm,
             is_secure,
             connection: None,
+            read_watcher: None,
         }
     }
 
-    /// Create a new (unconnected) L2CAP socket.
-    fn new_l2cap(device_addr: bluer::Address, psm: u16, is_secure: bool) -> Self {
-        Self {
-            device_addr,
-            rfcomm_channel: None,
-            l2cap_psm: Some(psm),
-            is_secure,
-            connection: None,
-        }
+    /// Take the connected stream out of the socket, if it is connected
+    pub fn into_stream(self) -> Option<bluer::l2cap::Stream> {
+        self.connection
     }
 }
 
-impl tokio::io::AsyncRead for BluetoothRfcommSocket {
+impl tokio::io::AsyncRead for BluetoothL2capSocket {
     fn poll_read(
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -170,7 +624,7 @@ impl tokio::io::AsyncRead for BluetoothRfcommSocket {
     }
 }
 
-impl tokio::io::AsyncWrite for BluetoothRfcommSocket {
+impl tokio::io::AsyncWrite for BluetoothL2capSocket {
     fn poll_write(
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
@@ -210,69 +664,100 @@ impl tokio::io::AsyncWrite for BluetoothRfcommSocket {
 }
 
 #[async_trait::async_trait]
-impl crate::BluetoothSocketTrait for BluetoothRfcommSocket {
+impl crate::BluetoothSocketTrait for BluetoothL2capSocket {
     fn supports_async(&mut self) -> Option<&mut (dyn super::AsyncReadWrite)> {
         Some(self)
     }
 
+    /// The callback is called from a task watching the socket
+    fn set_read_callback(&mut self, callback: crate::ReadCallback) -> Result<(), std::io::Error> {
+        use std::os::fd::AsRawFd;
+        let Some(conn) = &self.connection else {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        };
+        self.read_watcher = Some(ReadWatcher::new(conn.as_raw_fd(), callback)?);
+        Ok(())
+    }
+
+    fn clear_read_callback(&mut self) {
+        self.read_watcher = None;
+    }
+
     fn is_connected(&self) -> Result<bool, std::io::Error> {
         Ok(self.connection.is_some())
     }
 
+    fn max_receive_packet_size(&self) -> Result<usize, std::io::Error> {
+        let Some(conn) = &self.connection else {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        };
+        conn.as_ref().recv_mtu().map(usize::from)
+    }
+
+    fn max_transmit_packet_size(&self) -> Result<usize, std::io::Error> {
+        let Some(conn) = &self.connection else {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        };
+        conn.as_ref().send_mtu().map(usize::from)
+    }
+
+    /// The kernel keeps the timeout with the socket, rounded up to its clock tick. It is applied by the
+    /// timeout aware reads of the stream the socket turns into.
+    fn read_timeout(&self) -> Result<Option<std::time::Duration>, std::io::Error> {
+        let Some(conn) = &self.connection else {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        };
+        socket_timeout(conn, libc::SO_RCVTIMEO)
+    }
+
+    fn set_read_timeout(
+        &mut self,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<(), std::io::Error> {
+        let Some(conn) = &self.connection else {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected));
+        };
+        set_socket_timeout(conn, libc::SO_RCVTIMEO, crate::check_timeout(timeout)?)
+    }
+
     fn sync_connect(&mut self) -> Result<(), std::io::Error> {
-        Err(std::io::Error::new(std::io::ErrorKind::Other, "sync not supported"))
+        Err(std::io::Error::other("sync not supported"))
+    }
+
+    /// Dropping the connection closes the socket
+    fn close(&mut self) -> Result<(), crate::BluetoothError> {
+        self.read_watcher = None;
+        self.connection = None;
+        Ok(())
+    }
+
+    fn into_stream(self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        let stream = self
+            .connection
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotConnected))?;
+        Ok(crate::BluetoothStream::BluezL2cap(Box::pin(stream)))
     }
 
     async fn async_connect(&mut self) -> Result<(), std::io::Error> {
         if self.connection.is_some() {
             return Ok(());
         }
-        if let Some(channel) = self.rfcomm_channel {
-            let addr = bluer::rfcomm::SocketAddr::new(self.device_addr, channel);
-            let socket = bluer::rfcomm::Socket::new()
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-            if self.is_secure {
-                socket
-                    .set_security(bluer::rfcomm::Security {
-                        level: bluer::rfcomm::SecurityLevel::Medium,
-                        key_size: 0,
-                    })
-                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-            }
-            let stream = socket
-                .connect(addr)
-                .await
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-            log::info!("STREAM {:?} to {:?}", stream.as_ref().local_addr(), stream.peer_addr());
-            self.connection = Some(BluetoothConnection::Rfcomm(stream));
-            log::info!("Got an rfcomm stream");
-        } else if let Some(psm) = self.l2cap_psm {
-            let addr = bluer::l2cap::SocketAddr::new(
-                self.device_addr,
-                bluer::AddressType::BrEdr,
-                psm,
-            );
-            let socket = bluer::l2cap::Socket::<bluer::l2cap::Stream>::new_stream()
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-            if self.is_secure {
-                socket
-                    .set_security(bluer::l2cap::Security {
-                        level: bluer::l2cap::SecurityLevel::Medium,
-                        key_size: 0,
-                    })
-                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-            }
-            let stream = socket
-                .connect(addr)
-                .await
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-            self.connection = Some(BluetoothConnection::L2cap(stream));
-        } else {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "BluetoothRfcommSocket has neither an RFCOMM channel nor an L2CAP PSM configured",
-            ));
+        let addr =
+            bluer::l2cap::SocketAddr::new(self.device_addr, bluer::AddressType::BrEdr, self.psm);
+        let socket = bluer::l2cap::Socket::<bluer::l2cap::Stream>::new_stream()?;
+        if self.is_secure {
+            socket.set_security(bluer::l2cap::Security {
+                level: bluer::l2cap::SecurityLevel::Medium,
+                key_size: 0,
+            })?;
         }
+        let stream = socket.connect(addr).await?;
+        log::info!(
+            "L2CAP stream {:?} to {:?}",
+            stream.as_ref().local_addr(),
+            stream.peer_addr()
+        );
+        self.connection = Some(stream);
         Ok(())
     }
 }
@@ -285,32 +770,56 @@ impl crate::BluetoothSocketTrait for BluetoothRfcommSocket {
 /// stores open RFCOMM / L2CAP sockets so that returned `BluetoothSocket`
 /// references remain valid for the lifetime of this struct.
 pub struct LinuxBluetoothDevice {
+    /// The adapter the device was found on
+    adapter: bluer::Adapter,
     /// The underlying bluer device handle
     device: bluer::Device,
 }
 
 impl LinuxBluetoothDevice {
-    /// Wrap a `bluer::Device`.
-    pub fn new(device: bluer::Device) -> Self {
-        Self { device }
+    /// Wrap a `bluer::Device` that belongs to the given adapter.
+    pub fn new(adapter: bluer::Adapter, device: bluer::Device) -> Self {
+        Self { adapter, device }
+    }
+
+    /// Connect to the device if needed, and wait up to the timeout for the services to be resolved
+    async fn wait_services_resolved(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<(), std::io::Error> {
+        let resolve = async {
+            // Subscribe before connecting so the change cannot be missed
+            let mut events = self.device.events().await.map_err(berr)?;
+            if !self.device.is_connected().await.map_err(berr)? {
+                self.device.connect().await.map_err(berr)?;
+            }
+            while !self.device.is_services_resolved().await.map_err(berr)? {
+                if events.next().await.is_none() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::NotConnected,
+                        "The device went away while resolving services",
+                    ));
+                }
+            }
+            Ok(())
+        };
+        tokio::time::timeout(timeout, resolve).await.map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "Timed out waiting for the services to be resolved",
+            )
+        })?
     }
 }
 
 #[async_trait::async_trait]
 impl super::BluetoothDeviceAsyncTrait for LinuxBluetoothDevice {
     async fn get_uuids(&mut self) -> Result<Vec<crate::BluetoothUuid>, std::io::Error> {
-        let uuids =
-            self.device.uuids().await.map_err(|e| {
-                std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
-            })?;
+        let uuids = self.device.uuids().await.map_err(berr)?;
         Ok(uuids
             .unwrap_or_default()
             .into_iter()
-            .map(|u| {
-                use std::str::FromStr;
-                crate::BluetoothUuid::from_str(&u.to_string())
-                    .unwrap_or_else(|_| crate::BluetoothUuid::Unknown(u.to_string()))
-            })
+            .map(bluetooth_uuid)
             .collect())
     }
 
@@ -321,52 +830,186 @@ impl super::BluetoothDeviceAsyncTrait for LinuxBluetoothDevice {
         device
             .alias()
             .await
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+            .map_err(berr)
     }
         
+    async fn alias(&self) -> Result<String, std::io::Error> {
+        self.device.alias().await.map_err(berr)
+    }
+
+    async fn set_alias(&self, alias: &str) -> Result<(), std::io::Error> {
+        self.device.set_alias(alias.to_string()).await.map_err(berr)
+    }
+
     async fn get_pair_state(&self) -> Result<crate::PairingStatus, std::io::Error> {
         let device = self.device.clone();
         let paired = device
             .is_paired()
             .await
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+            .map_err(berr)?;
         Ok(if paired {
             crate::PairingStatus::Paired
         } else {
             crate::PairingStatus::NotPaired
         })
     }
-}
 
-impl super::BluetoothDeviceTrait for LinuxBluetoothDevice {
-    fn supports_async(&mut self) -> Option<&mut dyn super::BluetoothDeviceAsyncTrait> {
-        Some(self)
+    /// BlueZ only reports the rssi while the device is being discovered, and drops it once the device is no
+    /// longer seen, so the reading is always current
+    async fn get_rssi(&self) -> Result<Option<crate::RssiReading>, std::io::Error> {
+        let rssi = self.device.rssi().await.map_err(berr)?;
+        Ok(rssi.map(crate::RssiReading::new))
     }
 
-    fn supports_sync(&mut self) -> Option<&mut dyn super::BluetoothDeviceSyncTrait> {
-        None
+    /// The error message contains the reason given by BlueZ, such as `br-connection-page-timeout`
+    async fn connect_device(&self) -> Result<(), std::io::Error> {
+        self.device.connect().await.map_err(berr)
     }
 
-    fn get_address(&mut self) -> Result<String, std::io::Error> {
-        Ok(self.device.address().to_string())
+    async fn disconnect_device(&self) -> Result<(), std::io::Error> {
+        self.device.disconnect().await.map_err(berr)
     }
 
-    /// Return a socket suitable for an outgoing L2CAP connection to the given
-    /// service UUID.
-    ///
-    /// The PSM for the service must be known in advance.  Because bluer does
-    /// not expose a raw SDP query API, a proper PSM lookup is left as a
-    /// `// TODO` below; the default PSM of 1 (SDP channel itself) is a
-    /// placeholder and will need to be replaced with the actual dynamic PSM
-    /// discovered via SDP for real services.
-    fn get_l2cap_socket(
-        &mut self,
+    /// The pairing is confirmed through the registered agent, when the device requires it
+    async fn pair(&mut self) -> Result<(), std::io::Error> {
+        self.device.pair().await.map_err(berr)
+    }
+
+    async fn remove_bond(&mut self) -> Result<(), std::io::Error> {
+        self.adapter
+            .remove_device(self.device.address())
+            .await
+            .map_err(berr)
+    }
+
+    async fn is_trusted(&self) -> Result<bool, std::io::Error> {
+        self.device.is_trusted().await.map_err(berr)
+    }
+
+    async fn set_trusted(&self, trusted: bool) -> Result<(), std::io::Error> {
+        self.device.set_trusted(trusted).await.map_err(berr)
+    }
+
+    async fn is_blocked(&self) -> Result<bool, std::io::Error> {
+        self.device.is_blocked().await.map_err(berr)
+    }
+
+    async fn set_blocked(&self, blocked: bool) -> Result<(), std::io::Error> {
+        self.device.set_blocked(blocked).await.map_err(berr)
+    }
+
+    async fn watch_properties(
+        &mut self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::DevicePropertyEvent>, std::io::Error> {
+        let mut events = self.device.events().await.map_err(berr)?;
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    e = events.next() => e,
+                    _ = tx.closed() => break,
+                };
+                let Some(bluer::DeviceEvent::PropertyChanged(prop)) = event else {
+                    break;
+                };
+                let Some(event) = device_property_event(prop) else {
+                    continue;
+                };
+                if tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(rx)
+    }
+    /// The battery level comes from the BlueZ Battery1 interface, which is only present for devices that report it
+    async fn battery_percent(&self) -> Result<Option<u8>, std::io::Error> {
+        self.device.battery_percentage().await.map_err(berr)
+    }
+
+    async fn device_class(&self) -> Result<Option<crate::DeviceClass>, std::io::Error> {
+        Ok(self
+            .device
+            .class()
+            .await
+            .map_err(berr)?
+            .map(crate::DeviceClass::new))
+    }
+
+    async fn device_type(&self) -> Result<crate::DeviceTransport, std::io::Error> {
+        device_transport(&self.device).await.map_err(berr)
+    }
+
+    /// Bluez has no state for each profile, so the profile is connected when the device is connected and
+    /// supports the profile
+    async fn profile_connection_state(
+        &self,
+        profile: crate::WellKnownProfile,
+    ) -> Result<crate::ProfileConnectionState, std::io::Error> {
+        let ids: &[u16] = match profile {
+            // a2dp source, sink, and the advanced audio distribution service
+            crate::WellKnownProfile::A2dp => &[0x110a, 0x110b, 0x110d],
+            // handsfree and handsfree audio gateway
+            crate::WellKnownProfile::Hfp => &[0x111e, 0x111f],
+        };
+        let uuids = self.device.uuids().await.map_err(berr)?.unwrap_or_default();
+        let supported = ids.iter().any(|id| uuids.contains(&short_uuid(*id)));
+        if supported && self.device.is_connected().await.map_err(berr)? {
+            Ok(crate::ProfileConnectionState::Connected)
+        } else {
+            Ok(crate::ProfileConnectionState::Disconnected)
+        }
+    }
+
+    async fn gatt(&self) -> Result<crate::GattClient, std::io::Error> {
+        // The services are only listed once bluez has resolved them after connecting
+        self.wait_services_resolved(std::time::Duration::from_secs(10))
+            .await?;
+        Ok(crate::GattClient::Bluez(GattClient {
+            device: self.device.clone(),
+        }))
+    }
+
+    /// The uuids reported by bluez are stale until the services are resolved after connecting
+    async fn resolve_services(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<Vec<crate::BluetoothUuid>, std::io::Error> {
+        self.wait_services_resolved(timeout).await?;
+        self.get_uuids().await
+    }
+}
+
+impl super::BluetoothDeviceTrait for LinuxBluetoothDevice {
+    fn supports_async(&mut self) -> Option<&mut dyn super::BluetoothDeviceAsyncTrait> {
+        Some(self)
+    }
+
+    fn supports_sync(&mut self) -> Option<&mut dyn super::BluetoothDeviceSyncTrait> {
+        None
+    }
+
+    fn get_address(&mut self) -> Result<crate::BluetoothAdapterAddress, std::io::Error> {
+        Ok(crate::BluetoothAdapterAddress::Byte(
+            self.device.address().0,
+        ))
+    }
+
+    /// Return a socket suitable for an outgoing L2CAP connection to the given
+    /// PSM.
+    ///
+    /// The PSM for the service must be known in advance, either because it is
+    /// a well known fixed PSM or because it was discovered with SDP. When
+    /// `is_secure` is set the link is required to be authenticated and
+    /// encrypted before the connection completes.
+    fn get_l2cap_socket(
+        &mut self,
         psm: u16,
         is_secure: bool,
-    ) -> Result<crate::BluetoothSocket, String> {
+    ) -> Result<crate::BluetoothSocket, crate::BluetoothError> {
         let addr = self.device.address();
-        let socket = BluetoothRfcommSocket::new_l2cap(addr, psm, is_secure);
-        Ok(crate::BluetoothSocket::Bluez(socket))
+        let socket = BluetoothL2capSocket::new(addr, psm, is_secure);
+        Ok(crate::BluetoothSocket::BluezL2cap(socket))
     }
 
     /// Return a socket suitable for an outgoing RFCOMM connection to the given
@@ -381,9 +1024,9 @@ impl super::BluetoothDeviceTrait for LinuxBluetoothDevice {
         &mut self,
         channel: u8,
         is_secure: bool,
-    ) -> Result<crate::BluetoothSocket, String> {
+    ) -> Result<crate::BluetoothSocket, crate::BluetoothError> {
         let addr = self.device.address();
-        let socket = BluetoothRfcommSocket::new_rfcomm(addr, channel, is_secure);
+        let socket = BluetoothRfcommSocket::new(addr, channel, is_secure);
         Ok(crate::BluetoothSocket::Bluez(socket))
     }
 }
@@ -392,73 +1035,1152 @@ impl super::BluetoothDeviceTrait for LinuxBluetoothDevice {
 // BluetoothDiscovery
 // ────────────────────────────────────────────────────────────────────────────
 
-/// A struct for managing discovery of bluetooth devices
-pub struct BluetoothDiscovery {}
+/// Convert a bluer uuid into the uuid type of this crate
+fn bluetooth_uuid(u: bluer::Uuid) -> crate::BluetoothUuid {
+    crate::BluetoothUuid::from_u128(u.as_u128())
+}
+
+/// Convert a uuid of this crate into a bluer uuid
+fn bluer_uuid(u: &crate::BluetoothUuid) -> bluer::Uuid {
+    bluer::Uuid::from_u128(u.as_u128())
+}
+
+/// Parse a uuid string from the profile settings into a bluer uuid
+fn parse_bluer_uuid(s: &str) -> Result<bluer::Uuid, crate::UuidParseError> {
+    use std::str::FromStr;
+    crate::BluetoothUuid::from_str(s).map(|u| bluer_uuid(&u))
+}
+
+/// Expand a 16 bit bluetooth uuid with the bluetooth base uuid
+fn short_uuid(id: u16) -> bluer::Uuid {
+    bluer::Uuid::from_u128(((id as u128) << 96) | 0x1000_8000_0080_5f9b_34fb)
+}
+
+/// Build the scan mode of the adapter from its discoverable and pairable properties
+async fn scan_mode(adapter: &bluer::Adapter) -> Result<crate::ScanMode, crate::BluetoothError> {
+    if adapter.is_discoverable().await.map_err(berr)? {
+        Ok(crate::ScanMode::ConnectableDiscoverable)
+    } else if adapter.is_pairable().await.map_err(berr)? {
+        Ok(crate::ScanMode::Connectable)
+    } else {
+        Ok(crate::ScanMode::None)
+    }
+}
+
+/// Converts a property change of the device with the given address into an event
+type DevicePropertyMap<T> = fn(&str, bluer::DeviceProperty) -> Option<T>;
+
+/// Forward the property changes of the device, converted by the map, until the receiver is dropped
+async fn watch_device<T: Send + 'static>(
+    device: bluer::Device,
+    tx: tokio::sync::mpsc::Sender<T>,
+    map: DevicePropertyMap<T>,
+) -> Result<(), std::io::Error> {
+    let mut events = device.events().await.map_err(berr)?;
+    let address = device.address().to_string();
+    tokio::spawn(async move {
+        loop {
+            let event = tokio::select! {
+                e = events.next() => e,
+                _ = tx.closed() => break,
+            };
+            let Some(bluer::DeviceEvent::PropertyChanged(prop)) = event else {
+                break;
+            };
+            let Some(event) = map(&address, prop) else {
+                continue;
+            };
+            if tx.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Forward the property changes of every device of the adapters, including devices added later, converted by
+/// the map, until the receiver is dropped
+async fn watch_all_devices<T: Send + 'static>(
+    adapters: Vec<bluer::Adapter>,
+    map: DevicePropertyMap<T>,
+) -> Result<tokio::sync::mpsc::Receiver<T>, crate::BluetoothError> {
+    let (tx, rx) = tokio::sync::mpsc::channel(16);
+    for adapter in adapters {
+        let mut events = adapter.events().await.map_err(berr)?;
+        for address in adapter.device_addresses().await.map_err(berr)? {
+            watch_device(adapter.device(address).map_err(berr)?, tx.clone(), map).await?;
+        }
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    e = events.next() => e,
+                    _ = tx.closed() => break,
+                };
+                match event {
+                    Some(AdapterEvent::DeviceAdded(address)) => {
+                        if let Ok(device) = adapter.device(address) {
+                            let _ = watch_device(device, tx.clone(), map).await;
+                        }
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+        });
+    }
+    Ok(rx)
+}
+
+/// Convert a bluer device property into the property events reported by this crate
+fn device_property_event(prop: bluer::DeviceProperty) -> Option<crate::DevicePropertyEvent> {
+    use crate::DevicePropertyEvent;
+    Some(match prop {
+        bluer::DeviceProperty::Connected(c) => DevicePropertyEvent::Connected(c),
+        bluer::DeviceProperty::ServicesResolved(r) => DevicePropertyEvent::ServicesResolved(r),
+        bluer::DeviceProperty::Rssi(r) => DevicePropertyEvent::RssiChanged(r),
+        bluer::DeviceProperty::Name(n) => DevicePropertyEvent::NameChanged(n),
+        bluer::DeviceProperty::Paired(p) => DevicePropertyEvent::Paired(p),
+        bluer::DeviceProperty::Trusted(t) => DevicePropertyEvent::Trusted(t),
+        bluer::DeviceProperty::Blocked(b) => DevicePropertyEvent::Blocked(b),
+        _ => return None,
+    })
+}
+
+/// A struct for managing discovery of bluetooth devices. Discovery runs on
+/// every adapter for as long as this object is alive. The devices found are
+/// available as a stream of [`crate::DiscoveryEvent`].
+pub struct BluetoothDiscovery {
+    /// The task that owns the discovery sessions of all adapters
+    task: tokio::task::JoinHandle<()>,
+    /// The events generated by the discovery task, until taken by the user
+    events: Option<tokio::sync::mpsc::Receiver<crate::DiscoveryEvent>>,
+    /// Becomes true when the discovery task stops
+    finished: tokio::sync::watch::Receiver<bool>,
+}
 
 impl BluetoothDiscovery {
-    /// Construct a new self
-    fn new() -> Self {
-        Self {}
+    /// Start discovery on all of the given adapters, stopping after the duration if one is given. This fails when
+    /// discovery could not be started on any of the adapters.
+    async fn new(
+        adapters: &[bluer::Adapter],
+        duration: Option<std::time::Duration>,
+    ) -> Result<Self, crate::BluetoothError> {
+        let mut sessions = Vec::new();
+        let mut error = None;
+        for adapter in adapters {
+            match adapter.discover_devices_with_changes().await {
+                Ok(s) => {
+                    let adapter = adapter.clone();
+                    sessions.push(s.map(move |e| (adapter.clone(), e)).boxed())
+                }
+                Err(e) => {
+                    log::warn!("Failed to start discovery on {}: {}", adapter.name(), e);
+                    error = Some(e);
+                }
+            }
+        }
+        if sessions.is_empty() {
+            return Err(match error {
+                Some(e) => berr(e).into(),
+                None => crate::BluetoothError::AdapterUnavailable,
+            });
+        }
+        let (tx, rx) = tokio::sync::mpsc::channel(32);
+        let (finished_tx, finished) = tokio::sync::watch::channel(false);
+        // bluer only keeps discovery running while the event stream is polled
+        // and alive, so the streams are driven by a task that is aborted on drop.
+        // The task waits for room in the channel so that no event is lost.
+        let task = tokio::spawn(async move {
+            let mut adapter_events = futures::stream::select_all(sessions);
+            let mut device_events = futures::stream::SelectAll::new();
+            // The known devices, dropping the sender of a device ends its property stream
+            let mut known = std::collections::HashMap::new();
+            let deadline = async {
+                match duration {
+                    Some(d) => tokio::time::sleep(d).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::pin!(deadline);
+            loop {
+                let event = tokio::select! {
+                    _ = &mut deadline => break,
+                    Some((adapter, e)) = adapter_events.next() => match e {
+                        AdapterEvent::DeviceAdded(addr) => {
+                            if known.contains_key(&addr) {
+                                continue;
+                            }
+                            let Ok(device) = adapter.device(addr) else {
+                                continue;
+                            };
+                            let (removed_tx, removed) = tokio::sync::oneshot::channel::<()>();
+                            known.insert(addr, removed_tx);
+                            match device.events().await {
+                                Ok(events) => {
+                                    let events = events.take_until(removed).map(move |e| (addr, e));
+                                    device_events.push(events.boxed())
+                                }
+                                Err(e) => log::warn!("Failed to watch device {}: {}", addr, e),
+                            }
+                            let transport = device_transport(&device)
+                                .await
+                                .unwrap_or(crate::DeviceTransport::Unknown);
+                            crate::DiscoveryEvent::DeviceAdded {
+                                device: crate::BluetoothDevice::Bluez(LinuxBluetoothDevice::new(
+                                    adapter, device,
+                                )),
+                                transport,
+                            }
+                        }
+                        AdapterEvent::DeviceRemoved(addr) => {
+                            known.remove(&addr);
+                            crate::DiscoveryEvent::DeviceRemoved(
+                                crate::BluetoothAdapterAddress::Byte(addr.0),
+                            )
+                        }
+                        AdapterEvent::PropertyChanged(prop) => {
+                            log::debug!("Adapter property changed {:?}", prop);
+                            continue;
+                        }
+                    },
+                    Some((addr, e)) = device_events.next() => {
+                        let bluer::DeviceEvent::PropertyChanged(prop) = e;
+                        if !known.contains_key(&addr) {
+                            continue;
+                        }
+                        let Some(property) = device_property_event(prop) else {
+                            continue;
+                        };
+                        crate::DiscoveryEvent::DeviceUpdated {
+                            address: crate::BluetoothAdapterAddress::Byte(addr.0),
+                            property,
+                        }
+                    }
+                    else => break,
+                };
+                tokio::select! {
+                    _ = &mut deadline => break,
+                    _ = tx.send(event) => {}
+                }
+            }
+            // Dropping the event streams stops discovery
+            drop(adapter_events);
+            drop(device_events);
+            finished_tx.send_replace(true);
+        });
+        Ok(Self {
+            task,
+            events: Some(rx),
+            finished,
+        })
     }
 }
 
-impl super::BluetoothDiscoveryTrait for BluetoothDiscovery {}
+#[async_trait::async_trait]
+impl super::BluetoothDiscoveryTrait for BluetoothDiscovery {
+    fn take_events(&mut self) -> Option<tokio::sync::mpsc::Receiver<crate::DiscoveryEvent>> {
+        self.events.take()
+    }
+
+    fn try_recv(&mut self) -> Option<crate::DiscoveryEvent> {
+        self.events.as_mut()?.try_recv().ok()
+    }
+
+    async fn next(&mut self) -> Option<crate::DiscoveryEvent> {
+        self.events.as_mut()?.recv().await
+    }
+
+    fn is_running(&self) -> bool {
+        !*self.finished.borrow()
+    }
+
+    fn finished(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.finished.clone()
+    }
+}
+
+/// The discovery can be used directly as a stream of events, as long as the receiver has not been taken
+impl futures::Stream for BluetoothDiscovery {
+    type Item = crate::DiscoveryEvent;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        match self.events.as_mut() {
+            Some(rx) => rx.poll_recv(cx),
+            None => std::task::Poll::Ready(None),
+        }
+    }
+}
 
 impl Drop for BluetoothDiscovery {
-    fn drop(&mut self) {}
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A low energy scan, built from discovery limited to the low energy transport
+pub struct LeScanSession {
+    /// The task that owns the discovery sessions of all adapters
+    task: tokio::task::JoinHandle<()>,
+    /// The adapters scanning, so their discovery filter can be cleared afterwards
+    adapters: Vec<bluer::Adapter>,
+    /// The scan results, until taken by the user
+    results: Option<tokio::sync::mpsc::Receiver<crate::LeScanResult>>,
+}
+
+impl LeScanSession {
+    /// Start a low energy scan on all of the given adapters
+    async fn new(
+        adapters: Vec<bluer::Adapter>,
+        filter: crate::LeScanFilter,
+    ) -> Result<Self, std::io::Error> {
+        let uuids = filter.service_uuids.iter().map(bluer_uuid).collect();
+        let discovery_filter = bluer::DiscoveryFilter {
+            uuids,
+            transport: bluer::DiscoveryTransport::Le,
+            duplicate_data: true,
+            pattern: filter.name_prefix.clone(),
+            ..Default::default()
+        };
+        let mut sessions = Vec::new();
+        for adapter in &adapters {
+            adapter
+                .set_discovery_filter(discovery_filter.clone())
+                .await
+                .map_err(berr)?;
+            // Property changes are reported as added devices, so every advertisement produces a result
+            let events = adapter
+                .discover_devices_with_changes()
+                .await
+                .map_err(berr)?;
+            let adapter = adapter.clone();
+            sessions.push(events.map(move |e| (adapter.clone(), e)).boxed());
+        }
+        let (tx, rx) = tokio::sync::mpsc::channel(32);
+        let task = tokio::spawn(async move {
+            let mut events = futures::stream::select_all(sessions);
+            while let Some((adapter, event)) = events.next().await {
+                let AdapterEvent::DeviceAdded(addr) = event else {
+                    continue;
+                };
+                let Ok(device) = adapter.device(addr) else {
+                    continue;
+                };
+                let result = le_scan_result(&device).await;
+                if filter.matches(&result) {
+                    let _ = tx.try_send(result);
+                }
+            }
+        });
+        Ok(Self {
+            task,
+            adapters,
+            results: Some(rx),
+        })
+    }
+}
+
+/// The radio transports of a device. BlueZ does not report the transports directly. A device with a class has
+/// been seen over classic, and a device with a random address or advertising data has been seen over low energy.
+async fn device_transport(device: &bluer::Device) -> bluer::Result<crate::DeviceTransport> {
+    let classic = device.class().await?.is_some();
+    let le = device.address_type().await? == bluer::AddressType::LeRandom
+        || device.appearance().await?.is_some()
+        || device.manufacturer_data().await?.is_some()
+        || device.service_data().await?.is_some();
+    Ok(match (classic, le) {
+        (true, true) => crate::DeviceTransport::Dual,
+        (true, false) => crate::DeviceTransport::Classic,
+        (false, true) => crate::DeviceTransport::Le,
+        (false, false) => crate::DeviceTransport::Unknown,
+    })
+}
+
+/// Build a scan result from the properties bluez knows for the device
+async fn le_scan_result(device: &bluer::Device) -> crate::LeScanResult {
+    crate::LeScanResult {
+        address: crate::BluetoothAdapterAddress::Byte(device.address().0),
+        name: device.name().await.ok().flatten(),
+        rssi: device.rssi().await.ok().flatten(),
+        service_uuids: device
+            .uuids()
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+            .into_iter()
+            .map(bluetooth_uuid)
+            .collect(),
+        manufacturer_data: device
+            .manufacturer_data()
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+            .into_iter()
+            .collect(),
+    }
+}
+
+impl super::LeScanSessionTrait for LeScanSession {
+    fn take_results(&mut self) -> Option<tokio::sync::mpsc::Receiver<crate::LeScanResult>> {
+        self.results.take()
+    }
+}
+
+impl Drop for LeScanSession {
+    fn drop(&mut self) {
+        self.task.abort();
+        // The discovery filter outlives the scan, so it is cleared for later discovery
+        let adapters = std::mem::take(&mut self.adapters);
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                for adapter in adapters {
+                    let _ = adapter.set_discovery_filter(Default::default()).await;
+                }
+            });
+        }
+    }
 }
 
 // ────────────────────────────────────────────────────────────────────────────
-// TryFrom conversions for profile settings → bluer::rfcomm::Profile
+// GattClient
 // ────────────────────────────────────────────────────────────────────────────
 
-impl TryFrom<super::BluetoothRfcommProfileSettings> for bluer::rfcomm::Profile {
+/// A gatt client for a remote device
+pub struct GattClient {
+    /// The remote device
+    device: bluer::Device,
+}
+
+impl GattClient {
+    /// Look up the bluer characteristic for a characteristic returned by `services`
+    async fn characteristic(
+        &self,
+        c: &crate::GattCharacteristic,
+    ) -> Result<bluer::gatt::remote::Characteristic, std::io::Error> {
+        let service = self.device.service(c.service_id).await.map_err(berr)?;
+        service.characteristic(c.id).await.map_err(berr)
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::GattClientTrait for GattClient {
+    async fn services(&self) -> Result<Vec<crate::GattService>, std::io::Error> {
+        let mut services = Vec::new();
+        for service in self.device.services().await.map_err(berr)? {
+            let mut characteristics = Vec::new();
+            for c in service.characteristics().await.map_err(berr)? {
+                let flags = c.flags().await.map_err(berr)?;
+                characteristics.push(crate::GattCharacteristic {
+                    uuid: bluetooth_uuid(c.uuid().await.map_err(berr)?),
+                    flags: crate::GattCharacteristicFlags {
+                        read: flags.read,
+                        write: flags.write,
+                        write_without_response: flags.write_without_response,
+                        notify: flags.notify,
+                        indicate: flags.indicate,
+                    },
+                    service_id: service.id(),
+                    id: c.id(),
+                });
+            }
+            services.push(crate::GattService {
+                uuid: bluetooth_uuid(service.uuid().await.map_err(berr)?),
+                primary: service.primary().await.map_err(berr)?,
+                characteristics,
+            });
+        }
+        Ok(services)
+    }
+
+    async fn read(
+        &self,
+        characteristic: &crate::GattCharacteristic,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        self.characteristic(characteristic)
+            .await?
+            .read()
+            .await
+            .map_err(berr)
+    }
+
+    async fn write(
+        &self,
+        characteristic: &crate::GattCharacteristic,
+        data: &[u8],
+        mode: crate::WriteMode,
+    ) -> Result<(), std::io::Error> {
+        let c = self.characteristic(characteristic).await?;
+        let mut req = bluer::gatt::remote::CharacteristicWriteRequest::default();
+        req.op_type = match mode {
+            crate::WriteMode::WithResponse => bluer::gatt::WriteOp::Request,
+            crate::WriteMode::WithoutResponse => bluer::gatt::WriteOp::Command,
+        };
+        c.write_ext(data, &req).await.map_err(berr)
+    }
+
+    async fn subscribe(
+        &self,
+        characteristic: &crate::GattCharacteristic,
+    ) -> Result<tokio::sync::mpsc::Receiver<Vec<u8>>, std::io::Error> {
+        let c = self.characteristic(characteristic).await?;
+        let notifications = c.notify().await.map_err(berr)?;
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(async move {
+            // The characteristic is kept so the subscription lives as long as the task
+            let _c = c;
+            futures::pin_mut!(notifications);
+            loop {
+                let value = tokio::select! {
+                    v = notifications.next() => v,
+                    _ = tx.closed() => break,
+                };
+                let Some(value) = value else {
+                    break;
+                };
+                if tx.send(value).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(rx)
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// GattServiceHandle
+// ────────────────────────────────────────────────────────────────────────────
+
+/// A local gatt service registered on every adapter. The application is
+/// unregistered from each adapter when this is dropped.
+pub struct GattServiceHandle {
+    /// The handles for each adapter the application was registered with
+    _handles: Vec<bluer::gatt::local::ApplicationHandle>,
+}
+
+/// Convert the error of a gatt callback into the error reported to the remote device
+fn gatt_req_error(e: std::io::Error) -> bluer::gatt::local::ReqError {
+    match e.kind() {
+        std::io::ErrorKind::PermissionDenied => bluer::gatt::local::ReqError::NotPermitted,
+        std::io::ErrorKind::Unsupported => bluer::gatt::local::ReqError::NotSupported,
+        std::io::ErrorKind::InvalidInput => bluer::gatt::local::ReqError::InvalidValueLength,
+        _ => bluer::gatt::local::ReqError::Failed,
+    }
+}
+
+/// Get the value of a local characteristic for a remote device from the read callback
+async fn gatt_read(f: super::GattReadCallback) -> Result<Vec<u8>, bluer::gatt::local::ReqError> {
+    f().await.map_err(gatt_req_error)
+}
+
+/// Give the value written by a remote device to the write callback of a local characteristic
+async fn gatt_write(
+    f: super::GattWriteCallback,
+    data: Vec<u8>,
+) -> Result<(), bluer::gatt::local::ReqError> {
+    f(data).await.map_err(gatt_req_error)
+}
+
+impl TryFrom<&super::GattCharacteristicDefinition> for bluer::gatt::local::Characteristic {
     type Error = String;
-    fn try_from(value: super::BluetoothRfcommProfileSettings) -> Result<Self, Self::Error> {
-        let service = if let Some(v) = value.service_uuid {
-            Some(bluer::Uuid::parse_str(&v).map_err(|e| e.to_string())?)
+    fn try_from(value: &super::GattCharacteristicDefinition) -> Result<Self, Self::Error> {
+        use bluer::gatt::local::*;
+        let read = value.read.clone().map(|f| CharacteristicRead {
+            read: true,
+            fun: Box::new(move |_req| Box::pin(gatt_read(f.clone()))),
+            ..Default::default()
+        });
+        let write = value.write.clone().map(|f| CharacteristicWrite {
+            write: true,
+            write_without_response: true,
+            method: CharacteristicWriteMethod::Fun(Box::new(move |data, _req| {
+                Box::pin(gatt_write(f.clone(), data))
+            })),
+            ..Default::default()
+        });
+        let notify = value.notify.clone().map(|tx| CharacteristicNotify {
+            notify: true,
+            method: CharacteristicNotifyMethod::Fun(Box::new(move |mut notifier| {
+                let mut rx = tx.subscribe();
+                Box::pin(async move {
+                    tokio::spawn(async move {
+                        loop {
+                            let value = match rx.recv().await {
+                                Ok(v) => v,
+                                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                                    continue;
+                                }
+                                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                            };
+                            if notifier.notify(value).await.is_err() {
+                                break;
+                            }
+                        }
+                    });
+                })
+            })),
+            ..Default::default()
+        });
+        Ok(Self {
+            uuid: bluer_uuid(&value.uuid),
+            read,
+            write,
+            notify,
+            ..Default::default()
+        })
+    }
+}
+
+impl TryFrom<&super::GattServiceDefinition> for bluer::gatt::local::Application {
+    type Error = String;
+    fn try_from(value: &super::GattServiceDefinition) -> Result<Self, Self::Error> {
+        let characteristics = value
+            .characteristics
+            .iter()
+            .map(bluer::gatt::local::Characteristic::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            services: vec![bluer::gatt::local::Service {
+                uuid: bluer_uuid(&value.uuid),
+                primary: value.primary,
+                characteristics,
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// AdvertisementHandle
+// ────────────────────────────────────────────────────────────────────────────
+
+/// A low energy advertisement running on every adapter. The advertisement is
+/// unregistered from each adapter when this is dropped.
+pub struct AdvertisementHandle {
+    /// The handles for each adapter the advertisement was registered with
+    _handles: Vec<bluer::adv::AdvertisementHandle>,
+}
+
+/// The most manufacturer data that fits in a legacy advertisement, 31 bytes less the flags, the length and
+/// type of the field, and the company identifier
+const MAX_MANUFACTURER_DATA: usize = 31 - 3 - 2 - 2;
+
+impl TryFrom<super::LeAdvertisementSettings> for bluer::adv::Advertisement {
+    type Error = super::BluetoothError;
+    fn try_from(value: super::LeAdvertisementSettings) -> Result<Self, Self::Error> {
+        if let Some((company, data)) = value
+            .manufacturer_data
+            .iter()
+            .find(|(_, d)| d.len() > MAX_MANUFACTURER_DATA)
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "The manufacturer data of company {:#06x} has {} bytes, at most {} fit",
+                    company,
+                    data.len(),
+                    MAX_MANUFACTURER_DATA
+                ),
+            )
+            .into());
+        }
+        let service_uuids = value.service_uuids.iter().map(bluer_uuid).collect();
+        Ok(Self {
+            advertisement_type: bluer::adv::Type::Peripheral,
+            service_uuids,
+            manufacturer_data: value.manufacturer_data,
+            discoverable: Some(value.discoverable),
+            local_name: value.local_name,
+            ..Default::default()
+        })
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// HidDevice – the hid control and interrupt channels of a hid device
+// ────────────────────────────────────────────────────────────────────────────
+
+/// The uuid of the human interface device service
+const HID_SERVICE_UUID: u16 = 0x1124;
+/// The l2cap psm of the hid control channel
+const HID_CONTROL_PSM: u16 = 0x11;
+/// The l2cap psm of the hid interrupt channel
+const HID_INTERRUPT_PSM: u16 = 0x13;
+/// The l2cap mtu used when the mtu of a channel can't be read
+const L2CAP_DEFAULT_MTU: u16 = 672;
+/// The hidp HANDSHAKE result for a successful request
+const HIDP_HANDSHAKE_SUCCESSFUL: u8 = 0x00;
+/// The hidp HANDSHAKE result for a request that is not supported
+const HIDP_HANDSHAKE_ERR_UNSUPPORTED_REQUEST: u8 = 0x03;
+/// The hidp HANDSHAKE result for a request with an invalid parameter
+const HIDP_HANDSHAKE_ERR_INVALID_PARAMETER: u8 = 0x04;
+/// The hidp HID_CONTROL message type
+const HIDP_TYPE_CONTROL: u8 = 0x1;
+/// The hidp GET_REPORT message type
+const HIDP_TYPE_GET_REPORT: u8 = 0x4;
+/// The hidp SET_REPORT message type
+const HIDP_TYPE_SET_REPORT: u8 = 0x5;
+/// The hidp GET_PROTOCOL message type
+const HIDP_TYPE_GET_PROTOCOL: u8 = 0x6;
+/// The hidp SET_PROTOCOL message type
+const HIDP_TYPE_SET_PROTOCOL: u8 = 0x7;
+/// The hidp DATA message type
+const HIDP_TYPE_DATA: u8 = 0xa;
+/// The HID_CONTROL parameter for unplugging the virtual cable
+const HIDP_CONTROL_VIRTUAL_CABLE_UNPLUG: u8 = 0x5;
+/// The report protocol, the only protocol supported since the device is not a boot device
+const HIDP_PROTOCOL_REPORT: u8 = 0x1;
+
+/// The channels of a host connected to the hid device. Packets sent to them are written by the task of the channel.
+#[derive(Default)]
+struct HidChannels {
+    /// The control channel
+    control: Option<tokio::sync::mpsc::UnboundedSender<Vec<u8>>>,
+    /// The interrupt channel
+    interrupt: Option<tokio::sync::mpsc::UnboundedSender<Vec<u8>>>,
+}
+
+/// The state shared by the tasks of a hid device
+#[derive(Clone)]
+struct HidContext {
+    /// The channels of the connected hosts
+    connections: Arc<Mutex<HashMap<bluer::Address, HidChannels>>>,
+    /// Where the events are sent
+    events: tokio::sync::mpsc::Sender<crate::HidEvent>,
+    /// Do the reports start with a report id
+    report_ids: bool,
+}
+
+/// A hid device, made of an sdp record registered with BlueZ and the listeners for the hid control and interrupt
+/// channels. Hosts connect to the device, and every channel is closed when this is dropped.
+pub struct HidDevice {
+    /// The profile holding the sdp record
+    _profile: bluer::ProfileHandle,
+    /// The channels of the connected hosts
+    connections: Arc<Mutex<HashMap<bluer::Address, HidChannels>>>,
+    /// The tasks accepting the channels, which own the tasks for each channel
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+    /// The receiver for the events, until taken by the user
+    events: Option<tokio::sync::mpsc::Receiver<crate::HidEvent>>,
+}
+
+/// Escape text for an xml attribute value
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Build the sdp record of a hid device, in the xml format used by BlueZ
+fn hid_sdp_record(settings: &super::HidSettings) -> String {
+    let descriptor: String = settings
+        .descriptor
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" ?>
+<record>
+  <attribute id="0x0001"><sequence><uuid value="0x{service:04x}" /></sequence></attribute>
+  <attribute id="0x0004">
+    <sequence>
+      <sequence><uuid value="0x0100" /><uint16 value="0x{control:04x}" /></sequence>
+      <sequence><uuid value="0x0011" /></sequence>
+    </sequence>
+  </attribute>
+  <attribute id="0x0005"><sequence><uuid value="0x1002" /></sequence></attribute>
+  <attribute id="0x0006">
+    <sequence><uint16 value="0x656e" /><uint16 value="0x006a" /><uint16 value="0x0100" /></sequence>
+  </attribute>
+  <attribute id="0x0009">
+    <sequence><sequence><uuid value="0x{service:04x}" /><uint16 value="0x0101" /></sequence></sequence>
+  </attribute>
+  <attribute id="0x000d">
+    <sequence>
+      <sequence>
+        <sequence><uuid value="0x0100" /><uint16 value="0x{interrupt:04x}" /></sequence>
+        <sequence><uuid value="0x0011" /></sequence>
+      </sequence>
+    </sequence>
+  </attribute>
+  <attribute id="0x0100"><text value="{name}" /></attribute>
+  <attribute id="0x0101"><text value="{description}" /></attribute>
+  <attribute id="0x0102"><text value="{provider}" /></attribute>
+  <attribute id="0x0201"><uint16 value="0x0111" /></attribute>
+  <attribute id="0x0202"><uint8 value="0x{subclass:02x}" /></attribute>
+  <attribute id="0x0203"><uint8 value="0x00" /></attribute>
+  <attribute id="0x0204"><boolean value="true" /></attribute>
+  <attribute id="0x0205"><boolean value="true" /></attribute>
+  <attribute id="0x0206">
+    <sequence><sequence><uint8 value="0x22" /><text encoding="hex" value="{descriptor}" /></sequence></sequence>
+  </attribute>
+  <attribute id="0x0207">
+    <sequence><sequence><uint16 value="0x0409" /><uint16 value="0x0100" /></sequence></sequence>
+  </attribute>
+  <attribute id="0x020c"><uint16 value="0x0c80" /></attribute>
+  <attribute id="0x020d"><boolean value="false" /></attribute>
+  <attribute id="0x020e"><boolean value="false" /></attribute>
+</record>"#,
+        service = HID_SERVICE_UUID,
+        control = HID_CONTROL_PSM,
+        interrupt = HID_INTERRUPT_PSM,
+        name = xml_escape(&settings.name),
+        description = xml_escape(&settings.description),
+        provider = xml_escape(&settings.provider),
+        subclass = settings.subclass,
+    )
+}
+
+/// Check if a hid report descriptor has report id items, in which case every report starts with its id
+fn uses_report_ids(descriptor: &[u8]) -> bool {
+    let mut i = 0;
+    while let Some(&prefix) = descriptor.get(i) {
+        if prefix == 0xfe {
+            // A long item, with the size of its data in the next byte
+            i += 3 + descriptor.get(i + 1).copied().unwrap_or(0) as usize;
+            continue;
+        }
+        if prefix & 0xfc == 0x84 {
+            return true;
+        }
+        i += 1 + [0, 1, 2, 4][(prefix & 0x03) as usize];
+    }
+    false
+}
+
+/// Split the report id from the start of a report, when the reports have ids
+fn split_report_id(report: &[u8], report_ids: bool) -> (u8, &[u8]) {
+    match report.split_first() {
+        Some((id, data)) if report_ids => (*id, data),
+        _ => (0, report),
+    }
+}
+
+/// Build a hidp message carrying a report, leaving out the id when it is 0
+fn report_packet(header: u8, id: u8, data: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(data.len() + 2);
+    packet.push(header);
+    if id != 0 {
+        packet.push(id);
+    }
+    packet.extend_from_slice(data);
+    packet
+}
+
+/// Handle a message from the hid control channel, returning the event for it and the answer for the host
+fn hidp_control(
+    msg: &[u8],
+    report_ids: bool,
+    device: &crate::BluetoothAdapterAddress,
+) -> (Option<crate::HidEvent>, Option<Vec<u8>>) {
+    let Some((&header, body)) = msg.split_first() else {
+        return (None, None);
+    };
+    let param = header & 0x0f;
+    match header >> 4 {
+        HIDP_TYPE_CONTROL if param == HIDP_CONTROL_VIRTUAL_CABLE_UNPLUG => (
+            Some(crate::HidEvent::VirtualCableUnplug(device.clone())),
+            None,
+        ),
+        // Suspend and exit suspend need no answer
+        HIDP_TYPE_CONTROL => (None, None),
+        HIDP_TYPE_GET_REPORT | HIDP_TYPE_SET_REPORT => {
+            let Some(report_type) = super::HidReportType::from_value(param & 0x03) else {
+                return (None, Some(vec![HIDP_HANDSHAKE_ERR_INVALID_PARAMETER]));
+            };
+            let (id, data) = split_report_id(body, report_ids);
+            if header >> 4 == HIDP_TYPE_SET_REPORT {
+                let event = crate::HidEvent::SetReport {
+                    device: device.clone(),
+                    report_type,
+                    id,
+                    data: data.to_vec(),
+                };
+                return (Some(event), Some(vec![HIDP_HANDSHAKE_SUCCESSFUL]));
+            }
+            // The host gives the size of its buffer when the size bit is set
+            let buffer_size = match data {
+                [lo, hi, ..] if param & 0x08 != 0 => u16::from_le_bytes([*lo, *hi]),
+                _ => 0,
+            };
+            let event = crate::HidEvent::GetReport {
+                device: device.clone(),
+                report_type,
+                id,
+                buffer_size,
+            };
+            // The user answers with reply_report
+            (Some(event), None)
+        }
+        HIDP_TYPE_GET_PROTOCOL => (None, Some(vec![HIDP_TYPE_DATA << 4, HIDP_PROTOCOL_REPORT])),
+        HIDP_TYPE_SET_PROTOCOL if param == HIDP_PROTOCOL_REPORT => {
+            (None, Some(vec![HIDP_HANDSHAKE_SUCCESSFUL]))
+        }
+        HIDP_TYPE_SET_PROTOCOL => (None, Some(vec![HIDP_HANDSHAKE_ERR_INVALID_PARAMETER])),
+        _ => (None, Some(vec![HIDP_HANDSHAKE_ERR_UNSUPPORTED_REQUEST])),
+    }
+}
+
+/// Handle a message from the hid interrupt channel, which carries the output reports from the host
+fn hidp_interrupt(
+    msg: &[u8],
+    report_ids: bool,
+    device: &crate::BluetoothAdapterAddress,
+) -> Option<crate::HidEvent> {
+    let (&header, body) = msg.split_first()?;
+    if header != (HIDP_TYPE_DATA << 4) | super::HidReportType::Output.value() {
+        return None;
+    }
+    let (id, data) = split_report_id(body, report_ids);
+    Some(crate::HidEvent::OutputReport {
+        device: device.clone(),
+        id,
+        data: data.to_vec(),
+    })
+}
+
+/// Run a hid channel until the host closes it. Answers and reports queued for the channel are written here too.
+async fn hid_channel(
+    conn: bluer::l2cap::SeqPacket,
+    addr: bluer::Address,
+    interrupt: bool,
+    ctx: HidContext,
+) {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    {
+        let mut connections = ctx.connections.lock().unwrap();
+        let channels = connections.entry(addr).or_default();
+        if interrupt {
+            channels.interrupt = Some(tx.clone());
         } else {
-            None
+            channels.control = Some(tx.clone());
+        }
+    }
+    let device = crate::BluetoothAdapterAddress::Byte(addr.0);
+    // Reports can be sent once the interrupt channel is open
+    if interrupt {
+        let _ = ctx.events.try_send(crate::HidEvent::ConnectionChanged {
+            device: device.clone(),
+            connected: true,
+        });
+    }
+    let mtu = conn.as_ref().recv_mtu().unwrap_or(L2CAP_DEFAULT_MTU);
+    let writer = async {
+        while let Some(packet) = rx.recv().await {
+            if let Err(e) = conn.send(&packet).await {
+                log::warn!("Failed to write to the hid channel of {}: {}", addr, e);
+                break;
+            }
+        }
+    };
+    let reader = async {
+        let mut buf = vec![0u8; mtu as usize];
+        loop {
+            let len = match conn.recv(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(len) => len,
+            };
+            let event = if interrupt {
+                hidp_interrupt(&buf[..len], ctx.report_ids, &device)
+            } else {
+                let (event, answer) = hidp_control(&buf[..len], ctx.report_ids, &device);
+                if let Some(answer) = answer {
+                    let _ = tx.send(answer);
+                }
+                event
+            };
+            if let Some(event) = event {
+                let _ = ctx.events.try_send(event);
+            }
+        }
+    };
+    tokio::select! {
+        _ = writer => {}
+        _ = reader => {}
+    }
+    {
+        let mut connections = ctx.connections.lock().unwrap();
+        if let Some(channels) = connections.get_mut(&addr) {
+            // A newer channel from the same host replaces this one, and is left alone
+            let channel = if interrupt {
+                &mut channels.interrupt
+            } else {
+                &mut channels.control
+            };
+            if channel.as_ref().is_some_and(|c| c.same_channel(&tx)) {
+                *channel = None;
+            }
+            if channels.control.is_none() && channels.interrupt.is_none() {
+                connections.remove(&addr);
+            }
+        }
+    }
+    if interrupt {
+        let _ = ctx.events.try_send(crate::HidEvent::ConnectionChanged {
+            device,
+            connected: false,
+        });
+    }
+}
+
+/// Accept the hid channels from the hosts. The channels are closed when this task stops.
+async fn accept_hid_channels(
+    listener: bluer::l2cap::SeqPacketListener,
+    interrupt: bool,
+    ctx: HidContext,
+) {
+    let mut channels = tokio::task::JoinSet::new();
+    loop {
+        let (conn, addr) = match listener.accept().await {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("Failed to accept a hid channel: {}", e);
+                return;
+            }
+        };
+        while channels.try_join_next().is_some() {}
+        channels.spawn(hid_channel(conn, addr.addr, interrupt, ctx.clone()));
+    }
+}
+
+impl HidDevice {
+    /// Register the sdp record and listen for the hid channels on the local address
+    async fn new(
+        session: &bluer::Session,
+        local: bluer::Address,
+        settings: super::HidSettings,
+    ) -> Result<Self, std::io::Error> {
+        let listen = |psm| {
+            bluer::l2cap::SeqPacketListener::bind(bluer::l2cap::SocketAddr::new(
+                local,
+                bluer::AddressType::BrEdr,
+                psm,
+            ))
+        };
+        let control = listen(HID_CONTROL_PSM).await?;
+        let interrupt = listen(HID_INTERRUPT_PSM).await?;
+        let profile = session
+            .register_profile(bluer::rfcomm::Profile {
+                uuid: short_uuid(HID_SERVICE_UUID),
+                name: Some(settings.name.clone()),
+                service_record: Some(hid_sdp_record(&settings)),
+                ..Default::default()
+            })
+            .await
+            .map_err(berr)?;
+        let (tx, rx) = tokio::sync::mpsc::channel(32);
+        let ctx = HidContext {
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            events: tx,
+            report_ids: uses_report_ids(&settings.descriptor),
         };
+        let tasks = vec![
+            tokio::spawn(accept_hid_channels(control, false, ctx.clone())),
+            tokio::spawn(accept_hid_channels(interrupt, true, ctx.clone())),
+        ];
         Ok(Self {
-            uuid: bluer::Uuid::parse_str(&value.uuid).map_err(|e| e.to_string())?,
-            name: value.name,
-            service,
-            role: if value.channel.is_some() {
-                Some(bluer::rfcomm::Role::Server)
-            } else {
-                None
-            },
-            channel: value.channel,
-            psm: value.psm,
-            require_authentication: value.authenticate,
-            require_authorization: value.authorize,
-            auto_connect: value.auto_connect,
-            service_record: value.sdp_record,
-            version: value.sdp_version,
-            features: value.sdp_features,
-            ..Default::default()
+            _profile: profile,
+            connections: ctx.connections,
+            tasks,
+            events: Some(rx),
         })
     }
+
+    /// Queue a packet for a channel of a connected host
+    fn send(
+        &self,
+        device: &crate::BluetoothAdapterAddress,
+        interrupt: bool,
+        packet: Vec<u8>,
+    ) -> Result<(), crate::BluetoothError> {
+        let Some(address) = device.as_bytes() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid bluetooth address {}", device),
+            )
+            .into());
+        };
+        let connections = self.connections.lock().unwrap();
+        let channel = connections
+            .get(&bluer::Address::new(address))
+            .and_then(|c| {
+                if interrupt {
+                    c.interrupt.as_ref()
+                } else {
+                    c.control.as_ref()
+                }
+            });
+        match channel {
+            Some(c) if c.send(packet).is_ok() => Ok(()),
+            _ => Err(std::io::Error::from(std::io::ErrorKind::NotConnected).into()),
+        }
+    }
+}
+
+impl super::HidDeviceTrait for HidDevice {
+    fn send_report(
+        &self,
+        device: &crate::BluetoothAdapterAddress,
+        id: u8,
+        data: &[u8],
+    ) -> Result<(), crate::BluetoothError> {
+        let header = (HIDP_TYPE_DATA << 4) | super::HidReportType::Input.value();
+        self.send(device, true, report_packet(header, id, data))
+    }
+
+    fn reply_report(
+        &self,
+        device: &crate::BluetoothAdapterAddress,
+        report_type: super::HidReportType,
+        id: u8,
+        data: &[u8],
+    ) -> Result<(), crate::BluetoothError> {
+        let header = (HIDP_TYPE_DATA << 4) | report_type.value();
+        self.send(device, false, report_packet(header, id, data))
+    }
+
+    fn take_events(&mut self) -> Option<tokio::sync::mpsc::Receiver<crate::HidEvent>> {
+        self.events.take()
+    }
 }
 
-/// L2CAP profiles are registered through the same BlueZ D-Bus
-/// ProfileManager1 mechanism as RFCOMM profiles, but with the `psm` field
-/// set instead of `channel`.
-impl TryFrom<super::BluetoothL2capProfileSettings> for bluer::rfcomm::Profile {
+impl Drop for HidDevice {
+    fn drop(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// TryFrom conversion for profile settings → bluer::rfcomm::Profile
+// ────────────────────────────────────────────────────────────────────────────
+
+impl TryFrom<super::BluetoothRfcommProfileSettings> for bluer::rfcomm::Profile {
     type Error = String;
-    fn try_from(value: super::BluetoothL2capProfileSettings) -> Result<Self, Self::Error> {
+    fn try_from(value: super::BluetoothRfcommProfileSettings) -> Result<Self, Self::Error> {
         let service = if let Some(v) = value.service_uuid {
-            Some(bluer::Uuid::parse_str(&v).map_err(|e| e.to_string())?)
+            Some(parse_bluer_uuid(&v).map_err(|e| e.to_string())?)
         } else {
             None
         };
         Ok(Self {
-            uuid: bluer::Uuid::parse_str(&value.uuid).map_err(|e| e.to_string())?,
+            uuid: parse_bluer_uuid(&value.uuid).map_err(|e| e.to_string())?,
             name: value.name,
             service,
-            role: None,
-            channel: None,
+            role: match value.role {
+                Some(super::ProfileRole::Client) => Some(bluer::rfcomm::Role::Client),
+                Some(super::ProfileRole::Server) => Some(bluer::rfcomm::Role::Server),
+                None if value.channel.is_some() => Some(bluer::rfcomm::Role::Server),
+                None => None,
+            },
+            channel: value.channel,
             psm: value.psm,
             require_authentication: value.authenticate,
             require_authorization: value.authorize,
@@ -479,8 +2201,21 @@ impl TryFrom<super::BluetoothL2capProfileSettings> for bluer::rfcomm::Profile {
 pub struct BluetoothHandler {
     /// The current bluetooth session
     session: bluer::Session,
-    /// The list of bluetooth adapters for the system
-    adapters: Vec<bluer::Adapter>,
+    /// The list of bluetooth adapters in use. This is a single adapter when a specific adapter was selected.
+    /// It is kept up to date as adapters are added to or removed from the system.
+    adapters: Arc<Mutex<Vec<bluer::Adapter>>>,
+    /// The receivers of adapter hotplug events
+    hotplug_subscribers: Arc<Mutex<Vec<tokio::sync::mpsc::Sender<super::AdapterHotplugEvent>>>>,
+    /// Background tasks owned by the handler, stopped when the handler is dropped
+    tasks: Mutex<Vec<tokio::task::JoinHandle<()>>>,
+    /// The address of the adapter selected when building the handler
+    selected: Option<bluer::Address>,
+    /// How long discovery runs before it is stopped
+    discovery_duration: Option<std::time::Duration>,
+    /// The discovery started with a command, stopped when it is dropped
+    discovery: Mutex<Option<crate::BluetoothDiscovery>>,
+    /// The streams connected with a command, waiting to be taken
+    connections: Mutex<HashMap<super::BluetoothAdapterAddress, super::BluetoothStream>>,
     /// The agent for the handler
     _blue_agent_handle: bluer::agent::AgentHandle,
 }
@@ -493,83 +2228,498 @@ impl super::BluetoothAdapterTrait for BluetoothHandler {
     fn supports_sync(&self) -> Option<&dyn super::SyncBluetoothAdapterTrait> {
         None
     }
-}
+}
+
+#[async_trait::async_trait]
+impl super::AsyncBluetoothAdapterTrait for BluetoothHandler {
+    /// Register an rfcomm profile with BlueZ.
+    ///
+    /// Profiles registered with the BlueZ ProfileManager1 apply to every
+    /// adapter on the system, even when the handler was built for a single
+    /// adapter.
+    async fn register_rfcomm_profile(
+        &self,
+        settings: super::BluetoothRfcommProfileSettings,
+    ) -> Result<crate::BluetoothRfcommProfileAsync, crate::BluetoothError> {
+        settings.validate()?;
+        let handle = self
+            .session
+            .register_profile(settings.try_into()?)
+            .await
+            .map_err(berr)?;
+        Ok(super::BluetoothRfcommProfileAsync::Bluez(RfcommProfile {
+            handle,
+            session: self.session.clone(),
+        }))
+    }
+
+    /// Listen for L2CAP connections.
+    ///
+    /// The `psm` field in the settings selects the psm to listen on. When it is
+    /// `None` the kernel allocates a dynamic psm, which can be retrieved with
+    /// `BluetoothL2capProfileAsyncTrait::psm`. The listener only accepts
+    /// connections on the selected adapter, if there is one.
+    async fn register_l2cap_profile(
+        &self,
+        settings: super::BluetoothL2capProfileSettings,
+    ) -> Result<crate::BluetoothL2capProfileAsync, crate::BluetoothError> {
+        L2capProfile::new(&settings, self.selected.unwrap_or_else(bluer::Address::any))
+            .map(super::BluetoothL2capProfileAsync::Bluez)
+    }
+
+    async fn start_discovery(&self) -> Result<crate::BluetoothDiscovery, crate::BluetoothError> {
+        Ok(self.discover().await?.into())
+    }
+
+    async fn start_le_scan(
+        &self,
+        filter: crate::LeScanFilter,
+    ) -> Result<crate::LeScanSession, crate::BluetoothError> {
+        Ok(LeScanSession::new(self.adapters(), filter).await?.into())
+    }
+
+    /// Return all paired devices across every adapter.
+    ///
+    /// A device paired with more than one adapter is only reported once, using
+    /// the first adapter it was found on. Adapters that fail to enumerate their
+    /// devices are skipped.
+    async fn get_paired_devices(
+        &self,
+    ) -> Result<Vec<crate::BluetoothDevice>, crate::BluetoothError> {
+        let mut list = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        for adapter in &self.adapters() {
+            let addrs = match adapter.device_addresses().await {
+                Ok(addrs) => addrs,
+                Err(e) => {
+                    log::warn!("Failed to list devices for {}: {}", adapter.name(), e);
+                    continue;
+                }
+            };
+            for addr in addrs {
+                if seen.contains(&addr) {
+                    continue;
+                }
+                if let Ok(dev) = adapter.device(addr) {
+                    if dev.is_paired().await.unwrap_or(false) {
+                        seen.insert(addr);
+                        list.push(crate::BluetoothDevice::Bluez(LinuxBluetoothDevice::new(
+                            adapter.clone(),
+                            dev,
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(list)
+    }
+
+    async fn addresses(&self) -> Vec<super::BluetoothAdapterAddress> {
+        let mut a = Vec::new();
+        for adapter in &self.adapters() {
+            if let Ok(adr) = adapter.address().await {
+                a.push(super::BluetoothAdapterAddress::Byte(adr.0));
+            }
+        }
+        a
+    }
+
+    /// Bluez does not restrict applications with permissions
+    fn has_permission(&self, _permission: crate::BluetoothPermission) -> bool {
+        true
+    }
+
+    async fn request_permissions(
+        &self,
+        _permissions: &[crate::BluetoothPermission],
+    ) -> crate::PermissionOutcome {
+        crate::PermissionOutcome::Granted
+    }
+
+    async fn set_discoverable(
+        &self,
+        d: bool,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<bool, crate::BluetoothError> {
+        for adapter in &self.adapters() {
+            if let Some(timeout) = timeout {
+                let secs = timeout.as_secs().clamp(1, u32::MAX as u64) as u32;
+                adapter.set_discoverable_timeout(secs).await.map_err(berr)?;
+            }
+            adapter.set_discoverable(d).await.map_err(berr)?;
+        }
+        Ok(true)
+    }
+
+    async fn set_powered(
+        &self,
+        powered: bool,
+    ) -> Result<crate::PowerOutcome, crate::BluetoothError> {
+        for adapter in &self.adapters() {
+            adapter.set_powered(powered).await.map_err(berr)?;
+        }
+        Ok(crate::PowerOutcome::Completed)
+    }
+
+    /// Returns true when all adapters are powered
+    async fn is_powered(&self) -> Result<bool, crate::BluetoothError> {
+        let adapters = self.adapters();
+        let mut powered = !adapters.is_empty();
+        for adapter in &adapters {
+            powered &= adapter.is_powered().await.map_err(berr)?;
+        }
+        Ok(powered)
+    }
+
+    /// Returns the alias of the first adapter
+    async fn alias(&self) -> Result<String, crate::BluetoothError> {
+        let adapter = self
+            .adapters()
+            .into_iter()
+            .next()
+            .ok_or(crate::BluetoothError::AdapterUnavailable)?;
+        Ok(adapter.alias().await.map_err(berr)?)
+    }
+
+    async fn set_alias(&self, alias: &str) -> Result<(), crate::BluetoothError> {
+        for adapter in &self.adapters() {
+            adapter.set_alias(alias.to_string()).await.map_err(berr)?;
+        }
+        Ok(())
+    }
+
+    async fn set_pairable(&self, pairable: bool) -> Result<(), crate::BluetoothError> {
+        for adapter in &self.adapters() {
+            adapter.set_pairable(pairable).await.map_err(berr)?;
+        }
+        Ok(())
+    }
+
+    /// A zero timeout keeps the adapter pairable until it is turned off
+    async fn set_pairable_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<(), crate::BluetoothError> {
+        let secs = timeout.as_secs().min(u32::MAX as u64) as u32;
+        for adapter in &self.adapters() {
+            adapter.set_pairable_timeout(secs).await.map_err(berr)?;
+        }
+        Ok(())
+    }
 
-#[async_trait::async_trait]
-impl super::AsyncBluetoothAdapterTrait for BluetoothHandler {
-    async fn register_rfcomm_profile(
+    async fn adapter_events(
         &self,
-        settings: super::BluetoothRfcommProfileSettings,
-    ) -> Result<crate::BluetoothRfcommProfileAsync, String> {
-        self.session
-            .register_profile(settings.try_into()?)
-            .await
-            .map(|a| super::BluetoothRfcommProfileAsync::Bluez(a.into()))
-            .map_err(|e| e.to_string())
+    ) -> Result<tokio::sync::mpsc::Receiver<super::AdapterHotplugEvent>, crate::BluetoothError>
+    {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        self.hotplug_subscribers.lock().unwrap().push(tx);
+        Ok(rx)
     }
 
-    /// Register an L2CAP profile with BlueZ.
-    ///
-    /// BlueZ exposes a single `RegisterProfile` D-Bus method (ProfileManager1)
-    /// that handles both RFCOMM and L2CAP profiles.  The `psm` field in the
-    /// settings selects L2CAP; the RFCOMM `channel` field is left as `None`.
-    async fn register_l2cap_profile(
+    /// Bluez only reports the powered property, so the transitional states are never reported
+    async fn adapter_state_events(
         &self,
-        settings: super::BluetoothL2capProfileSettings,
-    ) -> Result<crate::BluetoothL2capProfileAsync, String> {
-        self.session
-            .register_profile(settings.try_into()?)
-            .await
-            .map(|a| super::BluetoothL2capProfileAsync::Bluez(a.into()))
-            .map_err(|e| e.to_string())
+    ) -> Result<tokio::sync::mpsc::Receiver<super::AdapterState>, crate::BluetoothError> {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        for adapter in self.adapters() {
+            let mut events = adapter.events().await.map_err(berr)?;
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                loop {
+                    let event = tokio::select! {
+                        e = events.next() => e,
+                        _ = tx.closed() => break,
+                    };
+                    let state = match event {
+                        Some(AdapterEvent::PropertyChanged(bluer::AdapterProperty::Powered(
+                            true,
+                        ))) => super::AdapterState::On,
+                        Some(AdapterEvent::PropertyChanged(bluer::AdapterProperty::Powered(
+                            false,
+                        ))) => super::AdapterState::Off,
+                        Some(_) => continue,
+                        None => break,
+                    };
+                    if tx.send(state).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        Ok(rx)
     }
 
-    fn start_discovery(&self) -> crate::BluetoothDiscovery {
-        BluetoothDiscovery::new().into()
+    /// Bluez has no scan mode, it is built from the discoverable and pairable properties of the first adapter
+    async fn scan_mode(&self) -> Result<super::ScanMode, crate::BluetoothError> {
+        let adapter = self
+            .adapters()
+            .into_iter()
+            .next()
+            .ok_or(crate::BluetoothError::AdapterUnavailable)?;
+        scan_mode(&adapter).await
     }
 
-    /// Return all paired devices across every adapter.
-    async fn get_paired_devices(&self) -> Option<Vec<crate::BluetoothDevice>> {
-        let mut list = Vec::new();
-        for adapter in &self.adapters {
-            let result = {
-                let addrs = adapter.device_addresses().await.ok()?;
-                let mut paired = Vec::new();
-                for addr in addrs {
-                    if let Ok(dev) = adapter.device(addr) {
-                        if dev.is_paired().await.unwrap_or(false) {
-                            paired.push(dev);
+    /// The scan mode is reported whenever the discoverable or pairable property of the first adapter changes
+    async fn scan_mode_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::ScanMode>, crate::BluetoothError> {
+        let adapter = self
+            .adapters()
+            .into_iter()
+            .next()
+            .ok_or(crate::BluetoothError::AdapterUnavailable)?;
+        let mut events = adapter.events().await.map_err(berr)?;
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    e = events.next() => e,
+                    _ = tx.closed() => break,
+                };
+                match event {
+                    Some(AdapterEvent::PropertyChanged(
+                        bluer::AdapterProperty::Discoverable(_)
+                        | bluer::AdapterProperty::Pairable(_),
+                    )) => {
+                        let Ok(mode) = scan_mode(&adapter).await else {
+                            continue;
+                        };
+                        if tx.send(mode).await.is_err() {
+                            break;
                         }
                     }
+                    Some(_) => {}
+                    None => break,
                 }
-                Ok::<Vec<bluer::Device>, bluer::Error>(paired)
+            }
+        });
+        Ok(rx)
+    }
+
+    /// The paired property of every device of every adapter is watched, including devices added later
+    async fn pairing_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::PairingEvent>, crate::BluetoothError> {
+        watch_all_devices(self.adapters(), |address, prop| {
+            let bluer::DeviceProperty::Paired(paired) = prop else {
+                return None;
             };
-            if let Ok(devices) = result {
-                for dev in devices {
-                    list.push(crate::BluetoothDevice::Bluez(LinuxBluetoothDevice::new(
-                        dev,
-                    )));
+            let status = |p| {
+                if p {
+                    crate::PairingStatus::Paired
+                } else {
+                    crate::PairingStatus::NotPaired
                 }
+            };
+            Some(crate::PairingEvent {
+                address: crate::BluetoothAdapterAddress::Byte(address.0),
+                old: status(!paired),
+                new: status(paired),
+            })
+        })
+        .await
+    }
+
+    /// The connected property of every device of every adapter is watched, including devices added later
+    async fn connection_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::ConnectionEvent>, crate::BluetoothError> {
+        watch_all_devices(self.adapters(), |address, prop| {
+            let bluer::DeviceProperty::Connected(connected) = prop else {
+                return None;
+            };
+            Some(crate::ConnectionEvent {
+                address: crate::BluetoothAdapterAddress::Byte(address.0),
+                connected,
+            })
+        })
+        .await
+    }
+
+    /// The device is taken from the first adapter that knows about it, or the first adapter when none do
+    async fn device_by_address(
+        &self,
+        address: &super::BluetoothAdapterAddress,
+    ) -> Result<crate::BluetoothDevice, crate::BluetoothError> {
+        let addr = bluer::Address(address.as_bytes().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid bluetooth address {}", address),
+            )
+        })?);
+        let adapters = self.adapters();
+        let mut chosen = None;
+        for adapter in &adapters {
+            if adapter
+                .device_addresses()
+                .await
+                .is_ok_and(|addrs| addrs.contains(&addr))
+            {
+                chosen = Some(adapter.clone());
+                break;
             }
         }
-        Some(list)
+        let adapter = chosen
+            .or_else(|| adapters.first().cloned())
+            .ok_or(crate::BluetoothError::AdapterUnavailable)?;
+        let device = adapter.device(addr).map_err(berr)?;
+        Ok(crate::BluetoothDevice::Bluez(LinuxBluetoothDevice::new(
+            adapter, device,
+        )))
     }
 
-    async fn addresses(&self) -> Vec<super::BluetoothAdapterAddress> {
-        let mut a = Vec::new();
-        for adapter in &self.adapters {
-            if let Ok(adr) = adapter.address().await {
-                a.push(super::BluetoothAdapterAddress::Byte(adr.0));
+    /// The channel for a uuid is looked up with an sdp query to the remote device
+    async fn connect_rfcomm(
+        &self,
+        address: &super::BluetoothAdapterAddress,
+        target: super::RfcommTarget,
+    ) -> Result<super::BluetoothStream, crate::BluetoothError> {
+        let addr = bluer::Address(address.as_bytes().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid bluetooth address {}", address),
+            )
+        })?);
+        let channel = match target {
+            super::RfcommTarget::Channel(c) => c,
+            super::RfcommTarget::Uuid(uuid) => {
+                let mac = addr.to_string();
+                let search = uuid.clone();
+                let record =
+                    tokio::task::spawn_blocking(move || super::sdp::run_sdp(&mac, &search))
+                        .await
+                        .map_err(std::io::Error::other)??;
+                record.rfcomm_channel().ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("{} does not advertise an rfcomm channel for {}", addr, uuid),
+                    )
+                })?
             }
+        };
+        let stream =
+            bluer::rfcomm::Stream::connect(bluer::rfcomm::SocketAddr::new(addr, channel)).await?;
+        Ok(super::BluetoothStream::Bluez(Box::pin(stream)))
+    }
+
+    /// The advertisement is registered with every adapter
+    async fn advertise(
+        &self,
+        settings: super::LeAdvertisementSettings,
+    ) -> Result<super::AdvertisementHandle, super::BluetoothError> {
+        let adv: bluer::adv::Advertisement = settings.try_into()?;
+        let mut handles = Vec::new();
+        for adapter in &self.adapters() {
+            handles.push(adapter.advertise(adv.clone()).await.map_err(berr)?);
         }
-        a
+        Ok(super::AdvertisementHandle::Bluez(AdvertisementHandle {
+            _handles: handles,
+        }))
     }
 
-    async fn set_discoverable(&self, d: bool) -> Result<(), ()> {
-        for adapter in &self.adapters {
-            adapter.set_discoverable(d).await.map_err(|_| ())?;
+    /// The service is registered with every adapter
+    async fn register_gatt_service(
+        &self,
+        service: super::GattServiceDefinition,
+    ) -> Result<super::GattServiceHandle, crate::BluetoothError> {
+        let mut handles = Vec::new();
+        for adapter in &self.adapters() {
+            // The application holds the callbacks, so each adapter gets its own copy
+            let app: bluer::gatt::local::Application = (&service)
+                .try_into()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+            handles.push(adapter.serve_gatt_application(app).await.map_err(berr)?);
+        }
+        Ok(super::GattServiceHandle::Bluez(GattServiceHandle {
+            _handles: handles,
+        }))
+    }
+
+    /// The hid channels use the psms reserved for hid, which requires the
+    /// CAP_NET_BIND_SERVICE capability. The input plugin of bluetoothd listens
+    /// on the same psms, so it must be disabled with `--noplugin=input`. Hosts
+    /// connect to the device, and reports can be sent once they have.
+    async fn register_hid_device(
+        &self,
+        settings: super::HidSettings,
+    ) -> Result<super::HidDeviceHandle, crate::BluetoothError> {
+        let local = self.selected.unwrap_or_else(bluer::Address::any);
+        Ok(super::HidDeviceHandle::Bluez(
+            HidDevice::new(&self.session, local, settings).await?,
+        ))
+    }
+
+    async fn issue_command(
+        &self,
+        cmd: super::BluetoothCommand,
+    ) -> Option<super::BluetoothResponse> {
+        match cmd {
+            super::BluetoothCommand::DetectAdapters => {
+                if let Err(e) = self.detect_adapters().await {
+                    return Some(super::BluetoothResponse::Error(e.to_string()));
+                }
+                Some(super::BluetoothResponse::AdapterList(
+                    self.adapter_info().await,
+                ))
+            }
+            super::BluetoothCommand::QueryNumAdapters => {
+                Some(super::BluetoothResponse::Adapters(self.adapters().len()))
+            }
+            super::BluetoothCommand::ListPairedDevices => Some(
+                super::BluetoothResponse::PairedDevices(self.paired_device_summaries().await),
+            ),
+            super::BluetoothCommand::StartDiscovery => {
+                // Stop the previous discovery first, so it does not stop the new one when dropped later
+                self.discovery.lock().unwrap().take();
+                match self.start_discovery().await {
+                    Ok(d) => {
+                        *self.discovery.lock().unwrap() = Some(d);
+                        Some(super::BluetoothResponse::DiscoveryStarted)
+                    }
+                    Err(e) => Some(super::BluetoothResponse::Error(e.to_string())),
+                }
+            }
+            super::BluetoothCommand::StopDiscovery => {
+                self.discovery.lock().unwrap().take();
+                Some(super::BluetoothResponse::DiscoveryStopped)
+            }
+            super::BluetoothCommand::SetDiscoverable {
+                enable,
+                timeout_secs,
+            } => {
+                let timeout = timeout_secs.map(|t| std::time::Duration::from_secs(t as u64));
+                Some(match self.set_discoverable(enable, timeout).await {
+                    Ok(d) => super::BluetoothResponse::Discoverable(d),
+                    Err(e) => super::BluetoothResponse::Error(e.to_string()),
+                })
+            }
+            super::BluetoothCommand::Pair { address } => {
+                Some(match self.pair_command(&address, true).await {
+                    Ok(a) => super::BluetoothResponse::Paired(a),
+                    Err(e) => super::BluetoothResponse::Error(e.to_string()),
+                })
+            }
+            super::BluetoothCommand::Unpair { address } => {
+                Some(match self.pair_command(&address, false).await {
+                    Ok(a) => super::BluetoothResponse::Unpaired(a),
+                    Err(e) => super::BluetoothResponse::Error(e.to_string()),
+                })
+            }
+            super::BluetoothCommand::ConnectRfcomm { address, uuid } => {
+                Some(match self.connect_command(&address, &uuid).await {
+                    Ok(a) => super::BluetoothResponse::RfcommConnected(a),
+                    Err(e) => super::BluetoothResponse::Error(e.to_string()),
+                })
+            }
+        }
+    }
+}
+
+impl Drop for BluetoothHandler {
+    fn drop(&mut self) {
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
         }
-        Ok(())
     }
 }
 
@@ -577,7 +2727,7 @@ impl BluetoothHandler {
     /// Retrieve the bluetooth addresses for all bluetooth adapters present
     pub async fn addresses(&self) -> Vec<bluer::Address> {
         let mut addrs = Vec::new();
-        for a in &self.adapters {
+        for a in &self.adapters() {
             if let Ok(addr) = a.address().await {
                 addrs.push(addr);
             }
@@ -585,50 +2735,149 @@ impl BluetoothHandler {
         addrs
     }
 
-    /// Construct a new self
+    /// Construct a new self. When an adapter address or name is given, only that adapter is used. Without a
+    /// sender, the agent answers requests by itself, accepting confirmations when auto_confirm is set.
     pub async fn new(
-        s: tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>,
-    ) -> Result<Self, String> {
-        let session = bluer::Session::new().await.map_err(|e| e.to_string())?;
-
-        let adapter_names = session.adapter_names().await.map_err(|e| e.to_string())?;
-        let adapters: Vec<bluer::Adapter> = adapter_names
+        s: Option<tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>>,
+        address: Option<super::BluetoothAdapterAddress>,
+        name: Option<String>,
+        config: super::AgentConfig,
+        auto_confirm: bool,
+        discovery_duration: Option<std::time::Duration>,
+    ) -> Result<Self, crate::BluetoothError> {
+        let session = bluer::Session::new().await.map_err(berr)?;
+
+        let adapter_names = session.adapter_names().await.map_err(berr)?;
+        let mut adapters: Vec<bluer::Adapter> = adapter_names
             .iter()
             .filter_map(|n| session.adapter(n).ok())
             .collect();
 
-        let blue_agent = Self::build_agent(s);
+        let mut selected = None;
+        if address.is_some() || name.is_some() {
+            let address = match address {
+                Some(a) => Some(bluer::Address(a.as_bytes().ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("Invalid bluetooth adapter address {}", a),
+                    )
+                })?)),
+                None => None,
+            };
+            let mut found = None;
+            let mut available = Vec::new();
+            for adapter in adapters.drain(..) {
+                let addr = adapter.address().await.map_err(berr)?;
+                available.push(format!("{} ({})", adapter.name(), addr));
+                let name_matches = name.as_ref().is_none_or(|n| n == adapter.name());
+                let address_matches = address.is_none_or(|a| a == addr);
+                if found.is_none() && name_matches && address_matches {
+                    found = Some((adapter, addr));
+                }
+            }
+            let Some((adapter, addr)) = found else {
+                log::error!(
+                    "No bluetooth adapter matching {}{}, available adapters: {}",
+                    name.as_deref().unwrap_or(""),
+                    address.map(|a| format!(" {}", a)).unwrap_or_default(),
+                    available.join(", ")
+                );
+                return Err(crate::BluetoothError::AdapterUnavailable);
+            };
+            adapters.push(adapter);
+            selected = Some(addr);
+        }
+
+        let adapters = Arc::new(Mutex::new(adapters));
+        let hotplug_subscribers = Arc::new(Mutex::new(Vec::new()));
+        let watch = Self::watch_adapters(
+            session.clone(),
+            adapters.clone(),
+            selected,
+            hotplug_subscribers.clone(),
+        )
+        .await?;
+
+        let blue_agent = match s {
+            Some(s) => Self::build_agent(session.clone(), s, config),
+            None => Self::build_default_agent(config, auto_confirm),
+        };
         let blue_agent_handle = session.register_agent(blue_agent).await;
         println!("Registered a bluetooth agent {}", blue_agent_handle.is_ok());
         Ok(Self {
             session,
             adapters,
-            _blue_agent_handle: blue_agent_handle.map_err(|e| e.to_string())?,
+            selected,
+            discovery_duration,
+            discovery: Mutex::new(None),
+            connections: Mutex::new(HashMap::new()),
+            hotplug_subscribers,
+            tasks: Mutex::new(vec![watch]),
+            _blue_agent_handle: blue_agent_handle.map_err(berr)?,
         })
     }
 
-    /// Enable all bluetooth adapters
-    async fn enable(&mut self) {
-        for adapter in &self.adapters {
-            adapter.set_powered(true).await.unwrap();
-            adapter.set_pairable(true).await.unwrap();
-        }
-    }
-
-    /// Disable all bluetooth adapters
-    async fn disable(&mut self) {
-        self.set_discoverable(false).await;
-        for adapter in &self.adapters {
-            adapter.set_powered(false).await.unwrap();
-            adapter.set_pairable(false).await.unwrap();
-        }
+    /// Get a copy of the list of adapters currently in use
+    fn adapters(&self) -> Vec<bluer::Adapter> {
+        self.adapters.lock().unwrap().clone()
     }
 
-    /// Enable or disable discoverable mode on all bluetooth adapters
-    pub async fn set_discoverable(&mut self, d: bool) {
-        for adapter in &self.adapters {
-            adapter.set_discoverable(d).await.unwrap();
+    /// Start a task that keeps the adapter list up to date as adapters are
+    /// plugged in or removed, and forwards the changes to the hotplug subscribers.
+    /// When an adapter was selected, only that adapter is added back to the list.
+    async fn watch_adapters(
+        session: bluer::Session,
+        adapters: Arc<Mutex<Vec<bluer::Adapter>>>,
+        selected: Option<bluer::Address>,
+        subscribers: Arc<Mutex<Vec<tokio::sync::mpsc::Sender<super::AdapterHotplugEvent>>>>,
+    ) -> Result<tokio::task::JoinHandle<()>, crate::BluetoothError> {
+        let mut events = session.events().await.map_err(berr)?;
+        // The address of an adapter can no longer be read once it is removed
+        let mut known = HashMap::new();
+        for name in session.adapter_names().await.map_err(berr)? {
+            if let Ok(addr) = session.adapter(&name).map_err(berr)?.address().await {
+                known.insert(name, addr);
+            }
         }
+        Ok(tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                let event = match event {
+                    bluer::SessionEvent::AdapterAdded(name) => {
+                        let Ok(adapter) = session.adapter(&name) else {
+                            continue;
+                        };
+                        let Ok(addr) = adapter.address().await else {
+                            continue;
+                        };
+                        known.insert(name.clone(), addr);
+                        if selected.is_none_or(|s| s == addr) {
+                            let mut list = adapters.lock().unwrap();
+                            if !list.iter().any(|a| a.name() == name) {
+                                list.push(adapter);
+                            }
+                        }
+                        super::AdapterHotplugEvent::AdapterAdded(
+                            super::BluetoothAdapterAddress::Byte(addr.0),
+                        )
+                    }
+                    bluer::SessionEvent::AdapterRemoved(name) => {
+                        adapters.lock().unwrap().retain(|a| a.name() != name);
+                        let Some(addr) = known.remove(&name) else {
+                            continue;
+                        };
+                        super::AdapterHotplugEvent::AdapterRemoved(
+                            super::BluetoothAdapterAddress::Byte(addr.0),
+                        )
+                    }
+                };
+                subscribers.lock().unwrap().retain(|s| {
+                    !matches!(
+                        s.try_send(event.clone()),
+                        Err(tokio::sync::mpsc::error::TrySendError::Closed(_))
+                    )
+                });
+            }
+        }))
     }
 
     /// Register an RFCOMM profile with the bluetooth session
@@ -639,66 +2888,180 @@ impl BluetoothHandler {
         self.session.register_profile(profile).await
     }
 
+    /// Wait for the host to answer a passkey display or confirmation. Answers are accepted until the
+    /// response timeout of the config expires, after which the configured default is used. The host is
+    /// always told to stop displaying the passkey once there is an outcome.
+    async fn wait_for_passkey_response<C: std::future::Future>(
+        s: &tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>,
+        responses: tokio::sync::mpsc::Receiver<super::ResponseToPasskey>,
+        config: &super::AgentConfig,
+        cancel: C,
+    ) -> bluer::agent::ReqResult<()> {
+        let answer = |r| match r {
+            super::ResponseToPasskey::Waiting => None,
+            r => Some(r),
+        };
+        let timed_out = config.default_on_timeout.clone();
+        Self::wait_for_host_response(s, responses, config, cancel, answer, timed_out).await
+    }
+
+    /// Wait for the host to answer a request, with `answer` turning an answer into the outcome or `None` when
+    /// the host is still deciding. Answers are accepted until the response timeout of the config expires, after
+    /// which `timed_out` is used. The host is always told to stop displaying the request once there is an
+    /// outcome.
+    async fn wait_for_host_response<T, C: std::future::Future>(
+        s: &tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>,
+        mut responses: tokio::sync::mpsc::Receiver<T>,
+        config: &super::AgentConfig,
+        cancel: C,
+        answer: impl Fn(T) -> Option<super::ResponseToPasskey>,
+        timed_out: super::ResponseToPasskey,
+    ) -> bluer::agent::ReqResult<()> {
+        let deadline = tokio::time::Instant::now() + config.response_timeout;
+        tokio::pin!(cancel);
+        let response = loop {
+            tokio::select! {
+                r = tokio::time::timeout_at(deadline, responses.recv()) => match r {
+                    Ok(Some(r)) => {
+                        if let Some(r) = answer(r) {
+                            break r;
+                        }
+                    }
+                    // The host dropped the responder without answering
+                    Ok(None) => break super::ResponseToPasskey::Cancel,
+                    Err(_) => break timed_out,
+                },
+                _ = &mut cancel => break super::ResponseToPasskey::Cancel,
+            }
+        };
+        let _ = s
+            .send(super::MessageToBluetoothHost::CancelDisplayPasskey)
+            .await;
+        match response {
+            super::ResponseToPasskey::Yes => Ok(()),
+            super::ResponseToPasskey::No => Err(bluer::agent::ReqError::Rejected),
+            super::ResponseToPasskey::Cancel | super::ResponseToPasskey::Waiting => {
+                Err(bluer::agent::ReqError::Canceled)
+            }
+        }
+    }
+
+    /// The outcome of a request the host allows or refuses
+    fn host_allowed(allowed: bool) -> super::ResponseToPasskey {
+        if allowed {
+            super::ResponseToPasskey::Yes
+        } else {
+            super::ResponseToPasskey::No
+        }
+    }
+
+    /// Get the name of the device an agent request is about, if it is known
+    async fn agent_device_name(
+        session: &bluer::Session,
+        adapter: &str,
+        device: bluer::Address,
+    ) -> Option<String> {
+        match session.adapter(adapter).and_then(|ad| ad.device(device)) {
+            Ok(d) => d.name().await.ok().flatten(),
+            Err(_) => None,
+        }
+    }
+
     /// Build a bluetooth agent for the handler
     fn build_agent(
+        session: bluer::Session,
         s: tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>,
+        config: super::AgentConfig,
     ) -> bluer::agent::Agent {
         let mut blue_agent = bluer::agent::Agent::default();
         blue_agent.request_default = true;
-        blue_agent.request_pin_code = None;
-        blue_agent.request_passkey = None;
         let s2 = s.clone();
-        blue_agent.display_passkey = Some(Box::new(move |mut a| {
-            println!("Running process for display_passkey: {:?}", a);
+        let config2 = config.clone();
+        blue_agent.request_pin_code = Some(Box::new(move |a| {
+            log::debug!("Need a pin code {:?}", a);
             let s3 = s2.clone();
+            let config = config2.clone();
             async move {
                 let mut chan = tokio::sync::mpsc::channel(5);
                 let _ = s3
-                    .send(super::MessageToBluetoothHost::DisplayPasskey(a.passkey, chan.0))
+                    .send(super::MessageToBluetoothHost::RequestPinCode {
+                        device: a.device.to_string(),
+                        responder: chan.0,
+                    })
                     .await;
-                loop {
-                    let f = tokio::time::timeout(std::time::Duration::from_secs(5), chan.1.recv());
-                    tokio::select! {
-                        asdf = f => {
-                            match asdf {
-                                Ok(Some(m)) => match m {
-                                    super::ResponseToPasskey::Yes => {
-                                        let _ = s3
-                                            .send(super::MessageToBluetoothHost::CancelDisplayPasskey)
-                                            .await;
-                                        return Ok(());
-                                    }
-                                    super::ResponseToPasskey::No => {
-                                        let _ = s3
-                                            .send(super::MessageToBluetoothHost::CancelDisplayPasskey)
-                                            .await;
-                                        return Err(bluer::agent::ReqError::Rejected);
-                                    }
-                                    super::ResponseToPasskey::Cancel => {
-                                        let _ = s3
-                                            .send(super::MessageToBluetoothHost::CancelDisplayPasskey)
-                                            .await;
-                                        return Err(bluer::agent::ReqError::Canceled);
-                                    }
-                                    super::ResponseToPasskey::Waiting => {}
-                                },
-                                Ok(None) => {}
-                                _ => {
-                                    let _ = s3
-                                        .send(super::MessageToBluetoothHost::CancelDisplayPasskey)
-                                        .await;
-                                    return Err(bluer::agent::ReqError::Canceled);
-                                }
-                            }
-                        }
-                        _ = &mut a.cancel => {
-                            let _ = s3
-                                .send(super::MessageToBluetoothHost::CancelDisplayPasskey)
-                                .await;
-                            break Err(bluer::agent::ReqError::Canceled);
-                        }
+                let f = tokio::time::timeout(config.response_timeout, chan.1.recv());
+                let r = match f.await {
+                    Ok(Some(r)) => match r.checked() {
+                        super::PinCodeReply::Pin(pin) => Ok(pin),
+                        super::PinCodeReply::Reject => Err(bluer::agent::ReqError::Rejected),
+                        super::PinCodeReply::Cancel => Err(bluer::agent::ReqError::Canceled),
+                    },
+                    _ => Err(bluer::agent::ReqError::Canceled),
+                };
+                let _ = s3
+                    .send(super::MessageToBluetoothHost::CancelDisplayPasskey)
+                    .await;
+                r
+            }
+            .boxed()
+        }));
+        let s2 = s.clone();
+        let config2 = config.clone();
+        let session2 = session.clone();
+        blue_agent.request_passkey = Some(Box::new(move |a| {
+            log::debug!("Need a passkey {:?}", a);
+            let s3 = s2.clone();
+            let session = session2.clone();
+            let config = config2.clone();
+            async move {
+                let name = Self::agent_device_name(&session, &a.adapter, a.device).await;
+                let mut chan = tokio::sync::mpsc::channel(5);
+                let _ = s3
+                    .send(super::MessageToBluetoothHost::RequestPasskey {
+                        device: super::BluetoothAdapterAddress::Byte(a.device.0),
+                        name,
+                        responder: chan.0,
+                    })
+                    .await;
+                let f = tokio::time::timeout(config.response_timeout, chan.1.recv());
+                let r = match f.await {
+                    Ok(Some(super::PasskeyEntryResponse::Passkey(p))) if p <= 999999 => Ok(p),
+                    Ok(Some(super::PasskeyEntryResponse::Passkey(p))) => {
+                        log::error!("Rejecting out of range passkey {}", p);
+                        Err(bluer::agent::ReqError::Rejected)
                     }
-                }
+                    Ok(Some(super::PasskeyEntryResponse::Reject)) => {
+                        Err(bluer::agent::ReqError::Rejected)
+                    }
+                    _ => Err(bluer::agent::ReqError::Canceled),
+                };
+                let _ = s3
+                    .send(super::MessageToBluetoothHost::CancelDisplayPasskey)
+                    .await;
+                r
+            }
+            .boxed()
+        }));
+        let s2 = s.clone();
+        let config2 = config.clone();
+        let session2 = session.clone();
+        blue_agent.display_passkey = Some(Box::new(move |mut a| {
+            println!("Running process for display_passkey: {:?}", a);
+            let s3 = s2.clone();
+            let session = session2.clone();
+            let config = config2.clone();
+            async move {
+                let name = Self::agent_device_name(&session, &a.adapter, a.device).await;
+                let chan = tokio::sync::mpsc::channel(5);
+                let _ = s3
+                    .send(super::MessageToBluetoothHost::DisplayPasskey {
+                        device: super::BluetoothAdapterAddress::Byte(a.device.0),
+                        name,
+                        passkey: a.passkey,
+                        responder: chan.0,
+                    })
+                    .await;
+                Self::wait_for_passkey_response(&s3, chan.1, &config, &mut a.cancel).await
             }
             .boxed()
         }));
@@ -711,127 +3074,426 @@ impl BluetoothHandler {
             .boxed()
         }));
         let s2 = s.clone();
+        let config2 = config.clone();
+        let session2 = session.clone();
         blue_agent.request_confirmation = Some(Box::new(move |a| {
             println!("Need to confirm {:?}", a);
             let s3 = s2.clone();
+            let session = session2.clone();
+            let config = config2.clone();
             async move {
-                let mut chan = tokio::sync::mpsc::channel(5);
+                let name = Self::agent_device_name(&session, &a.adapter, a.device).await;
+                let chan = tokio::sync::mpsc::channel(5);
                 let _ = s3
-                    .send(super::MessageToBluetoothHost::ConfirmPasskey(
-                        a.passkey, chan.0,
-                    ))
+                    .send(super::MessageToBluetoothHost::ConfirmPasskey {
+                        device: super::BluetoothAdapterAddress::Byte(a.device.0),
+                        name,
+                        passkey: a.passkey,
+                        responder: chan.0,
+                    })
                     .await;
-                loop {
-                    let f = tokio::time::timeout(std::time::Duration::from_secs(5), chan.1.recv());
-                    let asdf = f.await;
-                    match asdf {
-                        Ok(Some(m)) => match m {
-                            super::ResponseToPasskey::Yes => {
-                                let _ = s3
-                                    .send(super::MessageToBluetoothHost::CancelDisplayPasskey)
-                                    .await;
-                                return Ok(());
-                            }
-                            super::ResponseToPasskey::No => {
-                                let _ = s3
-                                    .send(super::MessageToBluetoothHost::CancelDisplayPasskey)
-                                    .await;
-                                return Err(bluer::agent::ReqError::Rejected);
-                            }
-                            super::ResponseToPasskey::Cancel => {
-                                let _ = s3
-                                    .send(super::MessageToBluetoothHost::CancelDisplayPasskey)
-                                    .await;
-                                return Err(bluer::agent::ReqError::Canceled);
-                            }
-                            super::ResponseToPasskey::Waiting => {}
-                        },
-                        Ok(None) => {}
-                        _ => {
-                            let _ = s3
-                                .send(super::MessageToBluetoothHost::CancelDisplayPasskey)
-                                .await;
-                            return Err(bluer::agent::ReqError::Canceled);
-                        }
-                    }
-                }
+                Self::wait_for_passkey_response(&s3, chan.1, &config, std::future::pending::<()>())
+                    .await
             }
             .boxed()
         }));
-        blue_agent.request_authorization = Some(Box::new(|a| {
+        let s2 = s.clone();
+        let config2 = config.clone();
+        let session2 = session.clone();
+        blue_agent.request_authorization = Some(Box::new(move |a| {
+            log::debug!("Need to authorize {:?}", a);
+            let s3 = s2.clone();
+            let session = session2.clone();
+            let config = config2.clone();
             async move {
-                println!("Need to authorize {:?}", a);
-                Ok(())
+                let name = Self::agent_device_name(&session, &a.adapter, a.device).await;
+                let mut chan = tokio::sync::mpsc::channel(1);
+                let _ = s3
+                    .send(super::MessageToBluetoothHost::AuthorizePairing {
+                        device_address: super::BluetoothAdapterAddress::Byte(a.device.0),
+                        name,
+                        responder: chan.0,
+                    })
+                    .await;
+                let answer = |allowed| Some(Self::host_allowed(allowed));
+                let timed_out = config.default_on_timeout.clone();
+                let pending = std::future::pending::<()>();
+                Self::wait_for_host_response(&s3, chan.1, &config, pending, answer, timed_out).await
             }
             .boxed()
         }));
-        blue_agent.authorize_service = Some(Box::new(|a| {
+        let s2 = s.clone();
+        blue_agent.authorize_service = Some(Box::new(move |a| {
+            log::debug!("Need to authorize service {:?}", a);
+            let s3 = s2.clone();
+            let session = session.clone();
+            let config = config.clone();
             async move {
-                println!("Need to authorize service {:?}", a);
-                Ok(())
+                let name = Self::agent_device_name(&session, &a.adapter, a.device).await;
+                let mut chan = tokio::sync::mpsc::channel(1);
+                let _ = s3
+                    .send(super::MessageToBluetoothHost::AuthorizeService {
+                        device: super::BluetoothAdapterAddress::Byte(a.device.0),
+                        name,
+                        service: bluetooth_uuid(a.service),
+                        responder: chan.0,
+                    })
+                    .await;
+                let answer = |allowed| Some(Self::host_allowed(allowed));
+                let timed_out = Self::host_allowed(config.authorize_service_default);
+                let pending = std::future::pending::<()>();
+                Self::wait_for_host_response(&s3, chan.1, &config, pending, answer, timed_out).await
             }
             .boxed()
         }));
         blue_agent
     }
 
-    /// Issues the specified bluetooth command, with an optional response for the command
-    pub async fn issue_command(
-        &mut self,
-        cmd: super::BluetoothCommand,
-    ) -> Option<super::BluetoothResponse> {
-        match cmd {
-            super::BluetoothCommand::QueryNumAdapters => {
-                Some(super::BluetoothResponse::Adapters(self.adapters.len()))
+    /// Build a bluetooth agent for a handler without a host. Confirmations and pairing authorizations are
+    /// accepted when auto_confirm is set, pin codes and passkeys are rejected because nobody can enter them.
+    fn build_default_agent(config: super::AgentConfig, auto_confirm: bool) -> bluer::agent::Agent {
+        let answer = move || {
+            if auto_confirm {
+                Ok(())
+            } else {
+                Err(bluer::agent::ReqError::Rejected)
+            }
+        };
+        let mut blue_agent = bluer::agent::Agent::default();
+        blue_agent.request_default = true;
+        blue_agent.request_pin_code = Some(Box::new(|a| {
+            log::info!("Rejecting pin code request {:?} without a host", a);
+            async move { Err(bluer::agent::ReqError::Rejected) }.boxed()
+        }));
+        blue_agent.request_passkey = Some(Box::new(|a| {
+            log::info!("Rejecting passkey request {:?} without a host", a);
+            async move { Err(bluer::agent::ReqError::Rejected) }.boxed()
+        }));
+        blue_agent.display_passkey = Some(Box::new(move |a| {
+            log::info!("Passkey display {:?} without a host", a);
+            async move { answer() }.boxed()
+        }));
+        blue_agent.request_confirmation = Some(Box::new(move |a| {
+            log::info!("Passkey confirmation {:?} without a host", a);
+            async move { answer() }.boxed()
+        }));
+        blue_agent.request_authorization = Some(Box::new(move |a| {
+            log::info!("Pairing authorization {:?} without a host", a);
+            async move { answer() }.boxed()
+        }));
+        blue_agent.authorize_service = Some(Box::new(move |a| {
+            log::info!("Service authorization {:?} without a host", a);
+            let allowed = config.authorize_service_default;
+            async move {
+                if allowed {
+                    Ok(())
+                } else {
+                    Err(bluer::agent::ReqError::Rejected)
+                }
             }
-            _ => None,
+            .boxed()
+        }));
+        blue_agent
+    }
+
+    /// Take the stream connected to the device by a `ConnectRfcomm` command
+    pub fn take_connection(
+        &self,
+        address: &super::BluetoothAdapterAddress,
+    ) -> Option<super::BluetoothStream> {
+        self.connections.lock().unwrap().remove(address)
+    }
+
+    /// Parse the address given to a command
+    fn command_address(
+        address: &str,
+    ) -> Result<super::BluetoothAdapterAddress, crate::BluetoothError> {
+        address
+            .parse()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e).into())
+    }
+
+    /// Pair with or remove the pairing of the device for the `Pair` and `Unpair` commands
+    async fn pair_command(
+        &self,
+        address: &str,
+        pair: bool,
+    ) -> Result<super::BluetoothAdapterAddress, crate::BluetoothError> {
+        use super::{AsyncBluetoothAdapterTrait, BluetoothDeviceAsyncTrait};
+        let address = Self::command_address(address)?;
+        let crate::BluetoothDevice::Bluez(mut device) = self.device_by_address(&address).await?
+        else {
+            return Err(crate::BluetoothError::Unsupported("Pairing"));
+        };
+        if pair {
+            device.pair().await?;
+        } else {
+            device.remove_bond().await?;
         }
+        Ok(address)
     }
 
-    /// Run a scan on all the bluetooth adapters, updating `bluetooth_devices`
-    /// with newly discovered or removed devices.
-    pub async fn scan<'a>(
-        &'a mut self,
-        bluetooth_devices: &mut HashMap<
-            bluer::Address,
-            (&'a bluer::Adapter, Option<bluer::Device>),
-        >,
-    ) {
-        let mut adapter_scanner = Vec::new();
-        for a in &self.adapters {
-            let da = a.discover_devices_with_changes().await.unwrap();
-            adapter_scanner.push((a, da));
+    /// Connect to the service of the device for the `ConnectRfcomm` command, keeping the stream
+    async fn connect_command(
+        &self,
+        address: &str,
+        uuid: &str,
+    ) -> Result<super::BluetoothAdapterAddress, crate::BluetoothError> {
+        use super::AsyncBluetoothAdapterTrait;
+        use std::str::FromStr;
+        let address = Self::command_address(address)?;
+        let uuid = super::BluetoothUuid::from_str(uuid)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let stream = self
+            .connect_rfcomm(&address, super::RfcommTarget::Uuid(uuid))
+            .await?;
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(address.clone(), stream);
+        Ok(address)
+    }
+
+    /// Get a summary of the devices paired with any adapter in use
+    async fn paired_device_summaries(&self) -> Vec<super::DeviceSummary> {
+        let mut list = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        for adapter in &self.adapters() {
+            let Ok(addrs) = adapter.device_addresses().await else {
+                continue;
+            };
+            for addr in addrs {
+                let Ok(dev) = adapter.device(addr) else {
+                    continue;
+                };
+                if !seen.contains(&addr) && dev.is_paired().await.unwrap_or(false) {
+                    seen.insert(addr);
+                    list.push(super::DeviceSummary {
+                        address: super::BluetoothAdapterAddress::Byte(addr.0),
+                        name: dev.name().await.ok().flatten(),
+                    });
+                }
+            }
         }
+        list
+    }
 
-        for (adapt, da) in &mut adapter_scanner {
-            if let Some(e) = da.next().await {
-                match e {
-                    AdapterEvent::DeviceAdded(addr) => {
-                        log::debug!("Device added {:?}", addr);
-                        bluetooth_devices.insert(addr, (adapt, None));
-                    }
-                    AdapterEvent::DeviceRemoved(addr) => {
-                        log::debug!("Device removed {:?}", addr);
-                        bluetooth_devices.remove_entry(&addr);
-                    }
-                    AdapterEvent::PropertyChanged(prop) => {
-                        log::debug!("Adapter property changed {:?}", prop);
-                    }
+    /// Enumerate the adapters of the session again, replacing the list of adapters in use.
+    /// When an adapter was selected, only that adapter is kept.
+    async fn detect_adapters(&self) -> Result<(), bluer::Error> {
+        let mut found = Vec::new();
+        for name in self.session.adapter_names().await? {
+            let adapter = self.session.adapter(&name)?;
+            if let Some(selected) = self.selected {
+                if adapter.address().await? != selected {
+                    continue;
                 }
             }
+            found.push(adapter);
         }
+        *self.adapters.lock().unwrap() = found;
+        Ok(())
+    }
 
-        for (addr, (adapter, dev)) in bluetooth_devices.iter_mut() {
-            if dev.is_none() {
-                if let Ok(d) = adapter.device(*addr) {
-                    if let Ok(ps) = d.all_properties().await {
-                        for p in ps {
-                            log::debug!("Device {:?} property: {:?}", addr, p);
-                        }
-                    }
-                    *dev = Some(d);
+    /// Get the details of every adapter in use
+    async fn adapter_info(&self) -> Vec<super::AdapterInfo> {
+        let mut list = Vec::new();
+        for adapter in &self.adapters() {
+            let address = match adapter.address().await {
+                Ok(a) => a,
+                Err(e) => {
+                    log::warn!("Failed to get the address of {}: {}", adapter.name(), e);
+                    continue;
                 }
+            };
+            list.push(super::AdapterInfo {
+                address: super::BluetoothAdapterAddress::Byte(address.0),
+                name: adapter.name().to_string(),
+                powered: adapter.is_powered().await.unwrap_or(false),
+                discoverable: adapter.is_discoverable().await.unwrap_or(false),
+            });
+        }
+        list
+    }
+
+    /// Start discovery on all the bluetooth adapters. The returned object is a
+    /// stream of the devices found, and discovery stops when it is dropped or
+    /// when the discovery duration the handler was built with has passed. This fails when discovery could not be
+    /// started on any adapter.
+    pub async fn discover(&self) -> Result<BluetoothDiscovery, crate::BluetoothError> {
+        BluetoothDiscovery::new(&self.adapters(), self.discovery_duration).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bluetooth base uuid, 16 bit uuids are shifted into bits 96 to 111
+    const BASE: u128 = 0x00000000_0000_1000_8000_00805f9b34fb;
+
+    /// A service with a characteristic that can be read and written, and one that notifies
+    fn gatt_service(value: Arc<Mutex<Vec<u8>>>) -> crate::GattServiceDefinition {
+        let read_value = value.clone();
+        let read: crate::GattReadCallback = Arc::new(move || -> crate::GattReadFuture {
+            let value = read_value.lock().unwrap().clone();
+            Box::pin(async move { Ok(value) })
+        });
+        let write: crate::GattWriteCallback = Arc::new(move |data| -> crate::GattWriteFuture {
+            *value.lock().unwrap() = data;
+            Box::pin(async { Ok(()) })
+        });
+        crate::GattServiceDefinition {
+            uuid: crate::BluetoothUuid::from_u16(0x180f),
+            primary: true,
+            characteristics: vec![
+                crate::GattCharacteristicDefinition {
+                    uuid: crate::BluetoothUuid::from_u16(0x2a19),
+                    read: Some(read),
+                    write: Some(write),
+                    notify: None,
+                },
+                crate::GattCharacteristicDefinition {
+                    uuid: crate::BluetoothUuid::from_u16(0x2a1a),
+                    read: None,
+                    write: None,
+                    notify: Some(tokio::sync::broadcast::channel(4).0),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn gatt_service_converts_to_bluer() {
+        let service = gatt_service(Arc::new(Mutex::new(Vec::new())));
+        let app = bluer::gatt::local::Application::try_from(&service).unwrap();
+        assert_eq!(app.services.len(), 1);
+        let s = &app.services[0];
+        assert_eq!(s.uuid, bluer::Uuid::from_u128((0x180f << 96) | BASE));
+        assert!(s.primary);
+        assert_eq!(s.characteristics.len(), 2);
+
+        let value = &s.characteristics[0];
+        assert_eq!(value.uuid, bluer::Uuid::from_u128((0x2a19 << 96) | BASE));
+        assert!(value.read.as_ref().is_some_and(|r| r.read));
+        assert!(
+            value
+                .write
+                .as_ref()
+                .is_some_and(|w| w.write && w.write_without_response)
+        );
+        assert!(value.notify.is_none());
+
+        let status = &s.characteristics[1];
+        assert!(status.read.is_none());
+        assert!(status.write.is_none());
+        assert!(status.notify.as_ref().is_some_and(|n| n.notify));
+    }
+
+    #[tokio::test]
+    async fn gatt_callbacks_loop_back() {
+        let service = gatt_service(Arc::new(Mutex::new(vec![1])));
+        let c = &service.characteristics[0];
+        let (read, write) = (c.read.clone().unwrap(), c.write.clone().unwrap());
+        assert_eq!(gatt_read(read.clone()).await.unwrap(), vec![1]);
+        gatt_write(write, vec![2, 3]).await.unwrap();
+        assert_eq!(gatt_read(read).await.unwrap(), vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn gatt_callback_errors_are_reported_to_the_remote_device() {
+        let read: crate::GattReadCallback = Arc::new(|| -> crate::GattReadFuture {
+            Box::pin(async { Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied)) })
+        });
+        let write: crate::GattWriteCallback = Arc::new(|_: Vec<u8>| -> crate::GattWriteFuture {
+            Box::pin(async { Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)) })
+        });
+        assert!(matches!(
+            gatt_read(read).await,
+            Err(bluer::gatt::local::ReqError::NotPermitted)
+        ));
+        assert!(matches!(
+            gatt_write(write, vec![0]).await,
+            Err(bluer::gatt::local::ReqError::InvalidValueLength)
+        ));
+    }
+
+    /// The settings of an advertisement for the serial port service
+    fn advertisement_settings() -> crate::LeAdvertisementSettings {
+        crate::LeAdvertisementSettings {
+            service_uuids: vec![crate::BluetoothUuid::SPP],
+            local_name: Some("Radio".to_string()),
+            manufacturer_data: [(0x0059, vec![1, 2, 3])].into_iter().collect(),
+            discoverable: true,
+        }
+    }
+
+    #[test]
+    fn advertisement_settings_convert_to_bluer() {
+        let adv = bluer::adv::Advertisement::try_from(advertisement_settings()).unwrap();
+        assert!(matches!(
+            adv.advertisement_type,
+            bluer::adv::Type::Peripheral
+        ));
+        let spp = bluer::Uuid::from_u128(0x00001101_0000_1000_8000_00805f9b34fb);
+        assert_eq!(adv.service_uuids, [spp].into_iter().collect());
+        assert_eq!(adv.local_name.as_deref(), Some("Radio"));
+        assert_eq!(adv.manufacturer_data.get(&0x0059), Some(&vec![1, 2, 3]));
+        assert_eq!(adv.discoverable, Some(true));
+    }
+
+    #[test]
+    fn default_advertisement_settings_convert_to_bluer() {
+        let adv =
+            bluer::adv::Advertisement::try_from(crate::LeAdvertisementSettings::default()).unwrap();
+        assert!(adv.service_uuids.is_empty());
+        assert!(adv.manufacturer_data.is_empty());
+        assert_eq!(adv.local_name, None);
+        assert_eq!(adv.discoverable, Some(false));
+    }
+
+    #[test]
+    fn oversized_manufacturer_data_is_rejected() {
+        let with_data = |len| {
+            let mut settings = advertisement_settings();
+            settings.manufacturer_data.insert(0x0059, vec![0; len]);
+            bluer::adv::Advertisement::try_from(settings)
+        };
+        assert!(with_data(MAX_MANUFACTURER_DATA).is_ok());
+        match with_data(MAX_MANUFACTURER_DATA + 1) {
+            Err(crate::BluetoothError::Io(e)) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput)
             }
+            _ => panic!("Expected an invalid input error"),
         }
     }
+
+    /// Wait for the Discovering property of the adapter to take the value
+    #[cfg(feature = "integration")]
+    async fn wait_for_discovering(adapter: &bluer::Adapter, discovering: bool) {
+        let wait = async {
+            while adapter.is_discovering().await.unwrap() != discovering {
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(10), wait)
+            .await
+            .unwrap_or_else(|_| panic!("Discovering did not become {discovering}"));
+    }
+
+    /// Needs bluetoothd and a bluetooth adapter that is not discovering already
+    #[cfg(feature = "integration")]
+    #[tokio::test]
+    async fn discovery_toggles_the_discovering_property() {
+        let session = bluer::Session::new().await.unwrap();
+        let adapter = session.default_adapter().await.unwrap();
+        adapter.set_powered(true).await.unwrap();
+        wait_for_discovering(&adapter, false).await;
+        let discovery = BluetoothDiscovery::new(std::slice::from_ref(&adapter), None)
+            .await
+            .unwrap();
+        wait_for_discovering(&adapter, true).await;
+        drop(discovery);
+        wait_for_discovering(&adapter, false).await;
+    }
 }
@@ -0,0 +1,1423 @@
+//! A scriptable mock bluetooth adapter for testing applications. Tests declare the remote devices, script what
+//! discovery finds, send pairing requests to the bluetooth host, and connect scripted peers to the profiles the
+//! application registers.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::io::AsyncWriteExt;
+
+/// A remote device declared on the mock adapter
+struct Device {
+    /// The name of the device
+    name: String,
+    /// The name assigned to the device by the user
+    alias: Option<String>,
+    /// The uuids of the services of the device
+    uuids: Vec<crate::BluetoothUuid>,
+    /// Is the device paired
+    paired: bool,
+    /// Is the device trusted
+    trusted: bool,
+    /// Is the device blocked
+    blocked: bool,
+    /// Is the device connected
+    connected: bool,
+    /// The receivers of property changes of the device
+    watchers: Vec<tokio::sync::mpsc::Sender<crate::DevicePropertyEvent>>,
+}
+
+impl Device {
+    /// Send a property change to the watchers, forgetting the ones whose receiver is gone
+    fn notify(&mut self, event: crate::DevicePropertyEvent) {
+        self.watchers.retain(|w| !w.is_closed());
+        for w in &self.watchers {
+            let _ = w.try_send(event.clone());
+        }
+    }
+}
+
+/// The state shared by a mock adapter and the objects created from it
+struct State {
+    /// The declared remote devices
+    devices: std::collections::HashMap<crate::BluetoothAdapterAddress, Device>,
+    /// The devices found by discovery, and how long after the start of discovery they are found
+    discovery: Vec<(std::time::Duration, crate::BluetoothAdapterAddress)>,
+    /// The sender for messages to the bluetooth host
+    host: Option<tokio::sync::mpsc::Sender<crate::MessageToBluetoothHost>>,
+    /// How requests to the bluetooth host are handled
+    config: crate::AgentConfig,
+    /// How confirmations are answered when there is no host
+    auto_confirm: bool,
+    /// The number of discovery objects that have not been dropped, the adapter is discovering while it is not 0
+    discovering: usize,
+}
+
+/// A mock bluetooth adapter for tests. Clones of the adapter share the same state, so a test can keep a clone
+/// to script the adapter after handing it to [`crate::BluetoothAdapterBuilder::with_mock`].
+///
+/// Profiles, streams, and the adapter properties work like the [`crate::DummyAdapter`] that the mock is built
+/// on. The connections the mock makes come from the declared devices instead of the adapter itself.
+#[derive(Clone)]
+pub struct MockAdapter {
+    /// The loopback adapter that provides the profiles and adapter properties
+    inner: crate::dummy::DummyAdapter,
+    /// The scripted state
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for MockAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockAdapter {
+    /// Construct a new mock adapter without any devices, with the address 00:00:00:00:00:01
+    pub fn new() -> Self {
+        Self::with_address([0, 0, 0, 0, 0, 1])
+    }
+
+    /// Construct a new mock adapter without any devices, with the given address
+    pub fn with_address(address: [u8; 6]) -> Self {
+        Self {
+            inner: crate::dummy::DummyAdapter::with_address(address),
+            state: Arc::new(Mutex::new(State {
+                devices: std::collections::HashMap::new(),
+                discovery: Vec::new(),
+                host: None,
+                config: crate::AgentConfig::default(),
+                auto_confirm: false,
+                discovering: 0,
+            })),
+        }
+    }
+
+    /// Set how requests to the bluetooth host are sent and answered, done by the adapter builder
+    pub(crate) fn set_host(
+        &self,
+        host: Option<tokio::sync::mpsc::Sender<crate::MessageToBluetoothHost>>,
+        config: crate::AgentConfig,
+        auto_confirm: bool,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        state.host = host;
+        state.config = config;
+        state.auto_confirm = auto_confirm;
+    }
+
+    /// Declare a remote device, replacing any device with the same address
+    pub fn add_device(
+        &self,
+        address: crate::BluetoothAdapterAddress,
+        name: &str,
+        uuids: Vec<crate::BluetoothUuid>,
+        paired: bool,
+    ) {
+        self.state.lock().unwrap().devices.insert(
+            address.normalized(),
+            Device {
+                name: name.to_string(),
+                alias: None,
+                uuids,
+                paired,
+                trusted: false,
+                blocked: false,
+                connected: false,
+                watchers: Vec::new(),
+            },
+        );
+    }
+
+    /// Make discovery find the device at the given time after discovery starts. Discovery finishes after the
+    /// last device is found.
+    pub fn add_discovery_result(
+        &self,
+        after: std::time::Duration,
+        address: crate::BluetoothAdapterAddress,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        state.discovery.push((after, address.normalized()));
+        state.discovery.sort_by_key(|(after, _)| *after);
+    }
+
+    /// Is the adapter discovering devices, like the Discovering property of a bluez adapter. Discovery runs from
+    /// `start_discovery` until the returned discovery object is dropped.
+    pub fn is_discovering(&self) -> bool {
+        self.state.lock().unwrap().discovering > 0
+    }
+
+    /// Get a handle for a declared device
+    fn device(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+    ) -> Result<crate::BluetoothDevice, std::io::Error> {
+        let address = address.clone().normalized();
+        if !self.state.lock().unwrap().devices.contains_key(&address) {
+            return Err(no_device(&address));
+        }
+        Ok(crate::BluetoothDevice::Mock(BluetoothDevice {
+            address,
+            mock: self.clone(),
+        }))
+    }
+
+    /// Run the closure on a declared device
+    fn with_device<T>(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        f: impl FnOnce(&mut Device) -> T,
+    ) -> Result<T, std::io::Error> {
+        let address = address.clone().normalized();
+        let mut state = self.state.lock().unwrap();
+        let device = state
+            .devices
+            .get_mut(&address)
+            .ok_or_else(|| no_device(&address))?;
+        Ok(f(device))
+    }
+
+    /// Change whether a device is paired, sending the pairing and property events when it changes
+    fn set_paired(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        paired: bool,
+    ) -> Result<(), std::io::Error> {
+        let changed = self.with_device(address, |d| {
+            let changed = d.paired != paired;
+            if changed {
+                d.paired = paired;
+                d.notify(crate::DevicePropertyEvent::Paired(paired));
+            }
+            changed
+        })?;
+        if changed {
+            let status = |p| {
+                if p {
+                    crate::PairingStatus::Paired
+                } else {
+                    crate::PairingStatus::NotPaired
+                }
+            };
+            self.inner.pairing_event(crate::PairingEvent {
+                address: address.clone().normalized(),
+                old: status(!paired),
+                new: status(paired),
+            });
+        }
+        Ok(())
+    }
+
+    /// Send a request to the bluetooth host and wait up to the response timeout for the answer. This is
+    /// `Ok(None)` when there is no host, or the host did not answer in time.
+    async fn ask<T>(
+        &self,
+        request: impl FnOnce(tokio::sync::mpsc::Sender<T>) -> crate::MessageToBluetoothHost,
+    ) -> Option<(T, tokio::sync::mpsc::Receiver<T>)> {
+        let (host, timeout) = {
+            let state = self.state.lock().unwrap();
+            (state.host.clone()?, state.config.response_timeout)
+        };
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(5);
+        host.send(request(sender)).await.ok()?;
+        match tokio::time::timeout(timeout, receiver.recv()).await {
+            Ok(Some(answer)) => Some((answer, receiver)),
+            _ => None,
+        }
+    }
+
+    /// Tell the bluetooth host to stop displaying a pairing request
+    async fn cancel_display(&self) {
+        let host = self.state.lock().unwrap().host.clone();
+        if let Some(host) = host {
+            let _ = host
+                .send(crate::MessageToBluetoothHost::CancelDisplayPasskey)
+                .await;
+        }
+    }
+
+    /// Wait for the answer to a passkey display or confirmation, skipping `Waiting` answers until the response
+    /// timeout expires. The request is built from the address and name of the device.
+    async fn passkey_request(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        request: impl FnOnce(
+            crate::BluetoothAdapterAddress,
+            Option<String>,
+            tokio::sync::mpsc::Sender<crate::ResponseToPasskey>,
+        ) -> crate::MessageToBluetoothHost,
+    ) -> Result<crate::ResponseToPasskey, crate::BluetoothError> {
+        let name = self.with_device(address, |d| d.name.clone())?;
+        let device = address.clone().normalized();
+        let (config, auto_confirm) = {
+            let state = self.state.lock().unwrap();
+            (state.config.clone(), state.auto_confirm)
+        };
+        let deadline = tokio::time::Instant::now() + config.response_timeout;
+        let response = match self.ask(|s| request(device, Some(name), s)).await {
+            None if self.state.lock().unwrap().host.is_none() => {
+                if auto_confirm {
+                    crate::ResponseToPasskey::Yes
+                } else {
+                    crate::ResponseToPasskey::No
+                }
+            }
+            None => config.default_on_timeout.clone(),
+            Some((mut response, mut receiver)) => loop {
+                if !matches!(response, crate::ResponseToPasskey::Waiting) {
+                    break response;
+                }
+                response = match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                    Ok(Some(r)) => r,
+                    Ok(None) => crate::ResponseToPasskey::Cancel,
+                    Err(_) => break config.default_on_timeout.clone(),
+                };
+            },
+        };
+        self.cancel_display().await;
+        if matches!(response, crate::ResponseToPasskey::Yes) {
+            self.set_paired(address, true)?;
+        }
+        Ok(response)
+    }
+
+    /// Have the device ask the bluetooth host to confirm the passkey, like a device pairing with numeric
+    /// comparison. The device becomes paired when the host answers `Yes`. A timeout gives the
+    /// `default_on_timeout` of the agent config. Without a host the answer follows the auto confirm setting of
+    /// the builder.
+    pub async fn confirm_passkey(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        passkey: u32,
+    ) -> Result<crate::ResponseToPasskey, crate::BluetoothError> {
+        self.passkey_request(address, |device, name, responder| {
+            crate::MessageToBluetoothHost::ConfirmPasskey {
+                device,
+                name,
+                passkey,
+                responder,
+            }
+        })
+        .await
+    }
+
+    /// Have the device ask the bluetooth host to display the passkey, which the user types on the device. The
+    /// answers are handled like [`MockAdapter::confirm_passkey`].
+    pub async fn display_passkey(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        passkey: u32,
+    ) -> Result<crate::ResponseToPasskey, crate::BluetoothError> {
+        self.passkey_request(address, |device, name, responder| {
+            crate::MessageToBluetoothHost::DisplayPasskey {
+                device,
+                name,
+                passkey,
+                responder,
+            }
+        })
+        .await
+    }
+
+    /// Have a legacy device ask the bluetooth host for a pin code. The device becomes paired when the host
+    /// answers with a pin of 1 to 16 ascii characters, other pins are turned into `Reject`. No answer in time
+    /// cancels the request, and it is rejected when there is no host.
+    pub async fn request_pin_code(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+    ) -> Result<crate::PinCodeReply, crate::BluetoothError> {
+        self.with_device(address, |_| ())?;
+        let device = address.to_string();
+        let response = match self
+            .ask(|responder| crate::MessageToBluetoothHost::RequestPinCode { device, responder })
+            .await
+        {
+            Some((r, _)) => r.checked(),
+            None if self.state.lock().unwrap().host.is_none() => crate::PinCodeReply::Reject,
+            None => crate::PinCodeReply::Cancel,
+        };
+        self.cancel_display().await;
+        if matches!(response, crate::PinCodeReply::Pin(_)) {
+            self.set_paired(address, true)?;
+        }
+        Ok(response)
+    }
+
+    /// Have the device ask the bluetooth host for the passkey it displays. The device becomes paired when the
+    /// host answers with a passkey in the range 0..=999999, passkeys out of range are turned into `Reject`. No
+    /// answer in time, or no host, cancels the request.
+    pub async fn request_passkey(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+    ) -> Result<crate::PasskeyEntryResponse, crate::BluetoothError> {
+        let name = self.with_device(address, |d| d.name.clone())?;
+        let device = address.clone().normalized();
+        let response = match self
+            .ask(|responder| crate::MessageToBluetoothHost::RequestPasskey {
+                device,
+                name: Some(name),
+                responder,
+            })
+            .await
+        {
+            Some((crate::PasskeyEntryResponse::Passkey(p), _)) if p > 999999 => {
+                crate::PasskeyEntryResponse::Reject
+            }
+            Some((r, _)) => r,
+            None if self.state.lock().unwrap().host.is_none() => {
+                crate::PasskeyEntryResponse::Reject
+            }
+            None => crate::PasskeyEntryResponse::Cancel,
+        };
+        self.cancel_display().await;
+        if matches!(response, crate::PasskeyEntryResponse::Passkey(_)) {
+            self.set_paired(address, true)?;
+        }
+        Ok(response)
+    }
+
+    /// Have a device without input or output capabilities ask the bluetooth host to allow pairing. The device
+    /// becomes paired when the host allows it. No answer in time is a refusal, and without a host the answer
+    /// follows the auto confirm setting of the builder.
+    pub async fn authorize_pairing(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+    ) -> Result<bool, crate::BluetoothError> {
+        let name = self.with_device(address, |d| d.name.clone())?;
+        let device_address = address.clone().normalized();
+        let allowed = match self
+            .ask(
+                |responder| crate::MessageToBluetoothHost::AuthorizePairing {
+                    device_address,
+                    name: Some(name),
+                    responder,
+                },
+            )
+            .await
+        {
+            Some((allowed, _)) => allowed,
+            None if self.state.lock().unwrap().host.is_none() => {
+                self.state.lock().unwrap().auto_confirm
+            }
+            None => false,
+        };
+        if allowed {
+            self.set_paired(address, true)?;
+        }
+        Ok(allowed)
+    }
+
+    /// Have a paired device ask the bluetooth host to allow a connection to the service. No answer in time, or
+    /// no host, gives the `authorize_service_default` of the agent config.
+    pub async fn authorize_service(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        service: crate::BluetoothUuid,
+    ) -> Result<bool, crate::BluetoothError> {
+        let name = self.with_device(address, |d| d.name.clone())?;
+        let device = address.clone().normalized();
+        let answer = self
+            .ask(
+                |responder| crate::MessageToBluetoothHost::AuthorizeService {
+                    device,
+                    name: Some(name),
+                    service,
+                    responder,
+                },
+            )
+            .await;
+        Ok(match answer {
+            Some((allowed, _)) => allowed,
+            None => self.state.lock().unwrap().config.authorize_service_default,
+        })
+    }
+
+    /// Connect the device to the rfcomm profile registered by the application. Once the application accepts
+    /// the connection, the canned bytes are written to it and the stream of the device is returned, so the test
+    /// can read what the application sends and send more. Blocked devices cannot connect.
+    pub async fn connect_peer(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        target: crate::RfcommTarget,
+        canned: &[u8],
+    ) -> Result<tokio::io::DuplexStream, std::io::Error> {
+        self.peer(
+            address,
+            crate::dummy::DummyAdapter::rfcomm_key(target),
+            canned,
+        )
+        .await
+    }
+
+    /// Connect the device to the l2cap profile registered by the application on the psm, like
+    /// [`MockAdapter::connect_peer`]
+    pub async fn connect_peer_l2cap(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        psm: u16,
+        canned: &[u8],
+    ) -> Result<tokio::io::DuplexStream, std::io::Error> {
+        self.peer(address, crate::dummy::ListenerKey::L2cap(psm), canned)
+            .await
+    }
+
+    /// Connect the device to the profile and write the canned bytes once the connection is accepted
+    async fn peer(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        key: crate::dummy::ListenerKey,
+        canned: &[u8],
+    ) -> Result<tokio::io::DuplexStream, std::io::Error> {
+        let (name, blocked) = self.with_device(address, |d| (d.name.clone(), d.blocked))?;
+        if blocked {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                format!("The device {} is blocked", address),
+            ));
+        }
+        let bytes = address.as_bytes().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid bluetooth address {}", address),
+            )
+        })?;
+        let answer = self.inner.connect_from(bytes, name, key)?;
+        let mut stream = answer.await.map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                "The connection was rejected",
+            )
+        })?;
+        self.with_device(address, |d| {
+            if !d.connected {
+                d.connected = true;
+                d.notify(crate::DevicePropertyEvent::Connected(true));
+            }
+        })?;
+        stream.write_all(canned).await?;
+        Ok(stream)
+    }
+
+    /// The paired devices
+    fn paired_devices(&self) -> Vec<crate::BluetoothDevice> {
+        let state = self.state.lock().unwrap();
+        state
+            .devices
+            .iter()
+            .filter(|(_, d)| d.paired)
+            .map(|(address, _)| {
+                crate::BluetoothDevice::Mock(BluetoothDevice {
+                    address: address.clone(),
+                    mock: self.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Start a discovery that finds the scripted devices at their scripted times
+    fn discovery(&self) -> crate::BluetoothDiscovery {
+        let script: Vec<_> = self
+            .state
+            .lock()
+            .unwrap()
+            .discovery
+            .iter()
+            .map(|(after, address)| {
+                (
+                    *after,
+                    crate::BluetoothDevice::Mock(BluetoothDevice {
+                        address: address.clone(),
+                        mock: self.clone(),
+                    }),
+                )
+            })
+            .collect();
+        let (sender, events) = tokio::sync::mpsc::channel(script.len().max(1));
+        self.state.lock().unwrap().discovering += 1;
+        let (done, finished) = tokio::sync::watch::channel(false);
+        let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let stop2 = stop.clone();
+        std::thread::spawn(move || {
+            let start = std::time::Instant::now();
+            for (after, device) in script {
+                loop {
+                    if stop2.load(std::sync::atomic::Ordering::Relaxed) {
+                        return;
+                    }
+                    let remaining = after.saturating_sub(start.elapsed());
+                    if remaining.is_zero() {
+                        break;
+                    }
+                    std::thread::sleep(remaining.min(std::time::Duration::from_millis(10)));
+                }
+                if sender
+                    .blocking_send(crate::DiscoveryEvent::DeviceAdded {
+                        device,
+                        transport: crate::DeviceTransport::Classic,
+                    })
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            let _ = done.send(true);
+        });
+        crate::BluetoothDiscovery::Mock(BluetoothDiscovery {
+            events: Some(events),
+            finished,
+            stop,
+            state: self.state.clone(),
+        })
+    }
+}
+
+/// The error for a device that was not declared
+fn no_device(address: &crate::BluetoothAdapterAddress) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("The mock adapter has no device {}", address),
+    )
+}
+
+impl crate::BluetoothAdapterTrait for MockAdapter {
+    fn supports_async(&self) -> Option<&dyn crate::AsyncBluetoothAdapterTrait> {
+        Some(self)
+    }
+
+    fn supports_sync(&self) -> Option<&dyn crate::SyncBluetoothAdapterTrait> {
+        Some(self)
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::AsyncBluetoothAdapterTrait for MockAdapter {
+    async fn register_rfcomm_profile(
+        &self,
+        settings: crate::BluetoothRfcommProfileSettings,
+    ) -> Result<crate::BluetoothRfcommProfileAsync, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::register_rfcomm_profile(&self.inner, settings).await
+    }
+
+    async fn register_l2cap_profile(
+        &self,
+        settings: crate::BluetoothL2capProfileSettings,
+    ) -> Result<crate::BluetoothL2capProfileAsync, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::register_l2cap_profile(&self.inner, settings).await
+    }
+
+    async fn get_paired_devices(
+        &self,
+    ) -> Result<Vec<crate::BluetoothDevice>, crate::BluetoothError> {
+        Ok(self.paired_devices())
+    }
+
+    async fn start_discovery(&self) -> Result<crate::BluetoothDiscovery, crate::BluetoothError> {
+        Ok(self.discovery())
+    }
+
+    async fn start_le_scan(
+        &self,
+        filter: crate::LeScanFilter,
+    ) -> Result<crate::LeScanSession, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::start_le_scan(&self.inner, filter).await
+    }
+
+    async fn register_hid_device(
+        &self,
+        settings: crate::HidSettings,
+    ) -> Result<crate::HidDeviceHandle, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::register_hid_device(&self.inner, settings).await
+    }
+
+    async fn addresses(&self) -> Vec<crate::BluetoothAdapterAddress> {
+        crate::AsyncBluetoothAdapterTrait::addresses(&self.inner).await
+    }
+
+    fn has_permission(&self, permission: crate::BluetoothPermission) -> bool {
+        crate::AsyncBluetoothAdapterTrait::has_permission(&self.inner, permission)
+    }
+
+    async fn request_permissions(
+        &self,
+        permissions: &[crate::BluetoothPermission],
+    ) -> crate::PermissionOutcome {
+        crate::AsyncBluetoothAdapterTrait::request_permissions(&self.inner, permissions).await
+    }
+
+    async fn set_discoverable(
+        &self,
+        d: bool,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<bool, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::set_discoverable(&self.inner, d, timeout).await
+    }
+
+    async fn set_powered(
+        &self,
+        powered: bool,
+    ) -> Result<crate::PowerOutcome, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::set_powered(&self.inner, powered).await
+    }
+
+    async fn is_powered(&self) -> Result<bool, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::is_powered(&self.inner).await
+    }
+
+    async fn alias(&self) -> Result<String, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::alias(&self.inner).await
+    }
+
+    async fn set_alias(&self, alias: &str) -> Result<(), crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::set_alias(&self.inner, alias).await
+    }
+
+    async fn set_pairable(&self, pairable: bool) -> Result<(), crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::set_pairable(&self.inner, pairable).await
+    }
+
+    async fn set_pairable_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<(), crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::set_pairable_timeout(&self.inner, timeout).await
+    }
+
+    async fn adapter_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::AdapterHotplugEvent>, crate::BluetoothError>
+    {
+        crate::AsyncBluetoothAdapterTrait::adapter_events(&self.inner).await
+    }
+
+    async fn adapter_state_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::AdapterState>, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::adapter_state_events(&self.inner).await
+    }
+
+    async fn scan_mode(&self) -> Result<crate::ScanMode, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::scan_mode(&self.inner).await
+    }
+
+    async fn scan_mode_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::ScanMode>, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::scan_mode_events(&self.inner).await
+    }
+
+    /// Events are sent when a device pairs through a request to the host, or the application pairs or unpairs
+    /// a device
+    async fn pairing_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::PairingEvent>, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::pairing_events(&self.inner).await
+    }
+
+    /// An event is sent when the application accepts a connection from a device
+    async fn connection_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::ConnectionEvent>, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::connection_events(&self.inner).await
+    }
+
+    /// The declared devices are known, as well as the adapter itself
+    async fn device_by_address(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+    ) -> Result<crate::BluetoothDevice, crate::BluetoothError> {
+        match self.device(address) {
+            Ok(d) => Ok(d),
+            Err(e) => crate::AsyncBluetoothAdapterTrait::device_by_address(&self.inner, address)
+                .await
+                .map_err(|_| e.into()),
+        }
+    }
+
+    /// Only the profiles of the adapter itself can be connected to
+    async fn connect_rfcomm(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        target: crate::RfcommTarget,
+    ) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::connect_rfcomm(&self.inner, address, target).await
+    }
+
+    async fn advertise(
+        &self,
+        settings: crate::LeAdvertisementSettings,
+    ) -> Result<crate::AdvertisementHandle, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::advertise(&self.inner, settings).await
+    }
+
+    async fn register_gatt_service(
+        &self,
+        service: crate::GattServiceDefinition,
+    ) -> Result<crate::GattServiceHandle, crate::BluetoothError> {
+        crate::AsyncBluetoothAdapterTrait::register_gatt_service(&self.inner, service).await
+    }
+}
+
+impl crate::SyncBluetoothAdapterTrait for MockAdapter {
+    fn register_rfcomm_profile(
+        &self,
+        settings: crate::BluetoothRfcommProfileSettings,
+    ) -> Result<crate::BluetoothRfcommProfileSync, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::register_rfcomm_profile(&self.inner, settings)
+    }
+
+    fn register_l2cap_profile(
+        &self,
+        settings: crate::BluetoothL2capProfileSettings,
+    ) -> Result<crate::BluetoothL2capProfileSync, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::register_l2cap_profile(&self.inner, settings)
+    }
+
+    fn get_paired_devices(&self) -> Result<Vec<crate::BluetoothDevice>, crate::BluetoothError> {
+        Ok(self.paired_devices())
+    }
+
+    fn start_discovery(&self) -> Result<crate::BluetoothDiscovery, crate::BluetoothError> {
+        Ok(self.discovery())
+    }
+
+    fn start_le_scan(
+        &self,
+        filter: crate::LeScanFilter,
+    ) -> Result<crate::LeScanSession, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::start_le_scan(&self.inner, filter)
+    }
+
+    fn register_hid_device(
+        &self,
+        settings: crate::HidSettings,
+    ) -> Result<crate::HidDeviceHandle, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::register_hid_device(&self.inner, settings)
+    }
+
+    fn addresses(&self) -> Vec<crate::BluetoothAdapterAddress> {
+        crate::SyncBluetoothAdapterTrait::addresses(&self.inner)
+    }
+
+    fn has_permission(&self, permission: crate::BluetoothPermission) -> bool {
+        crate::SyncBluetoothAdapterTrait::has_permission(&self.inner, permission)
+    }
+
+    fn request_permissions(
+        &self,
+        permissions: &[crate::BluetoothPermission],
+    ) -> crate::PermissionRequest {
+        crate::SyncBluetoothAdapterTrait::request_permissions(&self.inner, permissions)
+    }
+
+    fn set_discoverable(
+        &self,
+        d: bool,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<bool, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::set_discoverable(&self.inner, d, timeout)
+    }
+
+    fn set_powered(&self, powered: bool) -> Result<crate::PowerOutcome, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::set_powered(&self.inner, powered)
+    }
+
+    fn is_powered(&self) -> Result<bool, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::is_powered(&self.inner)
+    }
+
+    fn alias(&self) -> Result<String, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::alias(&self.inner)
+    }
+
+    fn set_alias(&self, alias: &str) -> Result<(), crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::set_alias(&self.inner, alias)
+    }
+
+    fn set_pairable(&self, pairable: bool) -> Result<(), crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::set_pairable(&self.inner, pairable)
+    }
+
+    fn set_pairable_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<(), crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::set_pairable_timeout(&self.inner, timeout)
+    }
+
+    fn adapter_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::AdapterHotplugEvent>, crate::BluetoothError>
+    {
+        crate::SyncBluetoothAdapterTrait::adapter_events(&self.inner)
+    }
+
+    fn adapter_state_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::AdapterState>, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::adapter_state_events(&self.inner)
+    }
+
+    fn scan_mode(&self) -> Result<crate::ScanMode, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::scan_mode(&self.inner)
+    }
+
+    fn scan_mode_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::ScanMode>, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::scan_mode_events(&self.inner)
+    }
+
+    /// Events are sent when a device pairs through a request to the host, or the application pairs or unpairs
+    /// a device
+    fn pairing_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::PairingEvent>, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::pairing_events(&self.inner)
+    }
+
+    /// An event is sent when the application accepts a connection from a device
+    fn connection_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::ConnectionEvent>, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::connection_events(&self.inner)
+    }
+
+    /// The declared devices are known, as well as the adapter itself
+    fn device_by_address(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+    ) -> Result<crate::BluetoothDevice, crate::BluetoothError> {
+        self.device(address).or_else(|e| {
+            crate::SyncBluetoothAdapterTrait::device_by_address(&self.inner, address)
+                .map_err(|_| e.into())
+        })
+    }
+
+    /// Only the profiles of the adapter itself can be connected to
+    fn connect_rfcomm(
+        &self,
+        address: &crate::BluetoothAdapterAddress,
+        target: crate::RfcommTarget,
+    ) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::connect_rfcomm(&self.inner, address, target)
+    }
+
+    fn advertise(
+        &self,
+        settings: crate::LeAdvertisementSettings,
+    ) -> Result<crate::AdvertisementHandle, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::advertise(&self.inner, settings)
+    }
+
+    fn register_gatt_service(
+        &self,
+        service: crate::GattServiceDefinition,
+    ) -> Result<crate::GattServiceHandle, crate::BluetoothError> {
+        crate::SyncBluetoothAdapterTrait::register_gatt_service(&self.inner, service)
+    }
+}
+
+/// A device declared on a mock adapter
+pub struct BluetoothDevice {
+    /// The address of the device
+    address: crate::BluetoothAdapterAddress,
+    /// The adapter the device is declared on
+    mock: MockAdapter,
+}
+
+impl BluetoothDevice {
+    /// Run the closure on the state of the device
+    fn with<T>(&self, f: impl FnOnce(&mut Device) -> T) -> Result<T, std::io::Error> {
+        self.mock.with_device(&self.address, f)
+    }
+
+    /// The uuids of the device
+    fn uuids(&self) -> Result<Vec<crate::BluetoothUuid>, std::io::Error> {
+        self.with(|d| d.uuids.clone())
+    }
+
+    /// The alias of the device, or its name when no alias was assigned
+    fn alias(&self) -> Result<String, std::io::Error> {
+        self.with(|d| d.alias.clone().unwrap_or_else(|| d.name.clone()))
+    }
+
+    /// Assign an alias to the device
+    fn set_alias(&self, alias: &str) -> Result<(), std::io::Error> {
+        self.with(|d| d.alias = Some(alias.to_string()))
+    }
+
+    /// The pairing status of the device
+    fn pair_state(&self) -> Result<crate::PairingStatus, std::io::Error> {
+        self.with(|d| {
+            if d.paired {
+                crate::PairingStatus::Paired
+            } else {
+                crate::PairingStatus::NotPaired
+            }
+        })
+    }
+
+    /// Connect or disconnect the device
+    fn set_connected(&self, connected: bool) -> Result<(), std::io::Error> {
+        self.with(|d| {
+            if d.connected != connected {
+                d.connected = connected;
+                d.notify(crate::DevicePropertyEvent::Connected(connected));
+            }
+        })
+    }
+
+    /// Trust or untrust the device
+    fn set_trusted(&self, trusted: bool) -> Result<(), std::io::Error> {
+        self.with(|d| {
+            if d.trusted != trusted {
+                d.trusted = trusted;
+                d.notify(crate::DevicePropertyEvent::Trusted(trusted));
+            }
+        })
+    }
+
+    /// Block or unblock the device
+    fn set_blocked(&self, blocked: bool) -> Result<(), std::io::Error> {
+        self.with(|d| {
+            if d.blocked != blocked {
+                d.blocked = blocked;
+                d.notify(crate::DevicePropertyEvent::Blocked(blocked));
+            }
+        })
+    }
+
+    /// Receive the property changes of the device
+    fn watch(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::DevicePropertyEvent>, std::io::Error> {
+        let (sender, receiver) = tokio::sync::mpsc::channel(16);
+        self.with(|d| d.watchers.push(sender))?;
+        Ok(receiver)
+    }
+}
+
+impl crate::BluetoothDeviceTrait for BluetoothDevice {
+    fn supports_async(&mut self) -> Option<&mut dyn crate::BluetoothDeviceAsyncTrait> {
+        Some(self)
+    }
+
+    fn supports_sync(&mut self) -> Option<&mut dyn crate::BluetoothDeviceSyncTrait> {
+        Some(self)
+    }
+
+    fn get_address(&mut self) -> Result<crate::BluetoothAdapterAddress, std::io::Error> {
+        Ok(self.address.clone())
+    }
+
+    /// Mock devices only connect to the application, see [`MockAdapter::connect_peer`]
+    fn get_rfcomm_socket(
+        &mut self,
+        _channel: u8,
+        _is_secure: bool,
+    ) -> Result<crate::BluetoothSocket, crate::BluetoothError> {
+        Err(crate::BluetoothError::Unsupported(
+            "Connecting to a mock device",
+        ))
+    }
+
+    /// Mock devices only connect to the application, see [`MockAdapter::connect_peer_l2cap`]
+    fn get_l2cap_socket(
+        &mut self,
+        _psm: u16,
+        _is_secure: bool,
+    ) -> Result<crate::BluetoothSocket, crate::BluetoothError> {
+        Err(crate::BluetoothError::Unsupported(
+            "Connecting to a mock device",
+        ))
+    }
+
+    fn run_sdp(
+        &mut self,
+        _uuid: crate::BluetoothUuid,
+    ) -> Result<crate::sdp::ServiceRecord, crate::BluetoothError> {
+        Err(crate::BluetoothError::Unsupported(
+            "The service discovery protocol on a mock device",
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::BluetoothDeviceAsyncTrait for BluetoothDevice {
+    async fn get_uuids(&mut self) -> Result<Vec<crate::BluetoothUuid>, std::io::Error> {
+        self.uuids()
+    }
+
+    async fn get_name(&self) -> Result<String, std::io::Error> {
+        self.with(|d| d.name.clone())
+    }
+
+    async fn alias(&self) -> Result<String, std::io::Error> {
+        BluetoothDevice::alias(self)
+    }
+
+    async fn set_alias(&self, alias: &str) -> Result<(), std::io::Error> {
+        BluetoothDevice::set_alias(self, alias)
+    }
+
+    async fn get_pair_state(&self) -> Result<crate::PairingStatus, std::io::Error> {
+        self.pair_state()
+    }
+
+    async fn get_rssi(&self) -> Result<Option<crate::RssiReading>, std::io::Error> {
+        Ok(None)
+    }
+
+    async fn connect_device(&self) -> Result<(), std::io::Error> {
+        self.set_connected(true)
+    }
+
+    async fn disconnect_device(&self) -> Result<(), std::io::Error> {
+        self.set_connected(false)
+    }
+
+    /// Pairing with a mock device always succeeds
+    async fn pair(&mut self) -> Result<(), std::io::Error> {
+        self.mock.set_paired(&self.address, true)
+    }
+
+    async fn remove_bond(&mut self) -> Result<(), std::io::Error> {
+        self.mock.set_paired(&self.address, false)
+    }
+
+    async fn is_trusted(&self) -> Result<bool, std::io::Error> {
+        self.with(|d| d.trusted)
+    }
+
+    async fn set_trusted(&self, trusted: bool) -> Result<(), std::io::Error> {
+        BluetoothDevice::set_trusted(self, trusted)
+    }
+
+    async fn is_blocked(&self) -> Result<bool, std::io::Error> {
+        self.with(|d| d.blocked)
+    }
+
+    async fn set_blocked(&self, blocked: bool) -> Result<(), std::io::Error> {
+        BluetoothDevice::set_blocked(self, blocked)
+    }
+
+    async fn watch_properties(
+        &mut self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::DevicePropertyEvent>, std::io::Error> {
+        self.watch()
+    }
+
+    async fn battery_percent(&self) -> Result<Option<u8>, std::io::Error> {
+        Ok(None)
+    }
+
+    async fn device_class(&self) -> Result<Option<crate::DeviceClass>, std::io::Error> {
+        Ok(None)
+    }
+
+    async fn device_type(&self) -> Result<crate::DeviceTransport, std::io::Error> {
+        Ok(crate::DeviceTransport::Classic)
+    }
+
+    async fn profile_connection_state(
+        &self,
+        _profile: crate::WellKnownProfile,
+    ) -> Result<crate::ProfileConnectionState, std::io::Error> {
+        Ok(crate::ProfileConnectionState::Disconnected)
+    }
+
+    /// The declared uuids are returned right away
+    async fn resolve_services(
+        &mut self,
+        _timeout: std::time::Duration,
+    ) -> Result<Vec<crate::BluetoothUuid>, std::io::Error> {
+        self.uuids()
+    }
+
+    async fn gatt(&self) -> Result<crate::GattClient, std::io::Error> {
+        Err(crate::BluetoothError::Unsupported("Gatt on a mock device").into())
+    }
+}
+
+impl crate::BluetoothDeviceSyncTrait for BluetoothDevice {
+    fn get_uuids(&mut self) -> Result<Vec<crate::BluetoothUuid>, std::io::Error> {
+        self.uuids()
+    }
+
+    fn get_name(&self) -> Result<String, std::io::Error> {
+        self.with(|d| d.name.clone())
+    }
+
+    fn alias(&self) -> Result<String, std::io::Error> {
+        BluetoothDevice::alias(self)
+    }
+
+    fn gatt(&self) -> Result<crate::GattClient, std::io::Error> {
+        Err(crate::BluetoothError::Unsupported("Gatt on a mock device").into())
+    }
+
+    fn set_alias(&self, alias: &str) -> Result<(), std::io::Error> {
+        BluetoothDevice::set_alias(self, alias)
+    }
+
+    fn get_pair_state(&self) -> Result<crate::PairingStatus, std::io::Error> {
+        self.pair_state()
+    }
+
+    fn get_rssi(&self) -> Result<Option<crate::RssiReading>, std::io::Error> {
+        Ok(None)
+    }
+
+    fn connect_device(&self) -> Result<(), std::io::Error> {
+        self.set_connected(true)
+    }
+
+    fn disconnect_device(&self) -> Result<(), std::io::Error> {
+        self.set_connected(false)
+    }
+
+    /// Pairing with a mock device always succeeds
+    fn pair(&mut self) -> Result<(), std::io::Error> {
+        self.mock.set_paired(&self.address, true)
+    }
+
+    fn remove_bond(&mut self) -> Result<(), std::io::Error> {
+        self.mock.set_paired(&self.address, false)
+    }
+
+    fn is_trusted(&self) -> Result<bool, std::io::Error> {
+        self.with(|d| d.trusted)
+    }
+
+    fn set_trusted(&self, trusted: bool) -> Result<(), std::io::Error> {
+        BluetoothDevice::set_trusted(self, trusted)
+    }
+
+    fn is_blocked(&self) -> Result<bool, std::io::Error> {
+        self.with(|d| d.blocked)
+    }
+
+    fn set_blocked(&self, blocked: bool) -> Result<(), std::io::Error> {
+        BluetoothDevice::set_blocked(self, blocked)
+    }
+
+    fn watch_properties(
+        &mut self,
+    ) -> Result<tokio::sync::mpsc::Receiver<crate::DevicePropertyEvent>, std::io::Error> {
+        self.watch()
+    }
+
+    fn battery_percent(&self) -> Result<Option<u8>, std::io::Error> {
+        Ok(None)
+    }
+
+    fn device_class(&self) -> Result<Option<crate::DeviceClass>, std::io::Error> {
+        Ok(None)
+    }
+
+    fn device_type(&self) -> Result<crate::DeviceTransport, std::io::Error> {
+        Ok(crate::DeviceTransport::Classic)
+    }
+
+    fn profile_connection_state(
+        &self,
+        _profile: crate::WellKnownProfile,
+    ) -> Result<crate::ProfileConnectionState, std::io::Error> {
+        Ok(crate::ProfileConnectionState::Disconnected)
+    }
+
+    /// The declared uuids are returned right away
+    fn resolve_services(
+        &mut self,
+        _timeout: std::time::Duration,
+    ) -> Result<Vec<crate::BluetoothUuid>, std::io::Error> {
+        self.uuids()
+    }
+}
+
+/// A discovery on a mock adapter, which finds the scripted devices
+pub struct BluetoothDiscovery {
+    /// The devices found
+    events: Option<tokio::sync::mpsc::Receiver<crate::DiscoveryEvent>>,
+    /// Becomes true when the last scripted device is found
+    finished: tokio::sync::watch::Receiver<bool>,
+    /// Stops the script when the discovery is dropped
+    stop: Arc<std::sync::atomic::AtomicBool>,
+    /// The state of the adapter, which stops discovering when this is dropped
+    state: Arc<Mutex<State>>,
+}
+
+impl Drop for BluetoothDiscovery {
+    fn drop(&mut self) {
+        self.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        self.state.lock().unwrap().discovering -= 1;
+    }
+}
+
+#[async_trait::async_trait]
+impl crate::BluetoothDiscoveryTrait for BluetoothDiscovery {
+    fn take_events(&mut self) -> Option<tokio::sync::mpsc::Receiver<crate::DiscoveryEvent>> {
+        self.events.take()
+    }
+
+    fn try_recv(&mut self) -> Option<crate::DiscoveryEvent> {
+        self.events.as_mut()?.try_recv().ok()
+    }
+
+    async fn next(&mut self) -> Option<crate::DiscoveryEvent> {
+        self.events.as_mut()?.recv().await
+    }
+
+    fn is_running(&self) -> bool {
+        !*self.finished.borrow()
+    }
+
+    fn finished(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.finished.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        BluetoothAdapterTrait, BluetoothRfcommConnectableAsyncTrait,
+        BluetoothRfcommProfileAsyncTrait,
+    };
+    use tokio::io::AsyncReadExt;
+
+    /// The address of the remote device
+    const PHONE: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+
+    /// Build an adapter from the mock, with a host that gets the messages from the adapter
+    fn build(
+        mock: &MockAdapter,
+    ) -> (
+        crate::BluetoothAdapter,
+        tokio::sync::mpsc::Receiver<crate::MessageToBluetoothHost>,
+    ) {
+        let (sender, receiver) = tokio::sync::mpsc::channel(10);
+        let mut builder = crate::BluetoothAdapterBuilder::new();
+        builder.with_mock(mock.clone());
+        builder.with_sender(sender);
+        (builder.build().unwrap(), receiver)
+    }
+
+    #[tokio::test]
+    async fn rejected_passkey_leaves_the_device_unpaired() {
+        let mock = MockAdapter::new();
+        mock.add_device(PHONE.into(), "Phone", Vec::new(), false);
+        let (adapter, mut host) = build(&mock);
+        let app = tokio::spawn(async move {
+            match host.recv().await {
+                Some(crate::MessageToBluetoothHost::ConfirmPasskey {
+                    passkey, responder, ..
+                }) => {
+                    assert_eq!(passkey, 123456);
+                    responder.send(crate::ResponseToPasskey::No).await.unwrap();
+                }
+                _ => panic!("Expected a passkey confirmation"),
+            }
+            assert!(matches!(
+                host.recv().await,
+                Some(crate::MessageToBluetoothHost::CancelDisplayPasskey)
+            ));
+        });
+        let response = mock.confirm_passkey(&PHONE.into(), 123456).await.unwrap();
+        assert!(matches!(response, crate::ResponseToPasskey::No));
+        app.await.unwrap();
+        let adapter = adapter.supports_async().unwrap();
+        assert!(adapter.get_paired_devices().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unanswered_passkey_uses_the_default() {
+        let mock = MockAdapter::new();
+        mock.add_device(PHONE.into(), "Phone", Vec::new(), false);
+        let (sender, _host) = tokio::sync::mpsc::channel(10);
+        mock.set_host(
+            Some(sender),
+            crate::AgentConfig {
+                response_timeout: std::time::Duration::from_millis(10),
+                default_on_timeout: crate::ResponseToPasskey::No,
+                ..crate::AgentConfig::default()
+            },
+            false,
+        );
+        let response = mock.confirm_passkey(&PHONE.into(), 1).await.unwrap();
+        assert!(matches!(response, crate::ResponseToPasskey::No));
+    }
+
+    #[tokio::test]
+    async fn peer_gets_the_bytes_written_after_accept() {
+        let mock = MockAdapter::new();
+        mock.add_device(PHONE.into(), "Phone", Vec::new(), true);
+        let (adapter, _host) = build(&mock);
+        let settings = crate::BluetoothRfcommProfileSettings::builder()
+            .uuid(crate::BluetoothUuid::SPP)
+            .build()
+            .unwrap();
+        let mut profile = adapter
+            .supports_async()
+            .unwrap()
+            .register_rfcomm_profile(settings)
+            .await
+            .unwrap();
+        let app = tokio::spawn(async move {
+            let (mut stream, peer, _) =
+                profile.connectable().await.unwrap().accept().await.unwrap();
+            assert_eq!(peer, PHONE);
+            let mut hello = [0u8; 5];
+            stream.read_exact(&mut hello).await.unwrap();
+            assert_eq!(&hello, b"HELLO");
+            stream.write_all(b"OK\r\n").await.unwrap();
+        });
+        let target = crate::RfcommTarget::Uuid(crate::BluetoothUuid::SPP);
+        let mut peer = mock
+            .connect_peer(&PHONE.into(), target, b"HELLO")
+            .await
+            .unwrap();
+        app.await.unwrap();
+        let mut reply = Vec::new();
+        peer.read_to_end(&mut reply).await.unwrap();
+        assert_eq!(reply, b"OK\r\n");
+    }
+
+    #[tokio::test]
+    async fn discovery_toggles_discovering() {
+        use crate::BluetoothDiscoveryTrait;
+        let mock = MockAdapter::new();
+        mock.add_device(PHONE.into(), "Phone", Vec::new(), false);
+        mock.add_discovery_result(std::time::Duration::from_millis(10), PHONE.into());
+        let (adapter, _host) = build(&mock);
+        let adapter = adapter.supports_async().unwrap();
+        assert!(!mock.is_discovering());
+
+        let mut discovery = adapter.start_discovery().await.unwrap();
+        assert!(mock.is_discovering());
+        let second = adapter.start_discovery().await.unwrap();
+        match discovery.next().await {
+            Some(crate::DiscoveryEvent::DeviceAdded {
+                device: mut d,
+                transport,
+            }) => {
+                use crate::BluetoothDeviceTrait;
+                assert_eq!(d.get_address().unwrap(), PHONE.into());
+                assert_eq!(transport, crate::DeviceTransport::Classic);
+            }
+            _ => panic!("Expected the phone to be found"),
+        }
+        // Discovery keeps running after the last scripted device, until it is dropped
+        assert!(mock.is_discovering());
+        drop(discovery);
+        assert!(mock.is_discovering());
+        drop(second);
+        assert!(!mock.is_discovering());
+    }
+
+    #[tokio::test]
+    async fn undeclared_devices_cannot_connect() {
+        let mock = MockAdapter::new();
+        let target = crate::RfcommTarget::Channel(1);
+        assert!(mock.connect_peer(&PHONE.into(), target, b"").await.is_err());
+    }
+}
@@ -278,7 +278,15 @@ fn build_sdp_request(txid: u16, uuid: u16) -> Vec<u8> {
     out
 }
 
-pub fn run_sdp(mac: &str, uuid: u16) -> std::io::Result<ServiceRecord> {
+/// Search the device for the service with the uuid. Only uuids derived from the bluetooth base uuid can be
+/// searched for, other uuids are an invalid input error.
+pub fn run_sdp(mac: &str, uuid: &crate::BluetoothUuid) -> std::io::Result<ServiceRecord> {
+    let Some(uuid) = uuid.as_u16() else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Only 16 bit uuids can be searched for with sdp, not {uuid}"),
+        ));
+    };
     let fd = unsafe { libc::socket(AF_BLUETOOTH, SOCK_SEQPACKET, BTPROTO_L2CAP) };
 
     if fd < 0 {
@@ -321,3 +329,15 @@ pub fn run_sdp(mac: &str, uuid: u16) -> std::io::Result<ServiceRecord> {
 
     Err(std::io::Error::other("Failed to find record".to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_16_bit_uuids_are_searched_for() {
+        // The android auto uuid is not derived from the base uuid, so it has no 16 bit form
+        let err = run_sdp("00:11:22:33:44:55", &crate::BluetoothUuid::AndroidAuto).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
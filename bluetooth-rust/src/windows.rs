@@ -12,41 +12,34 @@
 use windows::{
     Devices::Bluetooth::Rfcomm::{RfcommServiceId, RfcommServiceProvider},
     Devices::Bluetooth::{BluetoothAdapter as WinBtAdapter, BluetoothDevice as WinBtDevice},
-    Devices::Enumeration::{DeviceInformation, DeviceWatcher},
+    Devices::Enumeration::{DeviceInformation, DeviceInformationUpdate, DeviceWatcher},
     Foundation::{EventRegistrationToken, TypedEventHandler},
     Networking::Sockets::{
         SocketProtectionLevel, StreamSocket, StreamSocketListener,
         StreamSocketListenerConnectionReceivedEventArgs,
     },
     Storage::Streams::{DataReader, DataWriter, InputStreamOptions},
-    core::{GUID, HSTRING},
+    core::{GUID, HSTRING, IInspectable},
 };
 
 // ---------------------------------------------------------------------------
 // Internal helpers
 // ---------------------------------------------------------------------------
 
-/// Parse a hyphenated UUID string into a Windows `GUID`.
-fn parse_uuid_to_guid(uuid_str: &str) -> Result<GUID, String> {
-    let s = uuid_str.replace('-', "");
-    if s.len() != 32 {
-        return Err(format!(
-            "Invalid UUID (expected 32 hex chars after removing hyphens): {uuid_str}"
-        ));
-    }
-    let data1 = u32::from_str_radix(&s[0..8], 16).map_err(|e| e.to_string())?;
-    let data2 = u16::from_str_radix(&s[8..12], 16).map_err(|e| e.to_string())?;
-    let data3 = u16::from_str_radix(&s[12..16], 16).map_err(|e| e.to_string())?;
-    let mut data4 = [0u8; 8];
-    for i in 0..8 {
-        data4[i] = u8::from_str_radix(&s[16 + i * 2..18 + i * 2], 16).map_err(|e| e.to_string())?;
-    }
-    Ok(GUID {
-        data1,
-        data2,
-        data3,
-        data4,
-    })
+/// Build the error returned for adapter operations WinRT does not expose.
+fn unsupported(what: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("{what} is not supported on Windows via WinRT"),
+    )
+}
+
+/// Parse a UUID string into a Windows `GUID`.
+fn parse_uuid_to_guid(uuid_str: &str) -> Result<GUID, std::io::Error> {
+    use std::str::FromStr;
+    crate::BluetoothUuid::from_str(uuid_str)
+        .map(|u| GUID::from_u128(u.as_u128()))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
 }
 
 /// Convert a Windows Bluetooth address (u64, lower 48 bits) to a six-byte MAC
@@ -68,11 +61,17 @@ fn bt_u64_to_bytes(addr: u64) -> [u8; 6] {
 /// wrapping them in `tokio::task::spawn_blocking`.
 pub struct WindowsRfcommStream {
     /// The underlying socket kept alive so its COM ref-count stays positive.
-    _socket: StreamSocket,
+    socket: StreamSocket,
     /// Reads bytes from the socket input stream.
     reader: DataReader,
     /// Writes bytes to the socket output stream.
     writer: DataWriter,
+    /// The address of the remote device, read when the stream is created.
+    peer: crate::BluetoothAdapterAddress,
+    /// The name of the remote device, read when the stream is created.
+    peer_name: Option<String>,
+    /// The socket has been closed with `close`.
+    closed: bool,
 }
 
 impl WindowsRfcommStream {
@@ -87,11 +86,61 @@ impl WindowsRfcommStream {
         let output = socket.OutputStream()?;
         let writer = DataWriter::CreateDataWriter(&output)?;
         Ok(Self {
-            _socket: socket,
+            peer: socket_peer_address(&socket),
+            peer_name: socket_peer_name(&socket),
+            socket,
             reader,
             writer,
+            closed: false,
         })
     }
+
+    /// The address of the remote device
+    pub fn peer_address(&self) -> crate::BluetoothAdapterAddress {
+        self.peer.clone()
+    }
+
+    /// The name of the remote device, if it was known when the stream was created
+    pub fn peer_name(&self) -> Option<String> {
+        self.peer_name.clone()
+    }
+
+    /// Close the socket. Closing a closed stream does nothing.
+    pub fn close(&mut self) -> Result<(), crate::BluetoothError> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        self.socket.Close().map_err(std::io::Error::other)?;
+        Ok(())
+    }
+
+    /// WinRT does not report when the peer disconnects, so this is only false after `close`
+    pub fn is_open(&self) -> bool {
+        !self.closed
+    }
+}
+
+/// WinRT reports the remote address of a bluetooth socket as `(XX:XX:XX:XX:XX:XX)`
+fn socket_peer_address(socket: &StreamSocket) -> crate::BluetoothAdapterAddress {
+    let raw = socket
+        .Information()
+        .and_then(|i| i.RemoteAddress())
+        .and_then(|h| h.RawName())
+        .map(|n| n.to_string())
+        .unwrap_or_default();
+    let raw = raw.trim_start_matches('(').trim_end_matches(')');
+    crate::BluetoothAdapterAddress::String(raw.to_string()).normalized()
+}
+
+/// The display name of the remote host of a socket
+fn socket_peer_name(socket: &StreamSocket) -> Option<String> {
+    socket
+        .Information()
+        .and_then(|i| i.RemoteHostName())
+        .and_then(|h| h.DisplayName())
+        .map(|n| n.to_string())
+        .ok()
 }
 
 impl std::io::Read for WindowsRfcommStream {
@@ -150,11 +199,24 @@ pub struct BluetoothRfcommConnectable {
     socket: StreamSocket,
 }
 
+#[async_trait::async_trait]
 impl super::BluetoothRfcommConnectableAsyncTrait for BluetoothRfcommConnectable {
-    async fn accept(self) -> Result<crate::BluetoothStream, String> {
-        let stream = WindowsRfcommStream::new(self.socket).map_err(|e| e.to_string())?;
+    async fn accept(self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        let stream = WindowsRfcommStream::new(self.socket).map_err(std::io::Error::other)?;
         Ok(crate::BluetoothStream::Windows(stream))
     }
+
+    async fn reject(self) {
+        let _ = self.socket.Close();
+    }
+
+    fn peer_address(&self) -> super::BluetoothAdapterAddress {
+        socket_peer_address(&self.socket)
+    }
+
+    async fn peer_name(&self) -> Option<String> {
+        socket_peer_name(&self.socket)
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -185,8 +247,11 @@ impl Drop for BluetoothRfcommProfile {
     }
 }
 
+#[async_trait::async_trait]
 impl super::BluetoothRfcommProfileAsyncTrait for BluetoothRfcommProfile {
-    async fn connectable(&mut self) -> Result<crate::BluetoothRfcommConnectableAsync, String> {
+    async fn connectable(
+        &mut self,
+    ) -> Result<crate::BluetoothRfcommConnectableAsync, crate::BluetoothError> {
         self.rx
             .recv()
             .await
@@ -195,7 +260,9 @@ impl super::BluetoothRfcommProfileAsyncTrait for BluetoothRfcommProfile {
                     socket,
                 })
             })
-            .ok_or_else(|| "Connection channel closed".to_string())
+            .ok_or_else(|| {
+                crate::BluetoothError::ConnectionFailed("Connection channel closed".to_string())
+            })
     }
 }
 
@@ -210,16 +277,89 @@ impl super::BluetoothRfcommProfileAsyncTrait for BluetoothRfcommProfile {
 pub struct BluetoothDiscovery {
     /// The underlying OS device watcher.
     watcher: DeviceWatcher,
+    /// The devices found by the watcher, until taken by the user.
+    events: Option<tokio::sync::mpsc::Receiver<crate::DiscoveryEvent>>,
+    /// Becomes true when the watcher has stopped.
+    finished: tokio::sync::watch::Receiver<bool>,
 }
 
 impl BluetoothDiscovery {
-    /// Wrap an already-started `DeviceWatcher`.
-    fn new(watcher: DeviceWatcher) -> Self {
-        Self { watcher }
+    /// Register the event handlers on a `DeviceWatcher` that has not been
+    /// started yet, then start it.
+    fn new(watcher: DeviceWatcher) -> Result<Self, windows::core::Error> {
+        let (tx, rx) = tokio::sync::mpsc::channel(32);
+        let added = tx.clone();
+        watcher.Added(&TypedEventHandler::<DeviceWatcher, DeviceInformation>::new(
+            move |_sender, info| {
+                if let Some(info) = info {
+                    let id = info.Id()?;
+                    if let Ok(device) =
+                        futures::executor::block_on(async { WinBtDevice::FromIdAsync(&id)?.await })
+                    {
+                        // The watcher only looks for classic devices
+                        let device = BluetoothDevice { inner: device };
+                        let _ = added.try_send(crate::DiscoveryEvent::DeviceAdded {
+                            device: crate::BluetoothDevice::Windows(device),
+                            transport: crate::DeviceTransport::Classic,
+                        });
+                    }
+                }
+                Ok(())
+            },
+        ))?;
+        watcher.Removed(
+            &TypedEventHandler::<DeviceWatcher, DeviceInformationUpdate>::new(
+                move |_sender, update| {
+                    if let Some(update) = update {
+                        // Bluetooth device ids end with the address of the remote device
+                        let id = update.Id()?.to_string();
+                        let tail = &id[id.len().saturating_sub(17)..];
+                        if let Ok(address) = crate::BluetoothAdapterAddress::parse(tail) {
+                            let _ = tx.try_send(crate::DiscoveryEvent::DeviceRemoved(address));
+                        }
+                    }
+                    Ok(())
+                },
+            ),
+        )?;
+        let (finished_tx, finished) = tokio::sync::watch::channel(false);
+        watcher.Stopped(&TypedEventHandler::<DeviceWatcher, IInspectable>::new(
+            move |_sender, _| {
+                finished_tx.send_replace(true);
+                Ok(())
+            },
+        ))?;
+        watcher.Start()?;
+        Ok(Self {
+            watcher,
+            events: Some(rx),
+            finished,
+        })
     }
 }
 
-impl super::BluetoothDiscoveryTrait for BluetoothDiscovery {}
+#[async_trait::async_trait]
+impl super::BluetoothDiscoveryTrait for BluetoothDiscovery {
+    fn take_events(&mut self) -> Option<tokio::sync::mpsc::Receiver<crate::DiscoveryEvent>> {
+        self.events.take()
+    }
+
+    fn try_recv(&mut self) -> Option<crate::DiscoveryEvent> {
+        self.events.as_mut()?.try_recv().ok()
+    }
+
+    async fn next(&mut self) -> Option<crate::DiscoveryEvent> {
+        self.events.as_mut()?.recv().await
+    }
+
+    fn is_running(&self) -> bool {
+        !*self.finished.borrow()
+    }
+
+    fn finished(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.finished.clone()
+    }
+}
 
 impl Drop for BluetoothDiscovery {
     fn drop(&mut self) {
@@ -252,16 +392,12 @@ impl super::BluetoothDeviceTrait for BluetoothDevice {
             .map_err(|e| std::io::Error::other(e.to_string()))
     }
 
-    fn get_address(&mut self) -> Result<String, std::io::Error> {
+    fn get_address(&mut self) -> Result<crate::BluetoothAdapterAddress, std::io::Error> {
         let addr = self
             .inner
             .BluetoothAddress()
             .map_err(|e| std::io::Error::other(e.to_string()))?;
-        let b = bt_u64_to_bytes(addr);
-        Ok(format!(
-            "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
-            b[0], b[1], b[2], b[3], b[4], b[5]
-        ))
+        Ok(crate::BluetoothAdapterAddress::Byte(bt_u64_to_bytes(addr)))
     }
 
     fn get_pair_state(&self) -> Result<crate::PairingStatus, std::io::Error> {
@@ -286,7 +422,7 @@ impl super::BluetoothDeviceTrait for BluetoothDevice {
         &mut self,
         _uuid: crate::BluetoothUuid,
         _is_secure: bool,
-    ) -> Result<crate::BluetoothSocket, String> {
+    ) -> Result<crate::BluetoothSocket, crate::BluetoothError> {
         // Requires GetRfcommServicesForIdAsync() then StreamSocket::ConnectAsync().
         todo!("Windows client-side RFCOMM socket not yet implemented")
     }
@@ -295,7 +431,7 @@ impl super::BluetoothDeviceTrait for BluetoothDevice {
         &mut self,
         _uuid: crate::BluetoothUuid,
         _is_secure: bool,
-    ) -> Result<crate::BluetoothSocket, String> {
+    ) -> Result<crate::BluetoothSocket, crate::BluetoothError> {
         // Classic BT L2CAP is not exposed via WinRT; only BLE L2CAP CoC is.
         todo!("Windows client-side L2CAP socket not yet implemented")
     }
@@ -328,6 +464,25 @@ impl crate::BluetoothSocketTrait for BluetoothRfcommSocket {
         // ConnectionHostName and ConnectionServiceName from RfcommDeviceService.
         todo!("Windows BluetoothRfcommSocket::connect not yet implemented")
     }
+
+    fn close(&mut self) -> Result<(), crate::BluetoothError> {
+        if self.connected {
+            self.socket
+                .Close()
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
+            self.connected = false;
+        }
+        Ok(())
+    }
+
+    fn into_stream(self) -> Result<crate::BluetoothStream, crate::BluetoothError> {
+        if !self.connected {
+            return Err(std::io::Error::from(std::io::ErrorKind::NotConnected).into());
+        }
+        let stream = WindowsRfcommStream::new(self.socket)
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        Ok(crate::BluetoothStream::Windows(stream))
+    }
 }
 
 impl std::io::Read for BluetoothRfcommSocket {
@@ -357,8 +512,8 @@ impl std::io::Write for BluetoothRfcommSocket {
 pub struct BluetoothHandler {
     /// The system's default Bluetooth radio.
     adapter: WinBtAdapter,
-    /// Channel back to the application for pairing UI messages.
-    _sender: tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>,
+    /// Channel back to the application for pairing UI messages, if there is one.
+    _sender: Option<tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>>,
 }
 
 impl super::BluetoothAdapterTrait for BluetoothHandler {
@@ -377,21 +532,22 @@ impl super::AsyncBluetoothAdapterTrait for BluetoothHandler {
     async fn register_rfcomm_profile(
         &self,
         settings: super::BluetoothRfcommProfileSettings,
-    ) -> Result<crate::BluetoothRfcommProfileAsync, String> {
+    ) -> Result<crate::BluetoothRfcommProfileAsync, crate::BluetoothError> {
+        settings.validate()?;
         // 1. Build the RFCOMM service ID from the profile UUID.
         let guid = parse_uuid_to_guid(&settings.uuid)?;
-        let service_id = RfcommServiceId::FromUuid(guid).map_err(|e| e.to_string())?;
+        let service_id = RfcommServiceId::FromUuid(guid).map_err(std::io::Error::other)?;
 
         // 2. Create the service provider; this registers an SDP record with the
         //    Bluetooth stack.
         let provider = RfcommServiceProvider::CreateAsync(&service_id)
-            .map_err(|e| e.to_string())?
+            .map_err(std::io::Error::other)?
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(std::io::Error::other)?;
 
         // 3. Create a socket listener and route accepted sockets through a
         //    bounded channel so callers can await them with `connectable()`.
-        let listener = StreamSocketListener::new().map_err(|e| e.to_string())?;
+        let listener = StreamSocketListener::new().map_err(std::io::Error::other)?;
 
         let (tx, rx) = tokio::sync::mpsc::channel::<StreamSocket>(16);
 
@@ -407,7 +563,7 @@ impl super::AsyncBluetoothAdapterTrait for BluetoothHandler {
                 }
                 Ok(())
             }))
-            .map_err(|e| e.to_string())?;
+            .map_err(std::io::Error::other)?;
 
         // 4. Bind the listener to the RFCOMM service name (= the UUID string
         //    as produced by RfcommServiceId::AsString) with the requested
@@ -420,20 +576,20 @@ impl super::AsyncBluetoothAdapterTrait for BluetoothHandler {
 
         let service_name: HSTRING = provider
             .ServiceId()
-            .map_err(|e| e.to_string())?
+            .map_err(std::io::Error::other)?
             .AsString()
-            .map_err(|e| e.to_string())?;
+            .map_err(std::io::Error::other)?;
 
         listener
             .BindServiceNameWithProtectionLevelAsync(&service_name, protection_level)
-            .map_err(|e| e.to_string())?
+            .map_err(std::io::Error::other)?
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(std::io::Error::other)?;
 
         // 5. Advertise the service so that remote devices can discover it.
         provider
             .StartAdvertising(&listener)
-            .map_err(|e| e.to_string())?;
+            .map_err(std::io::Error::other)?;
 
         Ok(crate::BluetoothRfcommProfileAsync::Windows(
             BluetoothRfcommProfile {
@@ -448,26 +604,25 @@ impl super::AsyncBluetoothAdapterTrait for BluetoothHandler {
     async fn register_l2cap_profile(
         &self,
         _settings: super::BluetoothL2capProfileSettings,
-    ) -> Result<crate::BluetoothL2capProfileAsync, String> {
+    ) -> Result<crate::BluetoothL2capProfileAsync, crate::BluetoothError> {
         // Classic BT L2CAP profile registration is not exposed via WinRT.
-        Err(
-            "Classic Bluetooth L2CAP profile registration is not supported              on Windows via WinRT"
-                .to_string(),
-        )
+        Err(crate::BluetoothError::Unsupported(
+            "Classic Bluetooth L2CAP profile registration",
+        ))
     }
 
-    fn get_paired_devices(&self) -> Option<Vec<crate::BluetoothDevice>> {
-        let selector = WinBtDevice::GetDeviceSelectorFromPairingState(true).ok()?;
+    fn get_paired_devices(&self) -> Result<Vec<crate::BluetoothDevice>, crate::BluetoothError> {
+        let selector =
+            WinBtDevice::GetDeviceSelectorFromPairingState(true).map_err(std::io::Error::other)?;
 
         let collection = futures::executor::block_on(async {
             DeviceInformation::FindAllAsyncAqsFilter(&selector)
-                .map_err(|e| e.to_string())?
+                .map_err(std::io::Error::other)?
                 .await
-                .map_err(|e| e.to_string())
-        })
-        .ok()?;
+                .map_err(std::io::Error::other)
+        })?;
 
-        let count = collection.Size().ok()?;
+        let count = collection.Size().map_err(std::io::Error::other)?;
         let mut devices = Vec::with_capacity(count as usize);
 
         for i in 0..count {
@@ -490,16 +645,30 @@ impl super::AsyncBluetoothAdapterTrait for BluetoothHandler {
                 }));
             }
         }
-        Some(devices)
+        Ok(devices)
+    }
+
+    async fn start_le_scan(
+        &self,
+        _filter: crate::LeScanFilter,
+    ) -> Result<crate::LeScanSession, crate::BluetoothError> {
+        Err(unsupported("Low energy scanning").into())
     }
 
-    fn start_discovery(&self) -> crate::BluetoothDiscovery {
-        let selector = WinBtDevice::GetDeviceSelector()
-            .expect("Failed to build Bluetooth device AQS selector");
-        let watcher = DeviceInformation::CreateWatcherAqsFilter(&selector)
-            .expect("Failed to create DeviceWatcher");
-        watcher.Start().expect("Failed to start DeviceWatcher");
-        BluetoothDiscovery::new(watcher).into()
+    async fn register_hid_device(
+        &self,
+        _settings: crate::HidSettings,
+    ) -> Result<crate::HidDeviceHandle, crate::BluetoothError> {
+        Err(unsupported("Acting as a hid device").into())
+    }
+
+    async fn start_discovery(&self) -> Result<crate::BluetoothDiscovery, crate::BluetoothError> {
+        let selector = WinBtDevice::GetDeviceSelector().map_err(std::io::Error::other)?;
+        let watcher =
+            DeviceInformation::CreateWatcherAqsFilter(&selector).map_err(std::io::Error::other)?;
+        Ok(BluetoothDiscovery::new(watcher)
+            .map_err(std::io::Error::other)?
+            .into())
     }
 
     async fn addresses(&self) -> Vec<super::BluetoothAdapterAddress> {
@@ -509,13 +678,135 @@ impl super::AsyncBluetoothAdapterTrait for BluetoothHandler {
         }
     }
 
-    async fn set_discoverable(&self, _d: bool) -> Result<(), ()> {
+    /// Desktop applications do not need permissions for bluetooth
+    fn has_permission(&self, _permission: crate::BluetoothPermission) -> bool {
+        true
+    }
+
+    async fn request_permissions(
+        &self,
+        _permissions: &[crate::BluetoothPermission],
+    ) -> crate::PermissionOutcome {
+        crate::PermissionOutcome::Granted
+    }
+
+    async fn set_discoverable(
+        &self,
+        _d: bool,
+        _timeout: Option<std::time::Duration>,
+    ) -> Result<bool, crate::BluetoothError> {
         // WinRT does not expose an API for controlling adapter discoverability
         // from third-party apps; this is handled by the OS Settings app.
-        log::warn!(
-            "Bluetooth discoverability cannot be set programmatically on              Windows via WinRT"
-        );
-        Ok(())
+        Err(unsupported("Changing the discoverable mode").into())
+    }
+
+    async fn set_powered(
+        &self,
+        _powered: bool,
+    ) -> Result<crate::PowerOutcome, crate::BluetoothError> {
+        // Radio state is controlled through Windows.Devices.Radios, which
+        // requires a separate capability declaration for packaged apps.
+        Err(unsupported("Changing the radio power state").into())
+    }
+
+    async fn is_powered(&self) -> Result<bool, crate::BluetoothError> {
+        Err(unsupported("Reading the radio power state").into())
+    }
+
+    async fn alias(&self) -> Result<String, crate::BluetoothError> {
+        Err(unsupported("Reading the adapter name").into())
+    }
+
+    async fn set_alias(&self, _alias: &str) -> Result<(), crate::BluetoothError> {
+        Err(unsupported("Changing the adapter name").into())
+    }
+
+    async fn set_pairable(&self, _pairable: bool) -> Result<(), crate::BluetoothError> {
+        Err(unsupported("Changing the pairable mode").into())
+    }
+
+    async fn set_pairable_timeout(
+        &self,
+        _timeout: std::time::Duration,
+    ) -> Result<(), crate::BluetoothError> {
+        Err(unsupported("Changing the pairable timeout").into())
+    }
+
+    async fn adapter_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::AdapterHotplugEvent>, crate::BluetoothError>
+    {
+        Err(unsupported("Watching for adapter changes").into())
+    }
+
+    async fn adapter_state_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::AdapterState>, crate::BluetoothError> {
+        Err(unsupported("Watching the radio power state").into())
+    }
+
+    async fn scan_mode(&self) -> Result<super::ScanMode, crate::BluetoothError> {
+        Err(unsupported("Reading the scan mode").into())
+    }
+
+    async fn scan_mode_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::ScanMode>, crate::BluetoothError> {
+        Err(unsupported("Watching the scan mode").into())
+    }
+
+    async fn pairing_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::PairingEvent>, crate::BluetoothError> {
+        Err(unsupported("Watching for pairing changes").into())
+    }
+
+    async fn connection_events(
+        &self,
+    ) -> Result<tokio::sync::mpsc::Receiver<super::ConnectionEvent>, crate::BluetoothError> {
+        Err(unsupported("Watching for device connections").into())
+    }
+
+    async fn device_by_address(
+        &self,
+        address: &super::BluetoothAdapterAddress,
+    ) -> Result<crate::BluetoothDevice, crate::BluetoothError> {
+        let b = address.as_bytes().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid bluetooth address {}", address),
+            )
+        })?;
+        let addr = u64::from_be_bytes([0, 0, b[0], b[1], b[2], b[3], b[4], b[5]]);
+        let device = WinBtDevice::FromBluetoothAddressAsync(addr)
+            .map_err(|e| std::io::Error::other(e.to_string()))?
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        Ok(crate::BluetoothDevice::Windows(BluetoothDevice {
+            inner: device,
+        }))
+    }
+
+    async fn connect_rfcomm(
+        &self,
+        _address: &super::BluetoothAdapterAddress,
+        _target: super::RfcommTarget,
+    ) -> Result<super::BluetoothStream, crate::BluetoothError> {
+        Err(unsupported("Connecting to an rfcomm channel by address").into())
+    }
+
+    async fn advertise(
+        &self,
+        _settings: super::LeAdvertisementSettings,
+    ) -> Result<super::AdvertisementHandle, super::BluetoothError> {
+        Err(super::BluetoothError::Unsupported("Low energy advertising"))
+    }
+
+    async fn register_gatt_service(
+        &self,
+        _service: super::GattServiceDefinition,
+    ) -> Result<super::GattServiceHandle, crate::BluetoothError> {
+        Err(unsupported("Registering a gatt service").into())
     }
 }
 
@@ -526,12 +817,12 @@ impl BluetoothHandler {
     /// Returns `Err` when no Bluetooth radio is present or when the Windows
     /// Runtime has not been initialised in the calling process.
     pub async fn new(
-        s: tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>,
-    ) -> Result<Self, String> {
+        s: Option<tokio::sync::mpsc::Sender<super::MessageToBluetoothHost>>,
+    ) -> Result<Self, crate::BluetoothError> {
         let adapter = WinBtAdapter::GetDefaultAsync()
-            .map_err(|e| e.to_string())?
+            .map_err(std::io::Error::other)?
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(std::io::Error::other)?;
         Ok(Self {
             adapter,
             _sender: s,
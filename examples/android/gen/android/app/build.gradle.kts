@@ -4,7 +4,8 @@ plugins {
     id("org.jetbrains.kotlin.android")
 }
 
-val sourceDirs = listOf("src/main/java", "src/fake/java")
+// The last directory holds the java classes that bluetooth-rust calls into
+val sourceDirs = listOf("src/main/java", "src/fake/java", "../../../../../bluetooth-rust/java")
 
 // Log contents of each source directory
 sourceDirs.forEach { dirPath ->
@@ -0,0 +1,14 @@
+// MainActivity.java
+package com.example.android;
+
+import android.app.NativeActivity;
+import com.github.uglyoldbob.bluetooth_rust.PermissionResultBridge;
+
+// The native activity does not forward the results of permission requests, this passes them to bluetooth-rust
+public class MainActivity extends NativeActivity {
+    @Override
+    public void onRequestPermissionsResult(int requestCode, String[] permissions, int[] grantResults) {
+        super.onRequestPermissionsResult(requestCode, permissions, grantResults);
+        PermissionResultBridge.onRequestPermissionsResult(requestCode, permissions, grantResults);
+    }
+}
@@ -9,7 +9,7 @@ use std::sync::{Arc, Mutex};
 use eframe::egui;
 use eframe::{NativeOptions, Renderer};
 
-use bluetooth_rust::{BluetoothAdapterTrait, BluetoothRfcommConnectableSyncTrait, BluetoothRfcommProfileSyncTrait, Java};
+use bluetooth_rust::{BluetoothAdapterTrait, BluetoothDeviceTrait, BluetoothDiscoveryTrait, BluetoothRfcommConnectableSyncTrait, BluetoothRfcommProfileSyncTrait, Java};
 
 #[cfg(target_os = "android")]
 use winit::platform::android::activity::AndroidApp;
@@ -46,8 +46,10 @@ pub struct MainWindow {
     known_uuids: BTreeMap<String, Vec<bluetooth_rust::BluetoothUuid>>,
     bluetooth_devs: BTreeMap<String, BluetoothConfig>,
     bluetooth_discovery: Option<bluetooth_rust::BluetoothDiscovery>,
-    profile: Option<Result<bluetooth_rust::BluetoothRfcommProfileSync, String>>,
-    bluetooth_stream: Result<bluetooth_rust::BluetoothStream, String>,
+    /// The devices found by the last discovery, with their name when known
+    discovered: BTreeMap<String, Option<String>>,
+    profile: Option<Result<bluetooth_rust::BluetoothRfcommProfileSync, bluetooth_rust::BluetoothError>>,
+    bluetooth_stream: Result<bluetooth_rust::BluetoothStream, bluetooth_rust::BluetoothError>,
     test: Result<bool, std::io::Error>,
     app: AndroidApp,
 }
@@ -67,7 +69,6 @@ impl MainWindow {
 
 impl eframe::App for MainWindow {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        //self.bluetooth.enable();
         ctx.request_repaint_after(std::time::Duration::from_millis(10));
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.label(format!("Time is {:?}", std::time::Instant::now()));
@@ -76,24 +77,53 @@ impl eframe::App for MainWindow {
                     .size(Self::font_size()),
             );
             let min_size = Self::min_size(ui);
+            if let Some(s) = self.bluetooth.supports_sync() {
+                if let Ok(false) = s.is_powered() {
+                    if ui.button("Turn on bluetooth").clicked() {
+                        let r = s.set_powered(true);
+                        log::info!("Turning on bluetooth: {:?}", r);
+                    }
+                }
+            }
             if ui.button("Start discovery").clicked() {
                 if let Some(s) = self.bluetooth.supports_sync() {
-                    self.bluetooth_discovery = Some(s.start_discovery());
+                    match s.start_discovery() {
+                        Ok(d) => {
+                            self.discovered.clear();
+                            self.bluetooth_discovery = Some(d);
+                        }
+                        Err(e) => log::error!("Failed to start discovery: {}", e),
+                    }
                 }
             }
+            if let Some(d) = &mut self.bluetooth_discovery {
+                while let Some(e) = d.try_recv() {
+                    self.discovery_event(e);
+                }
+            }
+            if self
+                .bluetooth_discovery
+                .as_ref()
+                .is_some_and(|d| !d.is_running())
+            {
+                self.bluetooth_discovery.take();
+            }
             if self.bluetooth_discovery.is_some() {
                 if ui.button("Cancel discovery").clicked() {
                     self.bluetooth_discovery.take();
                 }
             }
+            for (address, name) in &self.discovered {
+                ui.label(format!("{} {}", address, name.as_deref().unwrap_or("")));
+            }
             if ui.button("Set discoverable").clicked() {
                 if let Some(s) = self.bluetooth.supports_sync() {
-                    let _ = s.set_discoverable(true);
+                    let _ = s.set_discoverable(true, None);
                 }
             }
             if ui.button("Unset discoverable").clicked() {
                 if let Some(s) = self.bluetooth.supports_sync() {
-                    let _ = s.set_discoverable(false);
+                    let _ = s.set_discoverable(false, None);
                 }
             }
             ui.label(format!("Perm is {:?}", self.test));
@@ -102,17 +132,34 @@ impl eframe::App for MainWindow {
                     Ok(p) => {
                         ui.label("Got a valid bluetooth profile");
                         if self.bluetooth_stream.is_err() {
-                            let s = p.connectable();
+                            let s = p.connectable(std::time::Duration::from_millis(100));
                             if let Ok(s) = s {
-                                ui.label("Trying to accept");
-                                let t = s.accept(std::time::Duration::from_millis(100));
+                                ui.label(format!("Trying to accept {}", s.peer_address()));
+                                let t = s.accept();
                                 self.bluetooth_stream = t;
                             }
                         }
                         match &mut self.bluetooth_stream {
-                            Ok(_) => {
-                                ui.label("Got a bluetooth stream for stuff");
-                            }
+                            Ok(s) => match s.peer_address() {
+                                Ok(a) => {
+                                    let config = self
+                                        .bluetooth_devs
+                                        .entry(a.to_string())
+                                        .or_insert_with(BluetoothConfig::new);
+                                    ui.label(format!(
+                                        "Got a bluetooth stream from {} {}, {:?}",
+                                        a,
+                                        s.peer_name().unwrap_or_default(),
+                                        config
+                                    ));
+                                }
+                                Err(e) => {
+                                    ui.label(format!(
+                                        "Got a bluetooth stream from an unknown peer: {}",
+                                        e
+                                    ));
+                                }
+                            },
                             Err(e) => {
                                 ui.label(format!("Error accepting: {}", e));
                             }
@@ -128,6 +175,28 @@ impl eframe::App for MainWindow {
 }
 
 impl MainWindow {
+    /// Update the list of discovered devices with an event from discovery
+    fn discovery_event(&mut self, e: bluetooth_rust::DiscoveryEvent) {
+        match e {
+            bluetooth_rust::DiscoveryEvent::DeviceAdded { device: mut d, .. } => {
+                if let Ok(address) = d.get_address_string() {
+                    let name = d.supports_sync().and_then(|s| s.get_name().ok());
+                    self.discovered.insert(address, name);
+                }
+            }
+            bluetooth_rust::DiscoveryEvent::DeviceRemoved(address) => {
+                self.discovered.remove(&address.to_string());
+            }
+            bluetooth_rust::DiscoveryEvent::DeviceUpdated {
+                address,
+                property: bluetooth_rust::DevicePropertyEvent::NameChanged(name),
+            } => {
+                self.discovered.insert(address.to_string(), Some(name));
+            }
+            bluetooth_rust::DiscoveryEvent::DeviceUpdated { .. } => {}
+        }
+    }
+
     fn load_config(&mut self) {
         if let Some(p) = &self.local_storage {
             let mut config = p.clone();
@@ -170,7 +239,15 @@ impl MainWindow {
     fn new(_cc: &eframe::CreationContext<'_>, options: NativeOptions, app: AndroidApp) -> Self {
         let java = Java::make(app.clone());
         let java2 = Arc::new(Mutex::new(java));
-        let b = bluetooth_rust::Bluetooth::new(java2.clone());
+        let mut b = bluetooth_rust::Bluetooth::new(java2.clone());
+        // There is nobody to ask about incoming connections in this example
+        b.set_host(
+            None,
+            bluetooth_rust::AgentConfig {
+                authorize_service_default: true,
+                ..Default::default()
+            },
+        );
         let _ = b.check_permission("android.permission.BLUETOOTH_CONNECT");
         let perm2 = b.try_get_permissions(app.clone(), "android.permission.BLUETOOTH_CONNECT");
         let mut s = Self {
@@ -181,28 +258,27 @@ impl MainWindow {
             known_uuids: BTreeMap::new(),
             bluetooth_devs: BTreeMap::new(),
             bluetooth_discovery: None,
+            discovered: BTreeMap::new(),
             profile: None,
             app,
             test: perm2,
-            bluetooth_stream: Err("Not setup yet".to_string()),
+            bluetooth_stream: Err(bluetooth_rust::BluetoothError::Platform(
+                "Not setup yet".to_string(),
+            )),
         };
         s.load_config();
         if let Some(st) = s.bluetooth.supports_sync() {
-            let profile =
-                st.register_rfcomm_profile(bluetooth_rust::BluetoothRfcommProfileSettings {
-                    uuid: "00001124-0000-1000-8000-00805f9b34fb".to_string(),
-                    name: Some("NES joystick".to_string()),
-                    service_uuid: Some("00001124-0000-1000-8000-00805f9b34fb".to_string()),
-                    channel: Some(1),
-                    psm: None,
-                    authenticate: Some(true),
-                    authorize: Some(true),
-                    auto_connect: Some(true),
-                    sdp_record: None,
-                    sdp_version: None,
-                    sdp_features: None,
-                });
-            s.profile = Some(profile);
+            let settings = bluetooth_rust::BluetoothRfcommProfileSettings::builder()
+                .uuid(bluetooth_rust::BluetoothUuid::Hid)
+                .name("NES joystick")
+                .service_uuid(bluetooth_rust::BluetoothUuid::Hid)
+                .channel(1)
+                .authenticate(true)
+                .authorize(true)
+                .auto_connect(true)
+                .build()
+                .map_err(bluetooth_rust::BluetoothError::from);
+            s.profile = Some(settings.and_then(|settings| st.register_rfcomm_profile(settings)));
         }
         s
     }